@@ -0,0 +1,62 @@
+// Compares the two instruction dispatchers on loop-heavy programs:
+//
+//     cargo bench --bench dispatch
+
+use advent_of_code_2019::get_intcode_memory_from_file;
+use advent_of_code_2019::intcode::{Dispatch, Emulator, EmulatorMemoryType, QueueInput};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+// Counts cell 100 down from its initial value.
+fn countdown(iterations: EmulatorMemoryType) -> Vec<EmulatorMemoryType> {
+    let mut program = vec![1001, 100, -1, 100, 1005, 100, 0, 4, 100, 99];
+    program.resize(101, 0);
+    program[100] = iterations;
+    program
+}
+
+// A loop body mixing compares, adds and a conditional jump.
+fn compare_and_add(iterations: EmulatorMemoryType) -> Vec<EmulatorMemoryType> {
+    let mut program = vec![
+        1001, 100, -1, 100, 7, 101, 100, 102, 1, 101, 102, 101, 1005, 100, 0, 4, 101, 99,
+    ];
+    program.resize(103, 0);
+    program[100] = iterations;
+    program
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let day_5 = get_intcode_memory_from_file("input/5.txt");
+    let workloads: Vec<(&str, Vec<EmulatorMemoryType>, Vec<EmulatorMemoryType>)> = vec![
+        ("countdown", countdown(100_000), vec![]),
+        ("compare and add", compare_and_add(100_000), vec![]),
+        ("day 5 part 2", day_5, vec![5]),
+    ];
+    let mut group = c.benchmark_group("dispatch");
+    for (name, program, inputs) in &workloads {
+        for &(label, dispatch) in [("match", Dispatch::Match), ("table", Dispatch::Table)].iter() {
+            group.bench_function(BenchmarkId::new(label, name), |b| {
+                b.iter_batched(
+                    || {
+                        let mut emulator = Emulator::with_dispatch(
+                            program,
+                            QueueInput::from(inputs.clone()),
+                            dispatch,
+                        );
+                        emulator.set_history_size(0);
+                        emulator
+                    },
+                    |emulator| {
+                        for output in emulator {
+                            output.unwrap();
+                        }
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_dispatch);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+// Compares day 7 part 1 amplifier chains built with concrete input types
+// against the same chains built from DynEmulators:
+//
+//     cargo bench --bench dyn_input
+
+use advent_of_code_2019::get_intcode_memory_from_file;
+use advent_of_code_2019::intcode::{Emulator, EmulatorMemoryType};
+use std::iter::once;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 5;
+
+fn permutations(values: &[EmulatorMemoryType]) -> Vec<Vec<EmulatorMemoryType>> {
+    if values.len() <= 1 {
+        return vec![values.to_vec()];
+    }
+    let mut all = Vec::new();
+    for (index, &first) in values.iter().enumerate() {
+        let mut rest = values.to_vec();
+        rest.remove(index);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, first);
+            all.push(permutation);
+        }
+    }
+    all
+}
+
+fn concrete(program: &[EmulatorMemoryType], phases: &[EmulatorMemoryType]) -> EmulatorMemoryType {
+    let a = Emulator::with_inputs(program, [phases[0], 0]);
+    let b = Emulator::new(program, once(Ok(phases[1])).chain(a));
+    let c = Emulator::new(program, once(Ok(phases[2])).chain(b));
+    let d = Emulator::new(program, once(Ok(phases[3])).chain(c));
+    let e = Emulator::new(program, once(Ok(phases[4])).chain(d));
+    e.into_iter().last().unwrap().unwrap()
+}
+
+fn boxed(program: &[EmulatorMemoryType], phases: &[EmulatorMemoryType]) -> EmulatorMemoryType {
+    let first = Emulator::with_inputs(program, [phases[0], 0]).into_dyn();
+    let last = phases[1..].iter().fold(first, |previous, &phase| {
+        Emulator::boxed(program, once(Ok(phase)).chain(previous))
+    });
+    last.into_iter().last().unwrap().unwrap()
+}
+
+fn time(
+    program: &[EmulatorMemoryType],
+    all_phases: &[Vec<EmulatorMemoryType>],
+    run: fn(&[EmulatorMemoryType], &[EmulatorMemoryType]) -> EmulatorMemoryType,
+) -> (Duration, EmulatorMemoryType) {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            let highest = all_phases
+                .iter()
+                .map(|phases| run(program, phases))
+                .max()
+                .unwrap();
+            (start.elapsed(), highest)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let program = get_intcode_memory_from_file("input/7.txt");
+    let all_phases = permutations(&[0, 1, 2, 3, 4]);
+    let (by_concrete, concrete_answer) = time(&program, &all_phases, concrete);
+    let (by_boxed, boxed_answer) = time(&program, &all_phases, boxed);
+    assert_eq!(concrete_answer, boxed_answer);
+    println!(
+        "day 7 part 1     concrete {:>10.3?}  boxed {:>10.3?}",
+        by_concrete, by_boxed
+    );
+}
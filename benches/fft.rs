@@ -0,0 +1,41 @@
+// Compares the naive and prefix-sum day 16 phases on a signal the length of
+// the puzzle input and on one ten times longer:
+//
+//     cargo bench --bench fft
+
+use advent_of_code_2019::day16::{phase, phase_naive};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+fn random_signal(len: usize) -> Vec<i32> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 10) as i32
+        })
+        .collect()
+}
+
+fn bench_phases(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fft phase");
+    // The naive phase is quadratic, so the long signal takes a while.
+    group.sample_size(20);
+    for &len in [650, 6_500].iter() {
+        let signal = random_signal(len);
+        group.bench_with_input(BenchmarkId::new("naive", len), &signal, |b, signal| {
+            b.iter(|| phase_naive(black_box(signal)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("prefix sums", len),
+            &signal,
+            |b, signal| b.iter(|| phase(black_box(signal))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_phases);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+// Compares loading a large program from comma separated text and from .icb.
+// Run with `cargo run --release --example icb_parse_bench`.
+use advent_of_code_2019::intcode::EmulatorMemoryType;
+use advent_of_code_2019::program::{read_icb, read_text, write_icb};
+use std::time::{Duration, Instant};
+
+const CELLS: i64 = 1_000_000;
+const RUNS: u32 = 10;
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let memory: Vec<EmulatorMemoryType> = (0..CELLS)
+        .map(|cell| EmulatorMemoryType::from(cell.wrapping_mul(6_364_136_223_846_793_005) >> 3))
+        .collect();
+
+    let text = memory
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut binary = Vec::new();
+    write_icb(&memory, &mut binary).unwrap();
+
+    let text_time = time(|| assert_eq!(memory, read_text(text.as_bytes()).unwrap()));
+    let binary_time = time(|| assert_eq!(memory, read_icb(binary.as_slice()).unwrap()));
+
+    println!("{} cells, mean of {} runs", CELLS, RUNS);
+    println!("text: {:>10} bytes {:>12.2?}", text.len(), text_time);
+    println!("icb:  {:>10} bytes {:>12.2?}", binary.len(), binary_time);
+}
@@ -1,10 +1,10 @@
-#[deny(clippy::all)]
+#![deny(clippy::all)]
 
 extern crate proc_macro;
 extern crate syn;
 
 use quote::{format_ident, quote};
-use syn::{braced, bracketed, Block, parenthesized, parse_macro_input, token, Ident, Result, Token, LitInt, Stmt};
+use syn::{braced, bracketed, Attribute, Block, parenthesized, parse_macro_input, token, Ident, Result, Token, LitInt, Stmt};
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use std::collections::{HashMap, HashSet};
@@ -16,20 +16,33 @@ mod kw {
     syn::custom_keyword!(Input);
     syn::custom_keyword!(Output);
     syn::custom_keyword!(InstructionPointerOverride);
+    syn::custom_keyword!(InstructionPointer);
+    syn::custom_keyword!(RelativeBase);
 }
 
 struct OpCodeDeclaration {
+    dispatch_table: bool,
     ident: Ident,
     variants: Punctuated<OpCodeVariants, Token![,]>
 }
 
 impl Parse for OpCodeDeclaration {
     fn parse(input: ParseStream) -> Result<Self> {
+        // #[dispatch_table] also generates run_via_table.
+        let mut dispatch_table = false;
+        for attribute in input.call(Attribute::parse_outer)? {
+            if attribute.path.is_ident("dispatch_table") {
+                dispatch_table = true;
+            } else {
+                return Err(syn::Error::new_spanned(attribute, "the only supported attribute is #[dispatch_table]"));
+            }
+        }
         let ident = input.parse()?;
         let content;
         braced!(content in input);
         let variants = content.parse_terminated(OpCodeVariants::parse)?;
         Ok(OpCodeDeclaration{
+            dispatch_table,
             ident,
             variants
         })
@@ -43,6 +56,8 @@ struct OpCodeVariants {
     input_ident: Option<Ident>,
     outputs_value: bool,
     instruction_pointer_override_ident: Option<Ident>,
+    instruction_pointer_ident: Option<Ident>,
+    relative_base_ident: Option<Ident>,
     function: Vec<Stmt>,
     terminator: bool
 }
@@ -63,6 +78,8 @@ impl Parse for OpCodeVariants {
         let mut input_ident = None;
         let mut outputs_value = false;
         let mut instruction_pointer_override_ident = None;
+        let mut instruction_pointer_ident = None;
+        let mut relative_base_ident = None;
         if input.peek(token::Bracket) {
             let content;
             bracketed!(content in input);
@@ -70,10 +87,12 @@ impl Parse for OpCodeVariants {
             let mut input_declaration: Option<kw::Input> = None;
             let mut out_declaration: Option<kw::Output> = None;
             let mut instruction_pointer_override_declaration: Option<kw::InstructionPointerOverride> = None;
+            let mut instruction_pointer_declaration: Option<kw::InstructionPointer> = None;
+            let mut relative_base_declaration: Option<kw::RelativeBase> = None;
             for declaration in io_declarations.into_iter() {
                 match declaration {
                     IoDeclaration::Input{keyword, ident, ..} => {
-                        if let Some(_) = input_declaration {
+                        if input_declaration.is_some() {
                             return Err(syn::Error::new_spanned(keyword, "io declaration can only be declared once"));
                         } else {
                             input_declaration = Some(keyword);
@@ -81,7 +100,7 @@ impl Parse for OpCodeVariants {
                         }
                     },
                     IoDeclaration::Output{keyword} => {
-                        if let Some(_) = out_declaration {
+                        if out_declaration.is_some() {
                             return Err(syn::Error::new_spanned(keyword, "io declaration can only be declared once"));
                         } else {
                             out_declaration = Some(keyword);
@@ -89,12 +108,28 @@ impl Parse for OpCodeVariants {
                         }
                     },
                     IoDeclaration::InstructionPointerOverride{keyword, ident, ..} => {
-                        if let Some(_) = instruction_pointer_override_declaration {
+                        if instruction_pointer_override_declaration.is_some() {
                             return Err(syn::Error::new_spanned(keyword, "instruction pointer override declaration can only be declared once"));
                         } else {
                             instruction_pointer_override_declaration = Some(keyword);
                             instruction_pointer_override_ident = Some(ident);
                         }
+                    },
+                    IoDeclaration::InstructionPointer{keyword, ident, ..} => {
+                        if instruction_pointer_declaration.is_some() {
+                            return Err(syn::Error::new_spanned(keyword, "instruction pointer declaration can only be declared once"));
+                        } else {
+                            instruction_pointer_declaration = Some(keyword);
+                            instruction_pointer_ident = Some(ident);
+                        }
+                    },
+                    IoDeclaration::RelativeBase{keyword, ident, ..} => {
+                        if relative_base_declaration.is_some() {
+                            return Err(syn::Error::new_spanned(keyword, "relative base declaration can only be declared once"));
+                        } else {
+                            relative_base_declaration = Some(keyword);
+                            relative_base_ident = Some(ident);
+                        }
                     }
                 }
             }
@@ -121,14 +156,18 @@ impl Parse for OpCodeVariants {
             input_ident,
             outputs_value,
             instruction_pointer_override_ident,
+            instruction_pointer_ident,
+            relative_base_ident,
             function,
             terminator
         })
     }
 }
 
+// Separators and keywords are kept only so that parsing consumes them.
 struct Parameter {
     ident: Ident,
+    #[allow(dead_code)]
     separator: Token![:],
     parameter_type: ParameterType
 }
@@ -145,9 +184,11 @@ impl Parse for Parameter {
 
 enum ParameterType {
     ReadOnly {
+        #[allow(dead_code)]
         keyword: kw::ReadOnly
     },
     Writable {
+        #[allow(dead_code)]
         keyword: kw::Writable
     }
 }
@@ -172,6 +213,7 @@ impl Parse for ParameterType {
 enum IoDeclaration {
     Input {
         ident: Ident,
+        #[allow(dead_code)]
         separator: Token![:],
         keyword: kw::Input
     },
@@ -180,8 +222,18 @@ enum IoDeclaration {
     },
     InstructionPointerOverride {
         ident: Ident,
+        #[allow(dead_code)]
         separator: Token![:],
         keyword: kw::InstructionPointerOverride
+    },
+    // The address of the instruction being run, for error positions.
+    InstructionPointer {
+        ident: Ident,
+        keyword: kw::InstructionPointer
+    },
+    RelativeBase {
+        ident: Ident,
+        keyword: kw::RelativeBase
     }
 }
 
@@ -208,6 +260,16 @@ impl Parse for IoDeclaration {
                     separator,
                     keyword: input.parse()?
                 })
+            } else if lookahead.peek(kw::InstructionPointer) {
+                Ok(IoDeclaration::InstructionPointer {
+                    ident,
+                    keyword: input.parse()?
+                })
+            } else if lookahead.peek(kw::RelativeBase) {
+                Ok(IoDeclaration::RelativeBase {
+                    ident,
+                    keyword: input.parse()?
+                })
             } else {
                 Err(lookahead.error())
             }
@@ -259,34 +321,39 @@ pub fn make_op_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         let translation_from_code_match_arms = input.variants.iter().map(|variant| {
             let ident = &variant.ident;
             let code = &variant.code;
-            quote!{#code => Ok(#enum_name::#ident)}
+            quote!{Some(#code) => Ok(#enum_name::#ident)}
         });
         quote!{
-            fn get_current_instruction(memory: &[EmulatorMemoryType], instruction_pointer: usize) -> Result<(#enum_name, impl Iterator<Item = Result<ParameterMode, EmulatorError>>), EmulatorError> {
+            fn get_current_instruction<C: MemoryCell>(memory: &[C], instruction_pointer: usize) -> Result<(#enum_name, impl Iterator<Item = Result<ParameterMode, EmulatorError>>), EmulatorError> {
                 let instruction_value = *memory.get(instruction_pointer).ok_or(
                     EmulatorError::InstructionPointerOutOfBounds {
                         position: instruction_pointer,
+                        context: None,
                     })?;
-                let instruction = match (instruction_value % 100) {
+                // Negative instructions have no code, so are invalid.
+                let code: Option<usize> = (instruction_value % C::from(100)).try_into().ok();
+                let instruction = match code {
                     #(#translation_from_code_match_arms),*,
-                    _ => Err(EmulatorError::InvalidInstruction{value_found: instruction_value, position: instruction_pointer})
+                    _ => Err(EmulatorError::InvalidInstruction{value_found: instruction_value.widen(), position: instruction_pointer, context: None})
                 }?;
 
-                let parameter_mode_iterator = {
-                    let mut parameter_mode_digits = instruction_value / 100;
-                    std::iter::from_fn(move || {
-                        let parameter_mode_digit = parameter_mode_digits % 10;
-                        let result = match parameter_mode_digit {
-                            0 => Ok(ParameterMode::Position),
-                            1 => Ok(ParameterMode::Immediate),
-                            _ => Err(EmulatorError::InvalidParameterMode{value_found: parameter_mode_digit, position: instruction_pointer}),
-                        };
-                        parameter_mode_digits /= 10;
-                        Some(result)
-                    })
-                };
+                Ok((instruction, #enum_name::parameter_modes(instruction_value, instruction_pointer)))
+            }
 
-                Ok((instruction, parameter_mode_iterator))
+            fn parameter_modes<C: MemoryCell>(instruction_value: C, instruction_pointer: usize) -> impl Iterator<Item = Result<ParameterMode, EmulatorError>> {
+                let mut parameter_mode_digits = instruction_value / C::from(100);
+                std::iter::from_fn(move || {
+                    let parameter_mode_digit = parameter_mode_digits % C::from(10);
+                    let mode: Option<usize> = parameter_mode_digit.try_into().ok();
+                    let result = match mode {
+                        Some(0) => Ok(ParameterMode::Position),
+                        Some(1) => Ok(ParameterMode::Immediate),
+                        Some(2) => Ok(ParameterMode::Relative),
+                        _ => Err(EmulatorError::InvalidParameterMode{value_found: parameter_mode_digit.widen(), position: instruction_pointer, context: None}),
+                    };
+                    parameter_mode_digits = parameter_mode_digits / C::from(10);
+                    Some(result)
+                })
             }
         }
     };
@@ -314,53 +381,74 @@ pub fn make_op_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             let param_ident = &parameter.ident;
             match parameter.parameter_type {
                 ParameterType::ReadOnly{..} => quote!{
-                    #param_ident: EmulatorMemoryType
+                    #param_ident: C
                 },
                 ParameterType::Writable{..} => quote!{
-                    #param_ident: &mut EmulatorMemoryType
+                    #param_ident: &mut C
                 }
             }
         });
 
         let mut parameters = Vec::new();
-        let iterator_bound = if let Some(ident) = &variant.input_ident {
+        if let Some(ident) = &variant.input_ident {
             parameters.push(quote!{#ident: &mut I});
-            quote!{<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>>}
-        } else {
-            quote!{}
-        };
+        }
 
         if let Some(ident) = &variant.instruction_pointer_override_ident {
-            parameters.push(quote!{#ident: &mut Option<EmulatorMemoryType>});
+            parameters.push(quote!{#ident: &mut Option<C>});
+        };
+
+        if let Some(ident) = &variant.instruction_pointer_ident {
+            parameters.push(quote!{#ident: usize});
+        };
+
+        if let Some(ident) = &variant.relative_base_ident {
+            parameters.push(quote!{#ident: &mut C});
         };
 
         parameters.extend(fn_param_list);
         let parameters = quote!{(#(#parameters),*)};
 
         let okay_type = if variant.outputs_value {
-            quote!{EmulatorMemoryType}
+            quote!{C}
         } else {
             quote!{()}
         };
 
+        // A handler that never sees a cell can't be generic over one, as
+        // nothing would say which type to call it with.
+        let uses_cell = variant.input_ident.is_some()
+            || variant.instruction_pointer_override_ident.is_some()
+            || variant.relative_base_ident.is_some()
+            || variant.outputs_value
+            || !variant.parameters.is_empty();
+        let generics = if variant.input_ident.is_some() {
+            quote!{<C: MemoryCell, I: Iterator<Item = Result<C, EmulatorError>>>}
+        } else if uses_cell {
+            quote!{<C: MemoryCell>}
+        } else {
+            quote!{}
+        };
+
         let handler_name = format_ident!("handle_{}", ident.to_string().to_lowercase());
         quote!{
-            fn #handler_name#iterator_bound#parameters -> Result<#okay_type, EmulatorError> {
+            fn #handler_name#generics#parameters -> Result<#okay_type, EmulatorError> {
                 Ok({#(#stmts)*})
             }
         }
     });
 
-    let variant_handler_dispatchers = input.variants.iter().map(|variant| {
+    let variant_handler_dispatchers: Vec<_> = input.variants.iter().map(|variant| {
         let ident = &variant.ident;
         let parameter_amt = variant.parameters.len();
         let parameter_bounds_guard = if parameter_amt > 0 {
             quote!{
-                if instruction_pointer + 1 + #parameter_amt >= memory.len() {
+                if instruction_pointer + 1 + #parameter_amt > memory.len() {
                     return Err(EmulatorError::NotEnoughParametersForInstruction {
-                        instruction: instruction.to_opcode(),
+                        instruction: instruction.to_opcode().widen(),
                         expected: #parameter_amt,
-                        found: instruction_pointer + 1 + #parameter_amt - memory.len(),
+                        found: memory.len().saturating_sub(instruction_pointer + 1),
+                        context: None,
                     })
                 }
             }
@@ -370,51 +458,64 @@ pub fn make_op_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
         let parameter_initializers = variant.parameters.iter().enumerate().map(|(idx, parameter)| {
             let param_ident = &parameter.ident;
+            let address_ident = format_ident!("{}_address", param_ident);
             match parameter.parameter_type {
                 ParameterType::ReadOnly{..} => quote!{
-                    let #param_ident: EmulatorMemoryType = match parameter_mode_iterator.next().unwrap()? {
+                    let #param_ident: C = match parameter_mode_iterator.next().unwrap()? {
                         ParameterMode::Position => {
-                            let parameter_location = instruction_pointer + #idx + 1;
-                            let address = memory[parameter_location];
-                            let error = EmulatorError::InvalidMemoryLocation {
-                                value_found: address,
-                                position: parameter_location,
-                            };
-                            let address_converted = std::convert::TryInto::<usize>::try_into(address).or(Err(error))?;
-                            *memory.get(address_converted)
-                                .ok_or(error)?
+                            let word = memory[instruction_pointer + #idx + 1];
+                            let context = AddrContext::parameter(instruction_pointer, #idx, AddressPurpose::Read);
+                            let address = Address::try_from_cell(word, context)?;
+                            read_cell(memory, address, word, context)?
                         },
                         ParameterMode::Immediate => {
                             memory[instruction_pointer + #idx + 1]
+                        },
+                        ParameterMode::Relative => {
+                            let word = memory[instruction_pointer + #idx + 1];
+                            let context = AddrContext::parameter(instruction_pointer, #idx, AddressPurpose::Read);
+                            let effective = relative_address(word, *relative_base, context)?;
+                            let address = Address::try_from_cell(effective, context)?;
+                            read_cell(memory, address, effective, context)?
                         }
                     };
+                    resolved_parameters.push(#param_ident);
                 },
                 ParameterType::Writable{..} => quote!{
-                    let #param_ident: &mut EmulatorMemoryType = match parameter_mode_iterator.next().unwrap()? {
+                    let #address_ident: Address = match parameter_mode_iterator.next().unwrap()? {
                         ParameterMode::Position => {
-                            let parameter_location = instruction_pointer + #idx + 1;
-                            let address = memory[parameter_location];
-                            let error = EmulatorError::InvalidMemoryLocation {
-                                value_found: address,
-                                position: parameter_location,
-                            };
-                            let address_converted = std::convert::TryInto::<usize>::try_into(address).or(Err(error))?;
-                            memory.get_mut(address_converted)
-                                .ok_or(error)?
+                            let word = memory[instruction_pointer + #idx + 1];
+                            let context = AddrContext::parameter(instruction_pointer, #idx, AddressPurpose::Write);
+                            let address = Address::try_from_cell(word, context)?;
+                            resolved_parameters.push(word);
+                            address
                         },
                         ParameterMode::Immediate => {
                             return Err(EmulatorError::UnexpectedParameterModeForWritable {
                                 value_found: 1,
                                 position: instruction_pointer + #idx + 1,
+                                context: None,
                             })
+                        },
+                        ParameterMode::Relative => {
+                            let word = memory[instruction_pointer + #idx + 1];
+                            let context = AddrContext::parameter(instruction_pointer, #idx, AddressPurpose::Write);
+                            let effective = relative_address(word, *relative_base, context)?;
+                            let address = Address::try_from_cell(effective, context)?;
+                            resolved_parameters.push(effective);
+                            address
                         }
                     };
+                    // Written back once the handler succeeds, so an
+                    // instruction that fails, or waits for input, leaves
+                    // memory as it was.
+                    let mut #param_ident: C = memory.get_cell(#address_ident.get()).unwrap_or(C::from(0));
                 }
             }
         });
 
         let (output_binding, output) = if variant.outputs_value {
-            (quote!{let output: EmulatorMemoryType}, quote!{Some(output)})
+            (quote!{let output: C}, quote!{Some(output)})
         } else {
             (quote!{let _: ()}, quote!{None})
         };
@@ -430,10 +531,34 @@ pub fn make_op_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             parameters.push(quote!{&mut new_instruction_pointer});
         }
 
-        parameters.extend(variant.parameters.iter().map(|parameter| &parameter.ident).map(|ident| quote!{#ident}));
+        if variant.instruction_pointer_ident.is_some() {
+            parameters.push(quote!{instruction_pointer});
+        }
+
+        if variant.relative_base_ident.is_some() {
+            parameters.push(quote!{&mut *relative_base});
+        }
+
+        parameters.extend(variant.parameters.iter().map(|parameter| {
+            let ident = &parameter.ident;
+            match parameter.parameter_type {
+                ParameterType::ReadOnly{..} => quote!{#ident},
+                ParameterType::Writable{..} => quote!{&mut #ident},
+            }
+        }));
+
+        let write_backs = variant.parameters.iter().filter_map(|parameter| match parameter.parameter_type {
+            ParameterType::ReadOnly{..} => None,
+            ParameterType::Writable{..} => {
+                let ident = &parameter.ident;
+                let address_ident = format_ident!("{}_address", ident);
+                Some(quote!{memory.checked_write(#address_ident.get(), #ident)?;})
+            }
+        });
 
         let statement_runner = quote!{
             #output_binding = #enum_name::#handler_name(#(#parameters),*)?;
+            #(#write_backs)*
         };
 
         let instruction_offset = parameter_amt + 1; // + 1 for the instruction itself
@@ -443,53 +568,234 @@ pub fn make_op_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             }
         } else if variant.instruction_pointer_override_ident.is_some() {
             quote!{
-                Some(new_instruction_pointer.map(|value| std::convert::TryInto::<usize>::try_into(value)
-                                                    .or(Err(EmulatorError::InvalidMemoryLocation{value_found: value, position: 0})))
-                .unwrap_or(Ok(instruction_pointer + #instruction_offset))?)
+                Some(match new_instruction_pointer {
+                    Some(value) => Address::try_from_cell(value, AddrContext::jump(instruction_pointer))?.get(),
+                    None => instruction_pointer + #instruction_offset,
+                })
             }
         } else {
             quote!{
                 Some(instruction_pointer + #instruction_offset)
             }
         };
+        // Whether the instruction pointer override fired, for branch statistics.
+        let jumped = if variant.instruction_pointer_override_ident.is_some() {
+            quote!{new_instruction_pointer.is_some()}
+        } else {
+            quote!{false}
+        };
+        let body = quote!{
+            #parameter_bounds_guard
+            #(#parameter_initializers)*
+            #statement_runner
+            Ok((#instruction_pointer_update, #output, #jumped))
+        };
+        (ident, body)
+    }).collect();
+
+    let variant_handler_match_arms = variant_handler_dispatchers.iter().map(|(ident, body)| {
         quote!{
             #enum_name::#ident => {
-                #parameter_bounds_guard
-                #(#parameter_initializers)*
-                #statement_runner
-                Ok((#instruction_pointer_update, #output))
+                #body
+            }
+        }
+    });
+
+    // One small function per opcode for the dispatch table. The input
+    // iterator is a trait object here, so every entry has the same type.
+    let variant_table_handlers = variant_handler_dispatchers.iter().map(|(ident, body)| {
+        let table_handler_name = format_ident!("dispatch_{}", ident.to_string().to_lowercase());
+        quote!{
+            #[allow(unused_variables, unused_mut, clippy::ptr_arg)]
+            fn #table_handler_name<C: MemoryCell>(memory: &mut Memory<C>, instruction_pointer: usize, relative_base: &mut C, mut input_iter: &mut dyn Iterator<Item = Result<C, EmulatorError>>, resolved_parameters: &mut Vec<C>) -> Result<(Option<usize>, Option<C>, bool), EmulatorError> {
+                let instruction = #enum_name::#ident;
+                let mut parameter_mode_iterator = #enum_name::parameter_modes(memory[instruction_pointer], instruction_pointer);
+                let mut new_instruction_pointer: Option<C> = None;
+                let input_iter = &mut input_iter;
+                #body
+            }
+        }
+    });
+
+    let mut dispatch_slots = vec![quote!{None}; 100];
+    let mut dispatch_errors = Vec::new();
+    for variant in input.variants.iter() {
+        match variant.code.base10_parse::<usize>() {
+            Ok(code) if code < dispatch_slots.len() => {
+                let table_handler_name = format_ident!("dispatch_{}", variant.ident.to_string().to_lowercase());
+                dispatch_slots[code] = quote!{Some(#enum_name::#table_handler_name::<C> as HandlerFn<C>)};
+            }
+            _ => dispatch_errors.push(syn::Error::new_spanned(&variant.code, "opcodes in a dispatch table must be between 0 and 99").to_compile_error()),
+        }
+    }
+
+    let dispatch_table = if !input.dispatch_table {
+        quote!{}
+    } else if !dispatch_errors.is_empty() {
+        quote!{#(#dispatch_errors)*}
+    } else {
+        quote!{
+            pub const OPCODE_MODULUS: usize = 100;
+
+            pub type HandlerFn<C> = fn(&mut Memory<C>, usize, &mut C, &mut dyn Iterator<Item = Result<C, EmulatorError>>, &mut Vec<C>) -> Result<(Option<usize>, Option<C>, bool), EmulatorError>;
+
+            // A static can't be generic, so each cell type gets its table
+            // as an associated const.
+            struct DispatchTable<C>(std::marker::PhantomData<C>);
+
+            impl<C: MemoryCell> DispatchTable<C> {
+                // Indexed by instruction % OPCODE_MODULUS.
+                const HANDLERS: [Option<HandlerFn<C>>; OPCODE_MODULUS] = [#(#dispatch_slots),*];
+            }
+
+            impl #enum_name {
+                #(#variant_table_handlers)*
+
+                // Same behaviour as run, but looks the handler up in DispatchTable
+                // instead of matching on the decoded instruction.
+                fn run_via_table<C: MemoryCell>(memory: &mut Memory<C>, instruction_pointer: usize, relative_base: &mut C, input_iter: &mut dyn Iterator<Item = Result<C, EmulatorError>>, resolved_parameters: &mut Vec<C>) -> Result<(Option<usize>, Option<C>, bool), EmulatorError> {
+                    let instruction_value = *memory.get(instruction_pointer).ok_or(
+                        EmulatorError::InstructionPointerOutOfBounds {
+                            position: instruction_pointer,
+                            context: None,
+                        })?;
+                    let code: Option<usize> = (instruction_value % C::from(OPCODE_MODULUS as i8)).try_into().ok();
+                    // Through a reference, which is promoted to a static, so
+                    // the table isn't copied out of the const for each lookup.
+                    let handlers: &'static [Option<HandlerFn<C>>; OPCODE_MODULUS] = &DispatchTable::<C>::HANDLERS;
+                    let handler = code
+                        .and_then(|code| handlers[code])
+                        .ok_or(EmulatorError::InvalidInstruction{value_found: instruction_value.widen(), position: instruction_pointer, context: None})?;
+                    handler(memory, instruction_pointer, relative_base, input_iter, resolved_parameters)
+                }
+            }
+        }
+    };
+
+    let opcode_table_entries = input.variants.iter().map(|variant| {
+        let code = &variant.code;
+        let mnemonic = variant.ident.to_string();
+        let parameters = variant.parameters.iter().map(|parameter| {
+            let name = parameter.ident.to_string();
+            let kind = match parameter.parameter_type {
+                ParameterType::ReadOnly{..} => quote!{ParameterKind::Read},
+                ParameterType::Writable{..} => quote!{ParameterKind::Write},
+            };
+            quote!{ParameterInfo { name: #name, kind: #kind }}
+        });
+        let reads_input = variant.input_ident.is_some();
+        let writes_output = variant.outputs_value;
+        let halts = variant.terminator;
+        let jumps = variant.instruction_pointer_override_ident.is_some();
+        quote!{
+            OpCodeInfo {
+                code: #code,
+                mnemonic: #mnemonic,
+                parameters: &[#(#parameters),*],
+                reads_input: #reads_input,
+                writes_output: #writes_output,
+                halts: #halts,
+                jumps: #jumps,
             }
         }
     });
 
-    let output = if compile_errors.len() > 0 {
+    let output = if !compile_errors.is_empty() {
         quote! {
             #(#compile_errors)*
         }
     } else {
         quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
             pub enum #enum_name {
                 #(#variant_idents),*
             }
 
+            pub const OPCODE_TABLE: &[OpCodeInfo] = &[
+                #(#opcode_table_entries),*
+            ];
+
             impl #enum_name {
                 #get_current_instruction_fn
                 #to_opcode_fn
 
                 #(#variant_handler_functions)*
 
-                fn run<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>>(memory: &mut [EmulatorMemoryType], instruction_pointer: usize, input_iter: &mut I) -> Result<(Option<usize>, Option<EmulatorMemoryType>), EmulatorError> {
+                fn run<C: MemoryCell, I: Iterator<Item = Result<C, EmulatorError>>>(memory: &mut Memory<C>, instruction_pointer: usize, relative_base: &mut C, input_iter: &mut I, resolved_parameters: &mut Vec<C>) -> Result<(Option<usize>, Option<C>, bool), EmulatorError> {
                     let (instruction, mut parameter_mode_iterator) = #enum_name::get_current_instruction(memory, instruction_pointer)?;
                     let mut new_instruction_pointer = None;
                     match instruction {
-                        #(#variant_handler_dispatchers),*
+                        #(#variant_handler_match_arms),*
                     }
                 }
             }
+
+            #dispatch_table
         }
     };
 
-    println!("{}", output);
+    proc_macro::TokenStream::from(output)
+}
+
+// Same rules as program::read_text in the main crate: cells are separated by
+// commas and surrounded by optional whitespace.
+fn parse_intcode_text(text: &str) -> std::result::Result<Vec<i128>, String> {
+    let mut cells = Vec::new();
+    let mut offset = 0;
+    for (index, cell) in text.split(',').enumerate() {
+        match cell.trim().parse() {
+            Ok(value) => cells.push(value),
+            Err(_) => {
+                let start = offset + (cell.len() - cell.trim_start().len());
+                let line = text[..start].matches('\n').count() + 1;
+                let column = start - text[..start].rfind('\n').map_or(0, |newline| newline + 1) + 1;
+                return Err(format!("cell {} ('{}') at line {}, column {} is not an integer", index, cell.trim(), line, column));
+            }
+        }
+        offset += cell.len() + 1;
+    }
+    Ok(cells)
+}
+
+// include_intcode!("path") reads an Intcode program at compile time and
+// expands to a &'static [EmulatorMemoryType]. Like include_str!, the path is
+// relative to the file containing the invocation. Cells too wide for the
+// configured EmulatorMemoryType fail to compile as out of range literals.
+#[proc_macro]
+pub fn include_intcode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let call_site_file = input.clone().into_iter().next().and_then(|token| token.span().local_file());
+    let path_literal = parse_macro_input!(input as syn::LitStr);
+
+    let path = match call_site_file.as_ref().and_then(|file| file.parent()) {
+        Some(directory) => directory.join(path_literal.value()),
+        None => std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(path_literal.value()),
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) => {
+            let message = format!("couldn't read intcode program {:?}: {}", path_literal.value(), error);
+            return syn::Error::new(path_literal.span(), message).to_compile_error().into();
+        }
+    };
+    let cells = match parse_intcode_text(&text) {
+        Ok(cells) => cells,
+        Err(message) => {
+            let message = format!("{:?}: {}", path_literal.value(), message);
+            return syn::Error::new(path_literal.span(), message).to_compile_error().into();
+        }
+    };
 
+    let cells = cells.into_iter().map(proc_macro2::Literal::i128_unsuffixed);
+    // include_str! resolves relative paths against the invoking file, but
+    // local_file can be relative to the compiler's working directory.
+    let path = std::fs::canonicalize(&path).unwrap_or(path).to_string_lossy().into_owned();
+    let output = quote! {
+        {
+            // Makes cargo rebuild when the program changes.
+            const _: &str = include_str!(#path);
+            const CELLS: &[::advent_of_code_2019::intcode::EmulatorMemoryType] = &[#(#cells),*];
+            CELLS
+        }
+    };
     proc_macro::TokenStream::from(output)
 }
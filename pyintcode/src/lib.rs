@@ -0,0 +1,88 @@
+use advent_of_code_2019::intcode::{self, Emulator, EmulatorMemoryType, EmulatorResult};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+create_exception!(pyintcode, EmulatorError, PyException);
+
+fn to_py_err(error: intcode::EmulatorError) -> PyErr {
+    EmulatorError::new_err(error.to_string())
+}
+
+struct QueueInput(Rc<RefCell<VecDeque<EmulatorMemoryType>>>);
+
+impl Iterator for QueueInput {
+    type Item = Result<EmulatorMemoryType, intcode::EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.borrow_mut().pop_front().map(Ok)
+    }
+}
+
+#[pyclass(name = "Emulator", unsendable)]
+struct PyEmulator {
+    emulator: Emulator<QueueInput>,
+    inputs: Rc<RefCell<VecDeque<EmulatorMemoryType>>>,
+    outputs: Vec<EmulatorMemoryType>,
+}
+
+#[pymethods]
+impl PyEmulator {
+    #[new]
+    fn new(program: Vec<EmulatorMemoryType>) -> PyEmulator {
+        let inputs = Rc::new(RefCell::new(VecDeque::new()));
+        PyEmulator {
+            emulator: Emulator::new(&program, QueueInput(inputs.clone())),
+            inputs,
+            outputs: Vec::new(),
+        }
+    }
+
+    fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.inputs.borrow_mut().push_back(value);
+    }
+
+    // One of "success", "output", "done" or "needs_input". An Input
+    // instruction with nothing queued leaves the machine where it was, so
+    // stepping again after push_input picks up from the same instruction.
+    // Watchpoints can't be set from Python, but an instruction that hits
+    // one still ran, so it's a success.
+    fn step(&mut self) -> PyResult<&'static str> {
+        match self.emulator.step().map_err(to_py_err)? {
+            EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => Ok("success"),
+            EmulatorResult::SuccessWithValue(value) => {
+                self.outputs.push(value);
+                Ok("output")
+            }
+            EmulatorResult::Done => Ok("done"),
+            EmulatorResult::NeedsInput => Ok("needs_input"),
+        }
+    }
+
+    // None once the program halts. Running out of input raises
+    // EmulatorError, as there's no output to wait for until more is pushed.
+    fn run_until_output(&mut self) -> PyResult<Option<EmulatorMemoryType>> {
+        let output = self.emulator.run_until_output().map_err(to_py_err)?;
+        self.outputs.extend(output);
+        Ok(output)
+    }
+
+    fn outputs(&self) -> Vec<EmulatorMemoryType> {
+        self.outputs.clone()
+    }
+
+    #[getter]
+    fn memory(&self) -> Vec<EmulatorMemoryType> {
+        self.emulator.memory().into()
+    }
+}
+
+#[pymodule]
+fn pyintcode(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEmulator>()?;
+    m.add("EmulatorError", m.py().get_type::<EmulatorError>())?;
+    Ok(())
+}
@@ -0,0 +1,42 @@
+# Run with `maturin develop && pytest tests` from the pyintcode directory.
+import pytest
+
+from pyintcode import Emulator, EmulatorError
+
+EQUALS_EIGHT = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]
+
+
+@pytest.mark.parametrize("value, expected", [(7, 0), (8, 1), (9, 0)])
+def test_equals_with_position_mode(value, expected):
+    emulator = Emulator(EQUALS_EIGHT)
+    emulator.push_input(value)
+    assert emulator.run_until_output() == expected
+    assert emulator.run_until_output() is None
+    assert emulator.outputs() == [expected]
+
+
+def test_step_waits_for_input():
+    emulator = Emulator(EQUALS_EIGHT)
+    assert emulator.step() == "needs_input"
+    assert emulator.step() == "needs_input"
+    emulator.push_input(8)
+    assert emulator.step() == "success"
+    assert emulator.memory[9] == 8
+    assert emulator.step() == "success"
+    assert emulator.step() == "output"
+    assert emulator.step() == "done"
+    assert emulator.outputs() == [1]
+
+
+def test_run_until_output_raises_without_input():
+    emulator = Emulator(EQUALS_EIGHT)
+    with pytest.raises(EmulatorError, match="Input non existent"):
+        emulator.run_until_output()
+    emulator.push_input(8)
+    assert emulator.run_until_output() == 1
+
+
+def test_errors_carry_the_emulator_message():
+    emulator = Emulator([1, -1, 0, 0, 99])
+    with pytest.raises(EmulatorError, match="Invalid memory location -1 referenced at 1"):
+        emulator.step()
@@ -0,0 +1,161 @@
+use super::intcode::EmulatorMemoryType;
+use super::program::{self, ProgramLoadError};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+pub type Program = Vec<EmulatorMemoryType>;
+
+type LoadFn<T, E> = dyn Fn(&Path) -> Result<T, E> + Send + Sync;
+
+struct Entry<T> {
+    modified: Option<SystemTime>,
+    value: Arc<T>,
+    loads: u64,
+}
+
+// Parsed inputs keyed by canonical path, so each file is read and parsed
+// once per process. An entry is reloaded when the file's modification time
+// changes.
+pub struct ProgramCache<T = Program, E = ProgramLoadError> {
+    loader: Box<LoadFn<T, E>>,
+    entries: Mutex<HashMap<PathBuf, Entry<T>>>,
+    bypass: bool,
+}
+
+impl ProgramCache {
+    pub fn new() -> ProgramCache {
+        ProgramCache::with_loader(|path: &Path| program::load_program(path))
+    }
+}
+
+impl Default for ProgramCache {
+    fn default() -> ProgramCache {
+        ProgramCache::new()
+    }
+}
+
+impl<T, E: From<io::Error>> ProgramCache<T, E> {
+    pub fn with_loader<F: Fn(&Path) -> Result<T, E> + Send + Sync + 'static>(
+        loader: F,
+    ) -> ProgramCache<T, E> {
+        ProgramCache {
+            loader: Box::new(loader),
+            entries: Mutex::new(HashMap::new()),
+            bypass: false,
+        }
+    }
+
+    // Loads on every call, for tests that rewrite their inputs.
+    pub fn bypass(mut self) -> ProgramCache<T, E> {
+        self.bypass = true;
+        self
+    }
+
+    pub fn get_or_load<P: AsRef<Path>>(&self, path: P) -> Result<Arc<T>, E> {
+        let path = path.as_ref().canonicalize()?;
+        let modified = std::fs::metadata(&path)?.modified().ok();
+        // Held while loading, so concurrent callers don't parse twice.
+        let mut entries = self.entries.lock().unwrap();
+        let loads = match entries.get(&path) {
+            Some(entry) if !self.bypass && entry.modified == modified => {
+                return Ok(entry.value.clone())
+            }
+            Some(entry) => entry.loads,
+            None => 0,
+        };
+        let value = Arc::new((self.loader)(&path)?);
+        entries.insert(
+            path,
+            Entry {
+                modified,
+                value: value.clone(),
+                loads: loads + 1,
+            },
+        );
+        Ok(value)
+    }
+
+    // How many times path has been loaded, for checking the cache works.
+    pub fn loads<P: AsRef<Path>>(&self, path: P) -> u64 {
+        let entries = self.entries.lock().unwrap();
+        path.as_ref()
+            .canonicalize()
+            .ok()
+            .and_then(|path| entries.get(&path).map(|entry| entry.loads))
+            .unwrap_or(0)
+    }
+}
+
+// The cache behind get_intcode_memory_from_file.
+pub fn programs() -> &'static ProgramCache {
+    static PROGRAMS: OnceLock<ProgramCache> = OnceLock::new();
+    PROGRAMS.get_or_init(ProgramCache::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("aoc_cache_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_each_file_is_parsed_once() {
+        let parses = Arc::new(AtomicU64::new(0));
+        let counter = parses.clone();
+        let cache = ProgramCache::with_loader(move |path| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            program::load_program(path)
+        });
+        let path = temp_file("once.txt", "1,0,0,0,99");
+        let first = cache.get_or_load(&path).unwrap();
+        let second = cache.get_or_load(&path).unwrap();
+        assert_eq!(vec![1, 0, 0, 0, 99], *first);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(1, parses.load(Ordering::SeqCst));
+        assert_eq!(1, cache.loads(&path));
+
+        let bypassing = ProgramCache::new().bypass();
+        bypassing.get_or_load(&path).unwrap();
+        bypassing.get_or_load(&path).unwrap();
+        assert_eq!(2, bypassing.loads(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_modified_file_is_reloaded() {
+        let path = temp_file("mtime.txt", "99");
+        let cache = ProgramCache::new();
+        assert_eq!(vec![99], *cache.get_or_load(&path).unwrap());
+
+        std::fs::write(&path, "104,1,99").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(vec![104, 1, 99], *cache.get_or_load(&path).unwrap());
+        assert_eq!(2, cache.loads(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_second_part_reuses_the_program() {
+        let path = temp_file("day5.txt", &std::fs::read_to_string("input/5.txt").unwrap());
+        let path = path.to_str().unwrap();
+        crate::day5::run_part_1(path).unwrap();
+        crate::day5::run_part_2(path).unwrap();
+        assert_eq!(1, programs().loads(path));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,28 +1,41 @@
+use super::cache::ProgramCache;
+use super::parse::{parse_lines, LineParseError};
+use super::solution::{bad_input, check_not_empty, AocError, Example, Part, Solution};
 use std::fs::File;
-use std::io::prelude::*;
-
-pub fn run_part_1(path: &str) -> u64 {
-    let input_file = File::open(path).unwrap();
-    let reader = std::io::BufReader::new(input_file);
-    reader
-        .lines()
-        .map(|s| s.unwrap().parse::<u64>().unwrap())
+use std::sync::{Arc, OnceLock};
+
+fn read_masses(path: &str) -> Result<Arc<Vec<u64>>, AocError> {
+    static MASSES: OnceLock<ProgramCache<Vec<u64>, LineParseError>> = OnceLock::new();
+    // An empty list would otherwise quietly need no fuel.
+    check_not_empty(1, path)?;
+    MASSES
+        .get_or_init(|| {
+            ProgramCache::with_loader(|path| {
+                parse_lines(std::io::BufReader::new(File::open(path)?))
+            })
+        })
+        .get_or_load(path)
+        .map_err(|error| bad_input(1, path, error))
+}
+
+pub fn run_part_1(path: &str) -> Result<u64, AocError> {
+    Ok(read_masses(path)?
+        .iter()
+        .copied()
         .map(calculate_required_fuel_naive)
-        .sum()
+        .sum())
 }
 
 fn calculate_required_fuel_naive(mass: u64) -> u64 {
     (mass / 3).saturating_sub(2)
 }
 
-pub fn run_part_2(path: &str) -> u64 {
-    let input_file = File::open(path).unwrap();
-    let reader = std::io::BufReader::new(input_file);
-    reader
-        .lines()
-        .map(|s| s.unwrap().parse::<u64>().unwrap())
+pub fn run_part_2(path: &str) -> Result<u64, AocError> {
+    Ok(read_masses(path)?
+        .iter()
+        .copied()
         .map(calculate_required_fuel_with_wish)
-        .sum()
+        .sum())
 }
 
 fn calculate_required_fuel_with_wish(mass: u64) -> u64 {
@@ -38,3 +51,31 @@ fn calculate_required_fuel_with_wish(mass: u64) -> u64 {
     } {}
     total_mass
 }
+
+pub struct Day1;
+
+impl Solution for Day1 {
+    fn day(&self) -> u32 {
+        1
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(Part::One, "12\n", "2"),
+            Example::new(Part::One, "14\n", "2"),
+            Example::new(Part::One, "1969\n", "654"),
+            Example::new(Part::One, "100756\n", "33583"),
+            Example::new(Part::Two, "14\n", "2"),
+            Example::new(Part::Two, "1969\n", "966"),
+            Example::new(Part::Two, "100756\n", "50346"),
+        ]
+    }
+}
@@ -0,0 +1,216 @@
+use super::modmath::lcm;
+use super::search::{find_cycle, CycleInfo};
+use super::solution::{read_input, AocError, Example, Part, Solution};
+
+const PART_1_STEPS: usize = 1000;
+
+pub type Vector = [i64; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Moon {
+    pub position: Vector,
+    pub velocity: Vector,
+}
+
+impl Moon {
+    // <x=-1, y=0, z=2>
+    pub fn parse(line: &str) -> Result<Moon, String> {
+        let line = line.trim();
+        let inner = line
+            .strip_prefix('<')
+            .and_then(|rest| rest.strip_suffix('>'))
+            .ok_or_else(|| format!("'{}' is not wrapped in <>", line))?;
+        let mut position = [0; 3];
+        let mut fields = inner.split(',');
+        for (axis, name) in ["x", "y", "z"].iter().enumerate() {
+            let field = fields
+                .next()
+                .ok_or_else(|| format!("'{}' has no {}", line, name))?;
+            let mut split = field.splitn(2, '=');
+            if split.next().map(str::trim) != Some(*name) {
+                return Err(format!("expected {} in '{}'", name, line));
+            }
+            position[axis] = split
+                .next()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| format!("{} is not a number in '{}'", name, line))?;
+        }
+        if fields.next().is_some() {
+            return Err(format!("'{}' has more than three coordinates", line));
+        }
+        Ok(Moon {
+            position,
+            velocity: [0; 3],
+        })
+    }
+
+    pub fn energy(&self) -> i64 {
+        let sum = |vector: &Vector| vector.iter().map(|c| c.abs()).sum::<i64>();
+        sum(&self.position) * sum(&self.velocity)
+    }
+}
+
+pub fn parse_moons(text: &str) -> Result<Vec<Moon>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Moon::parse)
+        .collect()
+}
+
+// One axis of every moon, as (position, velocity). The axes never interact,
+// so each can be simulated on its own.
+fn step_axis(axis: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    axis.iter()
+        .map(|&(position, velocity)| {
+            let pull: i64 = axis
+                .iter()
+                .map(|&(other, _)| (other - position).signum())
+                .sum();
+            (position + velocity + pull, velocity + pull)
+        })
+        .collect()
+}
+
+fn axis(moons: &[Moon], axis: usize) -> Vec<(i64, i64)> {
+    moons
+        .iter()
+        .map(|moon| (moon.position[axis], moon.velocity[axis]))
+        .collect()
+}
+
+pub fn simulate(moons: &[Moon], steps: usize) -> Vec<Moon> {
+    let mut moons = moons.to_vec();
+    for index in 0..3 {
+        let mut state = axis(&moons, index);
+        for _ in 0..steps {
+            state = step_axis(&state);
+        }
+        for (moon, (position, velocity)) in moons.iter_mut().zip(state) {
+            moon.position[index] = position;
+            moon.velocity[index] = velocity;
+        }
+    }
+    moons
+}
+
+pub fn total_energy(moons: &[Moon]) -> i64 {
+    moons.iter().map(Moon::energy).sum()
+}
+
+pub fn axis_cycles(moons: &[Moon]) -> [CycleInfo; 3] {
+    let mut cycles = [CycleInfo {
+        start: 0,
+        period: 0,
+    }; 3];
+    for (index, cycle) in cycles.iter_mut().enumerate() {
+        *cycle = find_cycle(axis(moons, index), |state| step_axis(state));
+    }
+    cycles
+}
+
+// The whole system repeats once every axis is inside its cycle and they
+// line up. The steps are reversible, so in practice every start is 0.
+pub fn steps_until_repeat(moons: &[Moon]) -> u128 {
+    let cycles = axis_cycles(moons);
+    let start = cycles.iter().map(|cycle| cycle.start).max().unwrap_or(0);
+    let period = cycles
+        .iter()
+        .fold(1, |period, cycle| lcm(period, u128::from(cycle.period)));
+    u128::from(start) + period
+}
+
+pub fn run_part_1(path: &str) -> Result<i64, AocError> {
+    let moons = read_input(12, path, parse_moons)?;
+    Ok(total_energy(&simulate(&moons, PART_1_STEPS)))
+}
+
+pub fn run_part_2(path: &str) -> Result<u128, AocError> {
+    Ok(steps_until_repeat(&read_input(12, path, parse_moons)?))
+}
+
+const FIRST_EXAMPLE: &str = "\
+<x=-1, y=0, z=2>
+<x=2, y=-10, z=-7>
+<x=4, y=-8, z=8>
+<x=3, y=5, z=-1>
+";
+
+const SECOND_EXAMPLE: &str = "\
+<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>
+";
+
+pub struct Day12;
+
+impl Solution for Day12 {
+    fn day(&self) -> u32 {
+        12
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+
+    // Part 1's examples stop after 10 and 100 steps rather than 1000, so
+    // they are checked in the tests instead.
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(Part::Two, FIRST_EXAMPLE, "2772"),
+            Example::new(Part::Two, SECOND_EXAMPLE, "4686774924"),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moons(text: &str) -> Vec<Moon> {
+        parse_moons(text).unwrap()
+    }
+
+    #[test]
+    fn test_energy_after_steps() {
+        let after = simulate(&moons(FIRST_EXAMPLE), 10);
+        assert_eq!(
+            Moon {
+                position: [2, 1, -3],
+                velocity: [-3, -2, 1],
+            },
+            after[0]
+        );
+        assert_eq!(179, total_energy(&after));
+        assert_eq!(1940, total_energy(&simulate(&moons(SECOND_EXAMPLE), 100)));
+    }
+
+    #[test]
+    fn test_axis_cycles() {
+        let cycles = axis_cycles(&moons(FIRST_EXAMPLE));
+        assert!(cycles.iter().all(|cycle| cycle.start == 0));
+        assert_eq!(
+            vec![18, 28, 44],
+            cycles.iter().map(|cycle| cycle.period).collect::<Vec<_>>()
+        );
+        assert_eq!(2772, steps_until_repeat(&moons(FIRST_EXAMPLE)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err("'x=1, y=2, z=3' is not wrapped in <>".to_string()),
+            Moon::parse("x=1, y=2, z=3")
+        );
+        assert_eq!(
+            Err("y is not a number in '<x=1, y=q, z=3>'".to_string()),
+            Moon::parse("<x=1, y=q, z=3>")
+        );
+        assert!(Moon::parse("<x=1, y=2>").is_err());
+        assert!(Moon::parse("<x=1, z=2, y=3>").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use super::grid::{Position, SparseGrid};
+use super::intcode::cpu::{Cpu, CpuEvent};
+use super::intcode::*;
+
+pub mod render;
+#[cfg(feature = "terminal")]
+pub use render::play_rendered;
+
+// The arcade game loop. The puzzle's own program runs through IntcodeGame;
+// the input isn't checked in, so the tests script a GameBackend instead.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl Tile {
+    fn from_id(id: EmulatorMemoryType) -> Option<Tile> {
+        match id {
+            0 => Some(Tile::Empty),
+            1 => Some(Tile::Wall),
+            2 => Some(Tile::Block),
+            3 => Some(Tile::Paddle),
+            4 => Some(Tile::Ball),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Screen {
+    pub tiles: SparseGrid<Tile>,
+    pub score: EmulatorMemoryType,
+    ball: Option<Position>,
+    paddle: Option<Position>,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen::default()
+    }
+
+    // Outputs come in (x, y, tile id) triples, except (-1, 0, score).
+    // Unknown tile ids and positions off the grid are ignored.
+    fn draw(&mut self, triple: &[EmulatorMemoryType]) {
+        let (x, y, value) = (triple[0], triple[1], triple[2]);
+        if (x, y) == (-1, 0) {
+            self.score = value;
+            return;
+        }
+        let (x, y) = match (cell_to_i64(x), cell_to_i64(y)) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return,
+        };
+        if let Some(tile) = Tile::from_id(value) {
+            match tile {
+                Tile::Ball => self.ball = Some((x, y)),
+                Tile::Paddle => self.paddle = Some((x, y)),
+                _ => {}
+            }
+            self.tiles.insert((x, y), tile);
+        }
+    }
+
+    pub fn ball(&self) -> Option<Position> {
+        self.ball
+    }
+
+    pub fn paddle(&self) -> Option<Position> {
+        self.paddle
+    }
+}
+
+pub const JOYSTICK_LEFT: EmulatorMemoryType = -1;
+pub const JOYSTICK_NEUTRAL: EmulatorMemoryType = 0;
+pub const JOYSTICK_RIGHT: EmulatorMemoryType = 1;
+
+pub trait JoystickStrategy {
+    fn decide(&mut self, screen: &Screen, ball: Position, paddle: Position) -> EmulatorMemoryType;
+}
+
+// Keeps the paddle under the ball.
+pub struct FollowBall;
+
+impl JoystickStrategy for FollowBall {
+    fn decide(&mut self, _: &Screen, ball: Position, paddle: Position) -> EmulatorMemoryType {
+        EmulatorMemoryType::from((ball.0 - paddle.0).signum())
+    }
+}
+
+// Plays back fixed moves, then holds the joystick neutral.
+pub struct Scripted(pub Vec<EmulatorMemoryType>);
+
+impl JoystickStrategy for Scripted {
+    fn decide(&mut self, _: &Screen, _: Position, _: Position) -> EmulatorMemoryType {
+        if self.0.is_empty() {
+            JOYSTICK_NEUTRAL
+        } else {
+            self.0.remove(0)
+        }
+    }
+}
+
+// Wraps another strategy and keeps every decision it made, so a game can be
+// replayed with Scripted.
+pub struct Recording<T> {
+    pub inner: T,
+    decisions: Vec<EmulatorMemoryType>,
+}
+
+impl<T: JoystickStrategy> Recording<T> {
+    pub fn new(inner: T) -> Recording<T> {
+        Recording {
+            inner,
+            decisions: Vec::new(),
+        }
+    }
+
+    pub fn decisions(&self) -> &[EmulatorMemoryType] {
+        &self.decisions
+    }
+
+    pub fn into_script(self) -> Scripted {
+        Scripted(self.decisions)
+    }
+}
+
+impl<T: JoystickStrategy> JoystickStrategy for Recording<T> {
+    fn decide(&mut self, screen: &Screen, ball: Position, paddle: Position) -> EmulatorMemoryType {
+        let decision = self.inner.decide(screen, ball, paddle);
+        self.decisions.push(decision);
+        decision
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+    NeedsInput,
+    Halted,
+}
+
+pub trait GameBackend {
+    // Runs until the game wants the joystick or halts, appending every
+    // output to outputs.
+    fn run(&mut self, outputs: &mut Vec<EmulatorMemoryType>) -> Result<GameState, EmulatorError>;
+    fn push_joystick(&mut self, position: EmulatorMemoryType);
+}
+
+// Runs the game on any Cpu, the Emulator by default.
+pub struct IntcodeGame<C = Emulator<QueueInput>> {
+    cpu: C,
+}
+
+impl IntcodeGame {
+    pub fn new(program: &[EmulatorMemoryType]) -> IntcodeGame {
+        IntcodeGame::with_cpu(Emulator::with_queue(program))
+    }
+}
+
+impl<C: Cpu> IntcodeGame<C> {
+    pub fn with_cpu(cpu: C) -> IntcodeGame<C> {
+        IntcodeGame { cpu }
+    }
+}
+
+impl<C: Cpu> GameBackend for IntcodeGame<C> {
+    fn run(&mut self, outputs: &mut Vec<EmulatorMemoryType>) -> Result<GameState, EmulatorError> {
+        loop {
+            match self.cpu.run_until_event()? {
+                CpuEvent::Output(value) => outputs.push(value),
+                CpuEvent::NeedsInput { .. } => return Ok(GameState::NeedsInput),
+                CpuEvent::Halted => return Ok(GameState::Halted),
+                CpuEvent::Watchpoint { .. } => {}
+            }
+        }
+    }
+
+    fn push_joystick(&mut self, position: EmulatorMemoryType) {
+        self.cpu.push_input(position);
+    }
+}
+
+// Plays until the game halts, asking the strategy for exactly one joystick
+// position each time the game wants one. The ball and paddle are reported at
+// the origin until they have been drawn.
+pub fn play_with_strategy<B: GameBackend, S: JoystickStrategy>(
+    backend: &mut B,
+    strategy: &mut S,
+) -> Result<Screen, EmulatorError> {
+    play_frames(backend, strategy, |_| Ok(()))
+}
+
+// play_with_strategy, with the screen handed to frame each time the game
+// stops for the joystick or halts, before the strategy sees it.
+fn play_frames<B, S, E>(
+    backend: &mut B,
+    strategy: &mut S,
+    mut frame: impl FnMut(&Screen) -> Result<(), E>,
+) -> Result<Screen, E>
+where
+    B: GameBackend,
+    S: JoystickStrategy,
+    E: From<EmulatorError>,
+{
+    let mut screen = Screen::new();
+    let mut outputs = Vec::new();
+    loop {
+        let state = backend.run(&mut outputs)?;
+        let complete = outputs.len() - outputs.len() % 3;
+        for triple in outputs[..complete].chunks(3) {
+            screen.draw(triple);
+        }
+        outputs.drain(..complete);
+        frame(&screen)?;
+
+        match state {
+            GameState::Halted => return Ok(screen),
+            GameState::NeedsInput => {
+                let ball = screen.ball.unwrap_or((0, 0));
+                let paddle = screen.paddle.unwrap_or((0, 0));
+                let decision = strategy.decide(&screen, ball, paddle);
+                backend.push_joystick(decision);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::cpu::EvalCpu;
+
+    // The ball visits BALL_PATH one x per frame along y = 0 while the paddle
+    // moves along y = 1. Each frame the paddle ends under the ball scores a
+    // point.
+    const BALL_PATH: [EmulatorMemoryType; 6] = [1, 2, 2, 2, 1, 0];
+
+    struct ScriptedGame {
+        frame: usize,
+        paddle: EmulatorMemoryType,
+        score: EmulatorMemoryType,
+        joystick: Option<EmulatorMemoryType>,
+        inputs_requested: usize,
+    }
+
+    impl ScriptedGame {
+        fn new() -> ScriptedGame {
+            ScriptedGame {
+                frame: 0,
+                paddle: 0,
+                score: 0,
+                joystick: None,
+                inputs_requested: 0,
+            }
+        }
+    }
+
+    impl GameBackend for ScriptedGame {
+        fn run(
+            &mut self,
+            outputs: &mut Vec<EmulatorMemoryType>,
+        ) -> Result<GameState, EmulatorError> {
+            if self.frame == 0 {
+                outputs.extend_from_slice(&[-1, 1, 1, 4, 1, 1, 0, 0, 4, 0, 1, 3]);
+            } else {
+                let joystick = self
+                    .joystick
+                    .take()
+                    // No program, so no instruction to point at.
+                    .ok_or(EmulatorError::InputNonExistent {
+                        position: 0,
+                        context: None,
+                    })?;
+                outputs.extend_from_slice(&[self.paddle, 1, 0]);
+                self.paddle += joystick;
+                let ball = BALL_PATH[self.frame - 1];
+                outputs.extend_from_slice(&[ball, 0, 4, self.paddle, 1, 3]);
+                if ball == self.paddle {
+                    self.score += 1;
+                    outputs.extend_from_slice(&[-1, 0, self.score]);
+                }
+            }
+
+            if self.frame == BALL_PATH.len() {
+                return Ok(GameState::Halted);
+            }
+            self.frame += 1;
+            self.inputs_requested += 1;
+            Ok(GameState::NeedsInput)
+        }
+
+        fn push_joystick(&mut self, position: EmulatorMemoryType) {
+            assert_eq!(None, self.joystick.replace(position));
+        }
+    }
+
+    #[test]
+    fn test_scripted_strategy_scores_deterministically() {
+        let mut game = ScriptedGame::new();
+        let mut strategy = Scripted(vec![1, 1, 0, 0, -1, -1]);
+        let screen = play_with_strategy(&mut game, &mut strategy).unwrap();
+        assert_eq!(6, screen.score);
+        assert_eq!(Some((0, 1)), screen.paddle());
+        assert_eq!(Some((0, 0)), screen.ball());
+        assert_eq!(Some(&Tile::Wall), screen.tiles.get((-1, 1)));
+        assert_eq!(Some(&Tile::Empty), screen.tiles.get((2, 1)));
+    }
+
+    #[test]
+    fn test_recording_follow_ball_replays_identically() {
+        let mut game = ScriptedGame::new();
+        let mut recording = Recording::new(FollowBall);
+        let screen = play_with_strategy(&mut game, &mut recording).unwrap();
+        assert_eq!(game.inputs_requested, recording.decisions().len());
+        // The ball has already moved on by the time the paddle catches up.
+        assert_eq!(&[0, 1, 1, 0, 0, -1], recording.decisions());
+        assert_eq!(2, screen.score);
+
+        let mut replayed_game = ScriptedGame::new();
+        let replayed =
+            play_with_strategy(&mut replayed_game, &mut recording.into_script()).unwrap();
+        assert_eq!(screen, replayed);
+    }
+
+    #[test]
+    fn test_strategy_is_asked_once_per_input() {
+        struct Counting(usize);
+        impl JoystickStrategy for Counting {
+            fn decide(&mut self, _: &Screen, _: Position, _: Position) -> EmulatorMemoryType {
+                self.0 += 1;
+                JOYSTICK_RIGHT
+            }
+        }
+
+        let mut game = ScriptedGame::new();
+        let mut strategy = Counting(0);
+        play_with_strategy(&mut game, &mut strategy).unwrap();
+        assert_eq!(BALL_PATH.len(), strategy.0);
+        assert_eq!(game.inputs_requested, strategy.0);
+    }
+
+    #[test]
+    fn test_intcode_game() {
+        // Draws the ball and paddle, then reports the joystick as the score.
+        let mut program = vec![
+            104, 2, 104, 0, 104, 4, 104, 0, 104, 1, 104, 3, 3, 30, 104, -1, 104, 0, 4, 30, 99,
+        ];
+        program.resize(31, 0);
+        let mut game = IntcodeGame::new(&program);
+        let screen = play_with_strategy(&mut game, &mut FollowBall).unwrap();
+        assert_eq!(JOYSTICK_RIGHT, screen.score);
+
+        let mut game = IntcodeGame::with_cpu(EvalCpu::new(&program));
+        assert_eq!(
+            screen,
+            play_with_strategy(&mut game, &mut FollowBall).unwrap()
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use super::{GameBackend, JoystickStrategy, Tile};
+use crate::grid::{Position, SparseGrid};
+use crate::intcode::EmulatorMemoryType;
+use crate::solution::AocError;
+use std::io;
+use std::time::{Duration, Instant};
+
+// Where play_on_screen draws the arcade. Not to be confused with
+// day13::Screen, the game's own record of the tiles: this is only handed
+// the cells that changed.
+pub trait Screen {
+    fn draw_tile(&mut self, position: Position, tile: Tile) -> io::Result<()>;
+    fn draw_score(&mut self, score: EmulatorMemoryType) -> io::Result<()>;
+    // Ends a frame, showing everything drawn since the last one.
+    fn present(&mut self) -> io::Result<()>;
+}
+
+pub const DEFAULT_FRAMES_PER_SECOND: u32 = 60;
+
+// Keeps frames at least an interval apart. A late frame isn't made up for
+// by hurrying the ones after it.
+pub struct FrameLimiter {
+    interval: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    // 0 frames per second means no limit at all.
+    pub fn new(frames_per_second: u32) -> FrameLimiter {
+        let interval = match frames_per_second {
+            0 => Duration::ZERO,
+            frames_per_second => Duration::from_secs(1) / frames_per_second,
+        };
+        FrameLimiter {
+            interval,
+            next_frame: None,
+        }
+    }
+
+    // How long a frame ready at now has to wait before it's shown.
+    fn delay(&mut self, now: Instant) -> Duration {
+        let shown = match self.next_frame {
+            Some(next_frame) if next_frame > now => next_frame,
+            _ => now,
+        };
+        self.next_frame = Some(shown + self.interval);
+        shown - now
+    }
+
+    fn wait(&mut self) {
+        let delay = self.delay(Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+// What the screen is showing, so a frame only draws what differs from it.
+#[derive(Default)]
+struct Drawn {
+    tiles: SparseGrid<Tile>,
+    score: Option<EmulatorMemoryType>,
+}
+
+impl Drawn {
+    // Changed cells go row by row, then the score if it changed.
+    fn draw_changes<D: Screen>(&mut self, game: &super::Screen, screen: &mut D) -> io::Result<()> {
+        let mut changed: Vec<(Position, Tile)> = game
+            .tiles
+            .iter()
+            .filter(|&(position, tile)| self.tiles.get(position) != Some(tile))
+            .map(|(position, &tile)| (position, tile))
+            .collect();
+        changed.sort_by_key(|&((x, y), _)| (y, x));
+        for (position, tile) in changed {
+            screen.draw_tile(position, tile)?;
+            self.tiles.insert(position, tile);
+        }
+
+        if self.score != Some(game.score) {
+            screen.draw_score(game.score)?;
+            self.score = Some(game.score);
+        }
+        Ok(())
+    }
+}
+
+fn terminal_error(error: io::Error) -> AocError {
+    AocError::Terminal(error.to_string())
+}
+
+// Plays like play_with_strategy, drawing a frame on screen each time the
+// game stops for the joystick, and once more when it halts. Returns the
+// final score.
+pub fn play_on_screen<B, S, D>(
+    backend: &mut B,
+    strategy: &mut S,
+    screen: &mut D,
+    limiter: &mut FrameLimiter,
+) -> Result<EmulatorMemoryType, AocError>
+where
+    B: GameBackend,
+    S: JoystickStrategy,
+    D: Screen,
+{
+    let mut drawn = Drawn::default();
+    let game = super::play_frames(backend, strategy, |game| {
+        drawn.draw_changes(game, screen).map_err(terminal_error)?;
+        limiter.wait();
+        screen.present().map_err(terminal_error)
+    })?;
+    Ok(game.score)
+}
+
+// Two quarters in address 0 set the game to free play, so it runs until
+// the blocks are gone rather than drawing the board and halting.
+#[cfg(feature = "terminal")]
+const FREE_PLAY: EmulatorMemoryType = 2;
+
+#[cfg(feature = "terminal")]
+pub fn play_rendered(program: &[EmulatorMemoryType]) -> Result<EmulatorMemoryType, AocError> {
+    play_rendered_at(program, DEFAULT_FRAMES_PER_SECOND)
+}
+
+// Lets the game play itself on free play, following the ball, and returns
+// the final score. When stdout isn't a terminal nothing is drawn, and the
+// game runs as fast as it can.
+#[cfg(feature = "terminal")]
+pub fn play_rendered_at(
+    program: &[EmulatorMemoryType],
+    frames_per_second: u32,
+) -> Result<EmulatorMemoryType, AocError> {
+    use super::{play_with_strategy, FollowBall, IntcodeGame};
+    use std::io::IsTerminal;
+
+    let mut program = program.to_vec();
+    if let Some(quarters) = program.first_mut() {
+        *quarters = FREE_PLAY;
+    }
+    let mut game = IntcodeGame::new(&program);
+    if !io::stdout().is_terminal() {
+        return Ok(play_with_strategy(&mut game, &mut FollowBall)?.score);
+    }
+
+    let mut screen = TerminalScreen::new().map_err(terminal_error)?;
+    let mut limiter = FrameLimiter::new(frames_per_second);
+    play_on_screen(&mut game, &mut FollowBall, &mut screen, &mut limiter)
+}
+
+// Draws on stdout from its top left corner, with the score on a line of its
+// own under the board. The board is left on the terminal when this is
+// dropped, with the cursor below it.
+#[cfg(feature = "terminal")]
+pub struct TerminalScreen {
+    out: io::Stdout,
+    // One past the lowest row of tiles drawn so far.
+    rows: u16,
+}
+
+#[cfg(feature = "terminal")]
+impl TerminalScreen {
+    pub fn new() -> io::Result<TerminalScreen> {
+        use crossterm::cursor::Hide;
+        use crossterm::terminal::{Clear, ClearType};
+
+        let mut out = io::stdout();
+        crossterm::execute!(out, Hide, Clear(ClearType::All))?;
+        Ok(TerminalScreen { out, rows: 0 })
+    }
+
+    fn score_row(&self) -> u16 {
+        self.rows.saturating_add(1)
+    }
+
+    fn glyph(tile: Tile) -> char {
+        match tile {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '=',
+            Tile::Paddle => '-',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl Screen for TerminalScreen {
+    fn draw_tile(&mut self, (x, y): Position, tile: Tile) -> io::Result<()> {
+        use crossterm::cursor::MoveTo;
+        use crossterm::style::Print;
+        use std::convert::TryFrom;
+
+        // Tiles the terminal can't address aren't drawn.
+        let (x, y) = match (u16::try_from(x), u16::try_from(y)) {
+            (Ok(x), Ok(y)) => (x, y),
+            _ => return Ok(()),
+        };
+        self.rows = self.rows.max(y.saturating_add(1));
+        crossterm::queue!(self.out, MoveTo(x, y), Print(TerminalScreen::glyph(tile)))
+    }
+
+    fn draw_score(&mut self, score: EmulatorMemoryType) -> io::Result<()> {
+        use crossterm::cursor::MoveTo;
+        use crossterm::style::Print;
+        use crossterm::terminal::{Clear, ClearType};
+
+        let row = self.score_row();
+        crossterm::queue!(
+            self.out,
+            MoveTo(0, row),
+            Clear(ClearType::CurrentLine),
+            Print(format!("Score: {}", score))
+        )
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.out)
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl Drop for TerminalScreen {
+    fn drop(&mut self) {
+        use crossterm::cursor::{MoveTo, Show};
+
+        let row = self.score_row().saturating_add(1);
+        let _ = crossterm::execute!(self.out, MoveTo(0, row), Show);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day13::{GameState, Scripted};
+    use crate::intcode::EmulatorError;
+
+    #[derive(Debug, PartialEq)]
+    enum Draw {
+        Tile(Position, Tile),
+        Score(EmulatorMemoryType),
+        Present,
+    }
+
+    #[derive(Default)]
+    struct MockScreen {
+        draws: Vec<Draw>,
+        fail_to_present: bool,
+    }
+
+    impl Screen for MockScreen {
+        fn draw_tile(&mut self, position: Position, tile: Tile) -> io::Result<()> {
+            self.draws.push(Draw::Tile(position, tile));
+            Ok(())
+        }
+
+        fn draw_score(&mut self, score: EmulatorMemoryType) -> io::Result<()> {
+            self.draws.push(Draw::Score(score));
+            Ok(())
+        }
+
+        fn present(&mut self) -> io::Result<()> {
+            if self.fail_to_present {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+            }
+            self.draws.push(Draw::Present);
+            Ok(())
+        }
+    }
+
+    // Hands out one frame's outputs per run, and halts after the last.
+    struct Frames(Vec<Vec<EmulatorMemoryType>>);
+
+    impl GameBackend for Frames {
+        fn run(
+            &mut self,
+            outputs: &mut Vec<EmulatorMemoryType>,
+        ) -> Result<GameState, EmulatorError> {
+            outputs.extend(self.0.remove(0));
+            if self.0.is_empty() {
+                Ok(GameState::Halted)
+            } else {
+                Ok(GameState::NeedsInput)
+            }
+        }
+
+        fn push_joystick(&mut self, _: EmulatorMemoryType) {}
+    }
+
+    #[test]
+    fn test_only_changes_are_drawn() {
+        let mut game = Frames(vec![
+            vec![2, 0, 1, 1, 0, 4, 0, 0, 1, -1, 0, 0],
+            // The walls are sent again, but haven't changed.
+            vec![1, 0, 0, 1, 1, 4, 0, 0, 1, 2, 0, 1],
+            vec![-1, 0, 5],
+        ]);
+        let mut screen = MockScreen::default();
+        let score = play_on_screen(
+            &mut game,
+            &mut Scripted(vec![]),
+            &mut screen,
+            &mut FrameLimiter::new(0),
+        )
+        .unwrap();
+        assert_eq!(5, score);
+        assert_eq!(
+            vec![
+                Draw::Tile((0, 0), Tile::Wall),
+                Draw::Tile((1, 0), Tile::Ball),
+                Draw::Tile((2, 0), Tile::Wall),
+                Draw::Score(0),
+                Draw::Present,
+                Draw::Tile((1, 0), Tile::Empty),
+                Draw::Tile((1, 1), Tile::Ball),
+                Draw::Present,
+                Draw::Score(5),
+                Draw::Present,
+            ],
+            screen.draws
+        );
+    }
+
+    #[test]
+    fn test_screen_errors_stop_the_game() {
+        let mut game = Frames(vec![vec![0, 0, 1], vec![-1, 0, 5]]);
+        let mut screen = MockScreen {
+            fail_to_present: true,
+            ..MockScreen::default()
+        };
+        assert_eq!(
+            Err(AocError::Terminal("gone".to_string())),
+            play_on_screen(
+                &mut game,
+                &mut Scripted(vec![]),
+                &mut screen,
+                &mut FrameLimiter::new(0),
+            )
+        );
+        assert_eq!(1, game.0.len());
+    }
+
+    #[test]
+    fn test_frame_limiter() {
+        let mut limiter = FrameLimiter::new(10);
+        let start = Instant::now();
+        assert_eq!(Duration::ZERO, limiter.delay(start));
+        assert_eq!(
+            Duration::from_millis(70),
+            limiter.delay(start + Duration::from_millis(30))
+        );
+        // Late, so shown straight away, and the next one is spaced from it.
+        assert_eq!(
+            Duration::ZERO,
+            limiter.delay(start + Duration::from_millis(250))
+        );
+        assert_eq!(
+            Duration::from_millis(50),
+            limiter.delay(start + Duration::from_millis(300))
+        );
+
+        let mut unlimited = FrameLimiter::new(0);
+        assert_eq!(Duration::ZERO, unlimited.delay(start));
+        assert_eq!(Duration::ZERO, unlimited.delay(start));
+    }
+}
@@ -0,0 +1,395 @@
+use super::grid::{self, Position, SparseGrid};
+use super::intcode::cpu::{Cpu, CpuEvent};
+use super::intcode::*;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+
+// The repair droid. The puzzle's own program runs through IntcodeDroid;
+// the input isn't checked in, so the tests drive exploration through a
+// scripted Droid instead.
+
+pub const NORTH: EmulatorMemoryType = 1;
+pub const SOUTH: EmulatorMemoryType = 2;
+pub const WEST: EmulatorMemoryType = 3;
+pub const EAST: EmulatorMemoryType = 4;
+const COMMANDS: [EmulatorMemoryType; 4] = [NORTH, SOUTH, WEST, EAST];
+
+pub const STATUS_WALL: EmulatorMemoryType = 0;
+pub const STATUS_MOVED: EmulatorMemoryType = 1;
+pub const STATUS_FOUND_OXYGEN: EmulatorMemoryType = 2;
+
+fn neighbour((x, y): Position, command: EmulatorMemoryType) -> Position {
+    match command {
+        NORTH => (x, y - 1),
+        SOUTH => (x, y + 1),
+        WEST => (x - 1, y),
+        _ => (x + 1, y),
+    }
+}
+
+fn opposite(command: EmulatorMemoryType) -> EmulatorMemoryType {
+    match command {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        WEST => EAST,
+        _ => WEST,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tile {
+    Wall,
+    Open,
+    Oxygen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DroidError {
+    Emulator(EmulatorError),
+    Halted,
+    UnexpectedStatus(EmulatorMemoryType),
+    // A move along a path already mapped as open hit a wall.
+    Blocked {
+        from: Position,
+        command: EmulatorMemoryType,
+    },
+}
+
+impl std::fmt::Display for DroidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DroidError::Emulator(error) => write!(f, "{}", error),
+            DroidError::Halted => write!(f, "The droid program halted"),
+            DroidError::UnexpectedStatus(status) => write!(f, "Unexpected status {}", status),
+            DroidError::Blocked { from, command } => write!(
+                f,
+                "Moving {} from {:?} hit a wall on a mapped path",
+                command, from
+            ),
+        }
+    }
+}
+
+impl From<EmulatorError> for DroidError {
+    fn from(error: EmulatorError) -> DroidError {
+        DroidError::Emulator(error)
+    }
+}
+
+pub trait Droid {
+    // Returns the status code the droid reports for the move.
+    fn try_move(&mut self, command: EmulatorMemoryType) -> Result<EmulatorMemoryType, DroidError>;
+}
+
+// Runs the droid on any Cpu, the Emulator by default.
+pub struct IntcodeDroid<C = Emulator<QueueInput>> {
+    cpu: C,
+}
+
+impl IntcodeDroid {
+    pub fn new(program: &[EmulatorMemoryType]) -> IntcodeDroid {
+        IntcodeDroid::with_cpu(Emulator::new(program, QueueInput::new()))
+    }
+}
+
+impl<C: Cpu> IntcodeDroid<C> {
+    pub fn with_cpu(cpu: C) -> IntcodeDroid<C> {
+        IntcodeDroid { cpu }
+    }
+}
+
+impl<C: Cpu> Droid for IntcodeDroid<C> {
+    fn try_move(&mut self, command: EmulatorMemoryType) -> Result<EmulatorMemoryType, DroidError> {
+        self.cpu.push_input(command);
+        loop {
+            match self.cpu.run_until_event()? {
+                CpuEvent::Output(status) => return Ok(status),
+                CpuEvent::NeedsInput { position } => {
+                    return Err(EmulatorError::InputNonExistent {
+                        position,
+                        context: None,
+                    }
+                    .into())
+                }
+                CpuEvent::Halted => return Err(DroidError::Halted),
+                CpuEvent::Watchpoint { .. } => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exploration {
+    // The fully explored area, before the oxygen spreads.
+    pub map: SparseGrid<Tile>,
+    pub oxygen: Option<Position>,
+    // Fewest moves from the start to the oxygen system.
+    pub oxygen_distance: Option<u32>,
+    // Minutes until the oxygen fills every open tile.
+    pub fill_minutes: Option<u32>,
+}
+
+// Moves the droid along open tiles of map from `from` to `to`.
+fn walk<D: Droid>(
+    droid: &mut D,
+    map: &SparseGrid<Tile>,
+    from: Position,
+    to: Position,
+) -> Result<(), DroidError> {
+    let mut came_by = SparseGrid::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(position) = queue.pop_front() {
+        if position == to {
+            break;
+        }
+        for &command in COMMANDS.iter() {
+            let next = neighbour(position, command);
+            let open = matches!(map.get(next), Some(Tile::Open) | Some(Tile::Oxygen));
+            if open && next != from && came_by.get(next).is_none() {
+                came_by.insert(next, command);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut commands = Vec::new();
+    let mut position = to;
+    while position != from {
+        let command = *came_by.get(position).expect("to is reachable over the map");
+        commands.push(command);
+        position = neighbour(position, opposite(command));
+    }
+    for command in commands.into_iter().rev() {
+        match droid.try_move(command)? {
+            STATUS_MOVED | STATUS_FOUND_OXYGEN => position = neighbour(position, command),
+            STATUS_WALL => {
+                return Err(DroidError::Blocked {
+                    from: position,
+                    command,
+                })
+            }
+            status => return Err(DroidError::UnexpectedStatus(status)),
+        }
+    }
+    Ok(())
+}
+
+// Breadth-first over the whole area, then breadth-first again from the
+// oxygen system to fill it. frame_sink sees the grid after every completed
+// depth of both searches, numbered consecutively across the two, and can't
+// change either of them.
+pub fn map_with_frames<D: Droid, F: FnMut(u32, &SparseGrid<Tile>)>(
+    droid: &mut D,
+    mut frame_sink: F,
+) -> Result<Exploration, DroidError> {
+    let start = (0, 0);
+    let mut map = SparseGrid::new();
+    map.insert(start, Tile::Open);
+    let mut frame = 0;
+    let mut droid_at = start;
+    let mut oxygen = None;
+    let mut frontier = vec![start];
+    let mut depth = 0;
+    while !frontier.is_empty() {
+        let mut next_frontier = Vec::new();
+        for position in frontier {
+            walk(droid, &map, droid_at, position)?;
+            droid_at = position;
+            for &command in COMMANDS.iter() {
+                let next = neighbour(position, command);
+                if map.get(next).is_some() {
+                    continue;
+                }
+                let tile = match droid.try_move(command)? {
+                    STATUS_WALL => Tile::Wall,
+                    STATUS_MOVED => Tile::Open,
+                    STATUS_FOUND_OXYGEN => {
+                        oxygen = Some((next, depth + 1));
+                        Tile::Oxygen
+                    }
+                    status => return Err(DroidError::UnexpectedStatus(status)),
+                };
+                map.insert(next, tile);
+                if tile != Tile::Wall {
+                    next_frontier.push(next);
+                    droid.try_move(opposite(command))?;
+                }
+            }
+        }
+        frame_sink(frame, &map);
+        frame += 1;
+        frontier = next_frontier;
+        depth += 1;
+    }
+
+    let mut fill_minutes = None;
+    if let Some((source, _)) = oxygen {
+        let mut filled = map.clone();
+        let mut frontier = vec![source];
+        let mut minutes = 0;
+        loop {
+            let mut next_frontier = Vec::new();
+            for position in frontier {
+                for &command in COMMANDS.iter() {
+                    let next = neighbour(position, command);
+                    if filled.get(next) == Some(&Tile::Open) {
+                        filled.insert(next, Tile::Oxygen);
+                        next_frontier.push(next);
+                    }
+                }
+            }
+            if next_frontier.is_empty() {
+                break;
+            }
+            minutes += 1;
+            frame_sink(frame, &filled);
+            frame += 1;
+            frontier = next_frontier;
+        }
+        fill_minutes = Some(minutes);
+    }
+
+    Ok(Exploration {
+        map,
+        oxygen: oxygen.map(|(position, _)| position),
+        oxygen_distance: oxygen.map(|(_, distance)| distance),
+        fill_minutes,
+    })
+}
+
+pub fn explore_with_frames<F: FnMut(u32, &SparseGrid<Tile>)>(
+    program: &[EmulatorMemoryType],
+    frame_sink: F,
+) -> Result<Exploration, DroidError> {
+    map_with_frames(&mut IntcodeDroid::new(program), frame_sink)
+}
+
+pub fn explore(program: &[EmulatorMemoryType]) -> Result<Exploration, DroidError> {
+    explore_with_frames(program, |_, _| {})
+}
+
+pub fn render_frame(map: &SparseGrid<Tile>) -> String {
+    grid::render_text(map, |tile| match tile {
+        Some(Tile::Wall) => '#',
+        Some(Tile::Open) => '.',
+        Some(Tile::Oxygen) => 'O',
+        None => ' ',
+    })
+}
+
+// Writes frame_NNNN.txt and frame_NNNN.ppm into dir.
+pub fn write_frame(dir: &Path, frame: u32, map: &SparseGrid<Tile>) -> io::Result<()> {
+    let name = format!("frame_{:04}", frame);
+    std::fs::write(dir.join(format!("{}.txt", name)), render_frame(map))?;
+    let ppm = grid::render_ppm_with(map, 4, |tile| match tile {
+        Some(Tile::Wall) => [0, 0, 0],
+        Some(Tile::Open) => [255, 255, 255],
+        Some(Tile::Oxygen) => [64, 128, 255],
+        None => [128, 128, 128],
+    });
+    std::fs::write(dir.join(format!("{}.ppm", name)), ppm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The published oxygen example, with the droid starting at D.
+    const MAZE: &str = "\
+ ##
+#D.##
+#.#..#
+#.O.#
+ ###
+";
+
+    struct MazeDroid {
+        maze: Vec<Vec<char>>,
+        at: Position,
+    }
+
+    impl MazeDroid {
+        fn new(text: &str) -> MazeDroid {
+            let maze: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+            let at = maze
+                .iter()
+                .enumerate()
+                .find_map(|(y, row)| {
+                    row.iter()
+                        .position(|&c| c == 'D')
+                        .map(|x| (x as i64, y as i64))
+                })
+                .unwrap();
+            MazeDroid { maze, at }
+        }
+    }
+
+    impl Droid for MazeDroid {
+        fn try_move(
+            &mut self,
+            command: EmulatorMemoryType,
+        ) -> Result<EmulatorMemoryType, DroidError> {
+            let (x, y) = neighbour(self.at, command);
+            let c = self.maze[y as usize].get(x as usize).copied();
+            Ok(match c {
+                Some('.') | Some('D') => {
+                    self.at = (x, y);
+                    STATUS_MOVED
+                }
+                Some('O') => {
+                    self.at = (x, y);
+                    STATUS_FOUND_OXYGEN
+                }
+                _ => STATUS_WALL,
+            })
+        }
+    }
+
+    #[test]
+    fn test_explore() {
+        let exploration = map_with_frames(&mut MazeDroid::new(MAZE), |_, _| {}).unwrap();
+        assert_eq!(Some((1, 2)), exploration.oxygen);
+        assert_eq!(Some(3), exploration.oxygen_distance);
+        assert_eq!(Some(4), exploration.fill_minutes);
+        assert_eq!(
+            " ##   \n#..## \n#.#..#\n#.O.# \n ###  \n",
+            render_frame(&exploration.map)
+        );
+    }
+
+    #[test]
+    fn test_frames_do_not_change_results() {
+        let mut frames = Vec::new();
+        let with_frames = map_with_frames(&mut MazeDroid::new(MAZE), |frame, map| {
+            frames.push((frame, render_frame(map)))
+        })
+        .unwrap();
+        let without_frames = map_with_frames(&mut MazeDroid::new(MAZE), |_, _| {}).unwrap();
+        assert_eq!(without_frames, with_frames);
+
+        let numbers: Vec<u32> = frames.iter().map(|(frame, _)| *frame).collect();
+        assert_eq!((0..frames.len() as u32).collect::<Vec<_>>(), numbers);
+        // One frame per depth down to the farthest tile, 6 moves away, then
+        // one per minute of filling.
+        assert_eq!(7 + 4, frames.len());
+        let (_, last) = frames.last().unwrap();
+        assert!(!last.contains('.'));
+        assert_eq!(" ##   \n#OO## \n#O#OO#\n#OOO# \n ###  \n", last);
+    }
+
+    #[test]
+    fn test_write_frame() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("aoc_day15_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let exploration = map_with_frames(&mut MazeDroid::new(MAZE), |_, _| {}).unwrap();
+        write_frame(&dir, 7, &exploration.map)?;
+        let text = std::fs::read_to_string(dir.join("frame_0007.txt"))?;
+        let ppm = std::fs::read(dir.join("frame_0007.ppm"))?;
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(render_frame(&exploration.map), text);
+        assert!(ppm.starts_with(b"P6\n24 20\n255\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use super::solution::{read_input, AocError, Example, Part, Solution};
+
+const BASE_PATTERN: [i32; 4] = [0, 1, 0, -1];
+const PHASES: usize = 100;
+const MESSAGE_LENGTH: usize = 8;
+const OFFSET_DIGITS: usize = 7;
+const REPETITIONS: usize = 10_000;
+
+pub fn parse_signal(text: &str) -> Result<Vec<i32>, String> {
+    text.trim()
+        .chars()
+        .enumerate()
+        .map(|(position, c)| {
+            c.to_digit(10)
+                .map(|digit| digit as i32)
+                .ok_or_else(|| format!("'{}' at position {} is not a digit", c, position))
+        })
+        .collect()
+}
+
+fn to_string(digits: &[i32]) -> String {
+    digits.iter().map(|digit| digit.to_string()).collect()
+}
+
+// The straightforward phase: every output digit walks the whole pattern.
+// Kept as the reference the faster phase is checked against.
+pub fn phase_naive(signal: &[i32]) -> Vec<i32> {
+    (1..=signal.len())
+        .map(|repeat| {
+            let sum: i32 = signal
+                .iter()
+                .enumerate()
+                .map(|(index, digit)| digit * BASE_PATTERN[((index + 1) / repeat) % 4])
+                .sum();
+            sum.abs() % 10
+        })
+        .collect()
+}
+
+// Output digit k (1-based) adds the runs of k inputs under the pattern's 1s
+// and subtracts those under its -1s, so with prefix sums it costs O(n / k)
+// and the whole phase O(n log n).
+pub fn phase(signal: &[i32]) -> Vec<i32> {
+    let mut prefix_sums = Vec::with_capacity(signal.len() + 1);
+    prefix_sums.push(0);
+    for digit in signal {
+        prefix_sums.push(prefix_sums.last().unwrap() + digit);
+    }
+    let range_sum = |start: usize, end: usize| {
+        let end = std::cmp::min(end, signal.len());
+        if start < end {
+            prefix_sums[end] - prefix_sums[start]
+        } else {
+            0
+        }
+    };
+
+    (1..=signal.len())
+        .map(|repeat| {
+            let mut sum = 0;
+            // The first 1 in the shifted pattern lines up with index repeat - 1.
+            let mut start = repeat - 1;
+            while start < signal.len() {
+                sum += range_sum(start, start + repeat);
+                sum -= range_sum(start + 2 * repeat, start + 3 * repeat);
+                start += 4 * repeat;
+            }
+            sum.abs() % 10
+        })
+        .collect()
+}
+
+pub fn fft(signal: &[i32], phases: usize) -> Vec<i32> {
+    (0..phases).fold(signal.to_vec(), |signal, _| phase(&signal))
+}
+
+pub fn first_digits_after_phases(signal: &[i32]) -> String {
+    to_string(&fft(signal, PHASES)[..MESSAGE_LENGTH])
+}
+
+// The message offset always points into the second half of the repeated
+// signal, where every pattern is 0s followed by 1s, so each digit is just the
+// sum of the digits from it to the end.
+pub fn embedded_message(signal: &[i32]) -> String {
+    let offset = signal[..OFFSET_DIGITS]
+        .iter()
+        .fold(0, |offset, &digit| offset * 10 + digit as usize);
+    let total = signal.len() * REPETITIONS;
+    assert!(
+        offset >= total / 2 && offset + MESSAGE_LENGTH <= total,
+        "message offset {} is not in the second half of the signal",
+        offset
+    );
+
+    let mut tail: Vec<i32> = (offset..total)
+        .map(|index| signal[index % signal.len()])
+        .collect();
+    for _ in 0..PHASES {
+        let mut sum = 0;
+        for digit in tail.iter_mut().rev() {
+            sum = (sum + *digit) % 10;
+            *digit = sum;
+        }
+    }
+    to_string(&tail[..MESSAGE_LENGTH])
+}
+
+pub fn run_part_1(path: &str) -> Result<String, AocError> {
+    Ok(first_digits_after_phases(&read_input(
+        16,
+        path,
+        parse_signal,
+    )?))
+}
+
+pub fn run_part_2(path: &str) -> Result<String, AocError> {
+    Ok(embedded_message(&read_input(16, path, parse_signal)?))
+}
+
+pub struct Day16;
+
+impl Solution for Day16 {
+    fn day(&self) -> u32 {
+        16
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        run_part_1(path)
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        run_part_2(path)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(Part::One, "80871224585914546619083218645595", "24176176"),
+            Example::new(Part::One, "19617804207202209144916044189917", "73745418"),
+            Example::new(Part::One, "69317163492948606335995924319873", "52432133"),
+            Example::new(Part::Two, "03036732577212944063491565474664", "84462026"),
+            Example::new(Part::Two, "02935109699940807407585447034323", "78725270"),
+            Example::new(Part::Two, "03081770884921959731165446850517", "53553731"),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(text: &str) -> Vec<i32> {
+        parse_signal(text).unwrap()
+    }
+
+    #[test]
+    fn test_phases() {
+        let mut digits = signal("12345678");
+        for expected in &["48226158", "34040438", "03415518", "01029498"] {
+            digits = phase(&digits);
+            assert_eq!(*expected, to_string(&digits));
+        }
+    }
+
+    #[test]
+    fn test_phase_matches_naive() {
+        // xorshift64, so the signals are the same on every run.
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next_digit = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 10) as i32
+        };
+        for len in 1..=120 {
+            let digits: Vec<i32> = (0..len).map(|_| next_digit()).collect();
+            assert_eq!(phase_naive(&digits), phase(&digits), "{:?}", digits);
+        }
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(Ok(vec![0, 1, 2]), parse_signal("012\n"));
+        assert_eq!(
+            Err("'x' at position 2 is not a digit".to_string()),
+            parse_signal("01x")
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use super::solution::{bad_input, read_input, AocError, Example, Part, Solution};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+
+type Cell = (usize, usize);
+
+// Each collected key is one bit, a = bit 0. Doors use their key's bit.
+pub type KeyMask = u32;
+
+fn key_bit(letter: u8) -> KeyMask {
+    1 << (letter.to_ascii_lowercase() - b'a')
+}
+
+pub struct Vault {
+    rows: Vec<Vec<u8>>,
+}
+
+impl Vault {
+    pub fn parse(text: &str) -> Result<Vault, String> {
+        let rows: Vec<Vec<u8>> = text
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.as_bytes().to_vec())
+            .collect();
+        for (y, row) in rows.iter().enumerate() {
+            if let Some(x) = row
+                .iter()
+                .position(|&c| !matches!(c, b'#' | b'.' | b'@' | b'a'..=b'z' | b'A'..=b'Z'))
+            {
+                return Err(format!("Unexpected '{}' at ({}, {})", row[x] as char, x, y));
+            }
+        }
+        if rows.iter().flatten().all(|&c| c != b'@') {
+            return Err("The vault has no entrance".to_string());
+        }
+        Ok(Vault { rows })
+    }
+
+    fn get(&self, (x, y): Cell) -> u8 {
+        self.rows
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(b'#')
+    }
+
+    fn entrances(&self) -> Vec<Cell> {
+        self.cells()
+            .filter(|&cell| self.get(cell) == b'@')
+            .collect()
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| (0..row.len()).map(move |x| (x, y)))
+    }
+
+    // Part 2: walls off the single entrance and puts one in each diagonal,
+    // splitting the vault into four quadrants.
+    pub fn split_entrance(&mut self) -> Result<(), String> {
+        let (x, y) = match self.entrances().as_slice() {
+            &[entrance] => entrance,
+            _ => return Err("Only a vault with one entrance can be split".to_string()),
+        };
+        if x == 0 || y == 0 {
+            return Err("The entrance is on the edge of the vault".to_string());
+        }
+        for dy in 0..3 {
+            for dx in 0..3 {
+                if self.get((x + dx - 1, y + dy - 1)) != b'.' && (dx, dy) != (1, 1) {
+                    return Err("The entrance is not surrounded by open floor".to_string());
+                }
+            }
+        }
+        for dy in 0..3 {
+            for dx in 0..3 {
+                let corner = dx != 1 && dy != 1;
+                self.rows[y + dy - 1][x + dx - 1] = if corner { b'@' } else { b'#' };
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Node {
+    // Numbered in reading order.
+    Entrance(usize),
+    Key(char),
+}
+
+// The shortest path between two nodes of the condensed graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub to: Node,
+    pub distance: usize,
+    // Doors along the way, as the keys that open them.
+    pub doors: KeyMask,
+    // Keys passed over on the way, not counting either end.
+    pub keys: KeyMask,
+}
+
+// Distances from every entrance and key to every key it can reach. Vaults
+// are mazes without loops, so the one shortest path between two nodes is
+// the only one worth taking, whatever doors it crosses.
+pub struct KeyGraph {
+    edges: BTreeMap<Node, Vec<Edge>>,
+    all_keys: KeyMask,
+    entrances: usize,
+}
+
+impl KeyGraph {
+    pub fn build(vault: &Vault) -> KeyGraph {
+        let mut edges = BTreeMap::new();
+        let mut all_keys = 0;
+        let mut entrances = 0;
+        for cell in vault.cells() {
+            let node = match vault.get(cell) {
+                b'@' => {
+                    entrances += 1;
+                    Node::Entrance(entrances - 1)
+                }
+                c @ b'a'..=b'z' => {
+                    all_keys |= key_bit(c);
+                    Node::Key(c as char)
+                }
+                _ => continue,
+            };
+            edges.insert(node, KeyGraph::edges_from(vault, cell));
+        }
+        KeyGraph {
+            edges,
+            all_keys,
+            entrances,
+        }
+    }
+
+    fn edges_from(vault: &Vault, start: Cell) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        let mut seen = vec![start];
+        let mut queue = VecDeque::new();
+        queue.push_back((start, 0, 0, 0));
+        while let Some(((x, y), distance, doors, keys)) = queue.pop_front() {
+            // Off the top or left edge wraps to a huge index, which reads as wall.
+            for &next in &[
+                (x + 1, y),
+                (x.wrapping_sub(1), y),
+                (x, y + 1),
+                (x, y.wrapping_sub(1)),
+            ] {
+                let c = vault.get(next);
+                if c == b'#' || seen.contains(&next) {
+                    continue;
+                }
+                seen.push(next);
+                let (mut doors, mut keys) = (doors, keys);
+                match c {
+                    b'A'..=b'Z' => doors |= key_bit(c),
+                    b'a'..=b'z' => {
+                        edges.push(Edge {
+                            to: Node::Key(c as char),
+                            distance: distance + 1,
+                            doors,
+                            keys,
+                        });
+                        keys |= key_bit(c);
+                    }
+                    _ => {}
+                }
+                queue.push_back((next, distance + 1, doors, keys));
+            }
+        }
+        edges.sort_by_key(|edge| edge.to);
+        edges
+    }
+
+    pub fn edges(&self, from: Node) -> &[Edge] {
+        self.edges.get(&from).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn edge(&self, from: Node, to: Node) -> Option<&Edge> {
+        self.edges(from).iter().find(|edge| edge.to == to)
+    }
+
+    // Dijkstra over (where each robot is, which keys are collected). A robot
+    // only heads for a key whose doors are open and whose path crosses no
+    // uncollected key, since picking that one up first is never worse.
+    pub fn fewest_steps(&self) -> Option<usize> {
+        let start: Vec<Node> = (0..self.entrances).map(Node::Entrance).collect();
+        let mut best = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        best.insert((start.clone(), 0), 0);
+        queue.push(Reverse((0, start, 0)));
+        while let Some(Reverse((steps, robots, collected))) = queue.pop() {
+            if collected == self.all_keys {
+                return Some(steps);
+            }
+            if best.get(&(robots.clone(), collected)) < Some(&steps) {
+                continue;
+            }
+            for (robot, &at) in robots.iter().enumerate() {
+                for edge in self.edges(at) {
+                    let key = match edge.to {
+                        Node::Key(key) => key_bit(key as u8),
+                        Node::Entrance(_) => continue,
+                    };
+                    let needed = edge.doors | edge.keys;
+                    if collected & key != 0 || needed & !collected != 0 {
+                        continue;
+                    }
+                    let mut next = robots.clone();
+                    next[robot] = edge.to;
+                    let state = (next, collected | key);
+                    let steps = steps + edge.distance;
+                    if best.get(&state).is_none_or(|&known| steps < known) {
+                        best.insert(state.clone(), steps);
+                        queue.push(Reverse((steps, state.0, state.1)));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn run_part_1(path: &str) -> Result<usize, AocError> {
+    KeyGraph::build(&read_input(18, path, Vault::parse)?)
+        .fewest_steps()
+        .ok_or(AocError::NoAnswer { day: 18, part: 1 })
+}
+
+pub fn run_part_2(path: &str) -> Result<usize, AocError> {
+    let mut vault = read_input(18, path, Vault::parse)?;
+    vault
+        .split_entrance()
+        .map_err(|error| bad_input(18, path, error))?;
+    KeyGraph::build(&vault)
+        .fewest_steps()
+        .ok_or(AocError::NoAnswer { day: 18, part: 2 })
+}
+
+// Published examples, also used by the tests below.
+const SIMPLE: &str = "\
+#########
+#b.A.@.a#
+#########
+";
+
+const CORRIDOR: &str = "\
+########################
+#f.D.E.e.C.b.A.@.a.B.c.#
+######################.#
+#d.....................#
+########################
+";
+
+const LARGEST: &str = "\
+#################
+#i.G..c...e..H.p#
+########.########
+#j.A..b...f..D.o#
+########@########
+#k.E..a...g..B.n#
+########.########
+#l.F..d...h..C.m#
+#################
+";
+
+pub struct Day18;
+
+impl Solution for Day18 {
+    fn day(&self) -> u32 {
+        18
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(Part::One, SIMPLE, "8"),
+            Example::new(Part::One, CORRIDOR, "86"),
+            Example::new(
+                Part::One,
+                "\
+########################
+#...............b.C.D.f#
+#.######################
+#.....@.a.B.c.d.A.e.F.g#
+########################
+",
+                "132",
+            ),
+            Example::new(Part::One, LARGEST, "136"),
+            Example::new(
+                Part::One,
+                "\
+########################
+#@..............ac.GI.b#
+###d#e#f################
+###A#B#C################
+###g#h#i################
+########################
+",
+                "81",
+            ),
+            Example::new(
+                Part::Two,
+                "\
+#######
+#a.#Cd#
+##...##
+##.@.##
+##...##
+#cB#Ab#
+#######
+",
+                "8",
+            ),
+            Example::new(
+                Part::Two,
+                "\
+###############
+#d.ABC.#.....a#
+######...######
+######.@.######
+######...######
+#b.....#.....c#
+###############
+",
+                "24",
+            ),
+            Example::new(
+                Part::Two,
+                "\
+#############
+#g#f.D#..h#l#
+#F###e#E###.#
+#dCba...BcIJ#
+#####.@.#####
+#nK.L...G...#
+#M###N#H###.#
+#o#m..#i#jk.#
+#############
+",
+                "72",
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn steps(text: &str) -> Option<usize> {
+        KeyGraph::build(&Vault::parse(text).unwrap()).fewest_steps()
+    }
+
+    #[test]
+    fn test_pairwise_distances() {
+        let graph = KeyGraph::build(&Vault::parse(SIMPLE).unwrap());
+        let a = key_bit(b'a');
+        assert_eq!(
+            &[
+                Edge {
+                    to: Node::Key('a'),
+                    distance: 2,
+                    doors: 0,
+                    keys: 0
+                },
+                Edge {
+                    to: Node::Key('b'),
+                    distance: 4,
+                    doors: a,
+                    keys: 0
+                },
+            ],
+            graph.edges(Node::Entrance(0))
+        );
+        assert_eq!(
+            6,
+            graph.edge(Node::Key('a'), Node::Key('b')).unwrap().distance
+        );
+
+        let graph = KeyGraph::build(&Vault::parse(CORRIDOR).unwrap());
+        assert_eq!(
+            Some(&Edge {
+                to: Node::Key('c'),
+                distance: 10,
+                doors: a | key_bit(b'b'),
+                keys: a,
+            }),
+            graph.edge(Node::Key('b'), Node::Key('c'))
+        );
+        // Round the corner, through every door in the corridor.
+        let to_d = graph.edge(Node::Key('f'), Node::Key('d')).unwrap();
+        assert_eq!(44, to_d.distance);
+        let mask = |letters: &[u8]| letters.iter().fold(0, |mask, &c| mask | key_bit(c));
+        assert_eq!(mask(b"abcde"), to_d.doors);
+        assert_eq!(mask(b"abce"), to_d.keys);
+    }
+
+    #[test]
+    fn test_largest_example_is_fast() {
+        let start = Instant::now();
+        assert_eq!(Some(136), steps(LARGEST));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_split_entrance_needs_one_entrance() {
+        assert!(Vault::parse(SIMPLE).unwrap().split_entrance().is_err());
+    }
+}
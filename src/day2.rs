@@ -1,30 +1,128 @@
-use super::get_intcode_memory_from_file;
 use super::intcode::*;
+use super::progress::{self, ProgressSink};
+use super::solution::{check_intcode_input, read_intcode_input, AocError, Solution};
 
-pub fn run_part_1(path: &str) -> EmulatorMemoryType {
-    let mut initial_memory = get_intcode_memory_from_file(path);
+pub const TARGET_OUTPUT: EmulatorMemoryType = 19_690_720;
 
-    initial_memory[1] = 12;
-    initial_memory[2] = 2;
-    let mut emulator = emulator_with_empty_input(&initial_memory);
-    emulator.run_to_completion().unwrap()
+// The noun and verb go in cells 1 and 2.
+const MIN_CELLS: usize = 3;
+
+fn read_program(path: &str) -> Result<Vec<EmulatorMemoryType>, AocError> {
+    Day2.check_input(path)?;
+    read_intcode_input(Day2.day(), path)
+}
+
+pub fn run_part_1(path: &str) -> Result<EmulatorMemoryType, AocError> {
+    let initial_memory = read_program(path)?;
+    Ok(run_with_noun_verb(&initial_memory, 12, 2)?)
+}
+
+pub fn run_with_noun_verb(
+    program: &[EmulatorMemoryType],
+    noun: EmulatorMemoryType,
+    verb: EmulatorMemoryType,
+) -> Result<EmulatorMemoryType, EmulatorError> {
+    validate_entry(program)?;
+    EmulatorBuilder::new()
+        .program(program)
+        .patch(1, noun)
+        .patch(2, verb)
+        .build()?
+        .run_to_completion()
+}
+
+pub fn run_part_2(path: &str) -> Result<Option<EmulatorMemoryType>, AocError> {
+    let initial_memory = read_program(path)?;
+    validate_entry(&initial_memory)?;
+    Ok(find_noun_verb(&initial_memory, TARGET_OUTPUT, None)?)
 }
 
-pub fn run_part_2(path: &str) -> Option<EmulatorMemoryType> {
-    let mut initial_memory = get_intcode_memory_from_file(path);
+pub fn find_noun_verb(
+    program: &[EmulatorMemoryType],
+    target: EmulatorMemoryType,
+    mut progress: Option<&mut dyn ProgressSink>,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    let total = 100 * 100;
+    let mut tried = 0;
 
+    let mut emulator = emulator_with_empty_input(program);
     for noun in 0..=99 {
         for verb in 0..=99 {
-            initial_memory[1] = noun;
-            initial_memory[2] = verb;
-            let mut emulator = emulator_with_empty_input(&initial_memory);
+            emulator.reset();
+            emulator.write(1, noun)?;
+            emulator.write(2, verb)?;
+            let result = emulator.run_to_completion()?;
 
-            if emulator.run_to_completion().unwrap() == 19_690_720 {
+            tried += 1;
+            progress::report(&mut progress, tried, Some(total));
+
+            if result == target {
                 let answer = 100 * noun + verb;
-                return Some(answer);
+                return Ok(Some(answer));
             }
         }
     }
 
-    None
+    Ok(None)
+}
+
+pub struct Day2;
+
+impl Solution for Day2 {
+    fn day(&self) -> u32 {
+        2
+    }
+
+    fn check_input(&self, path: &str) -> Result<(), AocError> {
+        check_intcode_input(self.day(), path, MIN_CELLS, false)
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        match run_part_2(path)? {
+            Some(answer) => Ok(answer.to_string()),
+            None => Err(AocError::NoAnswer { day: 2, part: 2 }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_1_input_is_rejected_up_front() {
+        let masses = [105_371, 129_056, 134_974, 98_541];
+        assert_eq!(
+            Err(EmulatorError::InvalidEntryInstruction {
+                value_found: 105_371,
+                context: None,
+            }),
+            run_with_noun_verb(&masses, 12, 2)
+        );
+    }
+
+    #[test]
+    fn test_find_noun_verb_reports_progress() {
+        let program = crate::get_intcode_memory_from_file("input/2.txt");
+        let mut reports = Vec::new();
+        let answer = find_noun_verb(
+            &program,
+            TARGET_OUTPUT,
+            Some(&mut |done, total| reports.push((done, total))),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(Ok(Some(answer)), run_part_2("input/2.txt"));
+        assert!(reports.iter().all(|&(_, total)| total == Some(10_000)));
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(
+            Some(answer as u64 + 1),
+            reports.last().map(|report| report.0)
+        );
+    }
 }
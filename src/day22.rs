@@ -0,0 +1,185 @@
+use super::modmath::Affine;
+use super::solution::{bad_input, read_input, AocError, Solution};
+
+const PART_1_DECK_SIZE: u64 = 10_007;
+const PART_1_CARD: u64 = 2019;
+const PART_2_DECK_SIZE: u64 = 119_315_717_514_047;
+const PART_2_SHUFFLES: u64 = 101_741_582_076_661;
+const PART_2_POSITION: u64 = 2020;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Technique {
+    DealIntoNewStack,
+    Cut(i64),
+    DealWithIncrement(u64),
+}
+
+impl Technique {
+    pub fn parse(line: &str) -> Result<Technique, String> {
+        let line = line.trim();
+        let number = |text: &str| {
+            text.trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a number in '{}'", text.trim(), line))
+        };
+        if line == "deal into new stack" {
+            Ok(Technique::DealIntoNewStack)
+        } else if let Some(count) = line.strip_prefix("cut ") {
+            number(count).map(Technique::Cut)
+        } else if let Some(increment) = line.strip_prefix("deal with increment ") {
+            let increment = number(increment)?;
+            if increment <= 0 {
+                return Err(format!("the increment in '{}' must be positive", line));
+            }
+            Ok(Technique::DealWithIncrement(increment as u64))
+        } else {
+            Err(format!("Unknown technique '{}'", line))
+        }
+    }
+
+    // Where the card at position x ends up.
+    pub fn to_affine(self, deck_size: u64) -> Affine {
+        match self {
+            Technique::DealIntoNewStack => Affine::new(-1, -1, deck_size),
+            Technique::Cut(count) => Affine::new(1, -(count as i128), deck_size),
+            Technique::DealWithIncrement(increment) => Affine::new(increment as i128, 0, deck_size),
+        }
+    }
+}
+
+pub fn parse_techniques(text: &str) -> Result<Vec<Technique>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Technique::parse)
+        .collect()
+}
+
+// The whole shuffle as one map from a card's starting position to its final
+// one.
+pub fn shuffle(techniques: &[Technique], deck_size: u64) -> Affine {
+    techniques
+        .iter()
+        .fold(Affine::identity(deck_size), |shuffled, technique| {
+            technique.to_affine(deck_size).compose(&shuffled)
+        })
+}
+
+// The factory order deck after one shuffle, top card first.
+pub fn deal(techniques: &[Technique], deck_size: u64) -> Vec<u64> {
+    let shuffled = shuffle(techniques, deck_size);
+    let mut deck = vec![0; deck_size as usize];
+    for card in 0..deck_size {
+        deck[shuffled.apply(card) as usize] = card;
+    }
+    deck
+}
+
+pub fn run_part_1(path: &str) -> Result<u64, AocError> {
+    let techniques = read_input(22, path, parse_techniques)?;
+    Ok(shuffle(&techniques, PART_1_DECK_SIZE).apply(PART_1_CARD))
+}
+
+// Following the card back from its final position undoes every shuffle at
+// once. Deck sizes are prime, so every increment has an inverse.
+pub fn run_part_2(path: &str) -> Result<u64, AocError> {
+    let undo = shuffle(&read_input(22, path, parse_techniques)?, PART_2_DECK_SIZE)
+        .pow(PART_2_SHUFFLES)
+        .invert()
+        .map_err(|error| bad_input(22, path, error))?;
+    Ok(undo.apply(PART_2_POSITION))
+}
+
+pub struct Day22;
+
+impl Solution for Day22 {
+    fn day(&self) -> u32 {
+        22
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal_text(text: &str) -> Vec<u64> {
+        deal(&parse_techniques(text).unwrap(), 10)
+    }
+
+    #[test]
+    fn test_single_techniques() {
+        assert_eq!(
+            vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+            deal_text("deal into new stack")
+        );
+        assert_eq!(vec![3, 4, 5, 6, 7, 8, 9, 0, 1, 2], deal_text("cut 3"));
+        assert_eq!(vec![6, 7, 8, 9, 0, 1, 2, 3, 4, 5], deal_text("cut -4"));
+        assert_eq!(
+            vec![0, 7, 4, 1, 8, 5, 2, 9, 6, 3],
+            deal_text("deal with increment 3")
+        );
+    }
+
+    #[test]
+    fn test_examples() {
+        for (text, expected) in &[
+            (
+                "deal with increment 7\ndeal into new stack\ndeal into new stack\n",
+                [0, 3, 6, 9, 2, 5, 8, 1, 4, 7],
+            ),
+            (
+                "cut 6\ndeal with increment 7\ndeal into new stack\n",
+                [3, 0, 7, 4, 1, 8, 5, 2, 9, 6],
+            ),
+            (
+                "deal with increment 7\ndeal with increment 9\ncut -2\n",
+                [6, 3, 0, 7, 4, 1, 8, 5, 2, 9],
+            ),
+            (
+                "deal into new stack\ncut -2\ndeal with increment 7\ncut 8\ncut -4\n\
+                 deal with increment 7\ncut 3\ndeal with increment 9\n\
+                 deal with increment 3\ncut -1\n",
+                [9, 2, 5, 8, 1, 4, 7, 0, 3, 6],
+            ),
+        ] {
+            assert_eq!(expected.to_vec(), deal_text(text));
+        }
+    }
+
+    #[test]
+    fn test_repeated_shuffles_match_dealing_again() {
+        let techniques =
+            parse_techniques("cut 6\ndeal with increment 7\ndeal into new stack").unwrap();
+        let once = shuffle(&techniques, 11);
+        let mut positions: Vec<u64> = (0..11).collect();
+        for shuffles in 1..=30 {
+            for position in positions.iter_mut() {
+                *position = once.apply(*position);
+            }
+            let repeated = once.pow(shuffles);
+            assert!((0..11).all(|card| repeated.apply(card) == positions[card as usize]));
+            let undo = repeated.invert().unwrap();
+            assert!((0..11).all(|card| undo.apply(positions[card as usize]) == card));
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err("Unknown technique 'shuffle'".to_string()),
+            Technique::parse("shuffle")
+        );
+        assert_eq!(
+            Err("'x' is not a number in 'cut x'".to_string()),
+            Technique::parse("cut x")
+        );
+        assert!(Technique::parse("deal with increment 0").is_err());
+    }
+}
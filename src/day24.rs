@@ -0,0 +1,242 @@
+use super::search::find_cycle_with_states;
+use super::solution::{read_input, AocError, Example, Part, Solution};
+use std::collections::BTreeMap;
+
+const SIZE: usize = 5;
+const CENTER: usize = 12;
+const PART_2_MINUTES: usize = 200;
+
+// One bit per tile, row by row from the top left, so a layout's
+// biodiversity rating is the layout itself.
+pub type Layout = u32;
+
+pub fn parse_layout(text: &str) -> Result<Layout, String> {
+    let rows: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if rows.len() != SIZE || rows.iter().any(|row| row.len() != SIZE) {
+        return Err(format!("expected a {}x{} grid", SIZE, SIZE));
+    }
+    let mut layout = 0;
+    for (index, c) in rows.iter().flat_map(|row| row.chars()).enumerate() {
+        match c {
+            '#' => layout |= 1 << index,
+            '.' | '?' => {}
+            _ => return Err(format!("'{}' is not a tile", c)),
+        }
+    }
+    Ok(layout)
+}
+
+fn has_bug(layout: Layout, index: usize) -> bool {
+    layout & (1 << index) != 0
+}
+
+// A bug survives only with exactly one neighbour, and an empty tile is
+// infested by one or two.
+fn next_tile(bug: bool, neighbours: u32) -> bool {
+    neighbours == 1 || (!bug && neighbours == 2)
+}
+
+fn flat_neighbours(index: usize) -> impl Iterator<Item = usize> {
+    let (row, column) = (index / SIZE, index % SIZE);
+    let mut neighbours = Vec::with_capacity(4);
+    if row > 0 {
+        neighbours.push(index - SIZE);
+    }
+    if row + 1 < SIZE {
+        neighbours.push(index + SIZE);
+    }
+    if column > 0 {
+        neighbours.push(index - 1);
+    }
+    if column + 1 < SIZE {
+        neighbours.push(index + 1);
+    }
+    neighbours.into_iter()
+}
+
+pub fn step(layout: Layout) -> Layout {
+    (0..SIZE * SIZE)
+        .filter(|&index| {
+            let neighbours = flat_neighbours(index)
+                .filter(|&neighbour| has_bug(layout, neighbour))
+                .count() as u32;
+            next_tile(has_bug(layout, index), neighbours)
+        })
+        .fold(0, |next, index| next | 1 << index)
+}
+
+pub fn first_repeated_layout(layout: Layout) -> Layout {
+    let (cycle, layouts) = find_cycle_with_states(layout, |&layout| step(layout));
+    layouts[cycle.start as usize]
+}
+
+// Neighbours of a tile in recursive space, as (depth offset, index). Depth
+// -1 is the grid around this one and +1 the grid in its centre tile.
+fn recursive_neighbours(index: usize) -> Vec<(i64, usize)> {
+    let (row, column) = (index / SIZE, index % SIZE);
+    let mut neighbours = Vec::new();
+    // (row step, column step, outer tile, inner edge tiles when moving into
+    // the centre)
+    let directions: [(i64, i64, usize, [usize; SIZE]); 4] = [
+        (-1, 0, 7, [20, 21, 22, 23, 24]),
+        (1, 0, 17, [0, 1, 2, 3, 4]),
+        (0, -1, 11, [4, 9, 14, 19, 24]),
+        (0, 1, 13, [0, 5, 10, 15, 20]),
+    ];
+    for &(row_step, column_step, outer, inner_edge) in directions.iter() {
+        let next_row = row as i64 + row_step;
+        let next_column = column as i64 + column_step;
+        if next_row < 0 || next_row >= SIZE as i64 || next_column < 0 || next_column >= SIZE as i64
+        {
+            neighbours.push((-1, outer));
+            continue;
+        }
+        let next = next_row as usize * SIZE + next_column as usize;
+        if next == CENTER {
+            neighbours.extend(inner_edge.iter().map(|&inner| (1, inner)));
+        } else {
+            neighbours.push((0, next));
+        }
+    }
+    neighbours
+}
+
+// Every grid that has or could get a bug, keyed by depth.
+pub fn step_recursive(levels: &BTreeMap<i64, Layout>) -> BTreeMap<i64, Layout> {
+    let (shallowest, deepest) = match (levels.keys().next(), levels.keys().next_back()) {
+        (Some(&shallowest), Some(&deepest)) => (shallowest, deepest),
+        _ => return BTreeMap::new(),
+    };
+    let neighbours: Vec<Vec<(i64, usize)>> = (0..SIZE * SIZE).map(recursive_neighbours).collect();
+    let layout_at = |depth: i64| levels.get(&depth).copied().unwrap_or(0);
+
+    let mut next = BTreeMap::new();
+    for depth in shallowest - 1..=deepest + 1 {
+        let layout = layout_at(depth);
+        let mut next_layout = 0;
+        for index in (0..SIZE * SIZE).filter(|&index| index != CENTER) {
+            let count = neighbours[index]
+                .iter()
+                .filter(|&&(offset, neighbour)| has_bug(layout_at(depth + offset), neighbour))
+                .count() as u32;
+            if next_tile(has_bug(layout, index), count) {
+                next_layout |= 1 << index;
+            }
+        }
+        if next_layout != 0 {
+            next.insert(depth, next_layout);
+        }
+    }
+    next
+}
+
+pub fn bugs_after(layout: Layout, minutes: usize) -> u32 {
+    let mut levels = BTreeMap::new();
+    levels.insert(0, layout & !(1 << CENTER));
+    for _ in 0..minutes {
+        levels = step_recursive(&levels);
+    }
+    levels.values().map(|layout| layout.count_ones()).sum()
+}
+
+pub fn run_part_1(path: &str) -> Result<Layout, AocError> {
+    Ok(first_repeated_layout(read_input(24, path, parse_layout)?))
+}
+
+pub fn run_part_2(path: &str) -> Result<u32, AocError> {
+    Ok(bugs_after(
+        read_input(24, path, parse_layout)?,
+        PART_2_MINUTES,
+    ))
+}
+
+const EXAMPLE: &str = "\
+....#
+#..#.
+#..##
+..#..
+#....
+";
+
+pub struct Day24;
+
+impl Solution for Day24 {
+    fn day(&self) -> u32 {
+        24
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+
+    // Part 2's example stops after 10 minutes rather than 200, so it is
+    // checked in the tests instead.
+    fn examples(&self) -> Vec<Example> {
+        vec![Example::new(Part::One, EXAMPLE, "2129920")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(text: &str) -> Layout {
+        parse_layout(text).unwrap()
+    }
+
+    #[test]
+    fn test_steps() {
+        let mut current = layout(EXAMPLE);
+        for expected in &[
+            "#..#.\n####.\n###.#\n##.##\n.##..",
+            "#####\n....#\n....#\n...#.\n#.###",
+            "#....\n####.\n...##\n#.##.\n.##.#",
+            "####.\n....#\n##..#\n.....\n##...",
+        ] {
+            current = step(current);
+            assert_eq!(layout(expected), current);
+        }
+    }
+
+    #[test]
+    fn test_first_repeated_layout() {
+        let repeated = first_repeated_layout(layout(EXAMPLE));
+        assert_eq!(layout(".....\n.....\n.....\n#....\n.#..."), repeated);
+        assert_eq!(2_129_920, repeated);
+    }
+
+    #[test]
+    fn test_recursive_neighbours() {
+        // The puzzle's examples, numbering tiles from 1.
+        let count = |tile: usize| recursive_neighbours(tile - 1).len();
+        assert_eq!(4, count(19));
+        assert_eq!(4, count(7));
+        assert_eq!(8, count(14));
+        assert_eq!(
+            vec![(-1, 7), (0, 5), (-1, 11), (0, 1)],
+            recursive_neighbours(0)
+        );
+    }
+
+    #[test]
+    fn test_bugs_after_ten_minutes() {
+        assert_eq!(99, bugs_after(layout(EXAMPLE), 10));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_layout("....\n").is_err());
+        assert_eq!(
+            Err("'x' is not a tile".to_string()),
+            parse_layout("....x\n.....\n.....\n.....\n.....")
+        );
+    }
+}
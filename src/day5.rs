@@ -1,25 +1,102 @@
-use super::get_intcode_memory_from_file;
+use super::intcode::cpu::{Cpu, CpuEvent};
 use super::intcode::*;
-use std::iter::once;
+use super::solution::{check_intcode_input, read_intcode_input, AocError, Example, Part, Solution};
 
-pub fn run_part_1(path: &str) -> EmulatorMemoryType {
-    let initial_memory = get_intcode_memory_from_file(path);
+pub fn run_part_1(path: &str) -> Result<EmulatorMemoryType, AocError> {
+    run_diagnostics(path, Part::One, 1)
+}
+
+pub fn run_part_2(path: &str) -> Result<EmulatorMemoryType, AocError> {
+    run_diagnostics(path, Part::Two, 5)
+}
+
+// The diagnostic program reads the system ID and nothing else. If it fails,
+// the codes it printed up to that point say which tests went wrong.
+fn run_diagnostics(
+    path: &str,
+    part: Part,
+    system_id: EmulatorMemoryType,
+) -> Result<EmulatorMemoryType, AocError> {
+    Day5.check_input(path)?;
+    let initial_memory = read_intcode_input(Day5.day(), path)?;
+
+    validate_entry(&initial_memory)?;
+    match run_program(&initial_memory, &[system_id]) {
+        Ok(run) => run.outputs.last().copied().ok_or(AocError::NoAnswer {
+            day: 5,
+            part: part.number(),
+        }),
+        // run_program keeps nothing on failure, so run again to find the
+        // codes printed before it.
+        Err(error) => {
+            let (codes, _) =
+                Emulator::with_inputs(&initial_memory, [system_id]).collect_outputs_lossy();
+            Err(AocError::Diagnostics { error, codes })
+        }
+    }
+}
 
-    let emulator = Emulator::new(&initial_memory, once(Ok(1)));
-    let outputs = emulator
-        .into_output_iter()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
-    outputs[outputs.len() - 1]
+// The last output is the diagnostic code. Asking for a second input is an
+// error, as the program only ever gets the system ID.
+pub fn run_diagnostics_on<C: Cpu>(
+    cpu: &mut C,
+    system_id: EmulatorMemoryType,
+) -> Result<EmulatorMemoryType, EmulatorError> {
+    cpu.push_input(system_id);
+    let mut last_output = None;
+    loop {
+        match cpu.run_until_event()? {
+            CpuEvent::Output(value) => last_output = Some(value),
+            CpuEvent::NeedsInput { position } => {
+                return Err(EmulatorError::InputNonExistent {
+                    position,
+                    context: None,
+                })
+            }
+            CpuEvent::Halted => break,
+            CpuEvent::Watchpoint { .. } => {}
+        }
+    }
+    Ok(last_output.expect("the diagnostic program produces output"))
 }
 
-pub fn run_part_2(path: &str) -> EmulatorMemoryType {
-    let initial_memory = get_intcode_memory_from_file(path);
+pub struct Day5;
+
+impl Solution for Day5 {
+    fn day(&self) -> u32 {
+        5
+    }
+
+    // The program has to read the system ID.
+    fn check_input(&self, path: &str) -> Result<(), AocError> {
+        check_intcode_input(self.day(), path, 1, true)
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
 
-    let emulator = Emulator::new(&initial_memory, once(Ok(5)));
-    let outputs = emulator
-        .into_output_iter()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
-    outputs[outputs.len() - 1]
+    // Part 1 gives the programs system ID 1 and part 2 gives them 5.
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(Part::One, "3,0,4,0,99", "1"),
+            Example::new(Part::One, "3,9,8,9,10,9,4,9,99,-1,8", "0"),
+            Example::new(Part::Two, "3,9,7,9,10,9,4,9,99,-1,8", "1"),
+            Example::new(Part::Two, "3,3,1108,-1,8,3,4,3,99", "0"),
+            Example::new(Part::Two, "3,3,1107,-1,8,3,4,3,99", "1"),
+            Example::new(Part::One, "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", "1"),
+            Example::new(Part::Two, "3,3,1105,-1,9,1101,0,0,12,4,12,99,1", "1"),
+            Example::new(
+                Part::Two,
+                "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,\
+                 1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,\
+                 1105,1,46,98,99",
+                "999",
+            ),
+        ]
+    }
 }
@@ -1,95 +1,71 @@
-use super::get_intcode_memory_from_file;
 use super::intcode::*;
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::iter::once;
+use super::progress::{self, ProgressSink};
+use super::solution::{check_intcode_input, read_intcode_input, AocError, Example, Part, Solution};
+use std::ops::RangeInclusive;
 
-pub fn run_part_1(path: &str) -> EmulatorMemoryType {
-    let initial_memory = get_intcode_memory_from_file(path);
+fn read_program(path: &str) -> Result<Vec<EmulatorMemoryType>, AocError> {
+    Day7.check_input(path)?;
+    let initial_memory = read_intcode_input(Day7.day(), path)?;
+    validate_entry(&initial_memory)?;
+    Ok(initial_memory)
+}
 
-    let mut highest_thrust = None;
-    let initial_input = 0;
-    let mut permutator = Permutator::new((0..=4).collect());
-    while let Some(x) = permutator.next() {
-        let (phase_a, phase_b, phase_c, phase_d, phase_e) = (x[0], x[1], x[2], x[3], x[4]);
-        let emulator_a = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_a)).chain(once(Ok(initial_input))),
-        );
-        let emulator_b = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_b)).chain(emulator_a.into_output_iter()),
-        );
-        let emulator_c = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_c)).chain(emulator_b.into_output_iter()),
-        );
-        let emulator_d = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_d)).chain(emulator_c.into_output_iter()),
-        );
-        let emulator_e = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_e)).chain(emulator_d.into_output_iter()),
-        );
+pub fn run_part_1(path: &str) -> Result<EmulatorMemoryType, AocError> {
+    find_highest_thrust(&read_program(path)?, None)?.ok_or(AocError::NoAnswer { day: 7, part: 1 })
+}
 
-        let thrust_output = emulator_e.into_output_iter().last().unwrap().unwrap();
-        highest_thrust = Some(highest_thrust.map_or(thrust_output, |current| {
-            std::cmp::max(thrust_output, current)
-        }));
-    }
+pub fn find_highest_thrust(
+    initial_memory: &[EmulatorMemoryType],
+    progress: Option<&mut dyn ProgressSink>,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    find_highest(initial_memory, 0..=4, pipeline::run_chain, progress)
+}
 
-    highest_thrust.unwrap()
+pub fn run_part_2(path: &str) -> Result<EmulatorMemoryType, AocError> {
+    find_highest_feedback_thrust(&read_program(path)?, None)?
+        .ok_or(AocError::NoAnswer { day: 7, part: 2 })
 }
 
-pub fn run_part_2(path: &str) -> EmulatorMemoryType {
-    let initial_memory = get_intcode_memory_from_file(path);
+pub fn find_highest_feedback_thrust(
+    initial_memory: &[EmulatorMemoryType],
+    progress: Option<&mut dyn ProgressSink>,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    find_highest(initial_memory, 5..=9, pipeline::run_feedback_loop, progress)
+}
+
+type Amplifiers = fn(
+    &[EmulatorMemoryType],
+    &[EmulatorMemoryType],
+    EmulatorMemoryType,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError>;
 
+// The highest thrust run_amplifiers gives over every ordering of phases,
+// or None if the last amplifier gives no thrust for some ordering.
+fn find_highest(
+    initial_memory: &[EmulatorMemoryType],
+    phases: RangeInclusive<EmulatorMemoryType>,
+    run_amplifiers: Amplifiers,
+    mut progress: Option<&mut dyn ProgressSink>,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
     let mut highest_thrust = None;
     let initial_input = 0;
-    let mut permutator = Permutator::new((5..=9).collect());
+    let mut permutator = Permutator::new(phases.collect());
+    let total = permutator.count();
+    let mut tried = 0;
     while let Some(x) = permutator.next() {
-        let (phase_a, phase_b, phase_c, phase_d, phase_e) = (x[0], x[1], x[2], x[3], x[4]);
-
-        let emulator_e_a_loopback_pipe =
-            RefCell::new(VecDeque::<Result<EmulatorMemoryType, EmulatorError>>::new());
-
-        let emulator_a = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_a))
-                .chain(once(Ok(initial_input)))
-                .chain(std::iter::from_fn(|| {
-                    emulator_e_a_loopback_pipe.borrow_mut().pop_front()
-                })),
-        );
-        let emulator_b = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_b)).chain(emulator_a.into_output_iter()),
-        );
-        let emulator_c = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_c)).chain(emulator_b.into_output_iter()),
-        );
-        let emulator_d = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_d)).chain(emulator_c.into_output_iter()),
-        );
-        let emulator_e = Emulator::new(
-            &initial_memory,
-            once(Ok(phase_e)).chain(emulator_d.into_output_iter()),
-        );
-        let output_iterator = emulator_e.into_output_iter().map(|value| {
-            emulator_e_a_loopback_pipe.borrow_mut().push_back(value);
-            value
-        });
-
-        let thrust_output = output_iterator.last().unwrap().unwrap();
+        let thrust_output = match run_amplifiers(initial_memory, x, initial_input)? {
+            Some(thrust_output) => thrust_output,
+            None => return Ok(None),
+        };
         highest_thrust = Some(highest_thrust.map_or(thrust_output, |current| {
             std::cmp::max(thrust_output, current)
         }));
+
+        tried += 1;
+        progress::report(&mut progress, tried, Some(total));
     }
 
-    highest_thrust.unwrap()
+    Ok(highest_thrust)
 }
 
 struct Permutator {
@@ -105,6 +81,11 @@ impl Permutator {
         }
     }
 
+    // The number of permutations next() will produce in total.
+    fn count(&self) -> u64 {
+        (1..=self.array.len() as u64).product()
+    }
+
     fn next(&mut self) -> Option<&[EmulatorMemoryType]> {
         while let Some((start, swap_index, explored)) = self.recursion_stack.pop() {
             // let tab: String = std::iter::repeat(" ").take(self.recursion_stack.len()).collect();
@@ -127,3 +108,102 @@ impl Permutator {
         None
     }
 }
+
+pub struct Day7;
+
+impl Solution for Day7 {
+    fn day(&self) -> u32 {
+        7
+    }
+
+    // Every amplifier reads its phase setting.
+    fn check_input(&self, path: &str) -> Result<(), AocError> {
+        check_intcode_input(self.day(), path, 1, true)
+    }
+
+    fn part_1(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_1(path)?.to_string())
+    }
+
+    fn part_2(&self, path: &str) -> Result<String, AocError> {
+        Ok(run_part_2(path)?.to_string())
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example::new(
+                Part::One,
+                "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0",
+                "43210",
+            ),
+            Example::new(
+                Part::One,
+                "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+                "54321",
+            ),
+            Example::new(
+                Part::One,
+                "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,\
+                        31,1,32,31,31,4,31,99,0,0,0",
+                "65210",
+            ),
+            Example::new(
+                Part::Two,
+                "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,\
+                        28,6,99,0,0,5",
+                "139629729",
+            ),
+            Example::new(
+                Part::Two,
+                "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,\
+                        54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,\
+                        56,-1,56,1005,56,6,99,0,0,0,0,10",
+                "18216",
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_complete(reports: &[(u64, Option<u64>)]) {
+        assert!(reports.iter().all(|&(_, total)| total == Some(120)));
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(Some(120), reports.last().map(|report| report.0));
+    }
+
+    #[test]
+    fn test_highest_thrust_reports_progress() {
+        let program = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let mut reports = Vec::new();
+        assert_eq!(
+            Ok(Some(43210)),
+            find_highest_thrust(
+                &program,
+                Some(&mut |done, total| reports.push((done, total)))
+            )
+        );
+        assert_complete(&reports);
+    }
+
+    #[test]
+    fn test_highest_feedback_thrust_reports_progress() {
+        let program = [
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut reports = Vec::new();
+        assert_eq!(
+            Ok(Some(139_629_729)),
+            find_highest_feedback_thrust(
+                &program,
+                Some(&mut |done, total| reports.push((done, total)))
+            )
+        );
+        assert_complete(&reports);
+    }
+}
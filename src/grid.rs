@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub type Position = (i64, i64);
+
+// Only the cells that were written are stored, so painting robots can wander
+// in any direction from the origin. y grows downwards, like the puzzles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Position, T>,
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> SparseGrid<T> {
+        SparseGrid {
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, position: Position, value: T) -> Option<T> {
+        self.cells.insert(position, value)
+    }
+
+    pub fn get(&self, position: Position) -> Option<&T> {
+        self.cells.get(&position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.cells
+            .iter()
+            .map(|(&position, value)| (position, value))
+    }
+
+    // The smallest and largest corner covering every written cell.
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        self.cells.keys().fold(None, |bounds, &(x, y)| {
+            Some(match bounds {
+                None => ((x, y), (x, y)),
+                Some(((min_x, min_y), (max_x, max_y))) => {
+                    ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+                }
+            })
+        })
+    }
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> SparseGrid<T> {
+        SparseGrid::new()
+    }
+}
+
+// Rows of the grid within its bounds, None for unwritten cells.
+fn rows_of<T>(grid: &SparseGrid<T>) -> Vec<Vec<Option<&T>>> {
+    let ((min_x, min_y), (max_x, max_y)) = match grid.bounds() {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
+    (min_y..=max_y)
+        .map(|y| (min_x..=max_x).map(|x| grid.get((x, y))).collect())
+        .collect()
+}
+
+// Rows of the grid within its bounds, with unwritten cells treated as unset.
+fn rows(grid: &SparseGrid<bool>) -> Vec<Vec<bool>> {
+    rows_of(grid)
+        .into_iter()
+        .map(|row| row.into_iter().map(|cell| cell == Some(&true)).collect())
+        .collect()
+}
+
+// Plain PBM: set cells are 1 (black), everything else 0 (white).
+pub fn render_pbm_string(grid: &SparseGrid<bool>) -> String {
+    let rows = rows(grid);
+    let width = rows.first().map_or(0, Vec::len);
+    let mut pbm = format!("P1\n{} {}\n", width, rows.len());
+    for row in rows.iter() {
+        let cells: Vec<&str> = row.iter().map(|&set| if set { "1" } else { "0" }).collect();
+        pbm.push_str(&cells.join(" "));
+        pbm.push('\n');
+    }
+    pbm
+}
+
+// One line of glyphs per row, for grids that aren't just on or off.
+pub fn render_text<T>(grid: &SparseGrid<T>, glyph: impl Fn(Option<&T>) -> char) -> String {
+    let mut text = String::new();
+    for row in rows_of(grid) {
+        text.extend(row.into_iter().map(&glyph));
+        text.push('\n');
+    }
+    text
+}
+
+// Binary PPM with every cell drawn as a scale x scale block.
+pub fn render_ppm(grid: &SparseGrid<bool>, scale: u32) -> Vec<u8> {
+    render_ppm_with(grid, scale, |cell| {
+        if cell == Some(&true) {
+            [0, 0, 0]
+        } else {
+            [255, 255, 255]
+        }
+    })
+}
+
+// Like render_ppm, with the colour of each cell chosen by color.
+pub fn render_ppm_with<T>(
+    grid: &SparseGrid<T>,
+    scale: u32,
+    color: impl Fn(Option<&T>) -> [u8; 3],
+) -> Vec<u8> {
+    let rows = rows_of(grid);
+    let scale = scale as usize;
+    let width = rows.first().map_or(0, Vec::len) * scale;
+    let height = rows.len() * scale;
+
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    ppm.reserve(width * height * 3);
+    for row in rows.iter() {
+        for _ in 0..scale {
+            for &cell in row.iter() {
+                let pixel = color(cell);
+                for _ in 0..scale {
+                    ppm.extend_from_slice(&pixel);
+                }
+            }
+        }
+    }
+    ppm
+}
+
+pub fn render_image(grid: &SparseGrid<bool>, path: &Path, scale: u32) -> io::Result<()> {
+    File::create(path)?.write_all(&render_ppm(grid, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An L with a gap, offset from the origin to check bounds are honoured.
+    fn tiny_grid() -> SparseGrid<bool> {
+        let mut grid = SparseGrid::new();
+        grid.insert((-1, 2), true);
+        grid.insert((-1, 3), true);
+        grid.insert((-1, 4), true);
+        grid.insert((0, 4), true);
+        grid.insert((1, 4), false);
+        grid.insert((1, 2), true);
+        grid
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(None, SparseGrid::<bool>::new().bounds());
+        assert_eq!(Some(((-1, 2), (1, 4))), tiny_grid().bounds());
+    }
+
+    #[test]
+    fn test_render_pbm_string() {
+        assert_eq!(
+            "P1\n3 3\n1 0 1\n1 0 0\n1 1 0\n",
+            render_pbm_string(&tiny_grid())
+        );
+        assert_eq!("P1\n0 0\n", render_pbm_string(&SparseGrid::new()));
+    }
+
+    #[test]
+    fn test_render_text() {
+        let glyph = |cell: Option<&bool>| match cell {
+            Some(true) => '#',
+            Some(false) => '.',
+            None => ' ',
+        };
+        assert_eq!("# #\n#  \n##.\n", render_text(&tiny_grid(), glyph));
+    }
+
+    #[test]
+    fn test_render_ppm_matches_golden() {
+        assert_eq!(
+            include_bytes!("../tests/fixtures/grid/tiny_scale_2.ppm").to_vec(),
+            render_ppm(&tiny_grid(), 2)
+        );
+    }
+
+    #[test]
+    fn test_render_image_writes_ppm() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("aoc_grid_{}.ppm", std::process::id()));
+        render_image(&tiny_grid(), &path, 1)?;
+        let bytes = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(render_ppm(&tiny_grid(), 1), bytes);
+        assert!(bytes.starts_with(b"P6\n3 3\n255\n"));
+        assert_eq!(11 + 3 * 3 * 3, bytes.len());
+        Ok(())
+    }
+}
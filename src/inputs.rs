@@ -0,0 +1,297 @@
+use super::verify::{strip_comment, unquote};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// The manifest sits next to the inputs it describes, so it can be checked
+// in while the inputs themselves are not.
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub day: u32,
+    // Relative to the input directory.
+    pub path: PathBuf,
+    // Lowercase hex.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Manifest {
+    entries: BTreeMap<u32, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManifestProblem {
+    Missing {
+        day: u32,
+        path: PathBuf,
+    },
+    Mismatch {
+        day: u32,
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    Unreadable {
+        day: u32,
+        path: PathBuf,
+        error: String,
+    },
+}
+
+impl std::fmt::Display for ManifestProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ManifestProblem::Missing { day, path } => {
+                write!(f, "day {}: {} is missing", day, path.display())
+            }
+            ManifestProblem::Mismatch {
+                day,
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "day {}: {} has sha256 {} but the manifest expects {}. Is it another day's input?",
+                day,
+                path.display(),
+                actual,
+                expected
+            ),
+            ManifestProblem::Unreadable { day, path, error } => {
+                write!(
+                    f,
+                    "day {}: could not read {}: {}",
+                    day,
+                    path.display(),
+                    error
+                )
+            }
+        }
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl Manifest {
+    // The same TOML subset as answers files: a [day] table per input, each
+    // with a path and a sha256 string.
+    //
+    //   [5]
+    //   path = "5.txt"
+    //   sha256 = "..."
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut tables: Vec<(usize, u32, BTreeMap<String, String>)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+                let day = unquote(header.trim())
+                    .parse()
+                    .map_err(|_| format!("line {}: '{}' is not a day", line_number, header))?;
+                if tables.iter().any(|(_, known, _)| *known == day) {
+                    return Err(format!("line {}: day {} is listed twice", line_number, day));
+                }
+                tables.push((line_number, day, BTreeMap::new()));
+                continue;
+            }
+
+            let (_, _, keys) = tables
+                .last_mut()
+                .ok_or_else(|| format!("line {}: expected a [day] header", line_number))?;
+            let mut split = line.splitn(2, '=');
+            let key = split.next().unwrap_or("").trim();
+            let value = split
+                .next()
+                .map(str::trim)
+                .filter(|value| value.len() >= 2 && value.starts_with('"') && value.ends_with('"'))
+                .ok_or_else(|| format!("line {}: expected `key = \"value\"`", line_number))?;
+            keys.insert(key.to_string(), unquote(value).to_string());
+        }
+
+        let mut entries = BTreeMap::new();
+        for (line_number, day, mut keys) in tables {
+            let mut take = |key: &str| {
+                keys.remove(key)
+                    .ok_or_else(|| format!("line {}: day {} has no {}", line_number, day, key))
+            };
+            let path = PathBuf::from(take("path")?);
+            let sha256 = take("sha256")?.to_ascii_lowercase();
+            if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "line {}: day {} sha256 is not 64 hex digits",
+                    line_number, day
+                ));
+            }
+            if let Some(key) = keys.keys().next() {
+                return Err(format!(
+                    "line {}: day {} has unknown key '{}'",
+                    line_number, day, key
+                ));
+            }
+            entries.insert(day, ManifestEntry { day, path, sha256 });
+        }
+        Ok(Manifest { entries })
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        Manifest::parse(&text)
+    }
+
+    // None when input_dir has no manifest.
+    pub fn load_from_dir(input_dir: &Path) -> Result<Option<Manifest>, String> {
+        let path = input_dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Manifest::load(&path)
+            .map(Some)
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn entry(&self, day: u32) -> Option<&ManifestEntry> {
+        self.entries.get(&day)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.entries.values()
+    }
+
+    // Where day's input lives, and whether it is the one the manifest
+    // lists. Days the manifest doesn't mention are not checked.
+    pub fn check(&self, input_dir: &Path, day: u32) -> Option<Result<PathBuf, ManifestProblem>> {
+        let entry = self.entry(day)?;
+        let path = input_dir.join(&entry.path);
+        if !path.is_file() {
+            return Some(Err(ManifestProblem::Missing { day, path }));
+        }
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                return Some(Err(ManifestProblem::Unreadable {
+                    day,
+                    path,
+                    error: error.to_string(),
+                }))
+            }
+        };
+        let actual = sha256_hex(&bytes);
+        if actual != entry.sha256 {
+            return Some(Err(ManifestProblem::Mismatch {
+                day,
+                path,
+                expected: entry.sha256.clone(),
+                actual,
+            }));
+        }
+        Some(Ok(path))
+    }
+
+    pub fn verify(&self, input_dir: &Path) -> Vec<ManifestProblem> {
+        self.entries
+            .keys()
+            .filter_map(|&day| self.check(input_dir, day))
+            .filter_map(Result::err)
+            .collect()
+    }
+}
+
+// Checks every input listed in input_dir's manifest.
+pub fn verify_manifest(input_dir: &Path) -> Result<Vec<ManifestProblem>, String> {
+    let path = input_dir.join(MANIFEST_FILE);
+    let manifest =
+        Manifest::load(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(manifest.verify(input_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = "tests/fixtures/inputs";
+    const DAY_1_SHA256: &str = "cdbd252605d7ffe9b4259df9451f571e206b515542bf0d9f9a71f0f148eee2c8";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            sha256_hex(b"")
+        );
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            sha256_hex(b"abc")
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(&format!(
+            "# inputs\n[1]\npath = \"day1.txt\" # renamed\nsha256 = \"{}\"\n",
+            DAY_1_SHA256.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(
+            Some(&ManifestEntry {
+                day: 1,
+                path: PathBuf::from("day1.txt"),
+                sha256: DAY_1_SHA256.to_string(),
+            }),
+            manifest.entry(1)
+        );
+        assert_eq!(None, manifest.entry(2));
+
+        for (text, error) in &[
+            ("path = \"1.txt\"", "line 1: expected a [day] header"),
+            ("[x]", "line 1: 'x' is not a day"),
+            ("[1]\npath = \"1.txt\"", "line 1: day 1 has no sha256"),
+            (
+                "[1]\npath = \"1.txt\"\nsha256 = \"abc\"",
+                "line 1: day 1 sha256 is not 64 hex digits",
+            ),
+            ("[1]\n[1]", "line 2: day 1 is listed twice"),
+            ("[1]\npath = 1", "line 2: expected `key = \"value\"`"),
+        ] {
+            assert_eq!(Err(error.to_string()), Manifest::parse(text));
+        }
+    }
+
+    #[test]
+    fn test_verify_manifest() {
+        let dir = Path::new(FIXTURES);
+        assert_eq!(
+            Ok(vec![
+                ManifestProblem::Mismatch {
+                    day: 2,
+                    path: dir.join("2.txt"),
+                    expected: "0000000000000000000000000000000000000000000000000000000000000000"
+                        .to_string(),
+                    actual: DAY_1_SHA256.to_string(),
+                },
+                ManifestProblem::Missing {
+                    day: 5,
+                    path: dir.join("5.txt"),
+                },
+            ]),
+            verify_manifest(dir)
+        );
+
+        let manifest = Manifest::load_from_dir(dir).unwrap().unwrap();
+        assert_eq!(Some(Ok(dir.join("day1.txt"))), manifest.check(dir, 1));
+        assert_eq!(None, manifest.check(dir, 7));
+        assert_eq!(
+            Ok(None),
+            Manifest::load_from_dir(Path::new("tests/fixtures"))
+        );
+    }
+}
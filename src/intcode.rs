@@ -1,76 +1,330 @@
 use opcode_macro::make_op_code;
-use std::ops::Index;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut, Range};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
+mod address;
+pub mod ascii;
+pub mod asm;
+#[cfg(feature = "async")]
+pub mod async_emulator;
+mod batch;
+mod binary;
+pub mod bisect;
+mod call;
+mod cell;
+pub mod channel;
+pub mod core_dump;
+pub mod cpu;
+pub mod disassemble;
+pub mod dump;
+pub mod equiv;
+mod error_context;
+pub mod history;
+pub mod input;
+pub mod interactive;
+pub mod io;
+pub mod jump_check;
+pub mod layout;
+mod loop_check;
+mod memory;
+pub mod peek;
+mod pipe;
+pub mod pipeline;
+pub mod profile;
+mod queue_input;
+pub mod record;
+pub mod reference;
+pub mod replay;
+pub mod scheduler;
+pub mod snapshot;
+pub mod stats;
+pub mod stream;
+pub mod trace;
+mod unknown_opcode;
+mod validate;
+
+use address::relative_address;
+pub use address::{AddrContext, Address, AddressPurpose};
+pub use batch::{BatchResult, StopReason};
+pub use call::{call, CallResult};
+pub use cell::{MemoryCell, WideValue};
+pub use channel::{spawn, EmulatorHandle};
+use core_dump::{CoreDump, CoreDumpStatus};
+pub use error_context::{ErrorContext, CONTEXT_CELLS};
+use history::{InstructionHistory, InstructionRecord, DEFAULT_HISTORY_SIZE};
+pub use input::IntoEmulatorInput;
+use jump_check::JumpTargetCheck;
+use layout::MemoryLayout;
+use loop_check::LoopCheck;
+pub use loop_check::DEFAULT_LOOP_CHECK_INTERVAL;
+use memory::read_cell;
+pub use memory::Memory;
+pub use memory::MEMORY_LIMIT;
+pub use pipe::Pipe;
+use profile::Profiler;
+pub use queue_input::QueueInput;
+pub use replay::replay;
+use stats::ExecutionStats;
+pub use unknown_opcode::UnknownOpcodeCtx;
+use unknown_opcode::UnknownOpcodeHook;
+pub use validate::{validate, validate_without_growth, IssueKind, ValidationIssue};
+
+// i64 is enough for every puzzle's answers, but some programs' intermediate
+// products overflow it. Building with --no-default-features --features
+// mem-i128 widens every cell; an Emulator<I, i128> widens just that machine.
+#[cfg(not(feature = "mem-i128"))]
 pub type EmulatorMemoryType = i64;
+#[cfg(feature = "mem-i128")]
+pub type EmulatorMemoryType = i128;
+
+// Puzzle inputs assume at least a signed 64 bit cell.
+const _: () = assert!(EmulatorMemoryType::BITS >= 64 && EmulatorMemoryType::MIN < 0);
+
+// For the file formats and grids that stay 64 bit whichever width is
+// configured. None if the cell doesn't fit.
+#[allow(clippy::useless_conversion)]
+pub fn cell_to_i64(cell: EmulatorMemoryType) -> Option<i64> {
+    i64::try_from(cell).ok()
+}
 
-enum ParameterMode {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterMode {
     Position,  // = Position(memory: Memory, parameter_value: ParameterValue) {},
     Immediate, // = Immediate(parameter_value: ParameterValue) {},
+    Relative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterInfo {
+    pub name: &'static str,
+    pub kind: ParameterKind,
+}
+
+// One entry of OPCODE_TABLE, which make_op_code! emits in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpCodeInfo {
+    pub code: EmulatorMemoryType,
+    pub mnemonic: &'static str,
+    pub parameters: &'static [ParameterInfo],
+    pub reads_input: bool,
+    pub writes_output: bool,
+    pub halts: bool,
+    pub jumps: bool,
 }
 
 // 0 = Position for ReadOnly, Writable
 // 1 = Immediate for ReadOnly
-// 2 = Relative for ReadOnly
+// 2 = Relative for ReadOnly, Writable: the address is the parameter plus
+//     the relative base, which starts at 0 and moves with opcode 9
 
-make_op_code!(OpCode {
+// Self-modifying code: an instruction is decoded in full (opcode, modes and
+// every parameter) before it writes anything, and the next instruction
+// pointer comes from that decoding. So an instruction that overwrites its
+// own opcode or parameter cells still runs as decoded and advances by its
+// original length, and the new values take effect from the next step on.
+// Every execution path (both dispatchers and eval_one) must keep this.
+
+make_op_code!(#[dispatch_table] OpCode {
     1 = Add(addend1: ReadOnly, addend2: ReadOnly, dest: Writable) {
         *dest = addend1 + addend2;
     },
     2 = Multiply(factor1: ReadOnly, factor2: ReadOnly, dest: Writable) {
         *dest = factor1 * factor2;
     },
-    3 = Input(dest: Writable) [input_iter: Input] {
-        *dest = input_iter.next().ok_or(EmulatorError::InputNonExistent)??;
+    3 = Input(dest: Writable) [input_iter: Input, position: InstructionPointer] {
+        *dest = input_iter.next().ok_or(EmulatorError::InputNonExistent { position, context: None })??;
     },
     4 = Output(value: ReadOnly) [Output] {
         value
     },
     5 = JumpIfTrue(value: ReadOnly, new_address: ReadOnly) [new_instruction_pointer: InstructionPointerOverride] {
-        if value != 0 {
+        if value != 0.into() {
             *new_instruction_pointer = Some(new_address);
         }
     },
     6 = JumpIfFalse(value: ReadOnly, new_address: ReadOnly) [new_instruction_pointer: InstructionPointerOverride] {
-        if value == 0 {
+        if value == 0.into() {
             *new_instruction_pointer = Some(new_address);
         }
     },
     7 = LessThan(left_side: ReadOnly, right_side: ReadOnly, dest: Writable) {
-        *dest = if left_side < right_side { 1 } else { 0 };
+        *dest = if left_side < right_side { 1.into() } else { 0.into() };
     },
     8 = Equals(left_side: ReadOnly, right_side: ReadOnly, dest: Writable) {
-        *dest = if left_side == right_side { 1 } else { 0 };
+        *dest = if left_side == right_side { 1.into() } else { 0.into() };
+    },
+    9 = AdjustRelativeBase(offset: ReadOnly) [relative_base: RelativeBase] {
+        // Saturates, so a runaway base makes later relative addresses
+        // invalid rather than wrapping round to valid ones.
+        *relative_base = relative_base.saturating_add(offset);
     },
     99 = End!
 });
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+// What a single instruction did, as reported by OpCode::eval_one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneStepOutcome {
+    // None once the program has halted.
+    pub next_instruction_pointer: Option<usize>,
+    pub output: Option<EmulatorMemoryType>,
+    pub written_cells: BTreeSet<usize>,
+}
+
+impl std::fmt::Display for OneStepOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.next_instruction_pointer {
+            Some(next) => write!(f, "next {}", next)?,
+            None => write!(f, "halted")?,
+        }
+        if let Some(output) = self.output {
+            write!(f, ", output {}", output)?;
+        }
+        if !self.written_cells.is_empty() {
+            let cells: Vec<String> = self.written_cells.iter().map(|c| c.to_string()).collect();
+            write!(f, ", wrote {}", cells.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl OpCode {
+    // Runs the instruction at instruction_pointer against cells without an
+    // Emulator, popping any input from the front of inputs and adjusting
+    // relative_base in place.
+    pub fn eval_one(
+        cells: &mut Vec<EmulatorMemoryType>,
+        instruction_pointer: usize,
+        relative_base: &mut EmulatorMemoryType,
+        inputs: &mut Vec<EmulatorMemoryType>,
+    ) -> Result<OneStepOutcome, EmulatorError> {
+        // Looked up first, in case the instruction overwrites its own opcode.
+        let code = cells
+            .get(instruction_pointer)
+            .map(|instruction| instruction % 100);
+        let mut input_iter = std::iter::from_fn(|| {
+            if inputs.is_empty() {
+                None
+            } else {
+                Some(Ok(inputs.remove(0)))
+            }
+        });
+        let mut resolved_parameters = Vec::new();
+        let mut memory = Memory::from(std::mem::take(cells));
+        let run_result = OpCode::run(
+            &mut memory,
+            instruction_pointer,
+            relative_base,
+            &mut input_iter,
+            &mut resolved_parameters,
+        );
+        *cells = memory.into_vec();
+        let (next_instruction_pointer, output, _) = run_result?;
+
+        // Write parameters resolve to their destination address, worked out
+        // before the instruction could overwrite its own parameters.
+        let parameters = OPCODE_TABLE
+            .iter()
+            .find(|info| Some(info.code) == code)
+            .map_or(&[][..], |info| info.parameters);
+        Ok(OneStepOutcome {
+            next_instruction_pointer,
+            output,
+            written_cells: parameters
+                .iter()
+                .zip(resolved_parameters)
+                .filter(|(parameter, _)| parameter.kind == ParameterKind::Write)
+                .filter_map(|(_, address)| usize::try_from(address).ok())
+                .collect(),
+        })
+    }
+}
+
+// Every error has room for an ErrorContext, which the emulator fills in
+// when the error stops a step.
+#[derive(Debug, Clone)]
 pub enum EmulatorError {
     InvalidInstruction {
-        value_found: EmulatorMemoryType,
+        value_found: WideValue,
         position: usize,
+        context: Option<Box<ErrorContext>>,
     },
     NotEnoughParametersForInstruction {
-        instruction: EmulatorMemoryType,
+        instruction: WideValue,
         expected: usize,
         found: usize,
+        context: Option<Box<ErrorContext>>,
     },
     InvalidMemoryLocation {
-        value_found: EmulatorMemoryType,
+        value_found: WideValue,
         position: usize,
+        context: Option<Box<ErrorContext>>,
     },
     InstructionPointerOutOfBounds {
         position: usize,
+        context: Option<Box<ErrorContext>>,
     },
     InvalidParameterMode {
-        value_found: EmulatorMemoryType,
+        value_found: WideValue,
         position: usize,
+        context: Option<Box<ErrorContext>>,
     },
     UnexpectedParameterModeForWritable {
-        value_found: EmulatorMemoryType,
+        value_found: WideValue,
+        position: usize,
+        context: Option<Box<ErrorContext>>,
+    },
+    // position is the Input instruction that found no input.
+    InputNonExistent {
+        position: usize,
+        context: Option<Box<ErrorContext>>,
+    },
+    InvalidEntryInstruction {
+        value_found: WideValue,
+        context: Option<Box<ErrorContext>>,
+    },
+    WriteToReadOnlyMemory {
+        address: usize,
+        position: usize,
+        context: Option<Box<ErrorContext>>,
+    },
+    TooManyInputsConsumed {
+        expected: u64,
         position: usize,
+        context: Option<Box<ErrorContext>>,
+    },
+    // An argument to intcode::call addressed a cell outside the program.
+    ArgumentOutOfRange {
+        address: usize,
+        memory_size: usize,
+        context: Option<Box<ErrorContext>>,
+    },
+    // The step limit ran out before the program halted.
+    InstructionLimitExceeded {
+        executed: u64,
+        context: Option<Box<ErrorContext>>,
+    },
+    // With loop detection on, the machine came back to an earlier state
+    // without any input or output in between, so would never halt.
+    LoopDetected {
+        steps: u64,
+        context: Option<Box<ErrorContext>>,
+    },
+    // A write would have grown memory to cover requested, which is at or
+    // past the memory limit.
+    MemoryLimitExceeded {
+        requested: usize,
+        limit: usize,
+        context: Option<Box<ErrorContext>>,
     },
-    InputNonExistent,
 }
 
 impl std::fmt::Display for EmulatorError {
@@ -79,7 +333,7 @@ impl std::fmt::Display for EmulatorError {
             EmulatorError::InvalidInstruction {
                 value_found,
                 position,
-            } => write!(
+            .. } => write!(
                 f,
                 "Invalid instruction {} referenced at {}",
                 value_found, position
@@ -88,7 +342,7 @@ impl std::fmt::Display for EmulatorError {
                 instruction,
                 expected,
                 found,
-            } => write!(
+            .. } => write!(
                 f,
                 "Not enough parameters for instruction: {:?}. Expected {}, but found {}",
                 instruction, expected, found
@@ -96,262 +350,2780 @@ impl std::fmt::Display for EmulatorError {
             EmulatorError::InvalidMemoryLocation {
                 value_found,
                 position,
-            } => write!(
+            .. } => write!(
                 f,
                 "Invalid memory location {} referenced at {}.",
                 value_found, position
             ),
-            EmulatorError::InstructionPointerOutOfBounds { position } => write!(
+            EmulatorError::InstructionPointerOutOfBounds { position, .. } => write!(
                 f,
                 "Location pointer is at {} which is out of bounds",
                 position
             ),
-            EmulatorError::InvalidParameterMode { value_found, position } => write!(
+            EmulatorError::InvalidParameterMode { value_found, position, .. } => write!(
                 f,
                 "Invalid parameter mode {} referenced at {}",
                 value_found, position
             ),
-            EmulatorError::UnexpectedParameterModeForWritable { value_found, position } => write!(
+            EmulatorError::UnexpectedParameterModeForWritable { value_found, position, .. } => write!(
                 f,
-                "Writable parameter at {} has invalid parameter mode {}. The parameter mode must be 0",
+                "Writable parameter at {} has invalid parameter mode {}. The parameter mode must be position (0) or relative (2)",
                 position, value_found
             ),
-            EmulatorError::InputNonExistent => write!(
+            EmulatorError::InputNonExistent { position, .. } => write!(
+                f,
+                "Input non existent for the instruction at {}",
+                position
+            ),
+            EmulatorError::InvalidEntryInstruction { value_found, .. } => write!(
                 f,
-                "Input non existent"
+                "Program does not start with a valid instruction (found {})",
+                value_found
             ),
+            EmulatorError::WriteToReadOnlyMemory { address, position, .. } => write!(
+                f,
+                "Write to read-only address {} referenced at {}",
+                address, position
+            ),
+            EmulatorError::TooManyInputsConsumed { expected, position, .. } => write!(
+                f,
+                "Input at {} would consume more than the expected {} inputs",
+                position, expected
+            ),
+            EmulatorError::ArgumentOutOfRange { address, memory_size, .. } => write!(
+                f,
+                "Argument address {} is outside the {} cell program",
+                address, memory_size
+            ),
+            EmulatorError::InstructionLimitExceeded { executed, .. } => write!(
+                f,
+                "Instruction limit exceeded after {} instructions",
+                executed
+            ),
+            EmulatorError::LoopDetected { steps, .. } => write!(
+                f,
+                "Program is stuck in a loop, found after {} instructions",
+                steps
+            ),
+            EmulatorError::MemoryLimitExceeded { requested, limit, .. } => write!(
+                f,
+                "Write to address {} is past the memory limit of {} cells",
+                requested, limit
+            ),
+        }?;
+        match self.context() {
+            Some(context) => write!(f, "\n{}", context),
+            None => Ok(()),
+        }
+    }
+}
+
+impl EmulatorError {
+    // Where the emulator was when the error stopped it. Errors from an
+    // Emulator's step carry this; ones from decoding plain memory don't.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            EmulatorError::InvalidInstruction { context, .. }
+            | EmulatorError::NotEnoughParametersForInstruction { context, .. }
+            | EmulatorError::InvalidMemoryLocation { context, .. }
+            | EmulatorError::InstructionPointerOutOfBounds { context, .. }
+            | EmulatorError::InvalidParameterMode { context, .. }
+            | EmulatorError::UnexpectedParameterModeForWritable { context, .. }
+            | EmulatorError::InputNonExistent { context, .. }
+            | EmulatorError::InvalidEntryInstruction { context, .. }
+            | EmulatorError::WriteToReadOnlyMemory { context, .. }
+            | EmulatorError::TooManyInputsConsumed { context, .. }
+            | EmulatorError::ArgumentOutOfRange { context, .. }
+            | EmulatorError::InstructionLimitExceeded { context, .. }
+            | EmulatorError::LoopDetected { context, .. }
+            | EmulatorError::MemoryLimitExceeded { context, .. } => context.as_deref(),
+        }
+    }
+
+    pub fn with_context(mut self, new_context: ErrorContext) -> EmulatorError {
+        match &mut self {
+            EmulatorError::InvalidInstruction { context, .. }
+            | EmulatorError::NotEnoughParametersForInstruction { context, .. }
+            | EmulatorError::InvalidMemoryLocation { context, .. }
+            | EmulatorError::InstructionPointerOutOfBounds { context, .. }
+            | EmulatorError::InvalidParameterMode { context, .. }
+            | EmulatorError::UnexpectedParameterModeForWritable { context, .. }
+            | EmulatorError::InputNonExistent { context, .. }
+            | EmulatorError::InvalidEntryInstruction { context, .. }
+            | EmulatorError::WriteToReadOnlyMemory { context, .. }
+            | EmulatorError::TooManyInputsConsumed { context, .. }
+            | EmulatorError::ArgumentOutOfRange { context, .. }
+            | EmulatorError::InstructionLimitExceeded { context, .. }
+            | EmulatorError::LoopDetected { context, .. }
+            | EmulatorError::MemoryLimitExceeded { context, .. } => {
+                *context = Some(Box::new(new_context))
+            }
+        }
+        self
+    }
+}
+
+// Errors are equal if they are the same error, wherever they were raised:
+// the context is left out, so an expected error can be written without one.
+impl PartialEq for EmulatorError {
+    fn eq(&self, other: &EmulatorError) -> bool {
+        use EmulatorError::*;
+        match (self, other) {
+            (
+                InvalidInstruction {
+                    value_found: a,
+                    position: b,
+                    ..
+                },
+                InvalidInstruction {
+                    value_found: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                NotEnoughParametersForInstruction {
+                    instruction: a,
+                    expected: b,
+                    found: c,
+                    ..
+                },
+                NotEnoughParametersForInstruction {
+                    instruction: d,
+                    expected: e,
+                    found: g,
+                    ..
+                },
+            ) => (a, b, c) == (d, e, g),
+            (
+                InvalidMemoryLocation {
+                    value_found: a,
+                    position: b,
+                    ..
+                },
+                InvalidMemoryLocation {
+                    value_found: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                InstructionPointerOutOfBounds { position: a, .. },
+                InstructionPointerOutOfBounds { position: b, .. },
+            ) => a == b,
+            (
+                InvalidParameterMode {
+                    value_found: a,
+                    position: b,
+                    ..
+                },
+                InvalidParameterMode {
+                    value_found: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                UnexpectedParameterModeForWritable {
+                    value_found: a,
+                    position: b,
+                    ..
+                },
+                UnexpectedParameterModeForWritable {
+                    value_found: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (InputNonExistent { position: a, .. }, InputNonExistent { position: b, .. }) => a == b,
+            (
+                InvalidEntryInstruction { value_found: a, .. },
+                InvalidEntryInstruction { value_found: b, .. },
+            ) => a == b,
+            (
+                WriteToReadOnlyMemory {
+                    address: a,
+                    position: b,
+                    ..
+                },
+                WriteToReadOnlyMemory {
+                    address: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                TooManyInputsConsumed {
+                    expected: a,
+                    position: b,
+                    ..
+                },
+                TooManyInputsConsumed {
+                    expected: c,
+                    position: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                ArgumentOutOfRange {
+                    address: a,
+                    memory_size: b,
+                    ..
+                },
+                ArgumentOutOfRange {
+                    address: c,
+                    memory_size: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            (
+                InstructionLimitExceeded { executed: a, .. },
+                InstructionLimitExceeded { executed: b, .. },
+            ) => a == b,
+            (LoopDetected { steps: a, .. }, LoopDetected { steps: b, .. }) => a == b,
+            (
+                MemoryLimitExceeded {
+                    requested: a,
+                    limit: b,
+                    ..
+                },
+                MemoryLimitExceeded {
+                    requested: c,
+                    limit: d,
+                    ..
+                },
+            ) => (a, b) == (c, d),
+            _ => false,
+        }
+    }
+}
+
+// The address an instruction wrote to, given its parameters as recorded
+// in InstructionRecord.
+fn written_address<C: MemoryCell>(instruction: C, parameters: &[C]) -> Option<usize> {
+    let info = OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == opcode_of(instruction))?;
+    info.parameters
+        .iter()
+        .zip(parameters)
+        .find(|(parameter, _)| parameter.kind == ParameterKind::Write)
+        .and_then(|(_, &address)| address.try_into().ok())
+}
+
+// instruction % 100, as OPCODE_TABLE and the statistics key opcodes. Always
+// within -99..=99, so it fits whatever the cell type.
+fn opcode_of<C: MemoryCell>(instruction: C) -> EmulatorMemoryType {
+    (instruction % C::from(100))
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("an opcode fits in any cell"))
+}
+
+// The instruction at instruction_pointer, decoded without running it, for
+// memory that isn't in a running machine. Relative parameters resolve
+// against a base of 0; Emulator::peek_next uses the machine's own base.
+pub fn decode(
+    memory: &[EmulatorMemoryType],
+    instruction_pointer: usize,
+) -> Result<peek::DecodedInstruction, EmulatorError> {
+    peek::decode_at(memory, instruction_pointer, 0)
+}
+
+// What a program left behind after running to completion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramRun {
+    pub memory: Vec<EmulatorMemoryType>,
+    pub outputs: Vec<EmulatorMemoryType>,
+    pub steps: u64,
+}
+
+// Runs program to completion on inputs, for the days that only need the
+// outputs or the final memory. Memory grows as the program writes past the
+// end, and asking for more input than given is an error.
+pub fn run_program(
+    program: &[EmulatorMemoryType],
+    inputs: &[EmulatorMemoryType],
+) -> Result<ProgramRun, EmulatorError> {
+    let mut emulator = Emulator::with_inputs(program, inputs.iter().copied());
+    let outputs = emulator.run_collecting_outputs()?;
+    Ok(ProgramRun {
+        memory: emulator.memory.into_vec(),
+        outputs,
+        steps: emulator.steps,
+    })
+}
+
+// Checks that address 0 decodes to a known opcode whose parameters fit in
+// memory and use modes it accepts. Catches loading the wrong input file
+// before execution wanders off and fails somewhere confusing. Memory past
+// the end is fine once running, but a first instruction reaching for it is
+// far more likely to be the wrong file.
+pub fn validate_entry(memory: &[EmulatorMemoryType]) -> Result<(), EmulatorError> {
+    let instruction = *memory
+        .first()
+        .ok_or(EmulatorError::InstructionPointerOutOfBounds {
+            position: 0,
+            context: None,
+        })?;
+    let invalid = || EmulatorError::InvalidEntryInstruction {
+        value_found: instruction.widen(),
+        context: None,
+    };
+
+    let info = OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == instruction % 100)
+        .ok_or_else(invalid)?;
+    if info.parameters.len() >= memory.len() {
+        return Err(invalid());
+    }
+
+    let mut mode_digits = instruction / 100;
+    for (index, parameter) in info.parameters.iter().enumerate() {
+        let word = memory[index + 1];
+        let in_range = match (mode_digits % 10, parameter.kind) {
+            // The relative base is still 0 at entry.
+            (0, _) | (2, _) => word >= 0 && (word as u64) < memory.len() as u64,
+            (1, ParameterKind::Read) => true,
+            _ => false,
+        };
+        if !in_range {
+            return Err(invalid());
         }
+        mode_digits /= 10;
     }
+    Ok(())
 }
 
 #[derive(PartialEq, Debug)]
-pub enum EmulatorResult {
+pub enum EmulatorResult<C = EmulatorMemoryType> {
     Success,
-    SuccessWithValue(EmulatorMemoryType),
+    SuccessWithValue(C),
     Done,
+    // An Input instruction found its input iterator empty. Nothing ran and
+    // the instruction pointer is still on it, so stepping again once input
+    // is available retries it.
+    NeedsInput,
+    // The instruction ran and wrote to a watched address. Otherwise the
+    // same as Success.
+    Watchpoint { address: usize, old: C, new: C },
+}
+
+impl<C: MemoryCell> EmulatorResult<C> {
+    pub fn output(&self) -> Option<C> {
+        match self {
+            EmulatorResult::SuccessWithValue(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        *self == EmulatorResult::Done
+    }
+}
+
+// The CLI prints these, so the formats are pinned by tests.
+impl<C: MemoryCell> std::fmt::Display for EmulatorResult<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EmulatorResult::Success => write!(f, "ok"),
+            EmulatorResult::SuccessWithValue(value) => write!(f, "output {}", value),
+            EmulatorResult::Done => write!(f, "halted"),
+            EmulatorResult::NeedsInput => write!(f, "waiting for input"),
+            EmulatorResult::Watchpoint { address, old, new } => {
+                write!(f, "watchpoint {}: {} -> {}", address, old, new)
+            }
+        }
+    }
+}
+
+// How step finds the code for an instruction. Both behave identically;
+// benches/dispatch.rs compares their speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dispatch {
+    // The generated match in OpCode::run.
+    #[default]
+    Match,
+    // Function pointers in the generated DispatchTable.
+    Table,
 }
 
-pub struct Emulator<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
-    memory: Vec<EmulatorMemoryType>,
+// Cloning needs a cloneable input iterator. fork() copies the machine onto
+// a different input source instead.
+// C is the type of a memory cell. Constructors such as new, which take
+// programs written as integer literals, build EmulatorMemoryType machines;
+// with_cells builds a machine of any other MemoryCell.
+#[derive(Clone)]
+pub struct Emulator<I, C = EmulatorMemoryType>
+where
+    I: Iterator<Item = Result<C, EmulatorError>>,
+    C: MemoryCell,
+{
+    memory: Memory<C>,
+    // The memory the emulator was constructed with, for reset().
+    initial_memory: Box<[C]>,
     instruction_pointer: usize,
+    relative_base: C,
     input_iter: I,
+    steps: u64,
+    inputs_consumed: u64,
+    history: InstructionHistory<C>,
+    parameter_buffer: Vec<C>,
+    core_dump_template: Option<String>,
+    last_core_dump: Option<PathBuf>,
+    layout: Option<MemoryLayout>,
+    expected_input_count: Option<u64>,
+    step_limit: Option<u64>,
+    stats: Option<ExecutionStats>,
+    profiler: Option<Profiler>,
+    jump_check: Option<JumpTargetCheck<C>>,
+    loop_check: Option<LoopCheck>,
+    watchpoints: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    // Shared with clones and forks, so their outputs land in the same place.
+    output_sink: Option<OutputSink<C>>,
+    // Shared like output_sink.
+    observer: Option<StepObserver<C>>,
+    // Shared like output_sink.
+    unknown_opcode_hook: Option<UnknownOpcodeHook<C>>,
+    halted: bool,
+    dispatch: Dispatch,
+    input_record: Option<InputRecord<C>>,
+}
+
+// An emulator whose input type can be named, for storing machines with
+// differently built inputs together or returning them from functions.
+pub type DynEmulator =
+    Emulator<Box<dyn Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>>>;
+
+type OutputSink<C> = Arc<Mutex<dyn FnMut(C) + Send>>;
+
+type StepObserver<C> = Arc<Mutex<dyn FnMut(&StepEvent<C>) + Send>>;
+
+// One executed instruction, as seen by the observer. parameters are the
+// resolved values step records in InstructionRecord. After the halting
+// instruction next_instruction_pointer stays on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepEvent<'a, C = EmulatorMemoryType> {
+    pub instruction_pointer: usize,
+    pub opcode: OpCode,
+    pub parameters: &'a [C],
+    pub output: Option<C>,
+    pub next_instruction_pointer: usize,
+}
+
+// What Emulator::record_inputs has recorded.
+#[derive(Debug, Clone, PartialEq)]
+struct InputRecord<C> {
+    values: Vec<C>,
+    keep_on_reset: bool,
+}
+
+// Derived, it would only be Default for cells that are.
+impl<C> Default for InputRecord<C> {
+    fn default() -> InputRecord<C> {
+        InputRecord {
+            values: Vec::new(),
+            keep_on_reset: false,
+        }
+    }
+}
+
+struct CountingInput<'a, I, C> {
+    input_iter: &'a mut I,
+    consumed: &'a mut u64,
+    record: Option<&'a mut InputRecord<C>>,
+    // Set when input_iter runs dry, so step can tell that apart from an
+    // iterator yielding an error of its own.
+    exhausted: bool,
+    limit: Option<u64>,
+    instruction_pointer: usize,
+}
+
+impl<'a, C: MemoryCell, I: Iterator<Item = Result<C, EmulatorError>>> Iterator
+    for CountingInput<'a, I, C>
+{
+    type Item = Result<C, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Refused before pulling, so the input is still there afterwards.
+        if let Some(expected) = self.limit.filter(|&limit| *self.consumed >= limit) {
+            return Some(Err(EmulatorError::TooManyInputsConsumed {
+                expected,
+                position: self.instruction_pointer,
+                context: None,
+            }));
+        }
+        let value = self.input_iter.next();
+        match value {
+            Some(_) => *self.consumed += 1,
+            None => self.exhausted = true,
+        }
+        if let (Some(record), Some(Ok(value))) = (&mut self.record, &value) {
+            record.values.push(*value);
+        }
+        value
+    }
 }
 
 impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
     pub fn new(initial_memory: &[EmulatorMemoryType], input_iter: I) -> Emulator<I> {
-        Emulator {
-            memory: initial_memory.into(),
-            instruction_pointer: 0,
+        Emulator::with_dispatch(initial_memory, input_iter, Dispatch::Match)
+    }
+
+    pub fn with_dispatch(
+        initial_memory: &[EmulatorMemoryType],
+        input_iter: I,
+        dispatch: Dispatch,
+    ) -> Emulator<I> {
+        Emulator::with_cells(initial_memory, input_iter, dispatch)
+    }
+
+    // Boxes the input, keeping everything else as it is.
+    pub fn into_dyn(self) -> DynEmulator
+    where
+        I: 'static,
+    {
+        let Emulator {
+            memory,
+            initial_memory,
+            instruction_pointer,
+            relative_base,
             input_iter,
+            steps,
+            inputs_consumed,
+            history,
+            parameter_buffer,
+            core_dump_template,
+            last_core_dump,
+            layout,
+            expected_input_count,
+            step_limit,
+            stats,
+            profiler,
+            jump_check,
+            loop_check,
+            watchpoints,
+            breakpoints,
+            output_sink,
+            observer,
+            unknown_opcode_hook,
+            halted,
+            dispatch,
+            input_record,
+        } = self;
+        Emulator {
+            memory,
+            initial_memory,
+            instruction_pointer,
+            relative_base,
+            input_iter: Box::new(input_iter),
+            steps,
+            inputs_consumed,
+            history,
+            parameter_buffer,
+            core_dump_template,
+            last_core_dump,
+            layout,
+            expected_input_count,
+            step_limit,
+            stats,
+            profiler,
+            jump_check,
+            loop_check,
+            watchpoints,
+            breakpoints,
+            output_sink,
+            observer,
+            unknown_opcode_hook,
+            halted,
+            dispatch,
+            input_record,
         }
     }
 
-    pub fn run_to_completion(&mut self) -> Result<EmulatorMemoryType, EmulatorError> {
-        while self.step()? != EmulatorResult::Done {}
-        Ok(self.memory[0])
+    pub fn validate_entry(&self) -> Result<(), EmulatorError> {
+        validate_entry(&self.memory)
     }
 
-    pub fn step(&mut self) -> Result<EmulatorResult, EmulatorError> {
-        OpCode::run(
-            &mut self.memory,
-            self.instruction_pointer,
-            &mut self.input_iter,
+    // describe_error, then the instruction the emulator stopped at and the
+    // recent history leading up to it, for errors from deep in a run. The
+    // history holds as many instructions as set_history_size allows.
+    pub fn error_report(&self, error: &EmulatorError) -> String {
+        let stopped_at = match disassemble::disassemble_at(&self.memory, self.instruction_pointer) {
+            Some(instruction) => instruction.to_string(),
+            None => format!("{:>5}: past the end of memory", self.instruction_pointer),
+        };
+        format!(
+            "{}\nStopped at\n{}\n{}",
+            self.describe_error(error),
+            stopped_at,
+            self.history
         )
-        .map(|run_result| {
-            let (next_instruction_offset, output) = run_result;
-            match next_instruction_offset {
-                None => {
-                    return EmulatorResult::Done;
-                }
-                Some(next_instruction_pointer) => {
-                    self.instruction_pointer = next_instruction_pointer;
-                }
-            }
+    }
 
-            if let Some(output) = output {
-                return EmulatorResult::SuccessWithValue(output);
+    // Like the error's Display, but naming the segment a write was refused
+    // in, which the error doesn't carry itself.
+    pub fn describe_error(&self, error: &EmulatorError) -> String {
+        let segment = match (error, &self.layout) {
+            (EmulatorError::WriteToReadOnlyMemory { address, .. }, Some(layout)) => {
+                layout.segment_at(*address)
+            }
+            _ => None,
+        };
+        match (error, segment) {
+            (
+                EmulatorError::WriteToReadOnlyMemory {
+                    address,
+                    position,
+                    context,
+                },
+                Some(segment),
+            ) => {
+                let mut description = format!(
+                    "Write into segment '{}' (address {}) referenced at {}",
+                    segment.name, address, position
+                );
+                if let Some(context) = context {
+                    description += &format!("\n{}", context);
+                }
+                description
             }
+            _ => error.to_string(),
+        }
+    }
 
-            EmulatorResult::Success
-        })
+    pub fn into_output_iter(self) -> IntoOutputs<I> {
+        self.into_iter()
     }
 
-    pub fn into_output_iter(self) -> EmulatorOutputIterator<I> {
-        EmulatorOutputIterator { emulator: self }
+    // Runs to the end, keeping the outputs produced before any error.
+    pub fn collect_outputs_lossy(self) -> (Vec<EmulatorMemoryType>, Option<EmulatorError>) {
+        self.into_iter().collect_lossy()
     }
-}
 
-pub struct EmulatorOutputIterator<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
-    emulator: Emulator<I>,
+    // Rows of memory from range, with the cell at the instruction pointer
+    // marked. The range is cut short at the end of memory.
+    pub fn dump(&self, range: Range<usize>) -> String {
+        dump::format_range(&self.memory, range, Some(self.instruction_pointer))
+    }
 }
 
-impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Iterator
-    for EmulatorOutputIterator<I>
-{
-    type Item = Result<EmulatorMemoryType, EmulatorError>;
+impl<C: MemoryCell, I: Iterator<Item = Result<C, EmulatorError>>> Emulator<I, C> {
+    // with_dispatch, for a machine of any MemoryCell. The program has to be
+    // a slice of C already, as integer literals don't say which type they
+    // are.
+    pub fn with_cells(initial_memory: &[C], input_iter: I, dispatch: Dispatch) -> Emulator<I, C> {
+        Emulator {
+            memory: Memory::new(initial_memory.to_vec()),
+            initial_memory: initial_memory.into(),
+            instruction_pointer: 0,
+            relative_base: C::from(0),
+            input_iter,
+            steps: 0,
+            inputs_consumed: 0,
+            history: InstructionHistory::new(DEFAULT_HISTORY_SIZE),
+            parameter_buffer: Vec::new(),
+            core_dump_template: None,
+            last_core_dump: None,
+            layout: None,
+            expected_input_count: None,
+            step_limit: None,
+            stats: None,
+            profiler: None,
+            jump_check: None,
+            loop_check: None,
+            watchpoints: BTreeSet::new(),
+            breakpoints: BTreeSet::new(),
+            output_sink: None,
+            observer: None,
+            unknown_opcode_hook: None,
+            halted: false,
+            dispatch,
+            input_record: None,
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        while match self.emulator.step() {
-            Ok(EmulatorResult::Done) => false,
-            Ok(EmulatorResult::Success) => true,
-            Ok(EmulatorResult::SuccessWithValue(value)) => return Some(Ok(value)),
-            Err(e) => return Some(Err(e)),
-        } {}
-        None
+    pub fn dispatch(&self) -> Dispatch {
+        self.dispatch
     }
-}
 
-pub fn emulator_with_empty_input(
-    initial_memory: &[EmulatorMemoryType],
-) -> Emulator<impl Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
-    Emulator::new(initial_memory, std::iter::empty())
-}
+    // With recording on, every input value the program reads is kept, in the
+    // order it was read, for passing to replay later. Turning it off
+    // discards what was recorded.
+    pub fn record_inputs(&mut self, enabled: bool) {
+        match (enabled, &self.input_record) {
+            (true, None) => self.input_record = Some(InputRecord::default()),
+            (false, _) => self.input_record = None,
+            (true, Some(_)) => {}
+        }
+    }
 
-impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Index<usize> for Emulator<I> {
-    type Output = EmulatorMemoryType;
+    // reset normally starts the recording over. With keep on, it carries
+    // on, so the recording covers every run since recording started.
+    // Turns recording on if it was off.
+    pub fn keep_recorded_inputs_on_reset(&mut self, keep: bool) {
+        self.input_record
+            .get_or_insert_with(InputRecord::default)
+            .keep_on_reset = keep;
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        self.memory.index(index)
+    // The inputs read since recording started. Empty while it is off.
+    pub fn consumed_inputs(&self) -> &[C] {
+        self.input_record
+            .as_ref()
+            .map_or(&[], |record| &record.values)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::iter::once;
+    // Back to the state new() left it in, reusing the memory allocation.
+    // Settings such as the layout, input limit and enabled stats carry
+    // over, though the stats themselves start again. The input iterator is
+    // kept as it is; see reset_with_input.
+    pub fn reset(&mut self) {
+        self.memory.truncate(self.initial_memory.len());
+        self.memory.copy_from_slice(&self.initial_memory);
+        self.memory.clear_sparse_cells();
+        self.instruction_pointer = 0;
+        self.relative_base = C::from(0);
+        self.steps = 0;
+        self.inputs_consumed = 0;
+        self.history.clear();
+        self.last_core_dump = None;
+        if let Some(stats) = &mut self.stats {
+            *stats = ExecutionStats::new();
+        }
+        if let Some(profiler) = &mut self.profiler {
+            *profiler = Profiler::new(profiler.sample_every());
+        }
+        if let Some(jump_check) = &mut self.jump_check {
+            *jump_check = JumpTargetCheck::new();
+        }
+        if let Some(loop_check) = &mut self.loop_check {
+            loop_check.clear();
+        }
+        if let Some(record) = &mut self.input_record {
+            if !record.keep_on_reset {
+                record.values.clear();
+            }
+        }
+        self.halted = false;
+    }
 
-    #[test]
-    fn test_example() -> Result<(), EmulatorError> {
-        let initial_address = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    pub fn reset_with_input(&mut self, input_iter: I) {
+        self.reset();
+        self.input_iter = input_iter;
+    }
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(
-            &[1, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
-            emulator.memory.as_slice()
-        );
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(
-            &[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
-            emulator.memory.as_slice()
-        );
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+    // A copy of the machine, settings and history included, that reads
+    // from input_iter. For searches that branch at each input: the fork
+    // and the original run independently from here on.
+    pub fn fork<J: Iterator<Item = Result<C, EmulatorError>>>(
+        &self,
+        input_iter: J,
+    ) -> Emulator<J, C> {
+        Emulator {
+            memory: self.memory.clone(),
+            initial_memory: self.initial_memory.clone(),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            input_iter,
+            steps: self.steps,
+            inputs_consumed: self.inputs_consumed,
+            history: self.history.clone(),
+            parameter_buffer: Vec::new(),
+            core_dump_template: self.core_dump_template.clone(),
+            last_core_dump: None,
+            layout: self.layout.clone(),
+            expected_input_count: self.expected_input_count,
+            step_limit: self.step_limit,
+            stats: self.stats.clone(),
+            profiler: self.profiler.clone(),
+            jump_check: self.jump_check.clone(),
+            loop_check: self.loop_check.clone(),
+            watchpoints: self.watchpoints.clone(),
+            breakpoints: self.breakpoints.clone(),
+            output_sink: self.output_sink.clone(),
+            observer: self.observer.clone(),
+            unknown_opcode_hook: self.unknown_opcode_hook.clone(),
+            halted: self.halted,
+            dispatch: self.dispatch,
+            input_record: self.input_record.clone(),
+        }
+    }
 
-        Ok(())
+    pub fn is_halted(&self) -> bool {
+        self.halted
     }
 
-    #[test]
-    fn test_add() -> Result<(), EmulatorError> {
-        let initial_address = [1, 0, 0, 0, 99];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    pub fn relative_base(&self) -> C {
+        self.relative_base
+    }
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[2, 0, 0, 0, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+    // Where the next step will execute. After the program halts, this is
+    // still the halting instruction.
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
 
+    // Moves execution to instruction_pointer, which must be inside the
+    // contiguous memory; nothing else is executable. A halted machine runs
+    // again from there.
+    pub fn set_instruction_pointer(
+        &mut self,
+        instruction_pointer: usize,
+    ) -> Result<(), EmulatorError> {
+        if instruction_pointer >= self.memory.len() {
+            return Err(EmulatorError::InstructionPointerOutOfBounds {
+                position: instruction_pointer,
+                context: None,
+            });
+        }
+        self.instruction_pointer = instruction_pointer;
+        self.halted = false;
         Ok(())
     }
 
-    #[test]
-    fn test_multiply_1() -> Result<(), EmulatorError> {
-        let initial_address = [1, 0, 0, 0, 99];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
-
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[2, 0, 0, 0, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-
-        Ok(())
+    // Returns memory()[0] once the program halts, which is where day 2's
+    // programs leave their answer. Running out of input is an error here, as
+    // nothing can supply more.
+    pub fn run_to_completion(&mut self) -> Result<C, EmulatorError> {
+        loop {
+            match self.step()? {
+                EmulatorResult::Done => return Ok(self.memory[0]),
+                EmulatorResult::NeedsInput => return Err(self.missing_input()),
+                _ => {}
+            }
+        }
     }
 
-    #[test]
-    fn test_multiply_2() -> Result<(), EmulatorError> {
-        let initial_address = [2, 4, 4, 5, 99, 0];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    // Steps to the next output, or None once the program halts. Unlike
+    // into_output_iter this borrows, so memory and input can be changed
+    // between outputs.
+    pub fn run_until_output(&mut self) -> Result<Option<C>, EmulatorError> {
+        loop {
+            match self.step()? {
+                EmulatorResult::SuccessWithValue(value) => return Ok(Some(value)),
+                EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => {}
+                EmulatorResult::Done => return Ok(None),
+                EmulatorResult::NeedsInput => return Err(self.missing_input()),
+            }
+        }
+    }
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[2, 4, 4, 5, 99, 9801], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+    // Runs to halt like run_to_completion, but returns every output in order
+    // instead of memory()[0].
+    pub fn run_collecting_outputs(&mut self) -> Result<Vec<C>, EmulatorError> {
+        let mut outputs = Vec::new();
+        self.run_collecting_outputs_into(&mut outputs)?;
+        Ok(outputs)
+    }
 
-        Ok(())
+    // outputs keeps whatever was produced before an error, for reporting it.
+    pub fn run_collecting_outputs_into(
+        &mut self,
+        outputs: &mut Vec<C>,
+    ) -> Result<(), EmulatorError> {
+        loop {
+            match self.step()? {
+                EmulatorResult::SuccessWithValue(value) => outputs.push(value),
+                EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => {}
+                EmulatorResult::Done => return Ok(()),
+                EmulatorResult::NeedsInput => return Err(self.missing_input()),
+            }
+        }
     }
 
-    #[test]
-    fn test_overriding_future_instructions() -> Result<(), EmulatorError> {
-        let initial_address = [1, 1, 1, 4, 99, 5, 6, 0, 99];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    pub fn step(&mut self) -> Result<EmulatorResult<C>, EmulatorError> {
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Err(self.fail(EmulatorError::InstructionLimitExceeded {
+                executed: self.steps,
+                context: None,
+            }));
+        }
+        let instruction_pointer = self.instruction_pointer;
+        let inputs_consumed = self.inputs_consumed;
+        let instruction = self
+            .memory
+            .get(instruction_pointer)
+            .copied()
+            .unwrap_or(C::from(0));
+        let mut parameters = std::mem::take(&mut self.parameter_buffer);
+        parameters.clear();
+        let watched_before: Vec<(usize, C)> = self
+            .watchpoints
+            .iter()
+            .map(|&address| (address, self.get(address).unwrap_or(C::from(0))))
+            .collect();
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[1, 1, 1, 4, 2, 5, 6, 0, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[30, 1, 1, 4, 2, 5, 6, 0, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        let mut input_exhausted = false;
+        let mut elapsed = None;
+        let run_result = match self.check_writes() {
+            Err(error) => Err(error),
+            Ok(()) => {
+                let mut input = CountingInput {
+                    input_iter: &mut self.input_iter,
+                    consumed: &mut self.inputs_consumed,
+                    record: self.input_record.as_mut(),
+                    exhausted: false,
+                    limit: self.expected_input_count,
+                    instruction_pointer,
+                };
+                let started = self.profiler.as_ref().and_then(Profiler::start);
+                let run_result = match self.dispatch {
+                    Dispatch::Match => OpCode::run(
+                        &mut self.memory,
+                        instruction_pointer,
+                        &mut self.relative_base,
+                        &mut input,
+                        &mut parameters,
+                    ),
+                    Dispatch::Table => OpCode::run_via_table(
+                        &mut self.memory,
+                        instruction_pointer,
+                        &mut self.relative_base,
+                        &mut input,
+                        &mut parameters,
+                    ),
+                };
+                input_exhausted = input.exhausted;
+                elapsed = started.map(|started| started.elapsed());
+                run_result
+            }
+        };
+        let (next_instruction_offset, output, jumped) = match run_result {
+            Ok(run_result) => run_result,
+            // Decoding stops at the empty input before anything is written.
+            Err(EmulatorError::InputNonExistent { .. }) if input_exhausted => {
+                self.parameter_buffer = parameters;
+                return Ok(EmulatorResult::NeedsInput);
+            }
+            Err(EmulatorError::InvalidInstruction { position, .. })
+                if position == instruction_pointer && self.has_unknown_opcode_hook() =>
+            {
+                match self.run_unknown_opcode(instruction_pointer, instruction) {
+                    Ok(next_instruction_pointer) => (Some(next_instruction_pointer), None, false),
+                    Err(error) => {
+                        self.parameter_buffer = parameters;
+                        return Err(self.fail(error));
+                    }
+                }
+            }
+            Err(error) => {
+                self.parameter_buffer = parameters;
+                return Err(self.fail(error));
+            }
+        };
+
+        if let Some(jump_check) = &mut self.jump_check {
+            jump_check.record(
+                &self.memory,
+                self.relative_base,
+                instruction_pointer,
+                instruction,
+                &parameters,
+                jumped,
+            );
+        }
+        let watch_hit = if watched_before.is_empty() {
+            None
+        } else {
+            written_address(instruction, &parameters).and_then(|address| {
+                let &(_, old) = watched_before
+                    .iter()
+                    .find(|(watched, _)| *watched == address)?;
+                Some(EmulatorResult::Watchpoint {
+                    address,
+                    old,
+                    new: self.get(address).unwrap_or(C::from(0)),
+                })
+            })
+        };
+        // The instruction ran, so it decodes, even if it overwrote itself,
+        // unless the unknown opcode hook ran it.
+        let opcode = match &self.observer {
+            Some(_) => OpCode::get_current_instruction(&[instruction], 0)
+                .ok()
+                .map(|(opcode, _)| opcode),
+            None => None,
+        };
+        if let (Some(observer), Some(opcode)) = (&self.observer, opcode) {
+            let mut observer = observer
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            (*observer)(&StepEvent {
+                instruction_pointer,
+                opcode,
+                parameters: &parameters,
+                output,
+                next_instruction_pointer: next_instruction_offset.unwrap_or(instruction_pointer),
+            });
+        }
+        self.parameter_buffer = self.history.push(InstructionRecord {
+            step: self.steps,
+            instruction_pointer,
+            instruction,
+            parameters,
+        });
+        self.steps += 1;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(opcode_of(instruction), elapsed);
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.record_instruction(opcode_of(instruction));
+            let conditional_jump = OPCODE_TABLE
+                .iter()
+                .any(|info| info.code == opcode_of(instruction) && info.jumps);
+            if conditional_jump {
+                stats.record_branch(instruction_pointer, jumped);
+            }
+        }
+
+        match next_instruction_offset {
+            None => {
+                self.halted = true;
+                return Ok(EmulatorResult::Done);
+            }
+            Some(next_instruction_pointer) => {
+                self.instruction_pointer = next_instruction_pointer;
+            }
+        }
+
+        if let Some(loop_check) = &mut self.loop_check {
+            if output.is_some() || self.inputs_consumed != inputs_consumed {
+                loop_check.clear();
+            } else if loop_check.repeats(
+                self.steps,
+                self.instruction_pointer,
+                self.relative_base,
+                &self.memory,
+            ) {
+                return Err(self.fail(EmulatorError::LoopDetected {
+                    steps: self.steps,
+                    context: None,
+                }));
+            }
+        }
+
+        if let Some(output) = output {
+            if let Some(sink) = &self.output_sink {
+                let mut sink = sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                (*sink)(output);
+            }
+            return Ok(EmulatorResult::SuccessWithValue(output));
+        }
+
+        Ok(watch_hit.unwrap_or(EmulatorResult::Success))
+    }
+
+    // From the next step on, any instruction writing to address makes step
+    // return Watchpoint, even if the value stays the same.
+    pub fn watch(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn unwatch(&mut self, address: usize) {
+        self.watchpoints.remove(&address);
+    }
+
+    pub fn watched(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    // run_steps stops before running the instruction at address. step
+    // ignores breakpoints, as it only runs one instruction anyway.
+    pub fn set_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn clear_breakpoint(&mut self, address: usize) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn is_breakpoint(&self, address: usize) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    // Calls sink with every value the program outputs from the next step
+    // on. The sink sees each output before step returns it as
+    // SuccessWithValue, which still happens, so callers that read step
+    // results keep working alongside it. Replaces any earlier sink.
+    pub fn set_output_sink(&mut self, sink: impl FnMut(C) + Send + 'static) {
+        self.output_sink = Some(Arc::new(Mutex::new(sink)));
+    }
+
+    pub fn clear_output_sink(&mut self) {
+        self.output_sink = None;
+    }
+
+    // Calls observer after every instruction that executes from the next
+    // step on, including the one that halts. Steps that fail or wait for
+    // input aren't reported. Replaces any earlier observer.
+    pub fn set_observer(&mut self, observer: impl FnMut(&StepEvent<C>) + Send + 'static) {
+        self.observer = Some(Arc::new(Mutex::new(observer)));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    // What the run helpers return when step wants input that isn't there.
+    fn missing_input(&self) -> EmulatorError {
+        EmulatorError::InputNonExistent {
+            position: self.instruction_pointer,
+            context: None,
+        }
+    }
+
+    // The last few instructions that executed successfully, for logging
+    // alongside an error returned by step().
+    pub fn recent_history(&self) -> &InstructionHistory<C> {
+        &self.history
+    }
+
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history.set_capacity(size);
+    }
+
+    // On any error, write a core dump to path_template with {timestamp}
+    // filled in. See core_dump::CoreDump for loading it back.
+    pub fn dump_core_on_error(&mut self, path_template: &str) {
+        self.core_dump_template = Some(path_template.to_string());
+    }
+
+    pub fn last_core_dump(&self) -> Option<&Path> {
+        self.last_core_dump.as_deref()
+    }
+
+    // Adds the context of the instruction that failed, and dumps core if
+    // asked to. Whether the dump was written, and where, goes in the context
+    // too.
+    fn fail(&mut self, error: EmulatorError) -> EmulatorError {
+        let mut context = ErrorContext::capture(&self.memory, self.instruction_pointer);
+        if let Some(template) = self.core_dump_template.clone() {
+            // The dump holds the error as it was before the dump's path
+            // could be added to it.
+            let dumped_error = error.clone().with_context(context.clone());
+            context.core_dump = Some(self.write_core_dump(&template, &dumped_error));
+        }
+        error.with_context(context)
+    }
+
+    fn write_core_dump(&mut self, template: &str, error: &EmulatorError) -> CoreDumpStatus {
+        let path = core_dump::path_from_template(template);
+        match CoreDump::capture(self, error).save(&path) {
+            Ok(()) => {
+                self.last_core_dump = Some(path.clone());
+                CoreDumpStatus::Written(path)
+            }
+            Err(io_error) => CoreDumpStatus::Failed {
+                path,
+                error: io_error.to_string(),
+            },
+        }
+    }
+
+    // Reading more than count inputs fails with TooManyInputsConsumed at the
+    // Input instruction that would go over.
+    pub fn expect_input_count(&mut self, count: u64) {
+        self.expected_input_count = Some(count);
+    }
+
+    pub fn inputs_consumed(&self) -> u64 {
+        self.inputs_consumed
+    }
+
+    // Read-only segments of the layout are enforced from the next step on.
+    pub fn set_memory_layout(&mut self, layout: MemoryLayout) {
+        self.layout = Some(layout);
+    }
+
+    pub fn memory_layout(&self) -> Option<&MemoryLayout> {
+        self.layout.as_ref()
+    }
+
+    // Starts collecting ExecutionStats from the next step on.
+    pub fn enable_stats(&mut self) {
+        self.stats.get_or_insert_with(ExecutionStats::new);
+    }
+
+    pub fn stats(&self) -> Option<&ExecutionStats> {
+        self.stats.as_ref()
+    }
+
+    // Starts timing every instruction from the next step on, per opcode.
+    // See attach_sampling_profiler to time fewer.
+    pub fn attach_profiler(&mut self) {
+        self.attach_sampling_profiler(1);
+    }
+
+    // Counts every instruction but only times every sample_every'th,
+    // keeping the cost of reading the clock down on long runs. Replaces
+    // any profile collected so far.
+    pub fn attach_sampling_profiler(&mut self, sample_every: u64) {
+        self.profiler = Some(Profiler::new(sample_every));
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    // Starts watching for jumps through cells the program has written to,
+    // from the next step on. Warnings collect in jump_target_check() rather
+    // than stopping the program.
+    pub fn enable_jump_target_check(&mut self) {
+        self.jump_check.get_or_insert_with(JumpTargetCheck::new);
+    }
+
+    pub fn jump_target_check(&self) -> Option<&JumpTargetCheck<C>> {
+        self.jump_check.as_ref()
+    }
+
+    pub fn jump_target_check_mut(&mut self) -> Option<&mut JumpTargetCheck<C>> {
+        self.jump_check.as_mut()
+    }
+
+    // Writes that would grow memory to limit cells or more fail with
+    // MemoryLimitExceeded, and reads past the end from limit on with
+    // InvalidMemoryLocation. The limit starts at MEMORY_LIMIT; 0 lifts it.
+    // Sparse memory has no limit.
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory
+            .set_limit(Some(limit).filter(|&limit| limit > 0));
+    }
+
+    // None for no limit.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory.limit()
+    }
+
+    // Once limit instructions have executed in total, step fails with
+    // InstructionLimitExceeded instead of running another. reset() starts
+    // the count again.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = Some(limit);
+    }
+
+    pub fn step_limit(&self) -> Option<u64> {
+        self.step_limit
+    }
+
+    // With loop detection on, step fails with LoopDetected once the machine
+    // is back in a state it was in before, with no input or output since:
+    // it would go round forever. States are compared every
+    // loop_check_interval steps, so a loop is caught within that many times
+    // its length. Turning it on uses DEFAULT_LOOP_CHECK_INTERVAL.
+    pub fn detect_loops(&mut self, enabled: bool) {
+        self.loop_check = if enabled {
+            Some(LoopCheck::new(DEFAULT_LOOP_CHECK_INTERVAL))
+        } else {
+            None
+        };
+    }
+
+    // Turns loop detection on, comparing states interval steps apart.
+    // Smaller intervals catch loops sooner but hash memory more often.
+    pub fn set_loop_check_interval(&mut self, interval: u64) {
+        self.loop_check = Some(LoopCheck::new(interval));
+    }
+
+    // None while loop detection is off.
+    pub fn loop_check_interval(&self) -> Option<u64> {
+        self.loop_check.as_ref().map(LoopCheck::interval)
+    }
+
+    // Checks the writes the next instruction will make against the layout.
+    // Writable parameters are position or relative mode, so their addresses
+    // can be worked out from memory and the relative base.
+    fn check_writes(&self) -> Result<(), EmulatorError> {
+        if self.layout.is_none() {
+            return Ok(());
+        }
+        let instruction = match self.memory.get(self.instruction_pointer) {
+            Some(&instruction) => instruction,
+            None => return Ok(()),
+        };
+        let info = match OPCODE_TABLE
+            .iter()
+            .find(|info| info.code == opcode_of(instruction))
+        {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+
+        let ten = C::from(10);
+        let mut mode_digits = instruction / C::from(100);
+        for (index, parameter) in info.parameters.iter().enumerate() {
+            let position = self.instruction_pointer + 1 + index;
+            let base = if mode_digits % ten == C::from(2) {
+                self.relative_base
+            } else {
+                C::from(0)
+            };
+            mode_digits = mode_digits / ten;
+            let word = self.memory.get(position).copied().unwrap_or(C::from(0));
+            let address = word.checked_add(base).and_then(|word| word.try_into().ok());
+            let address = match (parameter.kind, address) {
+                (ParameterKind::Write, Some(address)) => address,
+                _ => continue,
+            };
+            if self
+                .layout
+                .as_ref()
+                .is_some_and(|layout| layout.is_read_only(address))
+            {
+                return Err(EmulatorError::WriteToReadOnlyMemory {
+                    address,
+                    position,
+                    context: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Everything the program has touched so far, including any growth past
+    // the initial program.
+    pub fn memory(&self) -> &[C] {
+        &self.memory
+    }
+
+    // For patching the program before or between steps. The length is
+    // fixed here; only the program's own writes grow memory.
+    pub fn memory_mut(&mut self) -> &mut [C] {
+        &mut self.memory
+    }
+
+    // None past the end of memory, where indexing would panic. The program
+    // itself would read 0 there.
+    pub fn get(&self, address: usize) -> Option<C> {
+        self.memory.get_cell(address)
+    }
+
+    // What the program would read at address: 0 past the end of memory, but
+    // InvalidMemoryLocation past the memory limit. Limiting memory to its
+    // current length makes every read past the end an error.
+    pub fn read(&self, address: usize) -> Result<C, EmulatorError> {
+        match self.memory.get_cell(address) {
+            Some(value) => Ok(value),
+            None if self.memory.limit().is_some_and(|limit| address >= limit) => {
+                Err(EmulatorError::InvalidMemoryLocation {
+                    value_found: WideValue::try_from(address).unwrap_or(WideValue::MAX),
+                    position: self.instruction_pointer,
+                    context: None,
+                })
+            }
+            None => Ok(C::from(0)),
+        }
+    }
+
+    // Writes as the program would, growing memory to reach address, or
+    // failing with MemoryLimitExceeded past the memory limit. Unlike
+    // indexing, never panics.
+    pub fn write(&mut self, address: usize, value: C) -> Result<(), EmulatorError> {
+        self.memory.checked_write(address, value)
+    }
+
+    // Keeps cells written past the end of memory in a map from now on,
+    // rather than growing memory to reach them. Such cells aren't limited
+    // by MEMORY_LIMIT, but can't be executed, and memory() and snapshots
+    // leave them out; sparse_cells lists them.
+    pub fn set_sparse_memory(&mut self) {
+        if !self.memory.is_sparse() {
+            self.memory = Memory::sparse(std::mem::take(&mut *self.memory));
+        }
+    }
+
+    pub fn sparse_cells(&self) -> Vec<(usize, C)> {
+        self.memory.sparse_cells()
+    }
+
+    // How many cells memory holds, sparse ones included.
+    pub fn touched_cells(&self) -> usize {
+        self.memory.touched_cells()
+    }
+}
+
+// Where the machine is, the instruction it will run next and all of
+// memory, for test failures. Memory is too big to read as a plain Vec once
+// programs are any size.
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> std::fmt::Debug
+    for Emulator<I>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Emulator {} {}, relative base {}, steps {}",
+            if self.halted { "halted at" } else { "at" },
+            self.instruction_pointer,
+            self.relative_base,
+            self.steps
+        )?;
+        match disassemble::disassemble_at(&self.memory, self.instruction_pointer) {
+            Some(instruction) => writeln!(f, "Next:\n{}", instruction)?,
+            None => writeln!(f, "Next: past the end of memory")?,
+        }
+        write!(f, "Memory:\n{}", self.dump(0..self.memory.len()))?;
+        for (address, value) in self.memory.sparse_cells() {
+            writeln!(f, "{:>5}: {} (sparse)", address, value)?;
+        }
+        Ok(())
+    }
+}
+
+// Runs the emulator, yielding each output until it halts or fails.
+pub struct IntoOutputs<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
+    emulator: Emulator<I>,
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> IntoOutputs<I> {
+    pub fn emulator(&self) -> &Emulator<I> {
+        &self.emulator
+    }
+
+    // Gives the emulator back, for its final memory once the outputs are
+    // drained, or to carry on stepping it by hand.
+    pub fn into_inner(self) -> Emulator<I> {
+        self.emulator
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Iterator for IntoOutputs<I> {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while match self.emulator.step() {
+            Ok(EmulatorResult::Done) => false,
+            Ok(EmulatorResult::Success | EmulatorResult::Watchpoint { .. }) => true,
+            Ok(EmulatorResult::SuccessWithValue(value)) => return Some(Ok(value)),
+            // The emulator owns its input, so none can arrive while iterating.
+            Ok(EmulatorResult::NeedsInput) => return Some(Err(self.emulator.missing_input())),
+            Err(e) => return Some(Err(e)),
+        } {}
+        None
+    }
+}
+
+// Collecting into Result<Vec<_>, _> drops everything before the error; this
+// keeps it, for diagnosing what a program got through before failing.
+pub trait CollectLossy {
+    // Stops at the first error.
+    fn collect_lossy(self) -> (Vec<EmulatorMemoryType>, Option<EmulatorError>);
+}
+
+impl<T: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> CollectLossy for T {
+    fn collect_lossy(self) -> (Vec<EmulatorMemoryType>, Option<EmulatorError>) {
+        let mut outputs = Vec::new();
+        for output in self {
+            match output {
+                Ok(value) => outputs.push(value),
+                Err(error) => return (outputs, Some(error)),
+            }
+        }
+        (outputs, None)
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> IntoIterator for Emulator<I> {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+    type IntoIter = IntoOutputs<I>;
+
+    fn into_iter(self) -> IntoOutputs<I> {
+        IntoOutputs { emulator: self }
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    // Like new, but input can be plain values as well as Results.
+    pub fn new_with<M, T: IntoEmulatorInput<M, Iter = I>>(
+        initial_memory: &[EmulatorMemoryType],
+        input: T,
+    ) -> Emulator<I> {
+        Emulator::new(initial_memory, input.into_emulator_input())
+    }
+}
+
+impl DynEmulator {
+    pub fn boxed<J>(initial_memory: &[EmulatorMemoryType], input_iter: J) -> DynEmulator
+    where
+        J: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>> + 'static,
+    {
+        Emulator::new(initial_memory, Box::new(input_iter))
+    }
+}
+
+// Sets up an emulator fed from a queue: the program, cells to patch before
+// it runs, its first inputs and a memory limit.
+#[derive(Debug, Clone, Default)]
+pub struct EmulatorBuilder {
+    program: Vec<EmulatorMemoryType>,
+    patches: Vec<(usize, EmulatorMemoryType)>,
+    inputs: Vec<EmulatorMemoryType>,
+    memory_limit: Option<usize>,
+    step_limit: Option<u64>,
+    grow_memory: bool,
+    sparse_memory: bool,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> EmulatorBuilder {
+        EmulatorBuilder::default()
+    }
+
+    pub fn program(mut self, program: &[EmulatorMemoryType]) -> EmulatorBuilder {
+        self.program = program.to_vec();
+        self
+    }
+
+    // Applied in order, so a later patch to the same cell wins.
+    pub fn patch(mut self, address: usize, value: EmulatorMemoryType) -> EmulatorBuilder {
+        self.patches.push((address, value));
+        self
+    }
+
+    // Queued after any inputs already given.
+    pub fn input<T: IntoIterator<Item = EmulatorMemoryType>>(
+        mut self,
+        inputs: T,
+    ) -> EmulatorBuilder {
+        self.inputs.extend(inputs);
+        self
+    }
+
+    // In cells; 0 for no limit. See Emulator::set_memory_limit.
+    pub fn memory_limit(mut self, limit: usize) -> EmulatorBuilder {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    pub fn step_limit(mut self, limit: u64) -> EmulatorBuilder {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    // Lets patches past the end of the program grow memory, as writes from
+    // the program itself do. Without it they are an error.
+    pub fn grow_memory(mut self, grow: bool) -> EmulatorBuilder {
+        self.grow_memory = grow;
+        self
+    }
+
+    // See Emulator::set_sparse_memory.
+    pub fn sparse_memory(mut self, sparse: bool) -> EmulatorBuilder {
+        self.sparse_memory = sparse;
+        self
+    }
+
+    pub fn build(self) -> Result<Emulator<QueueInput>, EmulatorError> {
+        let limit = match self.memory_limit {
+            Some(0) => usize::MAX,
+            limit => limit.unwrap_or(MEMORY_LIMIT),
+        };
+        let mut memory = self.program;
+        for &(address, value) in &self.patches {
+            let fits = address < memory.len() || (self.grow_memory && address < limit);
+            if !fits {
+                return Err(EmulatorError::ArgumentOutOfRange {
+                    address,
+                    memory_size: memory.len(),
+                    context: None,
+                });
+            }
+            if address >= memory.len() {
+                memory.resize(address + 1, 0);
+            }
+            memory[address] = value;
+        }
+
+        let mut emulator = Emulator::with_inputs(&memory, self.inputs);
+        if let Some(limit) = self.memory_limit {
+            emulator.set_memory_limit(limit);
+        }
+        if let Some(limit) = self.step_limit {
+            emulator.set_step_limit(limit);
+        }
+        if self.sparse_memory {
+            emulator.set_sparse_memory();
+        }
+        Ok(emulator)
+    }
+}
+
+pub fn emulator_with_empty_input(
+    initial_memory: &[EmulatorMemoryType],
+) -> Emulator<impl Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
+    Emulator::new(initial_memory, std::iter::empty())
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Index<usize> for Emulator<I> {
+    type Output = EmulatorMemoryType;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.memory.index(index)
+    }
+}
+
+// Like Index, panics past the end rather than growing memory: patching a
+// program before it runs should only touch cells it already has.
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> IndexMut<usize>
+    for Emulator<I>
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.memory.index_mut(index)
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Index<Range<usize>>
+    for Emulator<I>
+{
+    type Output = [EmulatorMemoryType];
+
+    fn index(&self, range: Range<usize>) -> &Self::Output {
+        self.memory.index(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::iter::{empty, once};
+
+    #[test]
+    fn test_example() -> Result<(), EmulatorError> {
+        let initial_address = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(0, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(
+            &[1, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+            emulator.memory()
+        );
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(
+            &[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+            emulator.memory()
+        );
+        assert_eq!(8, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(8, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(8, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_accessors() -> Result<(), EmulatorError> {
+        // Day 2's example, patched the way day 2 sets its noun and verb.
+        let mut emulator = emulator_with_empty_input(&[1, 0, 0, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        emulator.memory_mut()[1..3].copy_from_slice(&[9, 10]);
+        assert_eq!(Some(9), emulator.get(1));
+        assert_eq!(None, emulator.get(12));
+
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+        assert_eq!(3500, emulator.memory()[0]);
+        assert_eq!(Some(70), emulator.get(3));
+        assert_eq!(emulator.get(3), Some(emulator[3]));
+
+        // A halted emulator keeps reporting memory()[0], including edits.
+        emulator.memory_mut()[0] = -1;
+        assert_eq!(Ok(-1), emulator.run_to_completion());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_until_output() {
+        let mut emulator = Emulator::with_inputs(&[3, 9, 104, 5, 4, 9, 99], [42]);
+        assert_eq!(Ok(Some(5)), emulator.run_until_output());
+        // Outputs read the memory as it is when they run.
+        assert_eq!(Ok(()), emulator.write(9, 43));
+        assert_eq!(Ok(Some(43)), emulator.run_until_output());
+        assert_eq!(Ok(None), emulator.run_until_output());
+        assert_eq!(Ok(None), emulator.run_until_output());
+
+        let mut emulator = emulator_with_empty_input(&[1101, 1, 1, 0, 99]);
+        assert_eq!(Ok(None), emulator.run_until_output());
+        assert_eq!(Ok(2), emulator.read(0));
+
+        let mut emulator = emulator_with_empty_input(&[104, 1, 7, -1, 0, 0, 99]);
+        assert_eq!(Ok(Some(1)), emulator.run_until_output());
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -1,
+                position: 3,
+                context: None,
+            }),
+            emulator.run_until_output()
+        );
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            emulator_with_empty_input(&[3, 0, 99]).run_until_output()
+        );
+    }
+
+    #[test]
+    fn test_run_collecting_outputs() {
+        let mut emulator = Emulator::with_inputs(&[3, 11, 104, 5, 4, 11, 104, -1, 99], [42]);
+        assert_eq!(Ok(vec![5, 42, -1]), emulator.run_collecting_outputs());
+        assert!(emulator.is_halted());
+
+        let mut outputs = Vec::new();
+        let mut emulator = emulator_with_empty_input(&[104, 7, 3, 0, 99]);
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 2,
+                context: None,
+            }),
+            emulator.run_collecting_outputs_into(&mut outputs)
+        );
+        assert_eq!(vec![7], outputs);
+    }
+
+    #[test]
+    fn test_reset() -> Result<(), EmulatorError> {
+        let program = [1, 0, 0, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut emulator = emulator_with_empty_input(&program);
+        emulator.write(1, 9)?;
+        emulator.write(2, 10)?;
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+
+        emulator.reset();
+        assert_eq!(&program, emulator.memory());
+        assert!(!emulator.is_halted());
+        assert_eq!(0, emulator.steps);
+        assert!(emulator.recent_history().is_empty());
+        emulator.write(1, 10)?;
+        emulator.write(2, 11)?;
+        assert_eq!(Ok(4500), emulator.run_to_completion());
+
+        // Memory grown past the end of the program shrinks back too.
+        let mut emulator = Emulator::with_inputs(&[3, 20, 4, 20, 99], vec![7]);
+        assert_eq!(Ok(vec![7]), emulator.run_collecting_outputs());
+        assert_eq!(21, emulator.memory().len());
+        emulator.reset_with_input(QueueInput::from(vec![8]));
+        assert_eq!(5, emulator.memory().len());
+        assert_eq!(Ok(vec![8]), emulator.run_collecting_outputs());
+        assert_eq!(1, emulator.inputs_consumed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fork() -> Result<(), EmulatorError> {
+        // Doubles each input into cell 30 and prints it, forever.
+        let program = [3, 30, 1002, 30, 2, 30, 4, 30, 1105, 1, 0];
+        let mut parent = Emulator::with_inputs(&program, vec![1]);
+        assert_eq!(EmulatorResult::Success, parent.step()?);
+        assert_eq!(EmulatorResult::Success, parent.step()?);
+        let before = parent.snapshot();
+
+        let mut fork = parent.fork(QueueInput::from(vec![]));
+        assert!(fork.state_eq(&parent));
+        assert_eq!(parent.recent_history().len(), fork.recent_history().len());
+        assert_eq!(EmulatorResult::SuccessWithValue(2), fork.step()?);
+        fork.step()?;
+        fork.push_input(50);
+        fork.step()?;
+        fork.step()?;
+        assert_eq!(EmulatorResult::SuccessWithValue(100), fork.step()?);
+
+        // The parent hasn't moved, and carries on with its own input.
+        assert_eq!(before, parent.snapshot());
+        assert_eq!(EmulatorResult::SuccessWithValue(2), parent.step()?);
+        parent.step()?;
+        assert_eq!(EmulatorResult::NeedsInput, parent.step()?);
+        assert!(!fork.state_eq(&parent));
+
+        // Clone keeps the input queue as well.
+        parent.push_input(4);
+        let mut clone = parent.clone();
+        clone.step()?;
+        clone.step()?;
+        assert_eq!(EmulatorResult::SuccessWithValue(8), clone.step()?);
+        parent.step()?;
+        parent.step()?;
+        assert_eq!(EmulatorResult::SuccessWithValue(8), parent.step()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), EmulatorError> {
+        let mut emulator = EmulatorBuilder::new()
+            .program(&[1, 0, 0, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+            .patch(1, 9)
+            .patch(2, 7)
+            .patch(2, 10)
+            .build()?;
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+
+        // Adds two inputs into a cell past the end.
+        let program = [3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99];
+        let mut emulator = EmulatorBuilder::new()
+            .program(&program)
+            .input(vec![3])
+            .input(vec![4])
+            .build()?;
+        assert_eq!(Ok(vec![7]), emulator.run_collecting_outputs());
+
+        assert_eq!(
+            Err(EmulatorError::ArgumentOutOfRange {
+                address: 11,
+                memory_size: 11,
+                context: None,
+            }),
+            EmulatorBuilder::new()
+                .program(&program)
+                .patch(11, 1)
+                .build()
+                .map(|_| ())
+        );
+        let emulator = EmulatorBuilder::new()
+            .program(&program)
+            .grow_memory(true)
+            .patch(15, 1)
+            .build()?;
+        assert_eq!(16, emulator.memory().len());
+        assert!(EmulatorBuilder::new()
+            .program(&program)
+            .grow_memory(true)
+            .memory_limit(15)
+            .patch(15, 1)
+            .build()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_limit_setting() {
+        // Writes to 20, 21 and then 22, which the limit refuses.
+        let program = [3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99];
+        let mut emulator = EmulatorBuilder::new()
+            .program(&program)
+            .input(vec![3, 4])
+            .memory_limit(22)
+            .build()
+            .unwrap();
+        assert_eq!(Some(22), emulator.memory_limit());
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: 22,
+                limit: 22,
+                context: None,
+            }),
+            emulator.run_collecting_outputs()
+        );
+        assert_eq!(22, emulator.memory().len());
+
+        emulator.set_memory_limit(0);
+        assert_eq!(None, emulator.memory_limit());
+        assert_eq!(Ok(vec![7]), emulator.run_collecting_outputs());
+    }
+
+    #[test]
+    fn test_memory_limit_exceeded() -> Result<(), EmulatorError> {
+        let program = [1101, 2, 3, 2_000_000, 4, 2_000_000, 99];
+        let run = |limit| {
+            EmulatorBuilder::new()
+                .program(&program)
+                .memory_limit(limit)
+                .build()?
+                .run_collecting_outputs()
+        };
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: 2_000_000,
+                limit: 1_000_000,
+                context: None,
+            }),
+            run(1_000_000)
+        );
+        assert_eq!(Ok(vec![5]), run(2_000_001));
+        assert_eq!(Ok(vec![5]), run(0));
+
+        let emulator = Emulator::new(&program, std::iter::empty());
+        assert_eq!(Some(MEMORY_LIMIT), emulator.memory_limit());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoint_on_add() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        emulator.watch(3);
+        assert_eq!(
+            EmulatorResult::Watchpoint {
+                address: 3,
+                old: 3,
+                new: 70
+            },
+            emulator.step()?
+        );
+        // The next instruction writes to 0, which isn't watched.
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoint_on_input() -> Result<(), EmulatorError> {
+        let mut emulator = Emulator::with_inputs(&[3, 0, 4, 0, 99], vec![1337]);
+        emulator.watch(0);
+        assert_eq!(vec![0], emulator.watched().collect::<Vec<_>>());
+        assert_eq!(
+            EmulatorResult::Watchpoint {
+                address: 0,
+                old: 3,
+                new: 1337
+            },
+            emulator.step()?
+        );
+        assert_eq!(EmulatorResult::SuccessWithValue(1337), emulator.step()?);
+
+        // Writing the value already there still trips it.
+        let mut emulator = Emulator::with_inputs(&[3, 5, 99, 0, 0, 7], vec![7]);
+        emulator.watch(5);
+        assert_eq!(
+            EmulatorResult::Watchpoint {
+                address: 5,
+                old: 7,
+                new: 7
+            },
+            emulator.step()?
+        );
+        emulator.unwatch(5);
+        assert_eq!(0, emulator.watched().count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoint_only_trips_on_writes() -> Result<(), EmulatorError> {
+        // Reads cell 9 twice and outputs it, but never writes it.
+        let mut emulator = emulator_with_empty_input(&[1, 9, 9, 10, 4, 9, 99, 0, 0, 21, 0]);
+        emulator.watch(9);
+        let mut results = Vec::new();
+        while !emulator.is_halted() {
+            results.push(emulator.step()?);
+        }
+        assert_eq!(
+            vec![
+                EmulatorResult::Success,
+                EmulatorResult::SuccessWithValue(21),
+                EmulatorResult::Done
+            ],
+            results
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_limit_stops_infinite_loop() {
+        let mut emulator = emulator_with_empty_input(&[1105, 1, 0, 99]);
+        emulator.set_step_limit(1000);
+        assert_eq!(
+            Err(EmulatorError::InstructionLimitExceeded {
+                executed: 1000,
+                context: None,
+            }),
+            emulator.run_to_completion()
+        );
+        assert_eq!(1000, emulator.steps);
+        assert!(!emulator.is_halted());
+
+        let emulator = EmulatorBuilder::new()
+            .program(&[104, 1, 1105, 1, 0, 99])
+            .step_limit(10)
+            .build()
+            .unwrap();
+        let (outputs, error) = emulator.collect_outputs_lossy();
+        assert_eq!(vec![1; 5], outputs);
+        assert_eq!(
+            Some(EmulatorError::InstructionLimitExceeded {
+                executed: 10,
+                context: None,
+            }),
+            error
+        );
+    }
+
+    #[test]
+    fn test_step_limit_leaves_terminating_programs_alone() {
+        let program = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut emulator = emulator_with_empty_input(&program);
+        emulator.set_step_limit(1000);
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+
+        // Exactly enough steps to reach the halt.
+        let mut emulator = emulator_with_empty_input(&program);
+        emulator.set_step_limit(3);
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+    }
+
+    #[test]
+    fn test_detect_loops() -> Result<(), EmulatorError> {
+        let spin = [1105, 1, 0, 99];
+        let mut emulator = emulator_with_empty_input(&spin);
+        emulator.detect_loops(true);
+        assert_eq!(
+            Some(DEFAULT_LOOP_CHECK_INTERVAL),
+            emulator.loop_check_interval()
+        );
+        assert_eq!(
+            Err(EmulatorError::LoopDetected {
+                steps: 2 * DEFAULT_LOOP_CHECK_INTERVAL,
+                context: None,
+            }),
+            emulator.run_to_completion()
+        );
+        let mut emulator = emulator_with_empty_input(&spin);
+        emulator.set_loop_check_interval(1);
+        assert_eq!(
+            Err(EmulatorError::LoopDetected {
+                steps: 2,
+                context: None,
+            }),
+            emulator.run_to_completion()
+        );
+
+        // Compares its input to 8, with jumps.
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for &(input, output) in [(7, 999), (8, 1000), (9, 1001)].iter() {
+            let mut emulator = Emulator::with_inputs(&program, [input]);
+            emulator.set_loop_check_interval(1);
+            assert_eq!(vec![output], emulator.run_collecting_outputs()?);
+        }
+
+        // Echoes forever, but reading input each time round isn't a loop.
+        let mut emulator = Emulator::with_inputs(&[3, 7, 4, 7, 1105, 1, 0, 0], vec![1; 10]);
+        emulator.set_loop_check_interval(1);
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            emulator.run_collecting_outputs()
+        );
+        emulator.detect_loops(false);
+        assert_eq!(None, emulator.loop_check_interval());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_mut() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1, 0, 0, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        emulator[1] = 9;
+        emulator[2] = 10;
+        assert_eq!(&[1, 9, 10, 3], &emulator[0..4]);
+        assert_eq!(Ok(3500), emulator.run_to_completion());
+        assert_eq!(&[3500, 9, 10, 70], &emulator[0..4]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_mut_past_the_end_panics() {
+        let mut emulator = emulator_with_empty_input(&[99]);
+        emulator[1] = 0;
+    }
+
+    #[test]
+    fn test_add() -> Result<(), EmulatorError> {
+        let initial_address = [1, 0, 0, 0, 99];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[2, 0, 0, 0, 99], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(4, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiply_1() -> Result<(), EmulatorError> {
+        let initial_address = [1, 0, 0, 0, 99];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[2, 0, 0, 0, 99], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(4, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiply_2() -> Result<(), EmulatorError> {
+        let initial_address = [2, 4, 4, 5, 99, 0];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[2, 4, 4, 5, 99, 9801], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(4, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overriding_future_instructions() -> Result<(), EmulatorError> {
+        let initial_address = [1, 1, 1, 4, 99, 5, 6, 0, 99];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[1, 1, 1, 4, 2, 5, 6, 0, 99], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[30, 1, 1, 4, 2, 5, 6, 0, 99], emulator.memory());
+        assert_eq!(8, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(8, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    // Runs program to completion on every execution path, checking they all
+    // end with the same memory and outputs, which are returned.
+    fn run_on_every_path(
+        program: &[EmulatorMemoryType],
+    ) -> (Vec<EmulatorMemoryType>, Vec<EmulatorMemoryType>) {
+        let mut results = Vec::new();
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator = Emulator::with_dispatch(program, std::iter::empty(), dispatch);
+            let mut outputs = Vec::new();
+            while let Ok(result) = emulator.step() {
+                match result {
+                    EmulatorResult::Done | EmulatorResult::NeedsInput => break,
+                    _ => outputs.extend(result.output()),
+                }
+            }
+            assert!(emulator.is_halted());
+            results.push((emulator.memory().to_vec(), outputs));
+        }
+
+        let mut cells = program.to_vec();
+        let mut outputs = Vec::new();
+        let mut instruction_pointer = Some(0);
+        let mut relative_base = 0;
+        while let Some(ip) = instruction_pointer {
+            let outcome =
+                OpCode::eval_one(&mut cells, ip, &mut relative_base, &mut Vec::new()).unwrap();
+            outputs.extend(outcome.output);
+            instruction_pointer = outcome.next_instruction_pointer;
+        }
+        results.push((cells, outputs));
+
+        assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        results.pop().unwrap()
+    }
+
+    #[test]
+    fn test_overwriting_own_opcode_cell() {
+        // 1 + 1 lands on the Add itself, which still advances by four.
+        let (memory, _) = run_on_every_path(&[1101, 1, 1, 0, 99]);
+        assert_eq!(vec![2, 1, 1, 0, 99], memory);
+        // The new opcode only matters once execution comes back to it.
+        let (memory, _) = run_on_every_path(&[1101, 98, 1, 0, 1105, 1, 0, 0]);
+        assert_eq!(vec![99, 98, 1, 0, 1105, 1, 0, 0], memory);
+    }
+
+    #[test]
+    fn test_overwriting_own_parameter_cell() {
+        // The second addend is read from cell 2 before 7 + 2 overwrites it.
+        let (memory, _) = run_on_every_path(&[1, 5, 2, 2, 99, 7]);
+        assert_eq!(vec![1, 5, 9, 2, 99, 7], memory);
+        // The destination cell is its own target: the address 3 is decoded
+        // first, then replaced by the sum.
+        let (memory, _) = run_on_every_path(&[1101, 2, 5, 3, 99]);
+        assert_eq!(vec![1101, 2, 5, 7, 99], memory);
+    }
+
+    #[test]
+    fn test_overwriting_next_opcode_cell() {
+        // The next cell becomes a halt before it is reached.
+        let (memory, outputs) = run_on_every_path(&[1101, 100, -1, 4, 104, 7, 99]);
+        assert_eq!(vec![1101, 100, -1, 4, 99, 7, 99], memory);
+        assert!(outputs.is_empty());
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let (_, outputs) = run_on_every_path(&program);
+        assert_eq!(program.to_vec(), outputs);
+    }
+
+    #[test]
+    fn test_checked_read_and_write() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1101, 1, 2, 5, 99, 0]);
+        assert_eq!(Ok(1101), emulator.read(0));
+        // Past the end reads 0, as it would for the program, and doesn't
+        // grow memory.
+        assert_eq!(Ok(0), emulator.read(100));
+        assert_eq!(6, emulator.memory().len());
+        emulator.write(8, 4)?;
+        assert_eq!(&[1101, 1, 2, 5, 99, 0, 0, 0, 4], emulator.memory());
+
+        // Without room to grow, reads past the end are errors.
+        emulator.set_memory_limit(emulator.memory().len());
+        assert_eq!(Ok(4), emulator.read(8));
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: 9,
+                position: 0,
+                context: None,
+            }),
+            emulator.read(9)
+        );
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: 9,
+                limit: 9,
+                context: None,
+            }),
+            emulator.write(9, 1)
+        );
+        emulator.write(5, 7)?;
+        assert_eq!(Ok(7), emulator.read(5));
+
+        assert!(emulator_with_empty_input(&[99]).read(MEMORY_LIMIT).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_grows_past_the_end() -> Result<(), EmulatorError> {
+        // Stores 42 at 10_000, reads it back and outputs it.
+        let program = [1101, 7, 35, 10_000, 4, 10_000, 99, 0, 0, 0];
+        let (memory, outputs) = run_on_every_path(&program);
+        assert_eq!(10_001, memory.len());
+        assert_eq!(42, memory[10_000]);
+        assert_eq!(&program[..], &memory[..program.len()]);
+        assert_eq!(vec![42], outputs);
+
+        let mut emulator = emulator_with_empty_input(&program);
+        assert_eq!(Ok(1101), emulator.run_to_completion());
+        assert_eq!(Ok(42), emulator.read(10_000));
+
+        // Reads past the end see 0 without growing memory.
+        let mut emulator = emulator_with_empty_input(&[4, 500, 99]);
+        assert_eq!(Ok(EmulatorResult::SuccessWithValue(0)), emulator.step());
+        assert_eq!(3, emulator.memory().len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let limit = MEMORY_LIMIT as EmulatorMemoryType;
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator =
+                Emulator::with_dispatch(&[1101, 1, 1, limit, 99], std::iter::empty(), dispatch);
+            assert_eq!(
+                Err(EmulatorError::MemoryLimitExceeded {
+                    requested: MEMORY_LIMIT,
+                    limit: MEMORY_LIMIT,
+                    context: None,
+                }),
+                emulator.step()
+            );
+            assert_eq!(5, emulator.memory().len());
+        }
+    }
+
+    #[test]
+    fn test_sixteen_digit_output() {
+        let (_, outputs) = run_on_every_path(&[1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0]);
+        assert_eq!(vec![1_219_070_632_396_864], outputs);
+        assert_eq!(16, outputs[0].to_string().len());
+    }
+
+    // The product needs 82 bits, so only runs with mem-i128.
+    #[cfg(feature = "mem-i128")]
+    #[test]
+    fn test_wide_product() {
+        let factor = 3_000_000_000_000;
+        let (_, outputs) = run_on_every_path(&[1102, factor, factor + 7, 7, 4, 7, 99, 0]);
+        assert_eq!(vec![9_000_000_000_021_000_000_000_000], outputs);
+    }
+
+    // The same product, on an i128 machine in any build.
+    #[test]
+    fn test_wide_product_in_i128_cells() -> Result<(), EmulatorError> {
+        let factor: i128 = 3_000_000_000_000;
+        let program = [1102, factor, factor + 7, 7, 4, 7, 99, 0];
+        for dispatch in [Dispatch::Match, Dispatch::Table] {
+            let mut emulator = Emulator::with_cells(&program, empty(), dispatch);
+            assert_eq!(
+                vec![9_000_000_000_021_000_000_000_000],
+                emulator.run_collecting_outputs()?
+            );
+            assert!(emulator.is_halted());
+            assert_eq!(9_000_000_000_021_000_000_000_000, emulator.memory()[7]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_i128_cells() -> Result<(), EmulatorError> {
+        // Relative mode and jumps: the day 9 quine.
+        let quine: Vec<i128> = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut emulator = Emulator::with_cells(&quine, empty(), Dispatch::Table);
+        assert_eq!(quine, emulator.run_collecting_outputs()?);
+
+        // Input, echoed back doubled.
+        let input = once(Ok(i128::MAX / 4));
+        let mut emulator =
+            Emulator::with_cells(&[3, 9, 1002, 9, 2, 9, 4, 9, 99, 0], input, Dispatch::Match);
+        assert_eq!(vec![i128::MAX / 4 * 2], emulator.run_collecting_outputs()?);
+
+        // Errors carry the value as found, however wide.
+        for dispatch in [Dispatch::Match, Dispatch::Table] {
+            let mut emulator = Emulator::with_cells(&[i128::MAX], empty(), dispatch);
+            assert_eq!(
+                EmulatorError::InvalidInstruction {
+                    value_found: i128::MAX,
+                    position: 0,
+                    context: None,
+                },
+                emulator.step().unwrap_err()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_number_echo() {
+        let (_, outputs) = run_on_every_path(&[104, 1_125_899_906_842_624, 99]);
+        assert_eq!(vec![1_125_899_906_842_624], outputs);
+    }
+
+    #[test]
+    fn test_relative_mode_reads_and_writes() -> Result<(), EmulatorError> {
+        // With the base at 10, cell 13 = cell 11 + cell 12. Then the base
+        // moves to 17 and cell 13 is output through a negative offset.
+        let program = [109, 10, 22201, 1, 2, 3, 109, 7, 204, -4, 99, 20, 22, 0];
+        let (memory, outputs) = run_on_every_path(&program);
+        assert_eq!(42, memory[13]);
+        assert_eq!(vec![42], outputs);
+
+        let mut emulator = emulator_with_empty_input(&program);
+        emulator.run_to_completion()?;
+        assert_eq!(17, emulator.relative_base());
+        // Reads are recorded as values and the write as its address.
+        let add = emulator.recent_history().records().nth(1).unwrap();
+        assert_eq!(vec![20, 22, 13], add.parameters);
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_relative_address() {
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator =
+                Emulator::with_dispatch(&[109, -5, 204, 2, 99], std::iter::empty(), dispatch);
+            emulator.step().unwrap();
+            assert_eq!(
+                Err(EmulatorError::InvalidMemoryLocation {
+                    value_found: -3,
+                    position: 3,
+                    context: None,
+                }),
+                emulator.step()
+            );
+
+            let mut emulator = Emulator::with_dispatch(
+                &[109, -5, 21101, 1, 1, 0, 99],
+                std::iter::empty(),
+                dispatch,
+            );
+            emulator.step().unwrap();
+            assert_eq!(
+                Err(EmulatorError::InvalidMemoryLocation {
+                    value_found: -5,
+                    position: 5,
+                    context: None,
+                }),
+                emulator.step()
+            );
+        }
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -1,
+                position: 1,
+                context: None,
+            }),
+            OpCode::eval_one(&mut vec![204, -1, 99], 0, &mut 0, &mut vec![])
+        );
+    }
+
+    #[test]
+    fn test_parameter_modes() -> Result<(), EmulatorError> {
+        let initial_address = [1002, 4, 3, 4, 33];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[1002, 4, 3, 4, 99], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(4, emulator.instruction_pointer());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_output() -> Result<(), EmulatorError> {
+        let initial_address = [3, 0, 4, 0, 99];
+        let mut emulator = Emulator::new(&initial_address, once(Ok(1337)));
+        assert_eq!(&initial_address, emulator.memory());
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(&[1337, 0, 4, 0, 99], emulator.memory());
+        assert_eq!(2, emulator.instruction_pointer());
+
+        assert_eq!(EmulatorResult::SuccessWithValue(1337), emulator.step()?);
+        assert_eq!(&[1337, 0, 4, 0, 99], emulator.memory());
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        assert_eq!(4, emulator.instruction_pointer());
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_set_instruction_pointer() -> Result<(), EmulatorError> {
+        // Outputs 1, then 2, then halts.
+        let program = [104, 1, 104, 2, 99];
+        let mut emulator = emulator_with_empty_input(&program);
+        emulator.set_instruction_pointer(2)?;
+        assert_eq!(Ok(vec![2]), emulator.run_collecting_outputs());
+        assert_eq!(4, emulator.instruction_pointer());
+
+        emulator.set_instruction_pointer(0)?;
+        assert!(!emulator.is_halted());
+        assert_eq!(Ok(vec![1, 2]), emulator.run_collecting_outputs());
+
+        assert_eq!(
+            Err(EmulatorError::InstructionPointerOutOfBounds {
+                position: 5,
+                context: None,
+            }),
+            emulator.set_instruction_pointer(5)
+        );
+        assert_eq!(4, emulator.instruction_pointer());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_format() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        emulator.step()?;
+        assert_eq!(
+            "Emulator at 4, relative base 0, steps 1\n\
+             Next:\n    \
+                 4: Multiply [3] [11] [0]\n\
+             Memory:\n    \
+                 0: 1 9 10 70 <2> 3 11 0\n    \
+                 8: 99 30 40 50\n",
+            format!("{:?}", emulator)
+        );
+        assert_eq!("    2: 10 70 <2>\n", emulator.dump(2..5));
+
+        emulator.run_to_completion()?;
+        let debug = format!("{:?}", emulator);
+        assert!(debug.starts_with("Emulator halted at 8,"));
+        assert!(debug.contains("    8: <99> 30 40 50\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_iterator() -> Result<(), EmulatorError> {
+        let initial_address = [3, 0, 4, 0, 99];
+        let emulator = Emulator::new(&initial_address, once(Ok(1337)));
+        assert_eq!(&initial_address, emulator.memory());
+
+        let mut iterator = emulator.into_output_iter();
+        assert_eq!(Some(Ok(1337)), iterator.next());
+        assert_eq!(None, iterator.next());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_iterator_into_inner() -> Result<(), EmulatorError> {
+        // Outputs 3 and 4, leaving their product in cell 0.
+        let program = [104, 3, 104, 4, 1102, 3, 4, 0, 99];
+        let mut outputs = emulator_with_empty_input(&program).into_output_iter();
+        assert_eq!(vec![3, 4], outputs.by_ref().collect::<Result<Vec<_>, _>>()?);
+        assert!(outputs.emulator().is_halted());
+
+        let emulator = outputs.into_inner();
+        assert_eq!(&[12, 3, 104, 4, 1102, 3, 4, 0, 99], emulator.memory());
+        assert_eq!(4, emulator.steps);
+        Ok(())
+    }
+
+    #[test]
+    fn test_outputs_can_be_stored() -> Result<(), EmulatorError> {
+        struct Echo {
+            outputs: IntoOutputs<std::vec::IntoIter<Result<EmulatorMemoryType, EmulatorError>>>,
+        }
+
+        let emulator = Emulator::new(&[3, 0, 4, 0, 99], vec![Ok(42)].into_iter());
+        let mut echo = Echo {
+            outputs: emulator.into_iter(),
+        };
+        assert_eq!(Some(Ok(42)), echo.outputs.next());
+        assert_eq!(None, echo.outputs.next());
+
+        let mut outputs = Vec::new();
+        for output in Emulator::new(&[104, 1, 104, 2, 99], std::iter::empty()) {
+            outputs.push(output?);
+        }
+        assert_eq!(vec![1, 2], outputs);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_outputs_lossy() {
+        let program = [104, 1, 104, 2, 104, 3, 42, 99];
+        let (outputs, error) = emulator_with_empty_input(&program).collect_outputs_lossy();
+        assert_eq!(vec![1, 2, 3], outputs);
+        assert_eq!(
+            Some(EmulatorError::InvalidInstruction {
+                value_found: 42,
+                position: 6,
+                context: None,
+            }),
+            error
+        );
+
+        let (outputs, error) = emulator_with_empty_input(&program[..6])
+            .into_output_iter()
+            .take(2)
+            .collect_lossy();
+        assert_eq!((vec![1, 2], None), (outputs, error));
+    }
+
+    #[test]
+    fn test_new_with_values_or_results() -> Result<(), EmulatorError> {
+        let echo = [3, 0, 4, 0, 99];
+        let outputs: Result<Vec<_>, _> = Emulator::new_with(&echo, vec![7]).into_iter().collect();
+        assert_eq!(vec![7], outputs?);
+        let outputs: Result<Vec<_>, _> =
+            Emulator::new_with(&echo, once(Ok(8))).into_iter().collect();
+        assert_eq!(vec![8], outputs?);
+
+        // An error from the input itself is passed on as it is.
+        let failing = once(Err(EmulatorError::InputNonExistent {
+            position: 17,
+            context: None,
+        }));
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 17,
+                context: None,
+            }),
+            Emulator::new_with(&echo, failing).step()
+        );
         Ok(())
     }
 
     #[test]
-    fn test_parameter_modes() -> Result<(), EmulatorError> {
-        let initial_address = [1002, 4, 3, 4, 33];
-        let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    fn test_immediate_destination() {
+        let error = EmulatorError::UnexpectedParameterModeForWritable {
+            value_found: 1,
+            position: 3,
+            context: None,
+        };
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator = Emulator::with_dispatch(&[11101, 1, 1, 3, 99], empty(), dispatch);
+            assert_eq!(Err(error.clone()), emulator.run_to_completion());
+        }
+        assert_eq!(
+            "Writable parameter at 3 has invalid parameter mode 1. \
+             The parameter mode must be position (0) or relative (2)",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_missing_input_position() {
+        // Outputs 1, stores 5 in cell 20, then reads input at address 6.
+        let program = [104, 1, 1101, 2, 3, 20, 3, 20, 99];
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator = Emulator::with_dispatch(&program, empty(), dispatch);
+            let error = EmulatorError::InputNonExistent {
+                position: 6,
+                context: None,
+            };
+            assert_eq!(Err(error.clone()), emulator.run_to_completion());
+            assert_eq!(
+                "Input non existent for the instruction at 6",
+                error.to_string()
+            );
+        }
+        assert_eq!(
+            (
+                vec![1],
+                Some(EmulatorError::InputNonExistent {
+                    position: 6,
+                    context: None,
+                })
+            ),
+            emulator_with_empty_input(&program).collect_outputs_lossy()
+        );
+    }
+
+    #[test]
+    fn test_needs_input_pauses_until_input_arrives() -> Result<(), EmulatorError> {
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut emulator =
+                Emulator::with_dispatch(&[3, 0, 4, 0, 99], QueueInput::new(), dispatch);
+            assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+            assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+            assert_eq!(&[3, 0, 4, 0, 99], emulator.memory());
+            assert_eq!(0, emulator.recent_history().len());
+            assert!(!emulator.is_halted());
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[1002, 4, 3, 4, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+            emulator.push_input(42);
+            assert_eq!(EmulatorResult::Success, emulator.step()?);
+            assert_eq!(EmulatorResult::SuccessWithValue(42), emulator.step()?);
+            assert_eq!(EmulatorResult::Done, emulator.step()?);
+            assert_eq!(1, emulator.inputs_consumed());
+        }
 
+        // Nothing can supply more input to these, so running out is an error.
+        let mut emulator = emulator_with_empty_input(&[3, 0, 4, 0, 99]);
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            emulator.run_to_completion()
+        );
+        assert_eq!(
+            (
+                vec![],
+                Some(EmulatorError::InputNonExistent {
+                    position: 0,
+                    context: None,
+                })
+            ),
+            emulator_with_empty_input(&[3, 0, 4, 0, 99]).collect_outputs_lossy()
+        );
         Ok(())
     }
 
     #[test]
-    fn test_input_output() -> Result<(), EmulatorError> {
-        let initial_address = [3, 0, 4, 0, 99];
-        let mut emulator = Emulator::new(&initial_address, once(Ok(1337)));
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+    fn test_needs_input_leaves_memory_alone() -> Result<(), EmulatorError> {
+        let past_limit = MEMORY_LIMIT as EmulatorMemoryType;
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            for &destination in [1000, past_limit].iter() {
+                let program = [3, destination, 99];
+                let mut emulator = Emulator::with_dispatch(&program, QueueInput::new(), dispatch);
+                let before = emulator.snapshot();
+                assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+                assert_eq!(&program, emulator.memory());
+                assert_eq!(3, emulator.memory().len());
+                assert_eq!(before, emulator.snapshot());
+            }
 
-        assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[1337, 0, 4, 0, 99], emulator.memory.as_slice());
+            // The write is only refused once there's a value to write.
+            let mut emulator =
+                Emulator::with_dispatch(&[3, past_limit, 99], QueueInput::new(), dispatch);
+            assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+            emulator.push_input(7);
+            assert!(matches!(
+                emulator.step(),
+                Err(EmulatorError::MemoryLimitExceeded { requested, .. })
+                    if requested == MEMORY_LIMIT
+            ));
 
-        assert_eq!(EmulatorResult::SuccessWithValue(1337), emulator.step()?);
-        assert_eq!(&[1337, 0, 4, 0, 99], emulator.memory.as_slice());
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
-        assert_eq!(EmulatorResult::Done, emulator.step()?);
+            let far = 1 << 30;
+            let mut emulator = Emulator::with_dispatch(&[3, far, 99], QueueInput::new(), dispatch);
+            emulator.set_sparse_memory();
+            assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+            assert_eq!(3, emulator.touched_cells());
+            assert_eq!(
+                Vec::<(usize, EmulatorMemoryType)>::new(),
+                emulator.sparse_cells()
+            );
 
+            emulator.push_input(7);
+            assert_eq!(EmulatorResult::Success, emulator.step()?);
+            assert_eq!(vec![(far as usize, 7)], emulator.sparse_cells());
+        }
         Ok(())
     }
 
     #[test]
-    fn test_output_iterator() -> Result<(), EmulatorError> {
-        let initial_address = [3, 0, 4, 0, 99];
-        let emulator = Emulator::new(&initial_address, once(Ok(1337)));
-        assert_eq!(&initial_address, emulator.memory.as_slice());
-
-        let mut iterator = emulator.into_output_iter();
-        assert_eq!(Some(Ok(1337)), iterator.next());
-        assert_eq!(None, iterator.next());
+    fn test_display_formats() {
+        // A bare variant doesn't say what its cells are.
+        type Shown = EmulatorResult<EmulatorMemoryType>;
+        assert_eq!("ok", Shown::Success.to_string());
+        assert_eq!("output 1337", Shown::SuccessWithValue(1337).to_string());
+        assert_eq!("halted", Shown::Done.to_string());
+        assert_eq!("waiting for input", Shown::NeedsInput.to_string());
+        assert_eq!(
+            "watchpoint 3: 3 -> 70",
+            Shown::Watchpoint {
+                address: 3,
+                old: 3,
+                new: 70
+            }
+            .to_string()
+        );
+        assert_eq!(Some(-4), Shown::SuccessWithValue(-4).output());
+        assert_eq!(None, Shown::Done.output());
+        assert!(Shown::Done.is_done());
+        assert!(!Shown::Success.is_done());
+        assert!(!Shown::NeedsInput.is_done());
 
-        Ok(())
+        let outcome = OneStepOutcome {
+            next_instruction_pointer: Some(4),
+            output: None,
+            written_cells: vec![9, 5].into_iter().collect(),
+        };
+        assert_eq!("next 4, wrote 5 9", outcome.to_string());
+        let outcome = OneStepOutcome {
+            next_instruction_pointer: None,
+            output: Some(7),
+            written_cells: BTreeSet::new(),
+        };
+        assert_eq!("halted, output 7", outcome.to_string());
     }
 
     #[test]
     fn test_negatives() -> Result<(), EmulatorError> {
         let initial_address = [1101, 100, -1, 4, 0];
         let mut emulator = emulator_with_empty_input(&initial_address);
-        assert_eq!(&initial_address, emulator.memory.as_slice());
+        assert_eq!(&initial_address, emulator.memory());
 
         assert_eq!(EmulatorResult::Success, emulator.step()?);
-        assert_eq!(&[1101, 100, -1, 4, 99], emulator.memory.as_slice());
+        assert_eq!(&[1101, 100, -1, 4, 99], emulator.memory());
         assert_eq!(EmulatorResult::Done, emulator.step()?);
         assert_eq!(EmulatorResult::Done, emulator.step()?);
 
@@ -557,4 +3329,686 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_history_contains_jump_before_error() {
+        // Counts memory[20] up to 5, then jumps to an Add reading address -100.
+        let initial_address = [
+            1001, 20, 1, 20, 1007, 20, 5, 21, 1006, 21, 15, 1105, 1, 0, 99, 1, -100, 0, 0, 99, 0, 0,
+        ];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -100,
+                position: 16,
+                context: None,
+            }),
+            emulator.run_to_completion()
+        );
+
+        let history = emulator.recent_history();
+        assert_eq!(DEFAULT_HISTORY_SIZE, history.len());
+        assert_eq!(
+            Some(&InstructionRecord {
+                step: 18,
+                instruction_pointer: 8,
+                instruction: 1006,
+                parameters: vec![0, 15],
+            }),
+            history.last()
+        );
+        assert_eq!(
+            vec![3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18],
+            history
+                .records()
+                .map(|record| record.step)
+                .collect::<Vec<_>>()
+        );
+        assert!(history
+            .to_string()
+            .starts_with("Most recent instructions:\n    step 18 at 8: 1006 0 15\n    step 17 at 4: 1007 5 5 21\n"));
+    }
+
+    #[test]
+    fn test_error_report() {
+        // Counts memory[20] up to 5, then jumps to an Add reading address -100.
+        let initial_address = [
+            1001, 20, 1, 20, 1007, 20, 5, 21, 1006, 21, 15, 1105, 1, 0, 99, 1, -100, 0, 0, 99, 0, 0,
+        ];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        let error = emulator.run_to_completion().unwrap_err();
+        let report = emulator.error_report(&error);
+        let lines: Vec<&str> = report.lines().take(8).collect();
+        assert_eq!(
+            vec![
+                "Invalid memory location -100 referenced at 16.",
+                "While running Add at 15:",
+                "   10: 15 1105 1 0 99 <1> -100 0 0 99 5",
+                "Stopped at",
+                "   15: Add [-100] [0] [0]",
+                "Most recent instructions:",
+                "    step 18 at 8: 1006 0 15",
+                "    step 17 at 4: 1007 5 5 21",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_history_size() -> Result<(), EmulatorError> {
+        let initial_address = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        emulator.set_history_size(2);
+        emulator.run_to_completion()?;
+        assert_eq!(
+            vec![(4, 2, vec![70, 50, 0]), (8, 99, vec![])],
+            emulator
+                .recent_history()
+                .records()
+                .map(|record| (
+                    record.instruction_pointer,
+                    record.instruction,
+                    record.parameters.clone()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        emulator.set_history_size(0);
+        emulator.run_to_completion()?;
+        assert!(emulator.recent_history().is_empty());
+        assert_eq!(
+            "No instructions executed",
+            emulator.recent_history().to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_enough_parameters() {
+        let cases: [(&[EmulatorMemoryType], EmulatorMemoryType, usize, usize); 4] = [
+            (&[1, 0], 1, 3, 1),
+            (&[2, 5, 6], 2, 3, 2),
+            (&[1105, 1], 5, 2, 1),
+            (&[4], 4, 1, 0),
+        ];
+        for &(program, instruction, expected, found) in cases.iter() {
+            for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+                let mut emulator = Emulator::with_dispatch(program, empty(), dispatch);
+                let error = EmulatorError::NotEnoughParametersForInstruction {
+                    instruction: instruction.widen(),
+                    expected,
+                    found,
+                    context: None,
+                };
+                assert_eq!(Err(error.clone()), emulator.step(), "{:?}", program);
+                assert_eq!(
+                    Err(error),
+                    peek::decode_at(program, 0, 0).map(|_| ()),
+                    "{:?}",
+                    program
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parameters_ending_at_the_end_of_memory() {
+        for &dispatch in [Dispatch::Match, Dispatch::Table].iter() {
+            let mut add = Emulator::with_dispatch(&[1101, 2, 3, 3], empty(), dispatch);
+            assert_eq!(Ok(EmulatorResult::Success), add.step());
+            assert_eq!(&[1101, 2, 3, 5], add.memory());
+
+            let mut output = Emulator::with_dispatch(&[1101, 2, 3, 5, 4, 5], empty(), dispatch);
+            assert_eq!(Ok(EmulatorResult::Success), output.step());
+            assert_eq!(Ok(EmulatorResult::SuccessWithValue(5)), output.step());
+        }
+        assert!(peek::decode_at(&[4, 0], 0, 0).is_ok());
+        assert_eq!(
+            "    0: Output [0]",
+            disassemble::disassemble(&[4, 0], None)[0].to_string()
+        );
+
+        // One cell short still fails.
+        assert_eq!(
+            Err(EmulatorError::NotEnoughParametersForInstruction {
+                instruction: 1,
+                expected: 3,
+                found: 2,
+                context: None,
+            }),
+            emulator_with_empty_input(&[1101, 2, 3]).step()
+        );
+    }
+
+    #[test]
+    fn test_validate_entry() {
+        let examples: [&[EmulatorMemoryType]; 6] = [
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[1002, 4, 3, 4, 33],
+            &[1101, 100, -1, 4, 0],
+            &[3, 0, 4, 0, 99],
+            &[3, 3, 1108, -1, 8, 3, 4, 3, 99],
+            &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+        ];
+        for example in examples.iter() {
+            assert_eq!(Ok(()), emulator_with_empty_input(example).validate_entry());
+        }
+
+        for &memory in [
+            &[105_371, 129_056, 134_974][..],
+            &[1, 0, 0][..],
+            &[1, 0, 7, 0, 99][..],
+            &[11_101, 0, 0, 0, 99][..],
+            &[-1, 0, 0, 0][..],
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Err(EmulatorError::InvalidEntryInstruction {
+                    value_found: memory[0].widen(),
+                    context: None,
+                }),
+                validate_entry(memory)
+            );
+        }
+        assert_eq!(
+            "Program does not start with a valid instruction (found 105371)",
+            validate_entry(&[105_371, 129_056, 134_974])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        // Stores the input at 13, then jumps to an Add reading address -100.
+        let initial_address = [3, 13, 1105, 1, 7, 99, 99, 1, -100, 13, 13, 99, 99, 0];
+        let mut emulator = Emulator::new(&initial_address, once(Ok(42)));
+        let error = emulator.run_to_completion().unwrap_err();
+        // Compared without the context.
+        assert_eq!(
+            EmulatorError::InvalidMemoryLocation {
+                value_found: -100,
+                position: 8,
+                context: None,
+            },
+            error
+        );
+
+        let context = error.context().unwrap();
+        assert_eq!(7, context.instruction_pointer);
+        assert_eq!(Some(OpCode::Add), context.opcode);
+        assert_eq!(2, context.window_start);
+        assert_eq!(
+            vec![1105, 1, 7, 99, 99, 1, -100, 13, 13, 99, 99],
+            context.window
+        );
+        assert_eq!(
+            "Invalid memory location -100 referenced at 8.\n\
+             While running Add at 7:\n    2: 1105 1 7 99 99 <1> -100 13 13 99 99",
+            error.to_string()
+        );
+
+        // Errors from decoding plain memory have no emulator to describe.
+        assert_eq!(None, decode(&[42], 0).unwrap_err().context());
+        let mut emulator = emulator_with_empty_input(&[1105, 1, 9]);
+        let context = emulator.run_to_completion().unwrap_err().context().cloned();
+        assert_eq!(None, context.unwrap().opcode);
+    }
+
+    #[test]
+    fn test_read_only_segments_are_enforced() -> Result<(), EmulatorError> {
+        // Copies memory[9] into 10, then into 11.
+        let initial_address = [1001, 9, 0, 10, 1001, 9, 0, 11, 99, 7, 0, 0];
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        emulator.set_memory_layout(
+            "code:0..9:code,constants:9..11:ro,scratch:11..12"
+                .parse()
+                .unwrap(),
+        );
+
+        let error = emulator.run_to_completion().unwrap_err();
+        assert_eq!(
+            EmulatorError::WriteToReadOnlyMemory {
+                address: 10,
+                position: 3,
+                context: None,
+            },
+            error
+        );
+        assert_eq!(
+            "Write into segment 'constants' (address 10) referenced at 3\n\
+             While running Add at 0:\n    0: <1001> 9 0 10 1001 9",
+            emulator.describe_error(&error)
+        );
+        assert_eq!(&initial_address, emulator.memory());
+
+        let mut emulator = emulator_with_empty_input(&initial_address);
+        emulator.set_memory_layout("constants:9..10:ro".parse().unwrap());
+        emulator.run_to_completion()?;
+        assert_eq!(&[7, 7], &emulator.memory()[10..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_one_less_than() -> Result<(), EmulatorError> {
+        let mut cells = vec![1107, 3, 5, 5, 0, -1];
+        let outcome = OpCode::eval_one(&mut cells, 0, &mut 0, &mut vec![])?;
+        assert_eq!(1, cells[5]);
+        assert_eq!(
+            OneStepOutcome {
+                next_instruction_pointer: Some(4),
+                output: None,
+                written_cells: vec![5].into_iter().collect(),
+            },
+            outcome
+        );
+
+        let mut cells = vec![1107, 5, 3, 5, 0, -1];
+        OpCode::eval_one(&mut cells, 0, &mut 0, &mut vec![])?;
+        assert_eq!(0, cells[5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_one_jump_if_false() -> Result<(), EmulatorError> {
+        let mut cells = vec![1106, 0, 7, 0];
+        let outcome = OpCode::eval_one(&mut cells, 0, &mut 0, &mut vec![])?;
+        assert_eq!(Some(7), outcome.next_instruction_pointer);
+        assert!(outcome.written_cells.is_empty());
+
+        let mut cells = vec![1106, 1, 7, 0];
+        let outcome = OpCode::eval_one(&mut cells, 0, &mut 0, &mut vec![])?;
+        assert_eq!(Some(3), outcome.next_instruction_pointer);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_one_input_pops_front() -> Result<(), EmulatorError> {
+        let mut cells = vec![3, 3, 99, 0];
+        let mut inputs = vec![5, 6];
+        let outcome = OpCode::eval_one(&mut cells, 0, &mut 0, &mut inputs)?;
+        assert_eq!(5, cells[3]);
+        assert_eq!(vec![6], inputs);
+        assert_eq!(
+            vec![3],
+            outcome.written_cells.into_iter().collect::<Vec<_>>()
+        );
+
+        let mut inputs = vec![];
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            OpCode::eval_one(&mut cells, 0, &mut 0, &mut inputs)
+        );
+
+        let outcome = OpCode::eval_one(&mut cells, 2, &mut 0, &mut inputs)?;
+        assert_eq!(None, outcome.next_instruction_pointer);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_stats() -> Result<(), EmulatorError> {
+        // Counts cell 13 down from 3: Input, then three rounds of Add and
+        // JumpIfTrue, then Output and End.
+        let program = [3, 13, 101, -1, 13, 13, 1005, 13, 2, 4, 13, 99, 0, 0];
+        let mut emulator = Emulator::new(&program, once(Ok(3)));
+        emulator.enable_stats();
+        assert_eq!(3, emulator.run_to_completion()?);
+        let stats = emulator.stats().unwrap();
+        assert_eq!(9, stats.total_steps);
+        assert_eq!(
+            vec![(1, 3), (3, 1), (4, 1), (5, 3), (99, 1)],
+            stats.opcodes.clone().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(0, stats.opcode_count(2));
+        assert_eq!(
+            vec![(6, (2, 1))],
+            stats.branches.clone().into_iter().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiler() -> Result<(), EmulatorError> {
+        // The program from test_opcode_stats.
+        let program = [3, 13, 101, -1, 13, 13, 1005, 13, 2, 4, 13, 99, 0, 0];
+        for &sample_every in [1, 4].iter() {
+            let mut emulator = Emulator::new(&program, once(Ok(3)));
+            emulator.enable_stats();
+            emulator.attach_sampling_profiler(sample_every);
+            assert_eq!(3, emulator.run_to_completion()?);
+            let stats = emulator.stats().unwrap();
+            let report = emulator.profiler().unwrap().report();
+            assert_eq!(stats.total_steps, report.total_steps);
+
+            let mut calls: Vec<_> = report
+                .opcodes
+                .iter()
+                .map(|(opcode, profile)| (*opcode, profile.calls))
+                .collect();
+            calls.sort_unstable();
+            assert_eq!(stats.opcodes.clone().into_iter().collect::<Vec<_>>(), calls);
+            let sampled: u64 = report
+                .opcodes
+                .iter()
+                .map(|(_, profile)| profile.sampled)
+                .sum();
+            assert_eq!(9_u64.div_ceil(sample_every), sampled);
+        }
+
+        let mut emulator = Emulator::new(&program, once(Ok(3)));
+        emulator.attach_profiler();
+        assert_eq!(Some(1), emulator.profiler().map(Profiler::sample_every));
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_stats() -> Result<(), EmulatorError> {
+        let jump_in_position_mode = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let long_example = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        type BranchCounts = (usize, (u64, u64));
+        fn branches(
+            program: &[EmulatorMemoryType],
+            input: EmulatorMemoryType,
+        ) -> Result<Vec<BranchCounts>, EmulatorError> {
+            let mut emulator = Emulator::new(program, once(Ok(input)));
+            emulator.enable_stats();
+            emulator.run_to_completion()?;
+            Ok(emulator
+                .stats()
+                .unwrap()
+                .branches
+                .clone()
+                .into_iter()
+                .collect())
+        }
+
+        assert_eq!(vec![(2, (1, 0))], branches(&jump_in_position_mode, 0)?);
+        assert_eq!(vec![(2, (0, 1))], branches(&jump_in_position_mode, 7)?);
+        for &input in [0, 7].iter() {
+            assert_eq!(
+                vec![(6, (0, 1)), (13, (1, 0)), (33, (1, 0))],
+                branches(&long_example, input)?
+            );
+        }
+        assert_eq!(vec![(6, (1, 0)), (28, (1, 0))], branches(&long_example, 8)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_many_inputs_consumed() -> Result<(), EmulatorError> {
+        let initial_address = [3, 9, 3, 10, 4, 9, 4, 10, 99, 0, 0];
+        let mut emulator = Emulator::new(&initial_address, vec![Ok(5), Ok(6)].into_iter());
+        emulator.expect_input_count(1);
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(
+            Err(EmulatorError::TooManyInputsConsumed {
+                expected: 1,
+                position: 2,
+                context: None,
+            }),
+            emulator.step()
+        );
+        assert_eq!(1, emulator.inputs_consumed());
+        assert_eq!(Ok(0), emulator.read(10));
+
+        let emulator = Emulator::new(&initial_address, vec![Ok(5), Ok(6)].into_iter());
+        let mut outputs = emulator.into_output_iter();
+        assert_eq!(vec![5, 6], outputs.by_ref().collect::<Result<Vec<_>, _>>()?);
+        assert_eq!(2, outputs.emulator().inputs_consumed());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_position_mode_address() {
+        let mut emulator = emulator_with_empty_input(&[1, 0, -1, 0, 99]);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -1,
+                position: 2,
+                context: None,
+            }),
+            emulator.step()
+        );
+
+        let mut emulator = emulator_with_empty_input(&[1101, 1, 1, -7, 99]);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -7,
+                position: 3,
+                context: None,
+            }),
+            emulator.step()
+        );
+    }
+
+    #[test]
+    fn test_negative_jump_target() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1105, 1, 4, 99, 1106, 0, -5, 99]);
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -5,
+                position: 4,
+                context: None,
+            }),
+            emulator.step()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode() -> Result<(), EmulatorError> {
+        let decoded = decode(&[1002, 4, 3, 4, 33], 0)?;
+        assert_eq!(2, decoded.opcode());
+        assert_eq!("Multiply", decoded.name());
+        assert_eq!(3, decoded.parameter_count());
+        let modes: Vec<_> = decoded.parameters.iter().map(|p| p.mode).collect();
+        assert_eq!(
+            vec![
+                ParameterMode::Position,
+                ParameterMode::Immediate,
+                ParameterMode::Position
+            ],
+            modes
+        );
+        let raw: Vec<_> = decoded.parameters.iter().map(|p| p.word).collect();
+        assert_eq!(vec![4, 3, 4], raw);
+        assert_eq!(vec![33, 3, 4], decoded.resolved_parameters());
+
+        assert_eq!(
+            Some(EmulatorError::InvalidInstruction {
+                value_found: 33,
+                position: 4,
+                context: None,
+            }),
+            decode(&[1002, 4, 3, 4, 33], 4).err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_values() -> Result<(), EmulatorError> {
+        let program: [EmulatorMemoryType; 10] = [1002, 4, 3, 4, 33, 1101, 1, 1, 0, 99];
+        let (multiply, _) = OpCode::get_current_instruction(&program, 0)?;
+        let (add, _) = OpCode::get_current_instruction(&program, 5)?;
+        let (end, _) = OpCode::get_current_instruction(&program, 9)?;
+        assert_eq!(OpCode::Multiply, multiply);
+        assert_eq!(OpCode::Add, add);
+        assert_ne!(add, multiply);
+        assert_eq!("End", format!("{:?}", end));
+
+        let mut counts = HashMap::new();
+        for opcode in [add, multiply, add, end, add].iter() {
+            *counts.entry(*opcode).or_insert(0) += 1;
+        }
+        assert_eq!(Some(&3), counts.get(&OpCode::Add));
+        assert_eq!(Some(&1), counts.get(&OpCode::Multiply));
+        assert_eq!(None, counts.get(&OpCode::Output));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_sink() -> Result<(), EmulatorError> {
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let sink_outputs = outputs.clone();
+        // Outputs cell 7 three times.
+        let mut emulator = Emulator::with_inputs(&[4, 7, 4, 7, 4, 7, 99, 10], []);
+        emulator.set_output_sink(move |value| sink_outputs.lock().unwrap().push(value));
+
+        assert_eq!(EmulatorResult::SuccessWithValue(10), emulator.step()?);
+        emulator.memory_mut()[7] = 20;
+        assert_eq!(EmulatorResult::SuccessWithValue(20), emulator.step()?);
+        assert_eq!(vec![10, 20], *outputs.lock().unwrap());
+
+        emulator.clear_output_sink();
+        emulator.memory_mut()[7] = 30;
+        assert_eq!(vec![30], emulator.run_collecting_outputs()?);
+        assert_eq!(vec![10, 20], *outputs.lock().unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_observer() -> Result<(), EmulatorError> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let observed = events.clone();
+        // Adds 1 and 2 into cell 7, outputs it and halts.
+        let mut emulator = Emulator::with_inputs(&[1101, 1, 2, 7, 4, 7, 99, 0], []);
+        emulator.set_observer(move |event| {
+            observed.lock().unwrap().push((
+                event.instruction_pointer,
+                event.opcode,
+                event.parameters.to_vec(),
+                event.output,
+                event.next_instruction_pointer,
+            ))
+        });
+        assert_eq!(vec![3], emulator.run_collecting_outputs()?);
+        assert_eq!(
+            vec![
+                (0, OpCode::Add, vec![1, 2, 7], None, 4),
+                (4, OpCode::Output, vec![3], Some(3), 6),
+                (6, OpCode::End, vec![], None, 6),
+            ],
+            *events.lock().unwrap()
+        );
+
+        emulator.clear_observer();
+        emulator.step()?;
+        assert_eq!(3, events.lock().unwrap().len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dyn_emulator() -> Result<(), EmulatorError> {
+        let echo = [3, 0, 4, 0, 99];
+        let mut emulators: Vec<DynEmulator> = vec![
+            Emulator::boxed(&echo, once(Ok(1))),
+            Emulator::boxed(&echo, vec![Ok(2)].into_iter()),
+            Emulator::with_inputs(&echo, [3]).into_dyn(),
+        ];
+
+        // into_dyn keeps the machine where it was.
+        let mut started = Emulator::with_inputs(&[3, 0, 3, 1, 4, 1, 99], [4, 5]);
+        started.step()?;
+        let started = started.into_dyn();
+        assert_eq!(1, started.inputs_consumed());
+        assert_eq!(4, started.memory()[0]);
+        emulators.push(started);
+
+        let outputs = emulators
+            .iter_mut()
+            .map(|emulator| emulator.run_collecting_outputs())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(vec![vec![1], vec![2], vec![3], vec![5]], outputs);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_program() -> Result<(), EmulatorError> {
+        let run = run_program(&[3, 0, 4, 0, 1101, 2, 3, 9, 99], &[42])?;
+        assert_eq!(vec![42], run.outputs);
+        assert_eq!(vec![42, 0, 4, 0, 1101, 2, 3, 9, 99, 5], run.memory);
+        assert_eq!(4, run.steps);
+
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            run_program(&[3, 0, 99], &[])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_memory() -> Result<(), EmulatorError> {
+        // Unit tests have 32-bit addresses; tests/sparse_memory.rs goes
+        // further on 64-bit targets.
+        let far = 1 << 30;
+        // Writes 7 far away, then outputs it and the untouched cell after it.
+        let program = [1101, 7, 0, far, 4, far, 4, far + 1, 99];
+        let mut emulator = EmulatorBuilder::new()
+            .program(&program)
+            .sparse_memory(true)
+            .build()?;
+        assert_eq!(vec![7, 0], emulator.run_collecting_outputs()?);
+        assert_eq!(program.len() + 1, emulator.touched_cells());
+        assert_eq!(vec![(far as usize, 7)], emulator.sparse_cells());
+        assert_eq!(Some(7), emulator.get(far as usize));
+        assert_eq!(None, emulator.get(far as usize + 1));
+        assert_eq!(program.len(), emulator.memory().len());
+
+        emulator.reset();
+        assert_eq!(program.len(), emulator.touched_cells());
+
+        // Without it the write is past MEMORY_LIMIT.
+        let mut emulator = Emulator::with_inputs(&program, []);
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: far as usize,
+                limit: MEMORY_LIMIT,
+                context: None,
+            }),
+            emulator.run_collecting_outputs()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_beyond_usize() {
+        // Unit tests run with a 32-bit address limit.
+        let too_large = EmulatorMemoryType::from(u32::MAX) + 1;
+        let mut emulator = emulator_with_empty_input(&[1, too_large, 0, 0, 99]);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: too_large.widen(),
+                position: 1,
+                context: None,
+            }),
+            emulator.step()
+        );
+
+        let mut emulator = emulator_with_empty_input(&[1105, 1, too_large, 99]);
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: too_large.widen(),
+                position: 0,
+                context: None,
+            }),
+            emulator.step()
+        );
+    }
 }
@@ -0,0 +1,163 @@
+use super::{EmulatorError, EmulatorMemoryType, MemoryCell};
+use std::convert::TryFrom;
+
+// Unit tests pretend to run on a 32-bit target so the overflow path is
+// exercised without needing one.
+#[cfg(not(test))]
+type AddressWord = usize;
+#[cfg(test)]
+type AddressWord = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressPurpose {
+    Read,
+    Write,
+    Jump,
+}
+
+// Where an address came from, so every conversion failure points at the
+// same kind of location.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddrContext {
+    pub instruction_pointer: usize,
+    // None for jumps, where the target is whatever the handler chose.
+    pub parameter: Option<usize>,
+    pub purpose: AddressPurpose,
+}
+
+impl AddrContext {
+    pub fn parameter(
+        instruction_pointer: usize,
+        parameter: usize,
+        purpose: AddressPurpose,
+    ) -> AddrContext {
+        AddrContext {
+            instruction_pointer,
+            parameter: Some(parameter),
+            purpose,
+        }
+    }
+
+    pub fn jump(instruction_pointer: usize) -> AddrContext {
+        AddrContext {
+            instruction_pointer,
+            parameter: None,
+            purpose: AddressPurpose::Jump,
+        }
+    }
+
+    // The parameter cell holding the address, or the instruction itself.
+    pub fn position(&self) -> usize {
+        self.parameter
+            .map_or(self.instruction_pointer, |parameter| {
+                self.instruction_pointer + 1 + parameter
+            })
+    }
+
+    pub fn invalid<C: MemoryCell>(&self, value: C) -> EmulatorError {
+        EmulatorError::InvalidMemoryLocation {
+            value_found: value.widen(),
+            position: self.position(),
+            context: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(usize);
+
+impl Address {
+    pub fn try_from_word(
+        value: EmulatorMemoryType,
+        context: AddrContext,
+    ) -> Result<Address, EmulatorError> {
+        Address::try_from_cell(value, context)
+    }
+
+    // try_from_word, for cells of any MemoryCell.
+    #[allow(clippy::unnecessary_fallible_conversions)] // AddressWord is only usize outside tests
+    pub fn try_from_cell<C: MemoryCell>(
+        value: C,
+        context: AddrContext,
+    ) -> Result<Address, EmulatorError> {
+        let address = value
+            .try_into()
+            .ok()
+            .and_then(|address: usize| AddressWord::try_from(address).ok())
+            .ok_or_else(|| context.invalid(value))?;
+        #[allow(clippy::unnecessary_cast)] // AddressWord is only usize outside tests
+        Ok(Address(address as usize))
+    }
+
+    pub fn get(self) -> usize {
+        self.0
+    }
+}
+
+// The word a relative mode parameter stands for, before it is checked as an
+// address. A sum that overflows is reported against the parameter word.
+pub fn relative_address<C: MemoryCell>(
+    word: C,
+    relative_base: C,
+    context: AddrContext,
+) -> Result<C, EmulatorError> {
+    word.checked_add(relative_base)
+        .ok_or_else(|| context.invalid(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::WideValue;
+
+    #[test]
+    fn test_try_from_word() {
+        let context = AddrContext::parameter(10, 1, AddressPurpose::Read);
+        assert_eq!(Ok(Address(0)), Address::try_from_word(0, context));
+        assert_eq!(
+            Ok(Address(u32::MAX as usize)),
+            Address::try_from_word(EmulatorMemoryType::from(u32::MAX), context)
+        );
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -1,
+                position: 12,
+                context: None,
+            }),
+            Address::try_from_word(-1, context)
+        );
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: WideValue::from(u32::MAX) + 1,
+                position: 12,
+                context: None,
+            }),
+            Address::try_from_word(EmulatorMemoryType::from(u32::MAX) + 1, context)
+        );
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: -3,
+                position: 10,
+                context: None,
+            }),
+            Address::try_from_word(-3, AddrContext::jump(10))
+        );
+    }
+
+    #[test]
+    fn test_relative_address() {
+        let context = AddrContext::parameter(4, 0, AddressPurpose::Write);
+        assert_eq!(
+            Ok(7),
+            relative_address::<EmulatorMemoryType>(-3, 10, context)
+        );
+        assert_eq!(
+            Err(EmulatorError::InvalidMemoryLocation {
+                value_found: 1,
+                position: 5,
+                context: None,
+            }),
+            relative_address(1, EmulatorMemoryType::MAX, context)
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, IntoOutputs, QueueInput};
+use std::convert::TryFrom;
+
+// What an ASCII program prints: text a line at a time, or a value outside
+// the ASCII range, which is how days 17 and 21 report their answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiOutput {
+    // Without the newline.
+    Line(String),
+    NonAscii(EmulatorMemoryType),
+}
+
+// Groups an emulator's outputs into AsciiOutputs. Text before a non-ASCII
+// value is its own line even without a newline, as is any text left over
+// when the machine halts.
+pub struct AsciiOutputs<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> {
+    outputs: IntoOutputs<I>,
+    line: String,
+    pending: Option<AsciiOutput>,
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> AsciiOutputs<I> {
+    pub fn emulator(&self) -> &Emulator<I> {
+        self.outputs.emulator()
+    }
+
+    fn take_line(&mut self) -> AsciiOutput {
+        AsciiOutput::Line(std::mem::take(&mut self.line))
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Iterator for AsciiOutputs<I> {
+    type Item = Result<AsciiOutput, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(output) = self.pending.take() {
+            return Some(Ok(output));
+        }
+        loop {
+            let value = match self.outputs.next() {
+                Some(Ok(value)) => value,
+                Some(Err(error)) => return Some(Err(error)),
+                None if self.line.is_empty() => return None,
+                None => return Some(Ok(self.take_line())),
+            };
+            match u8::try_from(value) {
+                Ok(b'\n') => return Some(Ok(self.take_line())),
+                Ok(byte) if byte.is_ascii() => self.line.push(byte as char),
+                _ if self.line.is_empty() => return Some(Ok(AsciiOutput::NonAscii(value))),
+                _ => {
+                    self.pending = Some(AsciiOutput::NonAscii(value));
+                    return Some(Ok(self.take_line()));
+                }
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    pub fn ascii_output(self) -> AsciiOutputs<I> {
+        AsciiOutputs {
+            outputs: self.into_output_iter(),
+            line: String::new(),
+            pending: None,
+        }
+    }
+}
+
+impl Emulator<QueueInput> {
+    // Queues line as ASCII codes followed by a newline.
+    pub fn feed_ascii(&mut self, line: &str) {
+        self.extend(line.bytes().map(EmulatorMemoryType::from));
+        self.push_input(EmulatorMemoryType::from(b'\n'));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echoes input until it has echoed a newline.
+    const ECHO_LINE: [EmulatorMemoryType; 14] =
+        [3, 12, 4, 12, 1008, 12, 10, 13, 1006, 13, 0, 99, 0, 0];
+
+    #[test]
+    fn test_feed_ascii_round_trips() -> Result<(), EmulatorError> {
+        let mut emulator = Emulator::with_inputs(&ECHO_LINE, []);
+        emulator.feed_ascii("NOT A J, WALK!");
+        assert_eq!(15, emulator.pending_inputs());
+        let outputs = emulator.ascii_output().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![AsciiOutput::Line("NOT A J, WALK!".to_string())],
+            outputs
+        );
+
+        let mut emulator = Emulator::with_inputs(&ECHO_LINE, []);
+        emulator.feed_ascii("");
+        let outputs = emulator.ascii_output().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(vec![AsciiOutput::Line(String::new())], outputs);
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_ascii_values() -> Result<(), EmulatorError> {
+        // "Hi\n", 1000, "ok" then -1 without a newline, then a trailing "!".
+        let program = [
+            104, 72, 104, 105, 104, 10, 104, 1000, 104, 111, 104, 107, 104, -1, 104, 33, 99,
+        ];
+        let outputs = Emulator::with_inputs(&program, [])
+            .ascii_output()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![
+                AsciiOutput::Line("Hi".to_string()),
+                AsciiOutput::NonAscii(1000),
+                AsciiOutput::Line("ok".to_string()),
+                AsciiOutput::NonAscii(-1),
+                AsciiOutput::Line("!".to_string()),
+            ],
+            outputs
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_input_ends_the_output() {
+        let mut outputs = Emulator::with_inputs(&ECHO_LINE, []).ascii_output();
+        assert_eq!(
+            Some(Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            })),
+            outputs.next()
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use super::{EmulatorMemoryType, OpCodeInfo, ParameterKind, OPCODE_TABLE};
+use std::collections::HashMap;
+
+// Assembles Intcode from text, one instruction or directive per line:
+//
+//   ; Counts down from 3, printing each value.
+//           add #3 #0 @counter
+//   loop:   out @counter
+//           add @counter #-1 @counter
+//           jt @counter loop
+//           halt
+//   counter: data 0
+//
+// Operands are #value for immediate mode, @value or [value] for position
+// mode and [rb+value] for relative mode. A bare value is immediate, so
+// jump targets can be written as just the label. Values are integers or
+// labels, which stand for their address. Mnemonics are the short names
+// below or the full opcode names, in any case, so disassembler output
+// assembles too: its "address:" prefixes are checked against where the
+// line lands, and ';' starts a comment.
+const SHORT_MNEMONICS: [(&str, EmulatorMemoryType); 10] = [
+    ("add", 1),
+    ("mul", 2),
+    ("in", 3),
+    ("out", 4),
+    ("jt", 5),
+    ("jf", 6),
+    ("lt", 7),
+    ("eq", 8),
+    ("arb", 9),
+    ("halt", 99),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount {
+        mnemonic: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand(String),
+    ImmediateWrite(String),
+    UnknownLabel(String),
+    DuplicateLabel(String),
+    // A numeric "address:" prefix that doesn't match where the line lands.
+    AddressMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+// line and column are 1-based, and point at the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Line {} column {}: ", self.line, self.column)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => {
+                write!(f, "Unknown mnemonic '{}'", mnemonic)
+            }
+            AsmErrorKind::WrongOperandCount {
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "'{}' takes {} operands, but found {}",
+                mnemonic, expected, found
+            ),
+            AsmErrorKind::InvalidOperand(operand) => write!(f, "Invalid operand '{}'", operand),
+            AsmErrorKind::ImmediateWrite(operand) => write!(
+                f,
+                "Operand '{}' is written to, so it can't be immediate",
+                operand
+            ),
+            AsmErrorKind::UnknownLabel(label) => write!(f, "Unknown label '{}'", label),
+            AsmErrorKind::DuplicateLabel(label) => {
+                write!(f, "Label '{}' is already defined", label)
+            }
+            AsmErrorKind::AddressMismatch { expected, found } => write!(
+                f,
+                "Line is marked as address {}, but assembles to {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+// Splits on whitespace and commas, dropping any comment.
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let code = line.split(';').next().unwrap_or("");
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in code
+        .char_indices()
+        .chain(std::iter::once((code.len(), ' ')))
+    {
+        let separator = c.is_whitespace() || c == ',';
+        match (start, separator) {
+            (None, false) => start = Some(index),
+            (Some(begin), true) => {
+                tokens.push(Token {
+                    text: &code[begin..index],
+                    column: begin + 1,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(EmulatorMemoryType),
+    Label(String),
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    mode: EmulatorMemoryType,
+    value: Value,
+    column: usize,
+}
+
+enum Item {
+    Instruction {
+        info: &'static OpCodeInfo,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Operand>),
+}
+
+struct Line {
+    number: usize,
+    item: Item,
+}
+
+struct Assembler {
+    labels: HashMap<String, usize>,
+    lines: Vec<Line>,
+    address: usize,
+}
+
+fn error(line: usize, token: Token, kind: AsmErrorKind) -> AsmError {
+    AsmError {
+        line,
+        column: token.column,
+        kind,
+    }
+}
+
+fn parse_value(line: usize, token: Token, text: &str) -> Result<Value, AsmError> {
+    if is_label(text) {
+        return Ok(Value::Label(text.to_string()));
+    }
+    text.parse().map(Value::Number).map_err(|_| {
+        error(
+            line,
+            token,
+            AsmErrorKind::InvalidOperand(token.text.to_string()),
+        )
+    })
+}
+
+fn parse_operand(line: usize, token: Token) -> Result<Operand, AsmError> {
+    let text = token.text;
+    let (mode, value) = if let Some(value) = text.strip_prefix('#') {
+        (1, value)
+    } else if let Some(value) = text.strip_prefix('@') {
+        (0, value)
+    } else if let Some(inner) = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+    {
+        match inner.strip_prefix("rb") {
+            // The sign is part of the offset.
+            Some(offset) if offset.starts_with('+') || offset.starts_with('-') => {
+                (2, offset.strip_prefix('+').unwrap_or(offset))
+            }
+            _ => (0, inner),
+        }
+    } else {
+        (1, text)
+    };
+    Ok(Operand {
+        mode,
+        value: parse_value(line, token, value)?,
+        column: token.column,
+    })
+}
+
+fn find_opcode(mnemonic: &str) -> Option<&'static OpCodeInfo> {
+    let lower = mnemonic.to_ascii_lowercase();
+    let code = SHORT_MNEMONICS
+        .iter()
+        .find(|(short, _)| *short == lower)
+        .map(|&(_, code)| code);
+    OPCODE_TABLE.iter().find(|info| match code {
+        Some(code) => info.code == code,
+        None => info.mnemonic.eq_ignore_ascii_case(mnemonic),
+    })
+}
+
+impl Assembler {
+    // The first pass: places every line and records label addresses.
+    fn add_line(&mut self, number: usize, text: &str) -> Result<(), AsmError> {
+        let mut tokens = tokenize(text).into_iter().peekable();
+        while let Some(token) = tokens.peek().copied() {
+            let name = match token.text.strip_suffix(':') {
+                Some(name) => name,
+                None => break,
+            };
+            tokens.next();
+            if let Ok(expected) = name.parse::<usize>() {
+                if expected != self.address {
+                    return Err(error(
+                        number,
+                        token,
+                        AsmErrorKind::AddressMismatch {
+                            expected,
+                            found: self.address,
+                        },
+                    ));
+                }
+            } else if !is_label(name) {
+                return Err(error(
+                    number,
+                    token,
+                    AsmErrorKind::InvalidOperand(token.text.to_string()),
+                ));
+            } else if self.labels.insert(name.to_string(), self.address).is_some() {
+                return Err(error(
+                    number,
+                    token,
+                    AsmErrorKind::DuplicateLabel(name.to_string()),
+                ));
+            }
+        }
+
+        let mnemonic = match tokens.next() {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        let operands = tokens
+            .map(|token| parse_operand(number, token))
+            .collect::<Result<Vec<_>, _>>()?;
+        let item = if mnemonic.text.eq_ignore_ascii_case("data") {
+            self.address += operands.len();
+            Item::Data(operands)
+        } else {
+            let info = find_opcode(mnemonic.text).ok_or_else(|| {
+                error(
+                    number,
+                    mnemonic,
+                    AsmErrorKind::UnknownMnemonic(mnemonic.text.to_string()),
+                )
+            })?;
+            if operands.len() != info.parameters.len() {
+                return Err(error(
+                    number,
+                    mnemonic,
+                    AsmErrorKind::WrongOperandCount {
+                        mnemonic: mnemonic.text.to_string(),
+                        expected: info.parameters.len(),
+                        found: operands.len(),
+                    },
+                ));
+            }
+            self.address += operands.len() + 1;
+            Item::Instruction { info, operands }
+        };
+        self.lines.push(Line { number, item });
+        Ok(())
+    }
+
+    fn resolve(&self, line: usize, operand: &Operand) -> Result<EmulatorMemoryType, AsmError> {
+        match &operand.value {
+            Value::Number(value) => Ok(*value),
+            Value::Label(label) => self
+                .labels
+                .get(label)
+                .map(|&address| address as EmulatorMemoryType)
+                .ok_or_else(|| AsmError {
+                    line,
+                    column: operand.column,
+                    kind: AsmErrorKind::UnknownLabel(label.clone()),
+                }),
+        }
+    }
+
+    // The second pass, once every label is known.
+    fn emit(&self) -> Result<Vec<EmulatorMemoryType>, AsmError> {
+        let mut memory = Vec::with_capacity(self.address);
+        for line in self.lines.iter() {
+            match &line.item {
+                Item::Data(values) => {
+                    for value in values.iter() {
+                        memory.push(self.resolve(line.number, value)?);
+                    }
+                }
+                Item::Instruction { info, operands } => {
+                    let mut instruction = info.code;
+                    let mut place = 100;
+                    for (parameter, operand) in info.parameters.iter().zip(operands) {
+                        if parameter.kind == ParameterKind::Write && operand.mode == 1 {
+                            return Err(AsmError {
+                                line: line.number,
+                                column: operand.column,
+                                kind: AsmErrorKind::ImmediateWrite(parameter.name.to_string()),
+                            });
+                        }
+                        instruction += operand.mode * place;
+                        place *= 10;
+                    }
+                    memory.push(instruction);
+                    for operand in operands.iter() {
+                        memory.push(self.resolve(line.number, operand)?);
+                    }
+                }
+            }
+        }
+        Ok(memory)
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<EmulatorMemoryType>, AsmError> {
+    let mut assembler = Assembler {
+        labels: HashMap::new(),
+        lines: Vec::new(),
+        address: 0,
+    };
+    for (index, line) in source.lines().enumerate() {
+        assembler.add_line(index + 1, line)?;
+    }
+    assembler.emit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disassemble::disassemble;
+    use crate::intcode::{Emulator, EmulatorError};
+
+    fn run(
+        source: &str,
+        inputs: Vec<EmulatorMemoryType>,
+    ) -> Result<Vec<EmulatorMemoryType>, EmulatorError> {
+        Emulator::with_inputs(&assemble(source).unwrap(), inputs).run_collecting_outputs()
+    }
+
+    #[test]
+    fn test_countdown() -> Result<(), EmulatorError> {
+        let source = "
+            ; Counts down from 3, printing each value.
+                    add #3 #0 @counter
+            loop:   out @counter
+                    add @counter #-1 @counter
+                    jt @counter loop
+                    halt
+            counter: data 0
+        ";
+        assert_eq!(
+            vec![1101, 3, 0, 14, 4, 14, 1001, 14, -1, 14, 1005, 14, 4, 99, 0],
+            assemble(source).unwrap()
+        );
+        assert_eq!(vec![3, 2, 1], run(source, vec![])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_mnemonics_and_relative_mode() -> Result<(), EmulatorError> {
+        // Doubles its input through a cell addressed relative to the base.
+        let source = "
+            AdjustRelativeBase #table
+            Input [rb+1]
+            Multiply [rb+1], 2, [rb-0]
+            Output [rb+0]
+            End
+            table: data 0, 0
+        ";
+        assert_eq!(vec![42], run(source, vec![21])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassembly_reassembles() -> Result<(), EmulatorError> {
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99, 109, -3, 22201, -1, 2, 0,
+        ];
+        let listing: Vec<String> = disassemble(&program, None)
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect();
+        assert_eq!(program.to_vec(), assemble(&listing.join("\n")).unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_report_line_and_column() {
+        let error = |source: &str| assemble(source).unwrap_err();
+        assert_eq!(
+            AsmError {
+                line: 2,
+                column: 5,
+                kind: AsmErrorKind::UnknownMnemonic("sub".to_string()),
+            },
+            error("halt\n    sub #1 #2 @3")
+        );
+        assert_eq!(
+            "Line 1 column 7: Unknown label 'nowhere'",
+            error("jt #1 nowhere").to_string()
+        );
+        assert_eq!(
+            AsmErrorKind::WrongOperandCount {
+                mnemonic: "add".to_string(),
+                expected: 3,
+                found: 2,
+            },
+            error("add #1 #2").kind
+        );
+        let immediate_write = error("add #1 #2 #3");
+        assert_eq!(
+            (1, 11, AsmErrorKind::ImmediateWrite("dest".to_string())),
+            (
+                immediate_write.line,
+                immediate_write.column,
+                immediate_write.kind
+            )
+        );
+        assert_eq!(
+            AsmErrorKind::DuplicateLabel("a".to_string()),
+            error("a: halt\na: halt").kind
+        );
+        assert_eq!(
+            AsmErrorKind::AddressMismatch {
+                expected: 3,
+                found: 1,
+            },
+            error("0: halt\n3: halt").kind
+        );
+        assert_eq!(
+            AsmErrorKind::InvalidOperand("#x1!".to_string()),
+            error("out #x1!").kind
+        );
+    }
+}
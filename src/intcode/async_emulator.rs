@@ -0,0 +1,111 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, QueueInput};
+use futures::stream::{self, Stream, StreamExt};
+
+// An emulator fed from a Stream. Where the plain Emulator returns
+// NeedsInput, step here waits for the stream's next value instead. The
+// stream ending while the program wants input is InputNonExistent.
+pub struct AsyncEmulator<S> {
+    emulator: Emulator<QueueInput>,
+    inputs: S,
+}
+
+impl<S: Stream<Item = EmulatorMemoryType> + Unpin> AsyncEmulator<S> {
+    pub fn new(initial_memory: &[EmulatorMemoryType], inputs: S) -> AsyncEmulator<S> {
+        AsyncEmulator {
+            emulator: Emulator::new(initial_memory, QueueInput::new()),
+            inputs,
+        }
+    }
+
+    pub fn emulator(&self) -> &Emulator<QueueInput> {
+        &self.emulator
+    }
+
+    // Never returns NeedsInput.
+    pub async fn step(&mut self) -> Result<EmulatorResult, EmulatorError> {
+        loop {
+            match self.emulator.step()? {
+                EmulatorResult::NeedsInput => match self.inputs.next().await {
+                    Some(value) => self.emulator.push_input(value),
+                    None => return Err(self.emulator.missing_input()),
+                },
+                result => return Ok(result),
+            }
+        }
+    }
+
+    // Runs the machine as its outputs are polled. The stream ends when the
+    // machine halts, or just after the first error.
+    pub fn outputs(self) -> impl Stream<Item = Result<EmulatorMemoryType, EmulatorError>> {
+        stream::unfold(Some(self), |machine| async move {
+            let mut machine = machine?;
+            loop {
+                match machine.step().await {
+                    Ok(EmulatorResult::SuccessWithValue(value)) => {
+                        return Some((Ok(value), Some(machine)))
+                    }
+                    Ok(EmulatorResult::Done) => return None,
+                    Ok(_) => {}
+                    Err(error) => return Some((Err(error), None)),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use std::time::Duration;
+
+    // Echoes inputs until it reads 0.
+    const ECHO: [EmulatorMemoryType; 10] = [3, 9, 4, 9, 1005, 9, 0, 99, 0, 0];
+
+    #[tokio::test]
+    async fn test_delayed_inputs_arrive_in_order() {
+        let (sender, receiver) = mpsc::unbounded();
+        tokio::spawn(async move {
+            for value in [3, 1, 4, 1, 5, 0].iter() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                sender.unbounded_send(*value).unwrap();
+            }
+        });
+        let outputs = AsyncEmulator::new(&ECHO, receiver).outputs();
+        let outputs: Vec<_> = outputs.collect().await;
+        assert_eq!(vec![Ok(3), Ok(1), Ok(4), Ok(1), Ok(5), Ok(0)], outputs);
+    }
+
+    #[tokio::test]
+    async fn test_inputs_ending_early() {
+        let mut machine = AsyncEmulator::new(&ECHO, stream::iter(vec![7]));
+        assert_eq!(Ok(EmulatorResult::Success), machine.step().await);
+        assert_eq!(
+            Ok(EmulatorResult::SuccessWithValue(7)),
+            machine.step().await
+        );
+        assert_eq!(Ok(EmulatorResult::Success), machine.step().await);
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 0,
+                context: None,
+            }),
+            machine.step().await
+        );
+
+        let outputs: Vec<_> = AsyncEmulator::new(&ECHO, stream::iter(vec![2]))
+            .outputs()
+            .collect()
+            .await;
+        assert_eq!(
+            vec![
+                Ok(2),
+                Err(EmulatorError::InputNonExistent {
+                    position: 0,
+                    context: None,
+                })
+            ],
+            outputs
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult};
+
+// Why run_steps returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Done,
+    // All n instructions ran.
+    BudgetExhausted,
+    // The next instruction is an Input with no input available.
+    NeedsInput,
+    // The next instruction is at a breakpoint. It hasn't run.
+    Breakpoint {
+        address: usize,
+    },
+    // The last instruction that ran wrote to a watched address.
+    Watchpoint {
+        address: usize,
+        old: EmulatorMemoryType,
+        new: EmulatorMemoryType,
+    },
+    // The next instruction failed, leaving the machine as step does.
+    Error(EmulatorError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    // Instructions that ran.
+    pub steps: u64,
+    pub outputs: Vec<EmulatorMemoryType>,
+    pub stopped: StopReason,
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    // Steps up to n times, stopping early once the program halts, for
+    // input, at a breakpoint or after a watched address is written. A
+    // breakpoint at the instruction pointer when the batch starts doesn't
+    // count, so calling again carries on past it. An error stops the batch
+    // like the rest do, so the outputs from before it are kept.
+    pub fn run_steps(&mut self, n: u64) -> BatchResult {
+        let mut outputs = Vec::new();
+        let mut steps = 0;
+        let stopped = loop {
+            // Stepping a halted machine runs its halt again, which
+            // isn't worth counting.
+            if self.is_halted() {
+                break StopReason::Done;
+            }
+            if steps == n {
+                break StopReason::BudgetExhausted;
+            }
+            if steps > 0 && self.is_breakpoint(self.instruction_pointer()) {
+                break StopReason::Breakpoint {
+                    address: self.instruction_pointer(),
+                };
+            }
+            match self.step() {
+                Ok(EmulatorResult::Success) => {}
+                Ok(EmulatorResult::SuccessWithValue(value)) => outputs.push(value),
+                Ok(EmulatorResult::Done) => {}
+                Ok(EmulatorResult::NeedsInput) => break StopReason::NeedsInput,
+                Ok(EmulatorResult::Watchpoint { address, old, new }) => {
+                    steps += 1;
+                    break StopReason::Watchpoint { address, old, new };
+                }
+                Err(error) => break StopReason::Error(error),
+            }
+            steps += 1;
+        };
+        BatchResult {
+            steps,
+            outputs,
+            stopped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::empty;
+
+    // Adds 1 to memory[20] until it reaches 3, outputting it each time.
+    const COUNT_TO_3: [EmulatorMemoryType; 21] = [
+        1001, 20, 1, 20, 4, 20, 1007, 20, 3, 19, 1005, 19, 0, 99, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn test_halts_before_the_budget() {
+        // Counts memory[10] down from 2, outputting it: an Add, an Output
+        // and a jump back, twice, then the halt.
+        let program = [1001, 10, -1, 10, 4, 10, 1005, 10, 0, 99, 2];
+        let mut emulator = Emulator::new(&program, empty());
+        assert_eq!(
+            BatchResult {
+                steps: 7,
+                outputs: vec![1, 0],
+                stopped: StopReason::Done,
+            },
+            emulator.run_steps(100)
+        );
+        assert_eq!(0, emulator.run_steps(100).steps);
+    }
+
+    #[test]
+    fn test_budget_and_input() {
+        let mut emulator = Emulator::new(&COUNT_TO_3, empty());
+        assert_eq!(
+            BatchResult {
+                steps: 5,
+                outputs: vec![1],
+                stopped: StopReason::BudgetExhausted,
+            },
+            emulator.run_steps(5)
+        );
+        assert_eq!(4, emulator.instruction_pointer());
+        assert_eq!(0, emulator.run_steps(0).steps);
+        assert_eq!(
+            BatchResult {
+                steps: 8,
+                outputs: vec![2, 3],
+                stopped: StopReason::Done,
+            },
+            emulator.run_steps(100)
+        );
+
+        let mut emulator = Emulator::with_inputs(&[104, 1, 3, 7, 4, 7, 99, 0], vec![]);
+        assert_eq!(
+            BatchResult {
+                steps: 1,
+                outputs: vec![1],
+                stopped: StopReason::NeedsInput,
+            },
+            emulator.run_steps(10)
+        );
+        emulator.push_input(8);
+        assert_eq!(vec![8], emulator.run_steps(10).outputs);
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut emulator = Emulator::new(&COUNT_TO_3, empty());
+        emulator.set_breakpoint(4);
+        let result = emulator.run_steps(100);
+        assert_eq!(StopReason::Breakpoint { address: 4 }, result.stopped);
+        assert_eq!((1, vec![]), (result.steps, result.outputs));
+        // Starting on the breakpoint runs past it, to the next pass.
+        let result = emulator.run_steps(100);
+        assert_eq!(StopReason::Breakpoint { address: 4 }, result.stopped);
+        assert_eq!((4, vec![1]), (result.steps, result.outputs));
+
+        emulator.clear_breakpoint(4);
+        emulator.watch(19);
+        let result = emulator.run_steps(100);
+        assert_eq!(
+            StopReason::Watchpoint {
+                address: 19,
+                old: 1,
+                new: 1
+            },
+            result.stopped
+        );
+        assert_eq!((2, vec![2]), (result.steps, result.outputs));
+    }
+
+    #[test]
+    fn test_error_keeps_outputs() {
+        // Outputs 5, then runs into an unknown opcode.
+        let mut emulator = Emulator::new(&[104, 5, 42, 99], empty());
+        assert_eq!(
+            BatchResult {
+                steps: 1,
+                outputs: vec![5],
+                stopped: StopReason::Error(EmulatorError::InvalidInstruction {
+                    value_found: 42,
+                    position: 2,
+                    context: None,
+                }),
+            },
+            emulator.run_steps(10)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use super::MemoryCell;
+use std::convert::TryInto;
+use std::io;
+
+// Little endian helpers shared by the binary file formats. Counts and
+// positions are u64, cells are MemoryCell::BYTES wide, so a file only loads
+// back into cells of the type that wrote it.
+
+pub fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_cell<C: MemoryCell>(bytes: &mut Vec<u8>, value: C) {
+    value.put_le_bytes(bytes);
+}
+
+// A length followed by that many cells.
+pub fn put_cells<C: MemoryCell>(bytes: &mut Vec<u8>, cells: &[C]) {
+    put_u64(bytes, cells.len() as u64);
+    for &cell in cells {
+        put_cell(bytes, cell);
+    }
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// what names the file in errors, as in "core dump is truncated".
+pub struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < length {
+            return Err(invalid_data(&format!("{} is truncated", self.what)));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn usize(&mut self) -> io::Result<usize> {
+        self.u64()?
+            .try_into()
+            .map_err(|_| invalid_data(&format!("{} value does not fit in usize", self.what)))
+    }
+
+    pub fn cell<C: MemoryCell>(&mut self) -> io::Result<C> {
+        Ok(C::from_le_slice(self.take(C::BYTES)?))
+    }
+
+    pub fn cells<C: MemoryCell>(&mut self) -> io::Result<Vec<C>> {
+        let length = self.usize()?;
+        (0..length).map(|_| self.cell()).collect()
+    }
+
+    pub fn finish(&self) -> io::Result<()> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_data(&format!("trailing bytes after {}", self.what)))
+        }
+    }
+}
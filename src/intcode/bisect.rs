@@ -0,0 +1,188 @@
+use super::replay::Replay;
+use super::snapshot::EmulatorSnapshot;
+use super::{Emulator, EmulatorResult, QueueInput};
+use std::collections::BTreeMap;
+
+// A state partway through a replay, and how far into it that is.
+struct Checkpoint {
+    inputs_consumed: u64,
+    snapshot: EmulatorSnapshot,
+}
+
+// Replays from checkpoints rather than from the start, remembering every
+// state it stops at so later probes can start from the nearest one.
+struct Bisector<'a> {
+    replay: &'a Replay,
+    // Keyed by steps executed.
+    checkpoints: BTreeMap<u64, Checkpoint>,
+}
+
+impl<'a> Bisector<'a> {
+    fn resume(&self, steps: u64, checkpoint: &Checkpoint) -> Emulator<QueueInput> {
+        let pending = self.replay.inputs[checkpoint.inputs_consumed as usize..].to_vec();
+        let mut emulator = Emulator::restore(&checkpoint.snapshot, QueueInput::from(pending));
+        emulator.steps = steps;
+        emulator.inputs_consumed = checkpoint.inputs_consumed;
+        emulator
+    }
+
+    // The state after target steps, or after the last step the replay gets
+    // through if it stops before then. Returns the steps actually reached.
+    fn state_at(&mut self, target: u64) -> (u64, &EmulatorSnapshot) {
+        let (&steps, checkpoint) = self.checkpoints.range(..=target).next_back().unwrap();
+        let mut emulator = self.resume(steps, checkpoint);
+        while emulator.steps < target && !emulator.is_halted() {
+            match emulator.step() {
+                Ok(EmulatorResult::NeedsInput) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        let reached = emulator.steps;
+        let checkpoint = self.checkpoints.entry(reached).or_insert(Checkpoint {
+            inputs_consumed: emulator.inputs_consumed,
+            snapshot: emulator.snapshot(),
+        });
+        (reached, &checkpoint.snapshot)
+    }
+}
+
+// The number of steps after which the replay's state first fails is_good,
+// or None if every state it passes through is good. Step 0 is the program
+// as loaded. is_good should fail from some step on and keep failing, as
+// bisection only looks at a few states.
+//
+// States are checked after 1, 2, 4, 8, ... steps until one fails, then the
+// range between the last good and first bad state is bisected. Each probe
+// runs forward from the nearest state already reached, so the whole search
+// executes a small multiple of the steps up to the bad one.
+pub fn first_bad_step(replay: &Replay, is_good: impl Fn(&EmulatorSnapshot) -> bool) -> Option<u64> {
+    let mut checkpoints = BTreeMap::new();
+    checkpoints.insert(
+        0,
+        Checkpoint {
+            inputs_consumed: 0,
+            snapshot: Emulator::new(&replay.program, QueueInput::new()).snapshot(),
+        },
+    );
+    let mut bisector = Bisector {
+        replay,
+        checkpoints,
+    };
+
+    if !is_good(bisector.state_at(0).1) {
+        return Some(0);
+    }
+    let mut good = 0;
+    let mut target = 1;
+    let mut bad = loop {
+        let (reached, snapshot) = bisector.state_at(target);
+        if !is_good(snapshot) {
+            break reached;
+        }
+        if reached < target {
+            return None;
+        }
+        good = reached;
+        target *= 2;
+    };
+
+    while bad - good > 1 {
+        let middle = good + (bad - good) / 2;
+        let (_, snapshot) = bisector.state_at(middle);
+        if is_good(snapshot) {
+            good = middle;
+        } else {
+            bad = middle;
+        }
+    }
+    Some(bad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{EmulatorError, EmulatorMemoryType};
+    use std::cell::Cell;
+
+    // Counts cell 20 up to 150, three steps per count: after 3k - 2 steps
+    // the cell holds k.
+    const COUNTER: [EmulatorMemoryType; 12] = [1001, 20, 1, 20, 1007, 20, 150, 21, 1005, 21, 0, 99];
+
+    fn brute_force(replay: &Replay, is_good: impl Fn(&EmulatorSnapshot) -> bool) -> Option<u64> {
+        let mut emulator = Emulator::new(&replay.program, QueueInput::from(replay.inputs.clone()));
+        loop {
+            if !is_good(&emulator.snapshot()) {
+                return Some(emulator.steps);
+            }
+            if emulator.is_halted() {
+                return None;
+            }
+            emulator.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_finds_planted_divergence() -> Result<(), EmulatorError> {
+        let replay = Replay::record(&COUNTER, &[])?;
+        let counter = |snapshot: &EmulatorSnapshot| snapshot.memory.get(20).copied();
+
+        // The step that first brings the counter to 100.
+        let is_good = |snapshot: &EmulatorSnapshot| counter(snapshot).unwrap_or(0) < 100;
+        assert_eq!(Some(298), first_bad_step(&replay, is_good));
+        assert_eq!(Some(298), brute_force(&replay, is_good));
+
+        for limit in 1..=150 {
+            let is_good = |snapshot: &EmulatorSnapshot| counter(snapshot).unwrap_or(0) < limit;
+            assert_eq!(
+                brute_force(&replay, is_good),
+                first_bad_step(&replay, is_good)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_good_to_the_end_and_bad_from_the_start() -> Result<(), EmulatorError> {
+        let replay = Replay::record(&COUNTER, &[])?;
+        assert_eq!(None, first_bad_step(&replay, |_| true));
+        assert_eq!(Some(0), first_bad_step(&replay, |_| false));
+        assert_eq!(
+            Some(1),
+            first_bad_step(&replay, |snapshot| snapshot.instruction_pointer == 0)
+        );
+        // 150 counts of three steps, then the halt.
+        assert_eq!(
+            Some(451),
+            first_bad_step(&replay, |snapshot| !snapshot.halted)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_inputs_resume_from_checkpoints() -> Result<(), EmulatorError> {
+        // Adds inputs into a running total at 101 until it reads a 0.
+        let program = [3, 100, 1006, 100, 13, 1, 100, 101, 101, 1105, 1, 0, 0, 99];
+        let inputs: Vec<EmulatorMemoryType> = (1..=50).chain(std::iter::once(0)).collect();
+        let replay = Replay::record(&program, &inputs)?;
+        let total = |snapshot: &EmulatorSnapshot| snapshot.memory.get(101).copied().unwrap_or(0);
+        let is_good = |snapshot: &EmulatorSnapshot| total(snapshot) < 500;
+        let found = first_bad_step(&replay, is_good);
+        assert_eq!(brute_force(&replay, is_good), found);
+        assert!(found.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_count() -> Result<(), EmulatorError> {
+        let replay = Replay::record(&COUNTER, &[])?;
+        let probes = Cell::new(0);
+        let found = first_bad_step(&replay, |snapshot| {
+            probes.set(probes.get() + 1);
+            snapshot.memory.get(20).copied().unwrap_or(0) < 100
+        });
+        assert_eq!(Some(298), found);
+        // 10 doublings to pass 298, then about log2(256) bisection probes.
+        assert!(probes.get() <= 20, "{} probes", probes.get());
+        Ok(())
+    }
+}
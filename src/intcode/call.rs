@@ -0,0 +1,96 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, QueueInput};
+
+// What a program left behind after intcode::call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallResult {
+    pub outputs: Vec<EmulatorMemoryType>,
+    // memory[0], where programs like day 2's leave their answer.
+    pub result: EmulatorMemoryType,
+    memory: Vec<EmulatorMemoryType>,
+}
+
+impl CallResult {
+    pub fn memory(&self) -> &[EmulatorMemoryType] {
+        &self.memory
+    }
+
+    pub fn into_memory(self) -> Vec<EmulatorMemoryType> {
+        self.memory
+    }
+}
+
+// Runs program to completion with each (address, value) in args written
+// into memory first, like day 2's noun and verb. Every address is checked
+// before anything runs.
+pub fn call(
+    program: &[EmulatorMemoryType],
+    args: &[(usize, EmulatorMemoryType)],
+    inputs: &[EmulatorMemoryType],
+) -> Result<CallResult, EmulatorError> {
+    let mut memory = program.to_vec();
+    for &(address, value) in args {
+        let cell = memory
+            .get_mut(address)
+            .ok_or(EmulatorError::ArgumentOutOfRange {
+                address,
+                memory_size: program.len(),
+                context: None,
+            })?;
+        *cell = value;
+    }
+
+    let mut run = Emulator::new(&memory, QueueInput::from(inputs.to_vec())).into_output_iter();
+    let outputs = run.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let memory = run.emulator().memory().to_vec();
+    Ok(CallResult {
+        outputs,
+        result: memory[0],
+        memory,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_2_EXAMPLE: [EmulatorMemoryType; 12] = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+
+    #[test]
+    fn test_call_with_args() -> Result<(), EmulatorError> {
+        let called = call(&DAY_2_EXAMPLE, &[], &[])?;
+        assert_eq!(3500, called.result);
+        assert_eq!(&[3500, 9, 10, 70], &called.memory()[..4]);
+
+        // memory[3] = 30 + 40, then memory[0] = memory[3] * 50.
+        assert_eq!(3500, call(&DAY_2_EXAMPLE, &[(1, 9), (2, 10)], &[])?.result);
+        // 40 + 50 = 90, then 90 * 50.
+        assert_eq!(4500, call(&DAY_2_EXAMPLE, &[(1, 10), (2, 11)], &[])?.result);
+        // 30 + 30 = 60, then 60 * 40.
+        assert_eq!(
+            2400,
+            call(&DAY_2_EXAMPLE, &[(1, 9), (2, 9), (11, 40)], &[])?.result
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_with_inputs() -> Result<(), EmulatorError> {
+        let called = call(&[3, 0, 4, 0, 104, 7, 99], &[(5, 8)], &[42])?;
+        assert_eq!(vec![42, 8], called.outputs);
+        assert_eq!(42, called.result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_range_arg_is_rejected_before_running() {
+        // Would output if it ran.
+        assert_eq!(
+            Err(EmulatorError::ArgumentOutOfRange {
+                address: 5,
+                memory_size: 3,
+                context: None,
+            }),
+            call(&[104, 1, 99], &[(5, 0)], &[])
+        );
+    }
+}
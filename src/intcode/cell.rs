@@ -0,0 +1,105 @@
+use super::EmulatorMemoryType;
+use std::convert::TryInto;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, Div, Mul, Rem};
+
+// Errors report cell values at this width, which holds every MemoryCell, so
+// a wide machine's errors show the value it actually found.
+pub type WideValue = i128;
+
+// What an Emulator's memory can be made of. EmulatorMemoryType is the
+// default everywhere; an Emulator<I, i128> runs programs whose values
+// outgrow i64 without building everything with mem-i128.
+pub trait MemoryCell:
+    'static
+    + Copy
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + From<i8>
+    + TryInto<usize>
+    + TryInto<EmulatorMemoryType>
+{
+    // Bytes per cell in the binary file formats.
+    const BYTES: usize;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn saturating_add(self, other: Self) -> Self;
+    fn widen(self) -> WideValue;
+    fn put_le_bytes(self, bytes: &mut Vec<u8>);
+    // bytes is exactly BYTES long.
+    fn from_le_slice(bytes: &[u8]) -> Self;
+}
+
+impl MemoryCell for i64 {
+    const BYTES: usize = 8;
+
+    fn checked_add(self, other: i64) -> Option<i64> {
+        i64::checked_add(self, other)
+    }
+
+    fn saturating_add(self, other: i64) -> i64 {
+        i64::saturating_add(self, other)
+    }
+
+    fn widen(self) -> WideValue {
+        WideValue::from(self)
+    }
+
+    fn put_le_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn from_le_slice(bytes: &[u8]) -> i64 {
+        i64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl MemoryCell for i128 {
+    const BYTES: usize = 16;
+
+    fn checked_add(self, other: i128) -> Option<i128> {
+        i128::checked_add(self, other)
+    }
+
+    fn saturating_add(self, other: i128) -> i128 {
+        i128::saturating_add(self, other)
+    }
+
+    fn widen(self) -> WideValue {
+        self
+    }
+
+    fn put_le_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn from_le_slice(bytes: &[u8]) -> i128 {
+        i128::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_le_bytes_round_trip() {
+        for value in [0, -1, i128::MIN, i128::MAX] {
+            let mut bytes = Vec::new();
+            value.put_le_bytes(&mut bytes);
+            assert_eq!(i128::BYTES, bytes.len());
+            assert_eq!(value, i128::from_le_slice(&bytes));
+        }
+        let mut bytes = Vec::new();
+        (-5i64).put_le_bytes(&mut bytes);
+        assert_eq!(i64::BYTES, bytes.len());
+        assert_eq!(-5, i64::from_le_slice(&bytes));
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+// What an Input instruction does when the receiver has nothing queued.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhenEmpty {
+    // Reads this value instead, as day 23's network does with -1.
+    Default(EmulatorMemoryType),
+    // step returns NeedsInput, and the instruction is retried next step.
+    NeedsInput,
+    // Waits for a value. Once every sender is gone, step returns
+    // NeedsInput instead.
+    Block,
+}
+
+pub struct ChannelInput {
+    receiver: Receiver<EmulatorMemoryType>,
+    when_empty: WhenEmpty,
+}
+
+impl Iterator for ChannelInput {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.when_empty == WhenEmpty::Block {
+            return self.receiver.recv().ok().map(Ok);
+        }
+        match (self.receiver.try_recv(), self.when_empty) {
+            (Ok(value), _) => Some(Ok(value)),
+            (Err(TryRecvError::Empty), WhenEmpty::Default(value)) => Some(Ok(value)),
+            _ => None,
+        }
+    }
+}
+
+impl Emulator<ChannelInput> {
+    // Input instructions read from receiver and every output is sent on
+    // sender. Outputs are still returned from step as well; sending fails
+    // silently once the receiving end is gone.
+    pub fn with_channels(
+        initial_memory: &[EmulatorMemoryType],
+        receiver: Receiver<EmulatorMemoryType>,
+        sender: Sender<EmulatorMemoryType>,
+        when_empty: WhenEmpty,
+    ) -> Emulator<ChannelInput> {
+        let mut emulator = Emulator::new(
+            initial_memory,
+            ChannelInput {
+                receiver,
+                when_empty,
+            },
+        );
+        emulator.set_output_sink(move |value| {
+            let _ = sender.send(value);
+        });
+        emulator
+    }
+
+    // Runs the machine on its own thread until it halts. Wanting input it
+    // can't get is an error there, so threaded machines should use Block or
+    // Default. The sender is dropped when the thread finishes, so whoever
+    // reads the outputs sees them end.
+    pub fn spawn(mut self) -> JoinHandle<Result<Emulator<ChannelInput>, EmulatorError>> {
+        thread::spawn(move || loop {
+            match self.step()? {
+                EmulatorResult::Done => {
+                    self.clear_output_sink();
+                    return Ok(self);
+                }
+                EmulatorResult::NeedsInput => return Err(self.missing_input()),
+                _ => {}
+            }
+        })
+    }
+}
+
+// A machine running on its own thread, fed and read through channels.
+pub struct EmulatorHandle {
+    inputs: Sender<EmulatorMemoryType>,
+    outputs: Receiver<EmulatorMemoryType>,
+    thread: JoinHandle<Result<Emulator<ChannelInput>, EmulatorError>>,
+}
+
+// Starts program on a new thread with the given inputs queued. It blocks
+// when it wants more input than has been sent.
+pub fn spawn<T: IntoIterator<Item = EmulatorMemoryType>>(
+    program: &[EmulatorMemoryType],
+    inputs: T,
+) -> EmulatorHandle {
+    let (input_sender, input_receiver) = channel();
+    let (output_sender, output_receiver) = channel();
+    for value in inputs {
+        let _ = input_sender.send(value);
+    }
+    let emulator =
+        Emulator::with_channels(program, input_receiver, output_sender, WhenEmpty::Block);
+    EmulatorHandle {
+        inputs: input_sender,
+        outputs: output_receiver,
+        thread: emulator.spawn(),
+    }
+}
+
+impl EmulatorHandle {
+    // Ignored once the machine has finished.
+    pub fn send(&self, value: EmulatorMemoryType) {
+        let _ = self.inputs.send(value);
+    }
+
+    // Outputs in order, waiting for each; ends once the machine stops.
+    pub fn outputs(&self) -> impl Iterator<Item = EmulatorMemoryType> + '_ {
+        self.outputs.iter()
+    }
+
+    // Stops sending input and waits for the machine to finish, returning
+    // the outputs nobody read through outputs(). A machine still waiting
+    // for input fails with InputNonExistent.
+    pub fn join(self) -> Result<Vec<EmulatorMemoryType>, EmulatorError> {
+        drop(self.inputs);
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        Ok(self.outputs.try_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads x and outputs x + 1, halting after an output of 10 or more.
+    const PING: [EmulatorMemoryType; 22] = [
+        3, 20, 1001, 20, 1, 20, 4, 20, 1007, 20, 10, 21, 1005, 21, 0, 99, 0, 0, 0, 0, 0, 0,
+    ];
+
+    fn ping_pong(
+        when_empty: WhenEmpty,
+    ) -> (
+        Emulator<ChannelInput>,
+        Emulator<ChannelInput>,
+        Sender<EmulatorMemoryType>,
+    ) {
+        let (to_a, a_receiver) = channel();
+        let (to_b, b_receiver) = channel();
+        let a = Emulator::with_channels(&PING, a_receiver, to_b, when_empty);
+        let b = Emulator::with_channels(&PING, b_receiver, to_a.clone(), when_empty);
+        (a, b, to_a)
+    }
+
+    #[test]
+    fn test_ping_pong() -> Result<(), EmulatorError> {
+        let (mut a, mut b, to_a) = ping_pong(WhenEmpty::NeedsInput);
+        to_a.send(0).unwrap();
+        let mut packets = Vec::new();
+        while !(a.is_halted() && b.is_halted()) {
+            for machine in [&mut a, &mut b].iter_mut() {
+                loop {
+                    match machine.step()? {
+                        EmulatorResult::SuccessWithValue(value) => packets.push(value),
+                        EmulatorResult::NeedsInput | EmulatorResult::Done => break,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        assert_eq!((1..=11).collect::<Vec<_>>(), packets);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ping_pong_on_threads() -> Result<(), EmulatorError> {
+        let (a, b, to_a) = ping_pong(WhenEmpty::Block);
+        let (a, b) = (a.spawn(), b.spawn());
+        to_a.send(0).unwrap();
+        let (a, b) = (a.join().unwrap()?, b.join().unwrap()?);
+        assert_eq!(11, a.memory()[20]);
+        assert_eq!(10, b.memory()[20]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_handles_in_a_ring() -> Result<(), EmulatorError> {
+        let handles = [spawn(&PING, [0]), spawn(&PING, []), spawn(&PING, [])];
+        let mut packets = Vec::new();
+        for index in (0..3).cycle() {
+            match handles[index].outputs().next() {
+                Some(value) => {
+                    packets.push(value);
+                    handles[(index + 1) % 3].send(value);
+                }
+                None => break,
+            }
+        }
+        assert_eq!((1..=12).collect::<Vec<_>>(), packets);
+        for handle in handles {
+            assert_eq!(Vec::<EmulatorMemoryType>::new(), handle.join()?);
+        }
+
+        let handle = spawn(&PING, [20]);
+        assert_eq!(vec![21], handle.join()?);
+        let handle = spawn(&[3, 0, 3, 0, 99], [1]);
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 2,
+                context: None,
+            }),
+            handle.join()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_when_empty() -> Result<(), EmulatorError> {
+        let (_sender, receiver) = channel();
+        let (output, outputs) = channel();
+        let echo = [3, 0, 4, 0, 99];
+        let mut emulator = Emulator::with_channels(&echo, receiver, output, WhenEmpty::Default(-1));
+        assert_eq!(vec![-1], emulator.run_collecting_outputs()?);
+        assert_eq!(Ok(-1), outputs.try_recv());
+
+        // Blocking gives up once nothing can send any more.
+        let (_, receiver) = channel();
+        let (output, _) = channel();
+        let mut emulator = Emulator::with_channels(&echo, receiver, output, WhenEmpty::Block);
+        assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use super::binary::{invalid_data, put_cell, put_cells, put_u64, Reader};
+use super::history::{InstructionHistory, InstructionRecord};
+use super::{Dispatch, Emulator, EmulatorError, EmulatorMemoryType, MemoryCell};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"ICCORE02";
+
+pub const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+
+// Everything is little endian, as written by the binary module:
+//
+//   magic "ICCORE02"
+//   bytes per cell
+//   instruction pointer, steps executed, inputs consumed
+//   error message length, error message (utf-8)
+//   history length, then per record: step, instruction pointer,
+//     instruction, parameter count, parameters
+//   memory length, memory
+//
+// Cells are written at their own width, so a dump only loads back into a
+// CoreDump of the cell type that wrote it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreDump<C = EmulatorMemoryType> {
+    pub instruction_pointer: usize,
+    pub steps: u64,
+    pub inputs_consumed: u64,
+    pub error: String,
+    pub history: Vec<InstructionRecord<C>>,
+    pub memory: Vec<C>,
+}
+
+// What came of dumping core for an error, kept in its ErrorContext.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoreDumpStatus {
+    Written(PathBuf),
+    // The io::Error's message, as the error itself can't be cloned or
+    // compared.
+    Failed { path: PathBuf, error: String },
+}
+
+// The template's {timestamp} is replaced by the time of the failure; a
+// template without one gets the timestamp appended.
+pub(super) fn path_from_template(template: &str) -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let timestamp = format!("{}-{:09}", now.as_secs(), now.subsec_nanos());
+    if template.contains(TIMESTAMP_PLACEHOLDER) {
+        PathBuf::from(template.replace(TIMESTAMP_PLACEHOLDER, &timestamp))
+    } else {
+        PathBuf::from(format!("{}.{}", template, timestamp))
+    }
+}
+
+impl<C: MemoryCell> CoreDump<C> {
+    pub(super) fn capture<I: Iterator<Item = Result<C, EmulatorError>>>(
+        emulator: &Emulator<I, C>,
+        error: &EmulatorError,
+    ) -> CoreDump<C> {
+        CoreDump {
+            instruction_pointer: emulator.instruction_pointer,
+            steps: emulator.steps,
+            inputs_consumed: emulator.inputs_consumed,
+            error: error.to_string(),
+            history: emulator.history.records().cloned().collect(),
+            memory: emulator.memory.to_vec(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        put_u64(&mut bytes, C::BYTES as u64);
+        put_u64(&mut bytes, self.instruction_pointer as u64);
+        put_u64(&mut bytes, self.steps);
+        put_u64(&mut bytes, self.inputs_consumed);
+        put_u64(&mut bytes, self.error.len() as u64);
+        bytes.extend_from_slice(self.error.as_bytes());
+
+        put_u64(&mut bytes, self.history.len() as u64);
+        for record in self.history.iter() {
+            put_u64(&mut bytes, record.step);
+            put_u64(&mut bytes, record.instruction_pointer as u64);
+            put_cell(&mut bytes, record.instruction);
+            put_cells(&mut bytes, &record.parameters);
+        }
+        put_cells(&mut bytes, &self.memory);
+
+        fs::write(path, bytes)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<CoreDump<C>> {
+        let bytes = fs::read(path)?;
+        let mut reader = Reader {
+            bytes: &bytes,
+            what: "core dump",
+        };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a core dump"));
+        }
+        let cell_bytes = reader.usize()?;
+        if cell_bytes != C::BYTES {
+            return Err(invalid_data(&format!(
+                "core dump has {}-byte cells, expected {}",
+                cell_bytes,
+                C::BYTES
+            )));
+        }
+
+        let instruction_pointer = reader.usize()?;
+        let steps = reader.u64()?;
+        let inputs_consumed = reader.u64()?;
+        let error_length = reader.usize()?;
+        let error = String::from_utf8(reader.take(error_length)?.to_vec())
+            .map_err(|_| invalid_data("error message is not utf-8"))?;
+
+        let history_length = reader.usize()?;
+        let mut history = Vec::new();
+        for _ in 0..history_length {
+            let step = reader.u64()?;
+            let instruction_pointer = reader.usize()?;
+            let instruction = reader.cell()?;
+            let parameters = reader.cells()?;
+            history.push(InstructionRecord {
+                step,
+                instruction_pointer,
+                instruction,
+                parameters,
+            });
+        }
+
+        let memory = reader.cells()?;
+        reader.finish()?;
+
+        Ok(CoreDump {
+            instruction_pointer,
+            steps,
+            inputs_consumed,
+            error,
+            history,
+            memory,
+        })
+    }
+
+    // The original input source can't be saved, so the caller supplies a new
+    // one. The emulator sits on the instruction that failed.
+    pub fn into_emulator<I: Iterator<Item = Result<C, EmulatorError>>>(
+        self,
+        input_iter: I,
+    ) -> Emulator<I, C> {
+        let mut emulator = Emulator::with_cells(&self.memory, input_iter, Dispatch::default());
+        emulator.instruction_pointer = self.instruction_pointer;
+        emulator.steps = self.steps;
+        emulator.inputs_consumed = self.inputs_consumed;
+        emulator.history = InstructionHistory::new(emulator.history.capacity());
+        for record in self.history {
+            emulator.history.push(record);
+        }
+        emulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::emulator_with_empty_input;
+    use std::iter::once;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "advent_of_code_2019_core_dump_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_core_dump_on_error() -> io::Result<()> {
+        let dir = scratch_dir("on_error");
+        // Stores the input at 13, then jumps to an Add reading address -100.
+        let initial_address = [3, 13, 1105, 1, 7, 99, 99, 1, -100, 13, 13, 99, 99, 0];
+        let mut emulator = Emulator::new(&initial_address, once(Ok(42)));
+        emulator.dump_core_on_error(dir.join("core-{timestamp}.bin").to_str().unwrap());
+
+        let error = emulator.run_to_completion().unwrap_err();
+        assert_eq!(
+            EmulatorError::InvalidMemoryLocation {
+                value_found: -100,
+                position: 8,
+                context: None,
+            },
+            error
+        );
+
+        let path = emulator.last_core_dump().unwrap().to_path_buf();
+        assert!(path.starts_with(&dir));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("core-"));
+
+        let core_dump = CoreDump::load(&path)?;
+        assert_eq!(7, core_dump.instruction_pointer);
+        assert_eq!(2, core_dump.steps);
+        assert_eq!(1, core_dump.inputs_consumed);
+        // The dump was written before its path was added to the error.
+        assert_eq!(
+            Some(&CoreDumpStatus::Written(path.clone())),
+            error.context().unwrap().core_dump.as_ref()
+        );
+        assert_eq!(
+            format!("{}\nCore dumped to {}", core_dump.error, path.display()),
+            error.to_string()
+        );
+        assert_eq!(
+            vec![(0, 3, vec![13]), (2, 1105, vec![1, 7])],
+            core_dump
+                .history
+                .iter()
+                .map(|record| (
+                    record.instruction_pointer,
+                    record.instruction,
+                    record.parameters.clone()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(emulator.memory(), core_dump.memory.as_slice());
+        assert_eq!(42, core_dump.memory[13]);
+
+        let mut restored = core_dump.into_emulator(std::iter::empty());
+        assert_eq!(emulator.memory(), restored.memory());
+        assert_eq!(2, restored.recent_history().len());
+        assert_eq!(Err(error), restored.step());
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn test_no_core_dump_unless_enabled() {
+        let mut emulator = emulator_with_empty_input(&[1, -100, 0, 0, 99]);
+        let error = emulator.run_to_completion().unwrap_err();
+        assert_eq!(None, emulator.last_core_dump());
+        assert_eq!(None, error.context().unwrap().core_dump);
+    }
+
+    #[test]
+    fn test_failed_core_dump_is_reported_in_the_error() {
+        let dir = scratch_dir("failed");
+        // The directory doesn't exist, so the write fails.
+        let missing = dir.join("missing");
+        let mut emulator = emulator_with_empty_input(&[1, -100, 0, 0, 99]);
+        emulator.dump_core_on_error(missing.join("core.bin").to_str().unwrap());
+
+        let error = emulator.run_to_completion().unwrap_err();
+        assert_eq!(None, emulator.last_core_dump());
+        let path = match &error.context().unwrap().core_dump {
+            Some(CoreDumpStatus::Failed { path, .. }) => path.clone(),
+            status => panic!("expected a failed core dump, got {:?}", status),
+        };
+        assert_eq!(Some(missing.as_path()), path.parent());
+        assert!(error.to_string().contains(&format!(
+            "Could not write a core dump to {}: ",
+            path.display()
+        )));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_template_without_placeholder_gets_timestamp_appended() {
+        let path = path_from_template("core.bin");
+        let name = path.to_str().unwrap();
+        assert!(name.starts_with("core.bin."));
+        assert!(name.len() > "core.bin.".len());
+    }
+
+    #[test]
+    fn test_truncated_core_dump_is_rejected() -> io::Result<()> {
+        let dir = scratch_dir("truncated");
+        let path = dir.join("core.bin");
+        let mut emulator = emulator_with_empty_input(&[1, -100, 0, 0, 99]);
+        emulator.dump_core_on_error(path.to_str().unwrap());
+        assert!(emulator.step().is_err());
+
+        let dumped = emulator.last_core_dump().unwrap().to_path_buf();
+        assert!(CoreDump::<EmulatorMemoryType>::load(&dumped).is_ok());
+        let bytes = fs::read(&dumped)?;
+        fs::write(&dumped, &bytes[..bytes.len() - 1])?;
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            CoreDump::<EmulatorMemoryType>::load(&dumped)
+                .unwrap_err()
+                .kind()
+        );
+        fs::write(&dumped, b"not a core dump")?;
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            CoreDump::<EmulatorMemoryType>::load(&dumped)
+                .unwrap_err()
+                .kind()
+        );
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn test_wide_cells_are_dumped_at_their_own_width() -> io::Result<()> {
+        let dir = scratch_dir("wide");
+        // Outputs a value past i64, then reads from it as an address.
+        let wide = i128::from(i64::MAX) * 4;
+        let program = [104, wide, 1, wide, 0, 0, 99];
+        let mut emulator = Emulator::with_cells(&program, std::iter::empty(), Dispatch::Match);
+        emulator.dump_core_on_error(dir.join("core.bin").to_str().unwrap());
+        assert_eq!(Ok(Some(wide)), emulator.run_until_output());
+        let error = emulator.step().unwrap_err();
+        assert_eq!(
+            EmulatorError::InvalidMemoryLocation {
+                value_found: wide,
+                position: 3,
+                context: None,
+            },
+            error
+        );
+
+        let path = emulator.last_core_dump().unwrap();
+        let core_dump: CoreDump<i128> = CoreDump::load(path)?;
+        assert_eq!(program.to_vec(), core_dump.memory);
+        assert_eq!(vec![wide], core_dump.history[0].parameters);
+        assert_eq!(
+            format!("{}\nCore dumped to {}", core_dump.error, path.display()),
+            error.to_string()
+        );
+        if EmulatorMemoryType::BITS < i128::BITS {
+            assert_eq!(
+                io::ErrorKind::InvalidData,
+                CoreDump::<EmulatorMemoryType>::load(path)
+                    .unwrap_err()
+                    .kind()
+            );
+        }
+
+        fs::remove_dir_all(&dir)
+    }
+}
@@ -0,0 +1,242 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, OpCode, QueueInput};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuEvent {
+    Output(EmulatorMemoryType),
+    // Waiting for push_input; running again retries the Input instruction
+    // at position.
+    NeedsInput {
+        position: usize,
+    },
+    Halted,
+    // The instruction just run wrote to an address the emulator watches.
+    // Running again carries on after it.
+    Watchpoint {
+        address: usize,
+        old: EmulatorMemoryType,
+        new: EmulatorMemoryType,
+    },
+}
+
+// The surface day solutions need from an Intcode implementation, so the
+// same solution can be run on each of them and compared.
+pub trait Cpu {
+    fn push_input(&mut self, value: EmulatorMemoryType);
+    fn run_until_event(&mut self) -> Result<CpuEvent, EmulatorError>;
+    fn read(&self, address: usize) -> Option<EmulatorMemoryType>;
+    // Writing past the end of memory grows it with zeros.
+    fn write(&mut self, address: usize, value: EmulatorMemoryType);
+}
+
+fn write_growing(memory: &mut Vec<EmulatorMemoryType>, address: usize, value: EmulatorMemoryType) {
+    if address >= memory.len() {
+        memory.resize(address + 1, 0);
+    }
+    memory[address] = value;
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    // Runs to the next output, request for input, halt or write to a
+    // watched address, skipping the steps in between. The way to drive an
+    // interactive program:
+    //
+    //   let mut emulator = Emulator::with_queue(program);
+    //   loop {
+    //       match emulator.resume()? {
+    //           CpuEvent::Output(value) => ...,
+    //           CpuEvent::NeedsInput { .. } => emulator.push_input(...),
+    //           CpuEvent::Halted => break,
+    //           CpuEvent::Watchpoint { .. } => {}
+    //       }
+    //   }
+    pub fn resume(&mut self) -> Result<CpuEvent, EmulatorError> {
+        loop {
+            match self.step()? {
+                EmulatorResult::Success => {}
+                EmulatorResult::SuccessWithValue(value) => return Ok(CpuEvent::Output(value)),
+                EmulatorResult::Watchpoint { address, old, new } => {
+                    return Ok(CpuEvent::Watchpoint { address, old, new })
+                }
+                EmulatorResult::Done => return Ok(CpuEvent::Halted),
+                EmulatorResult::NeedsInput => {
+                    return Ok(CpuEvent::NeedsInput {
+                        position: self.instruction_pointer,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Cpu for Emulator<QueueInput> {
+    fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.input_iter.push(value);
+    }
+
+    fn run_until_event(&mut self) -> Result<CpuEvent, EmulatorError> {
+        self.resume()
+    }
+
+    fn read(&self, address: usize) -> Option<EmulatorMemoryType> {
+        self.memory.get_cell(address)
+    }
+
+    fn write(&mut self, address: usize, value: EmulatorMemoryType) {
+        self.memory.write(address, value);
+    }
+}
+
+// A bare Cpu built on OpCode::eval_one, without any of the Emulator's
+// bookkeeping. Mostly useful as a second implementation to check the
+// Emulator against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalCpu {
+    memory: Vec<EmulatorMemoryType>,
+    instruction_pointer: usize,
+    relative_base: EmulatorMemoryType,
+    inputs: Vec<EmulatorMemoryType>,
+    halted: bool,
+}
+
+impl EvalCpu {
+    pub fn new(program: &[EmulatorMemoryType]) -> EvalCpu {
+        EvalCpu {
+            memory: program.to_vec(),
+            instruction_pointer: 0,
+            relative_base: 0,
+            inputs: Vec::new(),
+            halted: false,
+        }
+    }
+}
+
+impl Cpu for EvalCpu {
+    fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.inputs.push(value);
+    }
+
+    fn run_until_event(&mut self) -> Result<CpuEvent, EmulatorError> {
+        while !self.halted {
+            let outcome = match OpCode::eval_one(
+                &mut self.memory,
+                self.instruction_pointer,
+                &mut self.relative_base,
+                &mut self.inputs,
+            ) {
+                Ok(outcome) => outcome,
+                Err(EmulatorError::InputNonExistent { position, .. }) => {
+                    return Ok(CpuEvent::NeedsInput { position })
+                }
+                Err(error) => return Err(error),
+            };
+            match outcome.next_instruction_pointer {
+                Some(next) => self.instruction_pointer = next,
+                None => self.halted = true,
+            }
+            if let Some(output) = outcome.output {
+                return Ok(CpuEvent::Output(output));
+            }
+        }
+        Ok(CpuEvent::Halted)
+    }
+
+    fn read(&self, address: usize) -> Option<EmulatorMemoryType> {
+        self.memory.get(address).copied()
+    }
+
+    fn write(&mut self, address: usize, value: EmulatorMemoryType) {
+        write_growing(&mut self.memory, address, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_echo<C: Cpu>(cpu: &mut C) -> Result<Vec<CpuEvent>, EmulatorError> {
+        let mut events = vec![cpu.run_until_event()?];
+        cpu.push_input(12);
+        events.push(cpu.run_until_event()?);
+        events.push(cpu.run_until_event()?);
+        Ok(events)
+    }
+
+    #[test]
+    fn test_resume_event_loop() -> Result<(), EmulatorError> {
+        // Outputs whether the input equals 8.
+        let compare_to_8 = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        for &(input, expected) in [(8, 1), (7, 0), (9, 0)].iter() {
+            let mut emulator = Emulator::with_queue(&compare_to_8);
+            let mut events = Vec::new();
+            loop {
+                let event = emulator.resume()?;
+                events.push(event);
+                match event {
+                    CpuEvent::Output(_) => {}
+                    CpuEvent::NeedsInput { .. } => emulator.push_input(input),
+                    CpuEvent::Halted => break,
+                    CpuEvent::Watchpoint { .. } => unreachable!("nothing is watched"),
+                }
+            }
+            assert_eq!(
+                vec![
+                    CpuEvent::NeedsInput { position: 0 },
+                    CpuEvent::Output(expected),
+                    CpuEvent::Halted,
+                ],
+                events
+            );
+        }
+
+        // Any input iterator works, not just a queue.
+        let mut emulator = Emulator::new(&compare_to_8, std::iter::once(Ok(8)));
+        assert_eq!(CpuEvent::Output(1), emulator.resume()?);
+        assert_eq!(CpuEvent::Halted, emulator.resume()?);
+
+        // The input is stored in the watched cell 9.
+        let mut emulator = Emulator::with_inputs(&compare_to_8, vec![7]);
+        emulator.watch(9);
+        assert_eq!(
+            CpuEvent::Watchpoint {
+                address: 9,
+                old: -1,
+                new: 7
+            },
+            emulator.resume()?
+        );
+        // Then the comparison's result replaces it.
+        assert_eq!(
+            CpuEvent::Watchpoint {
+                address: 9,
+                old: 7,
+                new: 0
+            },
+            emulator.resume()?
+        );
+        assert_eq!(CpuEvent::Output(0), emulator.resume()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_implementations_agree() -> Result<(), EmulatorError> {
+        let echo = [3, 0, 4, 0, 99];
+        let expected = vec![
+            CpuEvent::NeedsInput { position: 0 },
+            CpuEvent::Output(12),
+            CpuEvent::Halted,
+        ];
+        let mut emulator = Emulator::new(&echo, QueueInput::new());
+        let mut eval = EvalCpu::new(&echo);
+        assert_eq!(expected, run_echo(&mut emulator)?);
+        assert_eq!(expected, run_echo(&mut eval)?);
+
+        for cpu in [&mut emulator as &mut dyn Cpu, &mut eval].iter_mut() {
+            assert_eq!(Some(12), cpu.read(0));
+            assert_eq!(None, cpu.read(7));
+            cpu.write(7, 5);
+            assert_eq!(Some(0), cpu.read(6));
+            assert_eq!(Some(5), cpu.read(7));
+        }
+        Ok(())
+    }
+}
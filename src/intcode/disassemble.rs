@@ -0,0 +1,168 @@
+use super::layout::MemoryLayout;
+use super::{EmulatorMemoryType, OpCodeInfo, ParameterKind, OPCODE_TABLE};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembledInstruction {
+    pub address: usize,
+    // The instruction cell followed by its parameters, or a single data cell.
+    pub cells: Vec<EmulatorMemoryType>,
+    // None for cells that don't decode, which are listed as data.
+    pub info: Option<&'static OpCodeInfo>,
+}
+
+impl DisassembledInstruction {
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+// Position mode parameters are shown as [address], relative mode ones as
+// [rb+offset] and immediates as the value.
+impl std::fmt::Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:>5}: ", self.address)?;
+        let info = match self.info {
+            Some(info) => info,
+            None => return write!(f, "data {}", self.cells[0]),
+        };
+        write!(f, "{}", info.mnemonic)?;
+        let mut mode_digits = self.cells[0] / 100;
+        for value in self.cells[1..].iter() {
+            match mode_digits % 10 {
+                0 => write!(f, " [{}]", value)?,
+                2 => write!(f, " [rb{:+}]", value)?,
+                _ => write!(f, " {}", value)?,
+            }
+            mode_digits /= 10;
+        }
+        Ok(())
+    }
+}
+
+// The opcode at address, if its parameters fit before end and use modes the
+// opcode accepts.
+fn decode(
+    memory: &[EmulatorMemoryType],
+    address: usize,
+    end: usize,
+) -> Option<&'static OpCodeInfo> {
+    let instruction = memory[address];
+    let info = OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == instruction % 100)?;
+    if address + info.parameters.len() + 1 > end {
+        return None;
+    }
+    let mut mode_digits = instruction / 100;
+    for parameter in info.parameters.iter() {
+        match (mode_digits % 10, parameter.kind) {
+            (0, _) | (1, ParameterKind::Read) | (2, _) => {}
+            _ => return None,
+        }
+        mode_digits /= 10;
+    }
+    Some(info)
+}
+
+// A linear sweep from address 0. With a layout that marks code segments,
+// only those are decoded and everything else is listed as data.
+pub fn disassemble(
+    memory: &[EmulatorMemoryType],
+    layout: Option<&MemoryLayout>,
+) -> Vec<DisassembledInstruction> {
+    let code_only = layout.filter(|layout| layout.has_code_segments());
+    let mut instructions = Vec::new();
+    let mut address = 0;
+    while address < memory.len() {
+        let end = match code_only {
+            None => Some(memory.len()),
+            Some(layout) => layout
+                .segment_at(address)
+                .filter(|segment| segment.code)
+                .map(|segment| std::cmp::min(segment.range.end, memory.len())),
+        };
+        let info = end.and_then(|end| decode(memory, address, end));
+        let length = info.map_or(1, |info| info.parameters.len() + 1);
+        instructions.push(DisassembledInstruction {
+            address,
+            cells: memory[address..address + length].to_vec(),
+            info,
+        });
+        address += length;
+    }
+    instructions
+}
+
+// The instruction at address alone, decoded whatever precedes it. None past
+// the end of memory.
+pub fn disassemble_at(
+    memory: &[EmulatorMemoryType],
+    address: usize,
+) -> Option<DisassembledInstruction> {
+    if address >= memory.len() {
+        return None;
+    }
+    let info = decode(memory, address, memory.len());
+    let length = info.map_or(1, |info| info.parameters.len() + 1);
+    Some(DisassembledInstruction {
+        address,
+        cells: memory[address..address + length].to_vec(),
+        info,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(instructions: &[DisassembledInstruction]) -> Vec<String> {
+        instructions
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let memory = [1, 9, 10, 3, 1102, 3, 11, 0, 99, 30, 40, 50];
+        assert_eq!(
+            vec![
+                "    0: Add [9] [10] [3]",
+                "    4: Multiply 3 11 [0]",
+                "    8: End",
+                "    9: data 30",
+                "   10: data 40",
+                "   11: data 50",
+            ],
+            listing(&disassemble(&memory, None))
+        );
+    }
+
+    #[test]
+    fn test_disassemble_only_code_segments() {
+        let memory = [1101, 1, 2, 5, 99, 1, 1, 1, 1];
+        let layout = "code:0..5:code,table:5..9:ro".parse().unwrap();
+        assert_eq!(
+            vec![
+                "    0: Add 1 2 [5]",
+                "    4: End",
+                "    5: data 1",
+                "    6: data 1",
+                "    7: data 1",
+                "    8: data 1",
+            ],
+            listing(&disassemble(&memory, Some(&layout)))
+        );
+
+        // An instruction can't run past the end of its code segment.
+        let layout = "code:0..3:code".parse().unwrap();
+        assert_eq!(
+            "    0: data 1101",
+            disassemble(&memory, Some(&layout))[0].to_string()
+        );
+    }
+}
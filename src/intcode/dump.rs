@@ -0,0 +1,179 @@
+use super::disassemble::disassemble;
+use super::layout::MemoryLayout;
+use super::stats::ExecutionStats;
+use super::EmulatorMemoryType;
+use std::fmt::Write;
+use std::ops::Range;
+
+pub const CELLS_PER_ROW: usize = 8;
+
+fn header(layout: &MemoryLayout, address: usize, end: usize) -> String {
+    match layout.segment_at(address) {
+        Some(segment) => format!(
+            "[{} {}..{}{}]",
+            segment.name,
+            segment.range.start,
+            segment.range.end,
+            if segment.read_only { " ro" } else { "" }
+        ),
+        None => format!("[unassigned {}..{}]", address, end),
+    }
+}
+
+// Where the run of cells starting at address stops: the end of its segment,
+// or the start of the next one.
+fn run_end(layout: &MemoryLayout, address: usize, memory_length: usize) -> usize {
+    let end = match layout.segment_at(address) {
+        Some(segment) => segment.range.end,
+        None => layout
+            .segments()
+            .iter()
+            .map(|segment| segment.range.start)
+            .find(|&start| start > address)
+            .unwrap_or(memory_length),
+    };
+    std::cmp::min(end, memory_length)
+}
+
+// Rows of CELLS_PER_ROW cells prefixed by their address. With a layout, each
+// segment (and each gap between segments) starts on a new row under a header.
+pub fn format_memory(memory: &[EmulatorMemoryType], layout: Option<&MemoryLayout>) -> String {
+    let mut dump = String::new();
+    let mut address = 0;
+    while address < memory.len() {
+        let end = match layout {
+            Some(layout) => {
+                let end = run_end(layout, address, memory.len());
+                let _ = writeln!(dump, "{}", header(layout, address, end));
+                end
+            }
+            None => memory.len(),
+        };
+        write_rows(&mut dump, memory, address..end, None);
+        address = end;
+    }
+    dump
+}
+
+// Rows like format_memory's for the cells in range, which may run past the
+// end of memory. The cell at highlight, if it is in range, is shown as <n>.
+pub fn format_range(
+    memory: &[EmulatorMemoryType],
+    range: Range<usize>,
+    highlight: Option<usize>,
+) -> String {
+    let end = std::cmp::min(range.end, memory.len());
+    let start = std::cmp::min(range.start, end);
+    let mut dump = String::new();
+    write_rows(&mut dump, memory, start..end, highlight);
+    dump
+}
+
+fn write_rows(
+    dump: &mut String,
+    memory: &[EmulatorMemoryType],
+    range: Range<usize>,
+    highlight: Option<usize>,
+) {
+    let mut address = range.start;
+    for row in memory[range].chunks(CELLS_PER_ROW) {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(offset, cell)| {
+                if highlight == Some(address + offset) {
+                    format!("<{}>", cell)
+                } else {
+                    cell.to_string()
+                }
+            })
+            .collect();
+        let _ = writeln!(dump, "{:>5}: {}", address, cells.join(" "));
+        address += row.len();
+    }
+}
+
+// The disassembly with each conditional jump followed by how often it was
+// taken during the run that collected stats.
+pub fn format_annotated(
+    memory: &[EmulatorMemoryType],
+    layout: Option<&MemoryLayout>,
+    stats: &ExecutionStats,
+) -> String {
+    let mut dump = String::new();
+    for instruction in disassemble(memory, layout) {
+        let _ = write!(dump, "{}", instruction);
+        if let Some((taken, not_taken)) = stats.branch(instruction.address) {
+            let _ = write!(dump, "  ; taken {}, not taken {}", taken, not_taken);
+        }
+        dump.push('\n');
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_memory() {
+        let memory: Vec<EmulatorMemoryType> = (0..10).collect();
+        assert_eq!(
+            "    0: 0 1 2 3 4 5 6 7\n    8: 8 9\n",
+            format_memory(&memory, None)
+        );
+    }
+
+    #[test]
+    fn test_format_range() {
+        let memory: Vec<EmulatorMemoryType> = (0..20).collect();
+        assert_eq!(
+            "    3: 3 4 <5> 6 7 8 9 10
+   11: 11
+",
+            format_range(&memory, 3..12, Some(5))
+        );
+        assert_eq!(
+            "   18: 18 19
+",
+            format_range(&memory, 18..30, Some(2))
+        );
+        assert_eq!("", format_range(&memory, 25..30, None));
+    }
+
+    #[test]
+    fn test_format_memory_with_segment_headers() {
+        let memory: Vec<EmulatorMemoryType> = (0..14).collect();
+        let layout = "code:0..3:code,constants:5..12:ro".parse().unwrap();
+        assert_eq!(
+            "[code 0..3]\n    \
+                 0: 0 1 2\n\
+             [unassigned 3..5]\n    \
+                 3: 3 4\n\
+             [constants 5..12 ro]\n    \
+                 5: 5 6 7 8 9 10 11\n\
+             [unassigned 12..14]\n   \
+                12: 12 13\n",
+            format_memory(&memory, Some(&layout))
+        );
+    }
+
+    #[test]
+    fn test_format_annotated() {
+        let memory = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let mut stats = ExecutionStats::new();
+        stats.record_branch(2, true);
+        stats.record_branch(2, false);
+        stats.record_branch(2, false);
+        let annotated = format_annotated(&memory, None, &stats);
+        let lines: Vec<&str> = annotated.lines().take(3).collect();
+        assert_eq!(
+            vec![
+                "    0: Input [12]",
+                "    2: JumpIfFalse [12] [15]  ; taken 1, not taken 2",
+                "    5: Add [13] [14] [13]",
+            ],
+            lines
+        );
+    }
+}
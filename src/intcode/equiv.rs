@@ -0,0 +1,283 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Termination {
+    Halted,
+    Error(EmulatorError),
+    // The step budget ran out before the program halted.
+    OutOfSteps,
+}
+
+impl std::fmt::Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Termination::Halted => write!(f, "halted"),
+            Termination::Error(error) => write!(f, "failed ({})", error),
+            Termination::OutOfSteps => write!(f, "ran out of steps"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Output {
+        position: usize,
+    },
+    Termination {
+        a: Termination,
+        b: Termination,
+    },
+    ResultCell {
+        address: usize,
+        a: Option<EmulatorMemoryType>,
+        b: Option<EmulatorMemoryType>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquivFailure {
+    pub case_index: usize,
+    pub input: Vec<EmulatorMemoryType>,
+    pub difference: Difference,
+    pub outputs_a: Vec<EmulatorMemoryType>,
+    pub outputs_b: Vec<EmulatorMemoryType>,
+}
+
+fn describe_cell(cell: Option<EmulatorMemoryType>) -> String {
+    cell.map_or("nothing".to_string(), |cell| cell.to_string())
+}
+
+impl std::fmt::Display for EquivFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Input case {} {:?}: ", self.case_index, self.input)?;
+        // Outputs are shown up to and including the first difference.
+        let shown = match &self.difference {
+            Difference::Output { position } => {
+                write!(f, "output {} differs", position)?;
+                position + 1
+            }
+            Difference::Termination { a, b } => {
+                write!(f, "a {} but b {}", a, b)?;
+                usize::MAX
+            }
+            Difference::ResultCell { address, a, b } => {
+                write!(
+                    f,
+                    "memory[{}] is {} in a but {} in b",
+                    address,
+                    describe_cell(*a),
+                    describe_cell(*b)
+                )?;
+                usize::MAX
+            }
+        };
+        let prefix = |outputs: &[EmulatorMemoryType]| {
+            outputs[..std::cmp::min(shown, outputs.len())].to_vec()
+        };
+        write!(
+            f,
+            "\n    a output: {:?}\n    b output: {:?}",
+            prefix(&self.outputs_a),
+            prefix(&self.outputs_b)
+        )
+    }
+}
+
+impl std::error::Error for EquivFailure {}
+
+struct Run {
+    outputs: Vec<EmulatorMemoryType>,
+    termination: Termination,
+    memory: Vec<EmulatorMemoryType>,
+}
+
+fn run(program: &[EmulatorMemoryType], input: &[EmulatorMemoryType], max_steps: u64) -> Run {
+    let mut emulator = Emulator::new(program, input.iter().copied().map(Ok));
+    emulator.set_history_size(0);
+    let mut outputs = Vec::new();
+    let mut termination = Termination::OutOfSteps;
+    for _ in 0..max_steps {
+        match emulator.step() {
+            Ok(EmulatorResult::Done) => {
+                termination = Termination::Halted;
+                break;
+            }
+            Ok(EmulatorResult::Success | EmulatorResult::Watchpoint { .. }) => {}
+            Ok(EmulatorResult::SuccessWithValue(value)) => outputs.push(value),
+            Ok(EmulatorResult::NeedsInput) => {
+                termination = Termination::Error(emulator.missing_input());
+                break;
+            }
+            Err(error) => {
+                termination = Termination::Error(error);
+                break;
+            }
+        }
+    }
+    Run {
+        outputs,
+        termination,
+        memory: emulator.memory().to_vec(),
+    }
+}
+
+// Runs both programs on every input case for at most max_steps steps each,
+// comparing the outputs and how each run ended. Two runs that both exhaust
+// the budget with the same outputs so far count as equivalent.
+#[allow(clippy::result_large_err)] // at most one failure per check, so not boxed
+pub fn check(
+    a: &[EmulatorMemoryType],
+    b: &[EmulatorMemoryType],
+    input_cases: &[Vec<EmulatorMemoryType>],
+    max_steps: u64,
+) -> Result<(), EquivFailure> {
+    check_with_result_cells(a, b, input_cases, max_steps, &[])
+}
+
+// Like check, but also requires the given cells to match in the final
+// memory of both runs, for programs that leave their answer in memory.
+#[allow(clippy::result_large_err)]
+pub fn check_with_result_cells(
+    a: &[EmulatorMemoryType],
+    b: &[EmulatorMemoryType],
+    input_cases: &[Vec<EmulatorMemoryType>],
+    max_steps: u64,
+    result_cells: &[usize],
+) -> Result<(), EquivFailure> {
+    for (case_index, input) in input_cases.iter().enumerate() {
+        let run_a = run(a, input, max_steps);
+        let run_b = run(b, input, max_steps);
+
+        let output_count = std::cmp::max(run_a.outputs.len(), run_b.outputs.len());
+        let differing_output = (0..output_count)
+            .find(|&position| run_a.outputs.get(position) != run_b.outputs.get(position));
+        let difference = if let Some(position) = differing_output {
+            Some(Difference::Output { position })
+        } else if run_a.termination != run_b.termination {
+            Some(Difference::Termination {
+                a: run_a.termination,
+                b: run_b.termination,
+            })
+        } else {
+            result_cells
+                .iter()
+                .map(|&address| Difference::ResultCell {
+                    address,
+                    a: run_a.memory.get(address).copied(),
+                    b: run_b.memory.get(address).copied(),
+                })
+                .find(|difference| match difference {
+                    Difference::ResultCell { a, b, .. } => a != b,
+                    _ => false,
+                })
+        };
+
+        if let Some(difference) = difference {
+            return Err(EquivFailure {
+                case_index,
+                input: input.clone(),
+                difference,
+                outputs_a: run_a.outputs,
+                outputs_b: run_b.outputs,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECHO: [EmulatorMemoryType; 5] = [3, 0, 4, 0, 99];
+
+    fn cases() -> Vec<Vec<EmulatorMemoryType>> {
+        vec![vec![1], vec![5], vec![-3]]
+    }
+
+    #[test]
+    fn test_equivalent_programs() {
+        let echo_via_add = [3, 0, 1001, 0, 0, 0, 4, 0, 99];
+        assert_eq!(Ok(()), check(&ECHO, &echo_via_add, &cases(), 100));
+
+        // Both spin forever without output, which is indistinguishable
+        // within the budget.
+        assert_eq!(
+            Ok(()),
+            check(&[1105, 1, 0, 99], &[1106, 0, 0, 99], &[vec![]], 1_000)
+        );
+    }
+
+    #[test]
+    fn test_differing_output_is_reported() {
+        let echo_plus_one = [3, 0, 1001, 0, 1, 0, 4, 0, 99];
+        let failure = check(&ECHO, &echo_plus_one, &cases(), 100).unwrap_err();
+        assert_eq!(
+            EquivFailure {
+                case_index: 0,
+                input: vec![1],
+                difference: Difference::Output { position: 0 },
+                outputs_a: vec![1],
+                outputs_b: vec![2],
+            },
+            failure
+        );
+        assert_eq!(
+            "Input case 0 [1]: output 0 differs\n    a output: [1]\n    b output: [2]",
+            failure.to_string()
+        );
+    }
+
+    #[test]
+    fn test_differing_termination_is_reported() {
+        let failure = check(&[104, 7, 99], &[104, 7, 1105, 1, 2, 99], &[vec![]], 50).unwrap_err();
+        assert_eq!(
+            Difference::Termination {
+                a: Termination::Halted,
+                b: Termination::OutOfSteps,
+            },
+            failure.difference
+        );
+        assert_eq!(
+            "Input case 0 []: a halted but b ran out of steps\n    a output: [7]\n    b output: [7]",
+            failure.to_string()
+        );
+
+        let failure = check(&ECHO, &[3, 0, 4, 0, 3, 0, 99], &cases(), 100).unwrap_err();
+        assert_eq!(
+            Difference::Termination {
+                a: Termination::Halted,
+                b: Termination::Error(EmulatorError::InputNonExistent {
+                    position: 4,
+                    context: None,
+                }),
+            },
+            failure.difference
+        );
+    }
+
+    #[test]
+    fn test_result_cells() {
+        let day_2_example = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let folded = [1101, 30, 40, 3, 1002, 3, 50, 0, 99, 30, 40, 50];
+        let off_by_one = [1101, 30, 41, 3, 1002, 3, 50, 0, 99, 30, 40, 50];
+        assert_eq!(
+            Ok(()),
+            check_with_result_cells(&day_2_example, &folded, &[vec![]], 100, &[0])
+        );
+        assert_eq!(
+            Err(EquivFailure {
+                case_index: 0,
+                input: vec![],
+                difference: Difference::ResultCell {
+                    address: 0,
+                    a: Some(3500),
+                    b: Some(3550),
+                },
+                outputs_a: vec![],
+                outputs_b: vec![],
+            }),
+            check_with_result_cells(&day_2_example, &off_by_one, &[vec![]], 100, &[0])
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use super::core_dump::CoreDumpStatus;
+use super::{MemoryCell, OpCode, WideValue};
+use std::fmt;
+
+// How many cells either side of the instruction pointer an ErrorContext
+// copies.
+pub const CONTEXT_CELLS: usize = 5;
+
+// Where an emulator was when an error stopped it: the instruction it was
+// running and a copy of the memory around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorContext {
+    pub instruction_pointer: usize,
+    // None if the cell at the instruction pointer isn't an instruction.
+    pub opcode: Option<OpCode>,
+    // The address of window[0]. The window is cut short at either end of
+    // memory, so the instruction pointer isn't always its middle cell.
+    pub window_start: usize,
+    // Widened, like the values in errors.
+    pub window: Vec<WideValue>,
+    // Set when the emulator was asked to dump core on errors.
+    pub core_dump: Option<CoreDumpStatus>,
+}
+
+impl ErrorContext {
+    pub(crate) fn capture<C: MemoryCell>(memory: &[C], instruction_pointer: usize) -> ErrorContext {
+        let end = std::cmp::min(instruction_pointer + CONTEXT_CELLS + 1, memory.len());
+        let start = std::cmp::min(instruction_pointer.saturating_sub(CONTEXT_CELLS), end);
+        ErrorContext {
+            instruction_pointer,
+            opcode: OpCode::get_current_instruction(memory, instruction_pointer)
+                .ok()
+                .map(|(opcode, _)| opcode),
+            window_start: start,
+            window: memory[start..end]
+                .iter()
+                .map(|&cell| cell.widen())
+                .collect(),
+            core_dump: None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.opcode {
+            Some(opcode) => writeln!(
+                f,
+                "While running {:?} at {}:",
+                opcode, self.instruction_pointer
+            )?,
+            None => writeln!(
+                f,
+                "While at {}, which isn't an instruction:",
+                self.instruction_pointer
+            )?,
+        }
+        let cells: Vec<String> = self
+            .window
+            .iter()
+            .enumerate()
+            .map(|(offset, cell)| {
+                if self.window_start + offset == self.instruction_pointer {
+                    format!("<{}>", cell)
+                } else {
+                    cell.to_string()
+                }
+            })
+            .collect();
+        write!(f, "{:>5}: {}", self.window_start, cells.join(" "))?;
+        match &self.core_dump {
+            Some(CoreDumpStatus::Written(path)) => {
+                write!(f, "\nCore dumped to {}", path.display())
+            }
+            Some(CoreDumpStatus::Failed { path, error }) => write!(
+                f,
+                "\nCould not write a core dump to {}: {}",
+                path.display(),
+                error
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::EmulatorMemoryType;
+
+    #[test]
+    fn test_capture() {
+        let memory: Vec<EmulatorMemoryType> = (0..20).collect();
+        let context = ErrorContext::capture(&memory, 10);
+        assert_eq!((5..16).collect::<Vec<_>>(), context.window);
+        assert_eq!(5, context.window_start);
+        assert_eq!(None, context.opcode);
+
+        // Cut short at both ends of memory.
+        let memory: Vec<EmulatorMemoryType> = vec![1002, 4, 3, 4, 33];
+        let context = ErrorContext::capture(&memory, 2);
+        assert_eq!(vec![1002, 4, 3, 4, 33], context.window);
+        assert_eq!(0, context.window_start);
+        assert_eq!(
+            Some(OpCode::Multiply),
+            ErrorContext::capture(&memory, 0).opcode
+        );
+        let context = ErrorContext::capture(&memory, 8);
+        assert_eq!(vec![4, 33], context.window);
+        assert_eq!(3, context.window_start);
+        assert_eq!(
+            "While at 8, which isn't an instruction:\n    3: 4 33",
+            context.to_string()
+        );
+        assert_eq!(
+            "While running Multiply at 0:\n    0: <1002> 4 3 4 33",
+            ErrorContext::capture(&memory, 0).to_string()
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use super::EmulatorMemoryType;
+use std::collections::VecDeque;
+
+pub const DEFAULT_HISTORY_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionRecord<C = EmulatorMemoryType> {
+    pub step: u64,
+    pub instruction_pointer: usize,
+    pub instruction: C,
+    // Values read by ReadOnly parameters and addresses written by Writable
+    // parameters, in declaration order.
+    pub parameters: Vec<C>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstructionHistory<C = EmulatorMemoryType> {
+    capacity: usize,
+    records: VecDeque<InstructionRecord<C>>,
+}
+
+impl<C> InstructionHistory<C> {
+    pub fn new(capacity: usize) -> InstructionHistory<C> {
+        InstructionHistory {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.records.len() > capacity {
+            self.records.pop_front();
+        }
+    }
+
+    // Hands back the parameter buffer of the evicted record (or the one
+    // passed in when recording is disabled) so stepping doesn't allocate.
+    pub(super) fn push(&mut self, record: InstructionRecord<C>) -> Vec<C> {
+        if self.capacity == 0 {
+            return record.parameters;
+        }
+
+        let recycled = if self.records.len() == self.capacity {
+            self.records.pop_front().map(|evicted| evicted.parameters)
+        } else {
+            None
+        };
+        self.records.push_back(record);
+        recycled.unwrap_or_default()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    // Oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &InstructionRecord<C>> {
+        self.records.iter()
+    }
+
+    pub fn last(&self) -> Option<&InstructionRecord<C>> {
+        self.records.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl<C: std::fmt::Display> std::fmt::Display for InstructionRecord<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "step {} at {}: {}",
+            self.step, self.instruction_pointer, self.instruction
+        )?;
+        for parameter in self.parameters.iter() {
+            write!(f, " {}", parameter)?;
+        }
+        Ok(())
+    }
+}
+
+// Most recent instruction first, like a backtrace.
+impl<C: std::fmt::Display> std::fmt::Display for InstructionHistory<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.records.is_empty() {
+            return write!(f, "No instructions executed");
+        }
+        write!(f, "Most recent instructions:")?;
+        for record in self.records.iter().rev() {
+            write!(f, "\n    {}", record)?;
+        }
+        Ok(())
+    }
+}
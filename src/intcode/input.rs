@@ -0,0 +1,45 @@
+use super::{EmulatorError, EmulatorMemoryType};
+
+// Markers telling IntoEmulatorInput's two blanket impls apart. Both impls
+// cover "any IntoIterator", and coherence can't see that the Item types make
+// them disjoint, so each impl is for a different IntoEmulatorInput<Marker>
+// trait instead. Callers never name the marker; inference picks the only
+// impl that fits.
+pub struct Values;
+pub struct Results;
+
+// Anything that can feed an Emulator: plain values, or Results so chained
+// emulators can forward their errors.
+pub trait IntoEmulatorInput<Marker> {
+    type Iter: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>;
+
+    fn into_emulator_input(self) -> Self::Iter;
+}
+
+pub struct OkValues<I>(I);
+
+impl<I: Iterator<Item = EmulatorMemoryType>> Iterator for OkValues<I> {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Ok)
+    }
+}
+
+impl<T: IntoIterator<Item = EmulatorMemoryType>> IntoEmulatorInput<Values> for T {
+    type Iter = OkValues<T::IntoIter>;
+
+    fn into_emulator_input(self) -> Self::Iter {
+        OkValues(self.into_iter())
+    }
+}
+
+impl<T: IntoIterator<Item = Result<EmulatorMemoryType, EmulatorError>>> IntoEmulatorInput<Results>
+    for T
+{
+    type Iter = T::IntoIter;
+
+    fn into_emulator_input(self) -> Self::Iter {
+        self.into_iter()
+    }
+}
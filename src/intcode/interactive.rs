@@ -0,0 +1,325 @@
+use super::binary::{invalid_data, put_cell, put_cells, put_u64, Reader};
+use super::cpu::CpuEvent;
+use super::snapshot::{EmulatorSnapshot, StateKey};
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, QueueInput};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"ICSESS01";
+
+// A long running interactive program, such as day 25's text adventure, that
+// keeps every input it is given so it can be saved and picked up later.
+pub struct Session {
+    program: Vec<EmulatorMemoryType>,
+    emulator: Emulator<QueueInput>,
+    // Every input pushed, whether or not the program has read it yet.
+    transcript: Vec<EmulatorMemoryType>,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    // Replaying the transcript from the start failed outright.
+    Replay(EmulatorError),
+    // Replaying the transcript ended somewhere other than the saved state,
+    // so the emulator or program has changed since the session was saved.
+    Diverged { saved: StateKey, replayed: StateKey },
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SessionError::Io(error) => write!(f, "{}", error),
+            SessionError::Replay(error) => write!(f, "Replaying the session failed: {}", error),
+            SessionError::Diverged { saved, replayed } => write!(
+                f,
+                "Replaying the session ended at {:?} rather than the saved {:?}",
+                replayed, saved
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> SessionError {
+        SessionError::Io(error)
+    }
+}
+
+impl Session {
+    pub fn new(program: &[EmulatorMemoryType]) -> Session {
+        Session {
+            program: program.into(),
+            emulator: Emulator::new(program, QueueInput::new()),
+            transcript: Vec::new(),
+        }
+    }
+
+    pub fn emulator(&self) -> &Emulator<QueueInput> {
+        &self.emulator
+    }
+
+    pub fn transcript(&self) -> &[EmulatorMemoryType] {
+        &self.transcript
+    }
+
+    pub fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.transcript.push(value);
+        self.emulator.push_input(value);
+    }
+
+    // An ASCII command, newline included.
+    pub fn push_line(&mut self, line: &str) {
+        for byte in line.bytes().chain(std::iter::once(b'\n')) {
+            self.push_input(EmulatorMemoryType::from(byte));
+        }
+    }
+
+    // The outputs produced before the program asks for input it hasn't been
+    // given, or halts.
+    pub fn run_until_input(&mut self) -> Result<Vec<EmulatorMemoryType>, EmulatorError> {
+        let mut outputs = Vec::new();
+        loop {
+            match self.emulator.resume()? {
+                CpuEvent::Output(value) => outputs.push(value),
+                CpuEvent::NeedsInput { .. } | CpuEvent::Halted => return Ok(outputs),
+                CpuEvent::Watchpoint { .. } => {}
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        SessionFile::capture(self).save(path)
+    }
+
+    pub fn resume<P: AsRef<Path>>(path: P) -> Result<Session, SessionError> {
+        SessionFile::load(path)?.restore()
+    }
+}
+
+// A saved Session. Everything is little endian, as in core dumps:
+//
+//   magic "ICSESS01"
+//   program length, program
+//   transcript length, transcript
+//   inputs consumed, steps executed
+//   instruction pointer, relative base, halted (0 or 1)
+//   memory length, memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionFile {
+    pub program: Vec<EmulatorMemoryType>,
+    pub transcript: Vec<EmulatorMemoryType>,
+    pub inputs_consumed: u64,
+    pub steps: u64,
+    pub snapshot: EmulatorSnapshot,
+}
+
+impl SessionFile {
+    pub fn capture(session: &Session) -> SessionFile {
+        SessionFile {
+            program: session.program.clone(),
+            transcript: session.transcript.clone(),
+            inputs_consumed: session.emulator.inputs_consumed,
+            steps: session.emulator.steps,
+            snapshot: session.emulator.snapshot(),
+        }
+    }
+
+    pub fn fingerprint(&self) -> StateKey {
+        self.snapshot.state_key()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        put_cells(&mut bytes, &self.program);
+        put_cells(&mut bytes, &self.transcript);
+        put_u64(&mut bytes, self.inputs_consumed);
+        put_u64(&mut bytes, self.steps);
+        put_u64(&mut bytes, self.snapshot.instruction_pointer as u64);
+        put_cell(&mut bytes, self.snapshot.relative_base);
+        put_u64(&mut bytes, u64::from(self.snapshot.halted));
+        put_cells(&mut bytes, &self.snapshot.memory);
+        fs::write(path, bytes)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<SessionFile> {
+        let bytes = fs::read(path)?;
+        let mut reader = Reader {
+            bytes: &bytes,
+            what: "session file",
+        };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a session file"));
+        }
+
+        let program = reader.cells()?;
+        let transcript = reader.cells()?;
+        let inputs_consumed = reader.u64()?;
+        let steps = reader.u64()?;
+        let instruction_pointer = reader.usize()?;
+        let relative_base = reader.cell()?;
+        let halted = match reader.u64()? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid_data("session file halted flag is not 0 or 1")),
+        };
+        let memory = reader.cells()?;
+        reader.finish()?;
+        if inputs_consumed > transcript.len() as u64 {
+            return Err(invalid_data(
+                "session file consumed more inputs than its transcript holds",
+            ));
+        }
+
+        Ok(SessionFile {
+            program,
+            transcript,
+            inputs_consumed,
+            steps,
+            snapshot: EmulatorSnapshot {
+                memory,
+                instruction_pointer,
+                relative_base,
+                halted,
+            },
+        })
+    }
+
+    // Replays the transcript on a fresh machine for the saved number of
+    // steps first, and refuses to resume unless it lands on the saved state.
+    pub fn restore(self) -> Result<Session, SessionError> {
+        let mut replayed = Emulator::new(&self.program, QueueInput::from(self.transcript.clone()));
+        while replayed.steps < self.steps && !replayed.is_halted() {
+            if replayed.step().map_err(SessionError::Replay)? == EmulatorResult::NeedsInput {
+                break;
+            }
+        }
+        let saved = self.fingerprint();
+        if replayed.state_key() != saved
+            || replayed.steps != self.steps
+            || replayed.inputs_consumed != self.inputs_consumed
+        {
+            return Err(SessionError::Diverged {
+                saved,
+                replayed: replayed.state_key(),
+            });
+        }
+
+        let pending = self.transcript[self.inputs_consumed as usize..].to_vec();
+        let mut emulator = Emulator::restore(&self.snapshot, QueueInput::from(pending));
+        emulator.steps = self.steps;
+        emulator.inputs_consumed = self.inputs_consumed;
+        Ok(Session {
+            program: self.program,
+            emulator,
+            transcript: self.transcript,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads numbers forever, printing the running total after each. The
+    // total lives at 101, past the end of the program.
+    const RUNNING_TOTAL: [EmulatorMemoryType; 11] = [3, 100, 1, 100, 101, 101, 4, 101, 1105, 1, 0];
+
+    fn scratch_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "advent_of_code_2019_session_{}_{}.bin",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn feed(session: &mut Session, values: &[EmulatorMemoryType]) -> Vec<EmulatorMemoryType> {
+        let mut outputs = Vec::new();
+        for &value in values {
+            session.push_input(value);
+            outputs.extend(session.run_until_input().unwrap());
+        }
+        outputs
+    }
+
+    #[test]
+    fn test_resumed_session_matches_uninterrupted_run() -> Result<(), SessionError> {
+        let mut uninterrupted = Session::new(&RUNNING_TOTAL);
+        assert_eq!(
+            vec![1, 3, 6, 10, 15, 21],
+            feed(&mut uninterrupted, &[1, 2, 3, 4, 5, 6])
+        );
+
+        let path = scratch_file("resume");
+        let mut first = Session::new(&RUNNING_TOTAL);
+        assert_eq!(vec![1, 3, 6], feed(&mut first, &[1, 2, 3]));
+        // Queued but not yet read, so it must survive the save too.
+        first.push_input(4);
+        first.save(&path)?;
+
+        let mut resumed = Session::resume(&path)?;
+        assert_eq!(&[1, 2, 3, 4], resumed.transcript());
+        assert!(resumed.emulator().state_eq(first.emulator()));
+        assert_eq!(vec![10], resumed.run_until_input().unwrap());
+        assert_eq!(vec![15, 21], feed(&mut resumed, &[5, 6]));
+        assert!(resumed.emulator().state_eq(uninterrupted.emulator()));
+        assert_eq!(uninterrupted.transcript(), resumed.transcript());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_input() {
+        let mut session = Session::new(&[3, 20, 4, 20, 1105, 1, 0]);
+        session.push_line("go");
+        assert_eq!(vec![103, 111, 10], session.run_until_input().unwrap());
+        assert_eq!(&[103, 111, 10], session.transcript());
+    }
+
+    #[test]
+    fn test_changed_behaviour_is_detected() -> io::Result<()> {
+        let path = scratch_file("diverged");
+        let mut session = Session::new(&RUNNING_TOTAL);
+        feed(&mut session, &[1, 2]);
+        session.save(&path)?;
+
+        // As if the emulator now added differently: the saved total no
+        // longer matches what replaying the transcript gives.
+        let mut file = SessionFile::load(&path)?;
+        file.snapshot.memory[101] = 4;
+        match file.restore() {
+            Err(SessionError::Diverged { saved, replayed }) => {
+                assert_ne!(saved, replayed);
+                assert_eq!(session.emulator().state_key(), replayed);
+            }
+            other => panic!("expected a divergence, got {:?}", other.map(|_| ())),
+        }
+
+        let mut file = SessionFile::load(&path)?;
+        file.program[3] = 101;
+        assert!(matches!(file.restore(), Err(SessionError::Diverged { .. })));
+
+        fs::remove_file(&path)
+    }
+
+    #[test]
+    fn test_truncated_session_is_rejected() -> io::Result<()> {
+        let path = scratch_file("truncated");
+        Session::new(&RUNNING_TOTAL).save(&path)?;
+        assert!(SessionFile::load(&path).is_ok());
+        let bytes = fs::read(&path)?;
+        fs::write(&path, &bytes[..bytes.len() - 1])?;
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            SessionFile::load(&path).unwrap_err().kind()
+        );
+        fs::write(&path, b"ICCORE01")?;
+        assert_eq!(
+            "not a session file",
+            SessionFile::load(&path).unwrap_err().to_string()
+        );
+        fs::remove_file(&path)
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Emulator, EmulatorMemoryType, EmulatorResult, QueueInput};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+// An ASCII machine as a std::io stream: writes queue bytes as input and
+// reads run the machine for output bytes. A read stops early once the
+// machine wants input, and fails with WouldBlock if it had nothing to give
+// yet; after the machine halts reads return 0.
+pub struct EmulatorStream {
+    emulator: Emulator<QueueInput>,
+}
+
+impl EmulatorStream {
+    pub fn new(emulator: Emulator<QueueInput>) -> EmulatorStream {
+        EmulatorStream { emulator }
+    }
+
+    pub fn emulator(&self) -> &Emulator<QueueInput> {
+        &self.emulator
+    }
+
+    pub fn into_inner(self) -> Emulator<QueueInput> {
+        self.emulator
+    }
+}
+
+impl Read for EmulatorStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() && !self.emulator.is_halted() {
+            match self.emulator.step() {
+                Ok(EmulatorResult::Done) => break,
+                Ok(EmulatorResult::Success | EmulatorResult::Watchpoint { .. }) => {}
+                Ok(EmulatorResult::SuccessWithValue(value)) => {
+                    buf[read] = byte(value)?;
+                    read += 1;
+                }
+                Ok(EmulatorResult::NeedsInput) if read == 0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        self.emulator.missing_input().to_string(),
+                    ))
+                }
+                Ok(EmulatorResult::NeedsInput) => break,
+                Err(error) => return Err(io::Error::other(error.to_string())),
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Write for EmulatorStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.emulator
+            .extend(buf.iter().map(|&byte| EmulatorMemoryType::from(byte)));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn byte(value: EmulatorMemoryType) -> io::Result<u8> {
+    u8::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("output {} is not a byte", value),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_output() -> io::Result<()> {
+        let program = [104, 72, 104, 105, 104, 10, 99];
+        let mut stream = EmulatorStream::new(Emulator::with_inputs(&program, []));
+        let mut output = Vec::new();
+        io::copy(&mut stream, &mut output)?;
+        assert_eq!(b"Hi\n".to_vec(), output);
+        assert!(stream.emulator().is_halted());
+        assert_eq!(0, stream.read(&mut [0; 4])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reads_stop_for_input() -> io::Result<()> {
+        // Prints '>' and then echoes input forever.
+        let program = [104, 62, 3, 11, 4, 11, 1105, 1, 2, 0, 0, 0];
+        let mut stream = EmulatorStream::new(Emulator::with_inputs(&program, []));
+        let mut buf = [0; 8];
+        assert_eq!(1, stream.read(&mut buf)?);
+        assert_eq!(b'>', buf[0]);
+        assert_eq!(
+            io::ErrorKind::WouldBlock,
+            stream.read(&mut buf).unwrap_err().kind()
+        );
+
+        stream.write_all(b"ok")?;
+        assert_eq!(2, stream.read(&mut buf)?);
+        assert_eq!(b"ok", &buf[..2]);
+        assert_eq!(
+            io::ErrorKind::WouldBlock,
+            stream.read(&mut buf).unwrap_err().kind()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_byte_output() {
+        let mut stream = EmulatorStream::new(Emulator::with_inputs(&[104, 1000, 99], []));
+        let error = stream.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+}
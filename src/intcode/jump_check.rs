@@ -0,0 +1,200 @@
+use super::{opcode_of, EmulatorMemoryType, MemoryCell, ParameterKind, OPCODE_TABLE};
+use std::collections::HashMap;
+
+// A taken jump whose target came from a cell the program had overwritten.
+// Usually a hand-written program storing into the wrong cell and sending
+// execution into data, but programs that compute their jump targets do
+// this on purpose, so it is only ever a warning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JumpTargetWarning<C = EmulatorMemoryType> {
+    pub jump_address: usize,
+    pub target_cell: usize,
+    // The instruction that last wrote target_cell.
+    pub writer_address: usize,
+    pub target: C,
+}
+
+impl<C: MemoryCell> std::fmt::Display for JumpTargetWarning<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Jump at {} went to {}, read from cell {} which the instruction at {} overwrote",
+            self.jump_address, self.target, self.target_cell, self.writer_address
+        )
+    }
+}
+
+// Collected by an Emulator once enable_jump_target_check() has been called.
+// Only writes from then on count as modifications.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpTargetCheck<C = EmulatorMemoryType> {
+    // The address of the last instruction to write each cell.
+    last_writer: HashMap<usize, usize>,
+    warnings: Vec<JumpTargetWarning<C>>,
+}
+
+// Derived, it would only be Default for cells that are.
+impl<C> Default for JumpTargetCheck<C> {
+    fn default() -> JumpTargetCheck<C> {
+        JumpTargetCheck {
+            last_writer: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl<C: MemoryCell> JumpTargetCheck<C> {
+    pub fn new() -> JumpTargetCheck<C> {
+        JumpTargetCheck::default()
+    }
+
+    // parameters are as recorded in InstructionRecord, and memory and
+    // relative_base are as the instruction left them.
+    pub(crate) fn record(
+        &mut self,
+        memory: &[C],
+        relative_base: C,
+        instruction_pointer: usize,
+        instruction: C,
+        parameters: &[C],
+        jumped: bool,
+    ) {
+        let info = match OPCODE_TABLE
+            .iter()
+            .find(|info| info.code == opcode_of(instruction))
+        {
+            Some(info) => info,
+            None => return,
+        };
+
+        // The target is a jump's last parameter. Immediate targets are part
+        // of the instruction, so only position and relative ones are read
+        // from a cell that could have been overwritten.
+        if info.jumps && jumped {
+            let index = info.parameters.len() - 1;
+            let ten = C::from(10);
+            let place = (0..index + 2).fold(C::from(1), |place, _| place * ten);
+            let mode: Option<usize> = (instruction / place % ten).try_into().ok();
+            let base = match mode {
+                Some(0) => Some(C::from(0)),
+                Some(2) => Some(relative_base),
+                _ => None,
+            };
+            let target_cell = base
+                .and_then(|base| {
+                    memory
+                        .get(instruction_pointer + 1 + index)?
+                        .checked_add(base)
+                })
+                .and_then(|cell| cell.try_into().ok());
+            if let Some(target_cell) = target_cell {
+                if let Some(&writer_address) = self.last_writer.get(&target_cell) {
+                    self.warnings.push(JumpTargetWarning {
+                        jump_address: instruction_pointer,
+                        target_cell,
+                        writer_address,
+                        target: parameters[index],
+                    });
+                }
+            }
+        }
+
+        for (parameter, &address) in info.parameters.iter().zip(parameters) {
+            if let (ParameterKind::Write, Ok(address)) = (parameter.kind, address.try_into()) {
+                self.last_writer.insert(address, instruction_pointer);
+            }
+        }
+    }
+
+    pub fn warnings(&self) -> &[JumpTargetWarning<C>] {
+        &self.warnings
+    }
+
+    // Hands over the warnings so far, for logging as they happen.
+    pub fn take_warnings(&mut self) -> Vec<JumpTargetWarning<C>> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{Emulator, EmulatorError};
+
+    fn warnings(
+        program: &[EmulatorMemoryType],
+        inputs: &[EmulatorMemoryType],
+    ) -> Result<Vec<JumpTargetWarning>, EmulatorError> {
+        let mut emulator = Emulator::with_inputs(program, inputs.to_vec());
+        emulator.enable_jump_target_check();
+        emulator.run_to_completion()?;
+        Ok(emulator.jump_target_check().unwrap().warnings().to_vec())
+    }
+
+    #[test]
+    fn test_overwritten_target_warns_once() -> Result<(), EmulatorError> {
+        // Stores 10 into cell 14, then jumps through it. The second jump,
+        // through the untouched cell 15, is fine.
+        let program = [1101, 0, 10, 14, 105, 1, 14, 99, 0, 0, 105, 1, 15, 99, 0, 13];
+        let found = warnings(&program, &[])?;
+        assert_eq!(
+            vec![JumpTargetWarning {
+                jump_address: 4,
+                target_cell: 14,
+                writer_address: 0,
+                target: 10,
+            }],
+            found
+        );
+        assert_eq!(
+            "Jump at 4 went to 10, read from cell 14 which the instruction at 0 overwrote",
+            found[0].to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_targets_and_untaken_jumps() -> Result<(), EmulatorError> {
+        // Input goes to cell 14. The jump-if-false through it isn't taken,
+        // as cell 15 holds 1, but the relative jump through it is.
+        let program = [3, 14, 6, 15, 14, 109, 4, 2105, 1, 10, 99, 99, 99, 99, 0, 1];
+        let found = warnings(&program, &[12])?;
+        assert_eq!(
+            vec![(7, 14, 0, 12)],
+            found
+                .iter()
+                .map(|warning| (
+                    warning.jump_address,
+                    warning.target_cell,
+                    warning.writer_address,
+                    warning.target
+                ))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_day_5_examples_do_not_warn() -> Result<(), EmulatorError> {
+        let programs: [&[EmulatorMemoryType]; 5] = [
+            &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            &[3, 3, 1107, -1, 8, 3, 4, 3, 99],
+            &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+            &[
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+        ];
+        for program in programs.iter() {
+            for &input in [0, 7, 8, 9].iter() {
+                assert_eq!(
+                    Vec::<JumpTargetWarning>::new(),
+                    warnings(program, &[input])?
+                );
+            }
+        }
+        Ok(())
+    }
+}
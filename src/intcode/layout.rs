@@ -0,0 +1,228 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub name: String,
+    pub range: Range<usize>,
+    pub read_only: bool,
+    // Only code segments are decoded by the disassembler.
+    pub code: bool,
+}
+
+impl Segment {
+    pub fn new(name: &str, range: Range<usize>) -> Segment {
+        Segment {
+            name: name.to_string(),
+            range,
+            read_only: false,
+            code: false,
+        }
+    }
+
+    pub fn read_only(mut self) -> Segment {
+        self.read_only = true;
+        self
+    }
+
+    pub fn code(mut self) -> Segment {
+        self.code = true;
+        self
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.range.contains(&address)
+    }
+}
+
+impl std::fmt::Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}..{}", self.name, self.range.start, self.range.end)?;
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+        if self.code {
+            write!(f, ":code")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    Overlap { first: String, second: String },
+    EmptyRange { name: String },
+    Malformed(String),
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LayoutError::Overlap { first, second } => {
+                write!(f, "Segments '{}' and '{}' overlap", first, second)
+            }
+            LayoutError::EmptyRange { name } => write!(f, "Segment '{}' is empty", name),
+            LayoutError::Malformed(text) => write!(
+                f,
+                "'{}' is not a segment (expected name:start..end[:ro][:code])",
+                text
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+// Named, non-overlapping ranges of memory, kept sorted by start address.
+// Addresses outside every segment are unconstrained.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryLayout {
+    segments: Vec<Segment>,
+}
+
+impl MemoryLayout {
+    pub fn new(mut segments: Vec<Segment>) -> Result<MemoryLayout, LayoutError> {
+        if let Some(segment) = segments.iter().find(|segment| segment.range.is_empty()) {
+            return Err(LayoutError::EmptyRange {
+                name: segment.name.clone(),
+            });
+        }
+        segments.sort_by_key(|segment| segment.range.start);
+        if let Some(pair) = segments
+            .windows(2)
+            .find(|pair| pair[0].range.end > pair[1].range.start)
+        {
+            return Err(LayoutError::Overlap {
+                first: pair[0].name.clone(),
+                second: pair[1].name.clone(),
+            });
+        }
+        Ok(MemoryLayout { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn segment_at(&self, address: usize) -> Option<&Segment> {
+        self.segments
+            .iter()
+            .find(|segment| segment.contains(address))
+    }
+
+    pub fn is_read_only(&self, address: usize) -> bool {
+        self.segment_at(address)
+            .is_some_and(|segment| segment.read_only)
+    }
+
+    pub fn has_code_segments(&self) -> bool {
+        self.segments.iter().any(|segment| segment.code)
+    }
+}
+
+fn parse_segment(text: &str) -> Result<Segment, LayoutError> {
+    let malformed = || LayoutError::Malformed(text.to_string());
+    let mut fields = text.split(':').map(str::trim);
+    let name = fields
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(malformed)?;
+    let mut bounds = fields.next().ok_or_else(malformed)?.splitn(2, "..");
+    let start = bounds.next().and_then(|start| start.parse().ok());
+    let end = bounds.next().and_then(|end| end.parse().ok());
+    let mut segment = match (start, end) {
+        (Some(start), Some(end)) => Segment::new(name, start..end),
+        _ => return Err(malformed()),
+    };
+    for flag in fields {
+        match flag {
+            "ro" => segment.read_only = true,
+            "code" => segment.code = true,
+            _ => return Err(malformed()),
+        }
+    }
+    Ok(segment)
+}
+
+// Comma separated segments, e.g. "code:0..20:code,constants:20..24:ro".
+impl std::str::FromStr for MemoryLayout {
+    type Err = LayoutError;
+
+    fn from_str(s: &str) -> Result<MemoryLayout, LayoutError> {
+        let segments = s
+            .split(',')
+            .filter(|segment| !segment.trim().is_empty())
+            .map(parse_segment)
+            .collect::<Result<Vec<_>, _>>()?;
+        MemoryLayout::new(segments)
+    }
+}
+
+impl std::fmt::Display for MemoryLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| segment.to_string())
+            .collect();
+        write!(f, "{}", segments.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let layout: MemoryLayout = "scratch:30..40, code:0..20:code,constants:20..30:ro"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            &[
+                Segment::new("code", 0..20).code(),
+                Segment::new("constants", 20..30).read_only(),
+                Segment::new("scratch", 30..40),
+            ],
+            layout.segments()
+        );
+        assert_eq!(
+            "code:0..20:code,constants:20..30:ro,scratch:30..40",
+            layout.to_string()
+        );
+        assert_eq!(
+            Some("constants"),
+            layout.segment_at(29).map(|s| s.name.as_str())
+        );
+        assert_eq!(None, layout.segment_at(40));
+        assert!(layout.is_read_only(20));
+        assert!(!layout.is_read_only(19));
+    }
+
+    #[test]
+    fn test_overlap_is_rejected() {
+        assert_eq!(
+            Err(LayoutError::Overlap {
+                first: "code".to_string(),
+                second: "data".to_string()
+            }),
+            "data:10..20,code:0..11".parse::<MemoryLayout>()
+        );
+        assert!("code:0..10,data:10..20".parse::<MemoryLayout>().is_ok());
+    }
+
+    #[test]
+    fn test_malformed_segments_are_rejected() {
+        for text in ["code", "code:0-10", ":0..10", "code:0..10:rw", "code:a..10"].iter() {
+            assert_eq!(
+                Err(LayoutError::Malformed(text.to_string())),
+                text.parse::<MemoryLayout>()
+            );
+        }
+        assert_eq!(
+            Err(LayoutError::EmptyRange {
+                name: "code".to_string()
+            }),
+            "code:5..5".parse::<MemoryLayout>()
+        );
+    }
+}
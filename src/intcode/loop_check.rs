@@ -0,0 +1,84 @@
+use super::{Memory, MemoryCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+// How many steps apart states are compared, unless the emulator is told
+// otherwise.
+pub const DEFAULT_LOOP_CHECK_INTERVAL: u64 = 1000;
+
+// Hashes of the states an Emulator was in every interval steps, since it
+// last read input or printed output. A state after either can match one
+// from before without the machine looping, as what happens next depends
+// on the input, or on who is reading the output. States are only kept as
+// 64 bit hashes, so two different states matching is possible but
+// vanishingly unlikely.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LoopCheck {
+    interval: u64,
+    seen: HashSet<u64>,
+}
+
+impl LoopCheck {
+    pub(crate) fn new(interval: u64) -> LoopCheck {
+        LoopCheck {
+            interval: interval.max(1),
+            seen: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.seen.clear();
+    }
+
+    // True if the machine was in this state at an earlier check. steps is
+    // how many instructions have executed, and only every interval'th is
+    // checked.
+    pub(crate) fn repeats<C: MemoryCell>(
+        &mut self,
+        steps: u64,
+        instruction_pointer: usize,
+        relative_base: C,
+        memory: &Memory<C>,
+    ) -> bool {
+        if !steps.is_multiple_of(self.interval) {
+            return false;
+        }
+        let mut hasher = DefaultHasher::new();
+        instruction_pointer.hash(&mut hasher);
+        relative_base.hash(&mut hasher);
+        memory[..].hash(&mut hasher);
+        memory.sparse_cells().hash(&mut hasher);
+        !self.seen.insert(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeats() {
+        let mut memory = Memory::from(vec![1105, 1, 0, 99]);
+        let mut check = LoopCheck::new(2);
+        assert!(!check.repeats(1, 0, 0, &memory));
+        assert!(!check.repeats(2, 0, 0, &memory));
+        // Steps between checks aren't compared.
+        assert!(!check.repeats(3, 0, 0, &memory));
+        assert!(check.repeats(4, 0, 0, &memory));
+
+        // Any part of the state changing makes it new.
+        assert!(!check.repeats(6, 1, 0, &memory));
+        assert!(!check.repeats(8, 0, 5, &memory));
+        memory[3] = 98;
+        assert!(!check.repeats(10, 0, 0, &memory));
+
+        check.clear();
+        assert!(!check.repeats(12, 0, 0, &memory));
+        assert_eq!(1, LoopCheck::new(0).interval());
+    }
+}
@@ -0,0 +1,255 @@
+use super::{AddrContext, Address, EmulatorError, EmulatorMemoryType, MemoryCell};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+// Memory grows when a parameter addresses a cell past the end, up to this
+// many cells unless the limit is changed. The cap turns a garbage address
+// into an error rather than an attempt to allocate it: MemoryLimitExceeded
+// for a write, InvalidMemoryLocation for a read.
+pub const MEMORY_LIMIT: usize = 1 << 24;
+
+// An emulator's memory. It derefs to the contiguous cells, which hold the
+// program and, by default, grow to reach any cell written past the end.
+// With the sparse backend the contiguous cells stay the size of the
+// program, and cells written past them are kept in a map instead, so a
+// write far away costs one entry rather than everything up to it. Only the
+// contiguous cells can be executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory<C = EmulatorMemoryType> {
+    cells: Vec<C>,
+    sparse: Option<HashMap<usize, C>>,
+    // None for no limit. Sparse memory ignores it.
+    limit: Option<usize>,
+}
+
+impl<C: MemoryCell> Memory<C> {
+    // Memory::from, for cells of any MemoryCell.
+    pub fn new(cells: Vec<C>) -> Memory<C> {
+        Memory {
+            cells,
+            sparse: None,
+            limit: Some(MEMORY_LIMIT),
+        }
+    }
+
+    pub fn sparse(cells: Vec<C>) -> Memory<C> {
+        Memory {
+            cells,
+            sparse: Some(HashMap::new()),
+            limit: None,
+        }
+    }
+
+    // Addresses from limit on can't be written, or read past the end.
+    pub fn limit(&self) -> Option<usize> {
+        if self.is_sparse() {
+            None
+        } else {
+            self.limit
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        self.sparse.is_some()
+    }
+
+    // Cells that exist: the contiguous ones plus any sparse ones written.
+    pub fn touched_cells(&self) -> usize {
+        self.cells.len() + self.sparse.as_ref().map_or(0, HashMap::len)
+    }
+
+    // None for cells that were never written, past the end of memory.
+    pub fn get_cell(&self, index: usize) -> Option<C> {
+        match (self.cells.get(index), &self.sparse) {
+            (Some(&value), _) => Some(value),
+            (None, Some(sparse)) => sparse.get(&index).copied(),
+            (None, None) => None,
+        }
+    }
+
+    // Sparse cells in address order.
+    pub fn sparse_cells(&self) -> Vec<(usize, C)> {
+        let mut cells: Vec<_> = self
+            .sparse
+            .iter()
+            .flatten()
+            .map(|(&index, &value)| (index, value))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    pub fn clear_sparse_cells(&mut self) {
+        if let Some(sparse) = &mut self.sparse {
+            sparse.clear();
+        }
+    }
+
+    // Writes index, growing the contiguous cells or adding a sparse one.
+    pub fn write(&mut self, index: usize, value: C) {
+        *self.cell_mut_at(index) = value;
+    }
+
+    // Like write, but refuses to grow memory past the limit.
+    pub fn checked_write(&mut self, index: usize, value: C) -> Result<(), EmulatorError> {
+        self.check_limit(index)?;
+        self.write(index, value);
+        Ok(())
+    }
+
+    // Cells that already exist can be written wherever the limit is.
+    fn check_limit(&self, index: usize) -> Result<(), EmulatorError> {
+        match self.limit() {
+            Some(limit) if index >= limit && self.get_cell(index).is_none() => {
+                Err(EmulatorError::MemoryLimitExceeded {
+                    requested: index,
+                    limit,
+                    context: None,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn cell_mut_at(&mut self, index: usize) -> &mut C {
+        if index < self.cells.len() {
+            return &mut self.cells[index];
+        }
+        match &mut self.sparse {
+            Some(sparse) => sparse.entry(index).or_insert(C::from(0)),
+            None => {
+                self.cells.resize(index + 1, C::from(0));
+                &mut self.cells[index]
+            }
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<C> {
+        self.cells
+    }
+}
+
+impl<C> Deref for Memory<C> {
+    type Target = Vec<C>;
+
+    fn deref(&self) -> &Vec<C> {
+        &self.cells
+    }
+}
+
+impl<C> DerefMut for Memory<C> {
+    fn deref_mut(&mut self) -> &mut Vec<C> {
+        &mut self.cells
+    }
+}
+
+// Only for EmulatorMemoryType, so that Memory::from(vec![1, 2]) needs no
+// type named.
+impl From<Vec<EmulatorMemoryType>> for Memory {
+    fn from(cells: Vec<EmulatorMemoryType>) -> Memory {
+        Memory::new(cells)
+    }
+}
+
+impl From<&[EmulatorMemoryType]> for Memory {
+    fn from(cells: &[EmulatorMemoryType]) -> Memory {
+        Memory::from(cells.to_vec())
+    }
+}
+
+// Sources read_cell can read from: plain cells, for decoding memory that
+// isn't in an emulator, or an emulator's Memory.
+pub trait Cells<C = EmulatorMemoryType> {
+    // Reading past the end from here on is an error.
+    fn limit(&self) -> Option<usize>;
+    fn cell(&self, index: usize) -> Option<C>;
+}
+
+impl<C: MemoryCell> Cells<C> for [C] {
+    fn limit(&self) -> Option<usize> {
+        Some(MEMORY_LIMIT)
+    }
+
+    fn cell(&self, index: usize) -> Option<C> {
+        self.get(index).copied()
+    }
+}
+
+impl<C: MemoryCell> Cells<C> for Memory<C> {
+    fn limit(&self) -> Option<usize> {
+        Memory::limit(self)
+    }
+
+    fn cell(&self, index: usize) -> Option<C> {
+        self.get_cell(index)
+    }
+}
+
+// Cells past the end read as 0 without growing memory. word is what the
+// parameter resolved to, for the error.
+pub fn read_cell<C: MemoryCell, M: Cells<C> + ?Sized>(
+    memory: &M,
+    address: Address,
+    word: C,
+    context: AddrContext,
+) -> Result<C, EmulatorError> {
+    let index = address.get();
+    match memory.cell(index) {
+        Some(value) => Ok(value),
+        None if memory.limit().is_some_and(|limit| index >= limit) => Err(context.invalid(word)),
+        None => Ok(C::from(0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::AddressPurpose;
+
+    #[test]
+    fn test_cells_past_the_end() -> Result<(), EmulatorError> {
+        let context = AddrContext::parameter(0, 0, AddressPurpose::Write);
+        let mut memory = Memory::from(vec![1, 2, 3]);
+        assert_eq!(
+            Ok(0),
+            read_cell(&memory, Address::try_from_word(7, context)?, 7, context)
+        );
+        assert_eq!(3, memory.len());
+
+        memory.checked_write(5, 9)?;
+        assert_eq!(vec![1, 2, 3, 0, 0, 9], *memory);
+        memory.checked_write(1, 4)?;
+        assert_eq!(vec![1, 4, 3, 0, 0, 9], *memory);
+
+        let too_far = MEMORY_LIMIT as EmulatorMemoryType;
+        let address = Address::try_from_word(too_far, context)?;
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: MEMORY_LIMIT,
+                limit: MEMORY_LIMIT,
+                context: None,
+            }),
+            memory.checked_write(MEMORY_LIMIT, 1)
+        );
+        assert!(read_cell(&memory, address, too_far, context).is_err());
+        assert_eq!(6, memory.len());
+
+        memory.set_limit(Some(4));
+        memory.checked_write(5, 8)?;
+        assert_eq!(
+            Err(EmulatorError::MemoryLimitExceeded {
+                requested: 6,
+                limit: 4,
+                context: None,
+            }),
+            memory.checked_write(6, 8)
+        );
+        memory.set_limit(None);
+        assert!(read_cell(&memory, address, too_far, context).is_ok());
+        Ok(())
+    }
+}
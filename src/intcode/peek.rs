@@ -0,0 +1,269 @@
+use super::{
+    read_cell, relative_address, AddrContext, Address, AddressPurpose, Emulator, EmulatorError,
+    EmulatorMemoryType, MemoryCell, OpCodeInfo, ParameterKind, ParameterMode, OPCODE_TABLE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodedParameter {
+    pub kind: ParameterKind,
+    // The parameter cell as written.
+    pub word: EmulatorMemoryType,
+    pub mode: ParameterMode,
+    // For Read parameters, the value the instruction would read. For Write
+    // parameters, the address it would write to.
+    pub resolved: EmulatorMemoryType,
+    // For Write parameters, what the destination holds now.
+    pub current: Option<EmulatorMemoryType>,
+}
+
+// The instruction at the instruction pointer, decoded without running it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedInstruction {
+    pub address: usize,
+    pub instruction: EmulatorMemoryType,
+    pub info: &'static OpCodeInfo,
+    pub parameters: Vec<DecodedParameter>,
+}
+
+impl DecodedInstruction {
+    pub fn opcode(&self) -> EmulatorMemoryType {
+        self.info.code
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.info.mnemonic
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    // The same values step records in InstructionRecord::parameters.
+    pub fn resolved_parameters(&self) -> Vec<EmulatorMemoryType> {
+        self.parameters
+            .iter()
+            .map(|parameter| parameter.resolved)
+            .collect()
+    }
+}
+
+// Like the disassembler, with the values position and relative mode
+// parameters resolve to.
+impl std::fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:>5}: {}", self.address, self.info.mnemonic)?;
+        for parameter in self.parameters.iter() {
+            let value = parameter.current.unwrap_or(parameter.resolved);
+            match parameter.mode {
+                ParameterMode::Immediate => write!(f, " {}", parameter.word)?,
+                ParameterMode::Relative => write!(f, " [rb{:+}]={}", parameter.word, value)?,
+                ParameterMode::Position => write!(f, " [{}]={}", parameter.word, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Decodes the instruction at instruction_pointer, failing with the error
+// executing it would report first.
+pub fn decode_at(
+    memory: &[EmulatorMemoryType],
+    instruction_pointer: usize,
+    relative_base: EmulatorMemoryType,
+) -> Result<DecodedInstruction, EmulatorError> {
+    let instruction =
+        *memory
+            .get(instruction_pointer)
+            .ok_or(EmulatorError::InstructionPointerOutOfBounds {
+                position: instruction_pointer,
+                context: None,
+            })?;
+    let info = OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == instruction % 100)
+        .ok_or(EmulatorError::InvalidInstruction {
+            value_found: instruction.widen(),
+            position: instruction_pointer,
+            context: None,
+        })?;
+    let parameter_count = info.parameters.len();
+    if instruction_pointer + 1 + parameter_count > memory.len() {
+        return Err(EmulatorError::NotEnoughParametersForInstruction {
+            instruction: info.code.widen(),
+            expected: parameter_count,
+            found: memory.len().saturating_sub(instruction_pointer + 1),
+            context: None,
+        });
+    }
+
+    let mut parameters = Vec::with_capacity(parameter_count);
+    let mut mode_digits = instruction / 100;
+    for (index, parameter) in info.parameters.iter().enumerate() {
+        let word = memory[instruction_pointer + index + 1];
+        let mode = mode_digits % 10;
+        mode_digits /= 10;
+        let purpose = match parameter.kind {
+            ParameterKind::Read => AddressPurpose::Read,
+            ParameterKind::Write => AddressPurpose::Write,
+        };
+        let context = AddrContext::parameter(instruction_pointer, index, purpose);
+        let decoded = match (mode, parameter.kind) {
+            (0, kind) | (2, kind) => {
+                let relative = mode == 2;
+                let effective = if relative {
+                    relative_address(word, relative_base, context)?
+                } else {
+                    word
+                };
+                let address = Address::try_from_word(effective, context)?;
+                let value = read_cell(memory, address, effective, context)?;
+                let (resolved, current) = match kind {
+                    ParameterKind::Read => (value, None),
+                    ParameterKind::Write => (effective, Some(value)),
+                };
+                DecodedParameter {
+                    kind,
+                    word,
+                    mode: if relative {
+                        ParameterMode::Relative
+                    } else {
+                        ParameterMode::Position
+                    },
+                    resolved,
+                    current,
+                }
+            }
+            (1, ParameterKind::Read) => DecodedParameter {
+                kind: ParameterKind::Read,
+                word,
+                mode: ParameterMode::Immediate,
+                resolved: word,
+                current: None,
+            },
+            (1, ParameterKind::Write) => {
+                return Err(EmulatorError::UnexpectedParameterModeForWritable {
+                    value_found: 1,
+                    position: instruction_pointer + index + 1,
+                    context: None,
+                })
+            }
+            (mode, _) => {
+                return Err(EmulatorError::InvalidParameterMode {
+                    value_found: mode.widen(),
+                    position: instruction_pointer,
+                    context: None,
+                })
+            }
+        };
+        parameters.push(decoded);
+    }
+
+    Ok(DecodedInstruction {
+        address: instruction_pointer,
+        instruction,
+        info,
+        parameters,
+    })
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    // What step would execute next, without executing it.
+    pub fn peek_next(&self) -> Result<DecodedInstruction, EmulatorError> {
+        decode_at(&self.memory, self.instruction_pointer, self.relative_base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{emulator_with_empty_input, EmulatorResult};
+
+    // Steps through program, checking every peek against what the step then
+    // recorded or the error it returned.
+    fn assert_peeks_match_steps(program: &[EmulatorMemoryType], inputs: &[EmulatorMemoryType]) {
+        let mut emulator = Emulator::with_inputs(program, inputs.to_vec());
+        loop {
+            let before = emulator.memory().to_vec();
+            let peeked = emulator.peek_next();
+            assert_eq!(before, emulator.memory());
+            match (peeked, emulator.step()) {
+                // Decoding can't know whether input is waiting.
+                (Ok(decoded), Ok(EmulatorResult::NeedsInput)) => {
+                    assert!(decoded.info.reads_input);
+                    return;
+                }
+                (Ok(decoded), Ok(result)) => {
+                    let record = emulator.recent_history().last().unwrap();
+                    assert_eq!(record.instruction_pointer, decoded.address);
+                    assert_eq!(record.instruction, decoded.instruction);
+                    assert_eq!(record.parameters, decoded.resolved_parameters());
+                    if result.is_done() {
+                        return;
+                    }
+                }
+                (Err(peek_error), Err(step_error)) => {
+                    assert_eq!(step_error, peek_error);
+                    return;
+                }
+                (peeked, stepped) => panic!("peek {:?} but step {:?}", peeked, stepped),
+            }
+        }
+    }
+
+    #[test]
+    fn test_peek_matches_step() {
+        assert_peeks_match_steps(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]);
+        assert_peeks_match_steps(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], &[8]);
+        assert_peeks_match_steps(
+            &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            &[0],
+        );
+        // Garbage, a bad mode, an immediate destination, addresses past the
+        // end and running off the end.
+        assert_peeks_match_steps(&[104, 5, 42, 0], &[]);
+        assert_peeks_match_steps(&[304, 5, 99, 0], &[]);
+        assert_peeks_match_steps(&[11101, 1, 1, 1, 99], &[]);
+        assert_peeks_match_steps(&[1, 50, 0, 0, 99], &[]);
+        assert_peeks_match_steps(&[1101, 1, 1, 50, 4, 50, 99], &[]);
+        assert_peeks_match_steps(&[1101, 1, 1, 5, 99, 0, 0], &[]);
+        assert_peeks_match_steps(&[1105, 1, 9, 0], &[]);
+        assert_peeks_match_steps(&[3, 0, 99], &[]);
+        // Relative reads and writes, then a relative address below zero.
+        assert_peeks_match_steps(&[109, 5, 22201, 1, 2, 0, 204, -5, 99, 0], &[]);
+        assert_peeks_match_steps(&[109, -1, 204, 0, 99], &[]);
+    }
+
+    #[test]
+    fn test_peek_display() -> Result<(), EmulatorError> {
+        let mut emulator = emulator_with_empty_input(&[1001, 5, 7, 6, 99, 30, 2]);
+        assert_eq!(
+            "    0: Add [5]=30 7 [6]=2",
+            emulator.peek_next()?.to_string()
+        );
+        emulator.step()?;
+        assert_eq!("    4: End", emulator.peek_next()?.to_string());
+        emulator.step()?;
+        assert_eq!("    4: End", emulator.peek_next()?.to_string());
+
+        let mut emulator = emulator_with_empty_input(&[109, 4, 21201, 2, 1, -3, 99, 0]);
+        emulator.step()?;
+        assert_eq!(
+            "    2: Add [rb+2]=99 1 [rb-3]=4",
+            emulator.peek_next()?.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_past_the_end() {
+        let mut emulator = emulator_with_empty_input(&[1106, 0, 4, 0]);
+        assert!(emulator.step().is_ok());
+        assert_eq!(
+            Err(EmulatorError::InstructionPointerOutOfBounds {
+                position: 4,
+                context: None,
+            }),
+            emulator.peek_next().map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use super::{EmulatorError, EmulatorMemoryType};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+// A queue shared between clones: values pushed through any handle are read
+// through any other, in order. Used as an emulator's input, reading from an
+// empty pipe looks like the end of input, so step returns NeedsInput; it
+// carries on from whatever is pushed later.
+#[derive(Debug, Clone, Default)]
+pub struct Pipe {
+    queue: Rc<RefCell<VecDeque<EmulatorMemoryType>>>,
+}
+
+impl Pipe {
+    pub fn new() -> Pipe {
+        Pipe::default()
+    }
+
+    pub fn push(&self, value: EmulatorMemoryType) {
+        self.queue.borrow_mut().push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.borrow().is_empty()
+    }
+}
+
+impl Iterator for Pipe {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.queue.borrow_mut().pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{Emulator, EmulatorResult};
+
+    #[test]
+    fn test_interleaved_push_and_read() {
+        let pipe = Pipe::new();
+        let mut reader = pipe.clone();
+        pipe.push(1);
+        pipe.push(2);
+        assert_eq!(Some(Ok(1)), reader.next());
+        pipe.push(3);
+        assert_eq!(2, pipe.len());
+        assert_eq!(Some(Ok(2)), reader.next());
+        assert_eq!(Some(Ok(3)), reader.next());
+        assert!(pipe.is_empty());
+    }
+
+    #[test]
+    fn test_empty_pipe_resumes_after_push() -> Result<(), EmulatorError> {
+        let pipe = Pipe::new();
+        let mut reader = pipe.clone();
+        assert_eq!(None, reader.next());
+        pipe.push(4);
+        assert_eq!(Some(Ok(4)), reader.next());
+        assert_eq!(None, reader.next());
+
+        let mut emulator = Emulator::new(&[3, 0, 4, 0, 99], pipe.clone());
+        assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+        pipe.push(5);
+        assert_eq!(vec![5], emulator.run_collecting_outputs()?);
+        Ok(())
+    }
+}
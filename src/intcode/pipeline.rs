@@ -0,0 +1,150 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, Pipe};
+use std::iter::once;
+
+type Signal = Box<dyn Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>>;
+
+// Runs one copy of program per phase, each reading its phase and then the
+// previous stage's outputs; the first stage reads initial instead. Returns
+// the last stage's final output, or None if it printed nothing. With no
+// phases the initial signal comes straight back.
+pub fn run_chain(
+    program: &[EmulatorMemoryType],
+    phases: &[EmulatorMemoryType],
+    initial: EmulatorMemoryType,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    let input: Signal = Box::new(once(Ok(initial)));
+    let outputs = phases.iter().fold(input, |input, &phase| {
+        Box::new(Emulator::boxed(program, once(Ok(phase)).chain(input)).into_output_iter())
+    });
+    last_output(outputs, None)
+}
+
+// Like run_chain, but the last stage's outputs are also fed back to the
+// first stage, until every stage halts.
+pub fn run_feedback_loop(
+    program: &[EmulatorMemoryType],
+    phases: &[EmulatorMemoryType],
+    initial: EmulatorMemoryType,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    let (&first, rest) = match phases.split_first() {
+        Some(split) => split,
+        None => return Ok(Some(initial)),
+    };
+    let loopback = Pipe::new();
+    loopback.push(first);
+    loopback.push(initial);
+    let outputs: Signal = Box::new(Emulator::new(program, loopback.clone()).into_output_iter());
+    let outputs = rest.iter().fold(outputs, |input, &phase| {
+        Box::new(Emulator::boxed(program, once(Ok(phase)).chain(input)).into_output_iter())
+    });
+    last_output(outputs, Some(&loopback))
+}
+
+// Stops at the first error from any stage.
+fn last_output(
+    outputs: Signal,
+    loopback: Option<&Pipe>,
+) -> Result<Option<EmulatorMemoryType>, EmulatorError> {
+    let mut last = None;
+    for output in outputs {
+        let output = output?;
+        if let Some(loopback) = loopback {
+            loopback.push(output);
+        }
+        last = Some(output);
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_examples() -> Result<(), EmulatorError> {
+        let examples: [(
+            &[EmulatorMemoryType],
+            [EmulatorMemoryType; 5],
+            EmulatorMemoryType,
+        ); 3] = [
+            (
+                &[
+                    3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+                ],
+                [4, 3, 2, 1, 0],
+                43210,
+            ),
+            (
+                &[
+                    3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23,
+                    23, 4, 23, 99, 0, 0,
+                ],
+                [0, 1, 2, 3, 4],
+                54321,
+            ),
+            (
+                &[
+                    3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7,
+                    33, 1, 33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
+                ],
+                [1, 0, 4, 3, 2],
+                65210,
+            ),
+        ];
+        for (program, phases, thrust) in examples.iter() {
+            assert_eq!(Some(*thrust), run_chain(program, phases, 0)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_feedback_loop_examples() -> Result<(), EmulatorError> {
+        let examples: [(
+            &[EmulatorMemoryType],
+            [EmulatorMemoryType; 5],
+            EmulatorMemoryType,
+        ); 2] = [
+            (
+                &[
+                    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001,
+                    28, -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+                ],
+                [9, 8, 7, 6, 5],
+                139_629_729,
+            ),
+            (
+                &[
+                    3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26,
+                    1001, 54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55,
+                    2, 53, 55, 53, 4, 53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+                ],
+                [9, 7, 8, 5, 6],
+                18216,
+            ),
+        ];
+        for (program, phases, thrust) in examples.iter() {
+            assert_eq!(Some(*thrust), run_feedback_loop(program, phases, 0)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stage_count_and_errors() {
+        // Adds the phase to the signal.
+        let add_phase = [3, 11, 3, 12, 1, 11, 12, 12, 4, 12, 99, 0, 0];
+        assert_eq!(Ok(Some(7)), run_chain(&add_phase, &[3, 4], 0));
+        assert_eq!(Ok(Some(5)), run_chain(&add_phase, &[], 5));
+        assert_eq!(Ok(Some(5)), run_feedback_loop(&add_phase, &[], 5));
+
+        // Prints nothing, so a second stage runs out of input.
+        let silent = [3, 0, 3, 0, 99];
+        assert_eq!(Ok(None), run_chain(&silent, &[1], 0));
+        assert_eq!(
+            Err(EmulatorError::InputNonExistent {
+                position: 2,
+                context: None,
+            }),
+            run_chain(&silent, &[1, 2], 0)
+        );
+    }
+}
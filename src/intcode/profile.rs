@@ -0,0 +1,184 @@
+use super::stats::mnemonic;
+use super::EmulatorMemoryType;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+// Time spent in one opcode. Only sampled executions are timed, so time
+// covers sampled of the calls.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OpcodeProfile {
+    pub calls: u64,
+    pub sampled: u64,
+    pub time: Duration,
+}
+
+impl OpcodeProfile {
+    // time scaled up to every call, assuming the samples are typical.
+    pub fn estimated_time(&self) -> Duration {
+        if self.sampled == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = self.time.as_nanos() * u128::from(self.calls) / u128::from(self.sampled);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+// Collected by an Emulator once attach_profiler() has been called. Every
+// instruction is counted, but only every sample_every'th is timed, as
+// reading the clock costs about as much as a cheap instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profiler {
+    sample_every: u64,
+    steps: u64,
+    opcodes: BTreeMap<EmulatorMemoryType, OpcodeProfile>,
+}
+
+impl Profiler {
+    pub fn new(sample_every: u64) -> Profiler {
+        Profiler {
+            sample_every: sample_every.max(1),
+            steps: 0,
+            opcodes: BTreeMap::new(),
+        }
+    }
+
+    pub fn sample_every(&self) -> u64 {
+        self.sample_every
+    }
+
+    // When the next instruction started, if it is one to time.
+    pub(crate) fn start(&self) -> Option<Instant> {
+        if self.steps.is_multiple_of(self.sample_every) {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    // elapsed is None for instructions that weren't timed.
+    pub(crate) fn record(&mut self, opcode: EmulatorMemoryType, elapsed: Option<Duration>) {
+        self.steps += 1;
+        let profile = self.opcodes.entry(opcode).or_default();
+        profile.calls += 1;
+        if let Some(elapsed) = elapsed {
+            profile.sampled += 1;
+            profile.time += elapsed;
+        }
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let mut opcodes: Vec<(EmulatorMemoryType, OpcodeProfile)> = self
+            .opcodes
+            .iter()
+            .map(|(&opcode, &profile)| (opcode, profile))
+            .collect();
+        opcodes.sort_by_key(|&(opcode, profile)| (Reverse(profile.estimated_time()), opcode));
+        ProfileReport {
+            total_steps: self.steps,
+            sample_every: self.sample_every,
+            opcodes,
+        }
+    }
+}
+
+// A Profiler's findings, the opcodes taking the most time first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    pub total_steps: u64,
+    pub sample_every: u64,
+    pub opcodes: Vec<(EmulatorMemoryType, OpcodeProfile)>,
+}
+
+impl ProfileReport {
+    pub fn total_time(&self) -> Duration {
+        self.opcodes
+            .iter()
+            .map(|(_, profile)| profile.estimated_time())
+            .sum()
+    }
+}
+
+// One line per opcode with its calls and estimated time, then the totals.
+impl std::fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.sample_every > 1 {
+            writeln!(f, "timing 1 in {} instructions", self.sample_every)?;
+        }
+        for (opcode, profile) in self.opcodes.iter() {
+            writeln!(
+                f,
+                "{:<18} {:>10} {:>12.3?}",
+                mnemonic(*opcode),
+                profile.calls,
+                profile.estimated_time()
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<18} {:>10} {:>12.3?}",
+            "total",
+            self.total_steps,
+            self.total_time()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(calls: u64, sampled: u64, millis: u64) -> OpcodeProfile {
+        OpcodeProfile {
+            calls,
+            sampled,
+            time: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_estimated_time() {
+        assert_eq!(
+            Duration::from_millis(40),
+            profile(8, 2, 10).estimated_time()
+        );
+        assert_eq!(Duration::ZERO, profile(3, 0, 0).estimated_time());
+    }
+
+    #[test]
+    fn test_sampling() {
+        let mut profiler = Profiler::new(3);
+        for &opcode in [1, 1, 5, 1, 99].iter() {
+            let elapsed = profiler.start().map(|started| started.elapsed());
+            profiler.record(opcode, elapsed);
+        }
+        let report = profiler.report();
+        assert_eq!(5, report.total_steps);
+        let counts: BTreeMap<_, _> = report
+            .opcodes
+            .iter()
+            .map(|(opcode, profile)| (*opcode, (profile.calls, profile.sampled)))
+            .collect();
+        // The first and fourth instructions are timed.
+        assert_eq!(Some(&(3, 2)), counts.get(&1));
+        assert_eq!(Some(&(1, 0)), counts.get(&5));
+        assert_eq!(Some(&(1, 0)), counts.get(&99));
+    }
+
+    #[test]
+    fn test_display() {
+        let report = ProfileReport {
+            total_steps: 10,
+            sample_every: 2,
+            opcodes: vec![(2, profile(6, 3, 3)), (99, profile(4, 2, 1))],
+        };
+        assert_eq!(
+            "timing 1 in 2 instructions\n\
+             Multiply                    6      6.000ms\n\
+             End                         4      2.000ms\n\
+             total                      10      8.000ms\n",
+            report.to_string()
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType};
+use std::collections::VecDeque;
+
+// Input the owner can keep adding to while the emulator runs. Reading from
+// an empty queue makes step return NeedsInput without moving the
+// instruction pointer, so the instruction is retried once more is queued.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueueInput {
+    queue: VecDeque<Result<EmulatorMemoryType, EmulatorError>>,
+}
+
+impl QueueInput {
+    pub fn new() -> QueueInput {
+        QueueInput::default()
+    }
+
+    pub fn push(&mut self, value: EmulatorMemoryType) {
+        self.queue.push_back(Ok(value));
+    }
+
+    // Queued errors count as entries too.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // The queued values in the order they will be read, skipping errors.
+    pub fn values(&self) -> impl Iterator<Item = EmulatorMemoryType> + '_ {
+        self.queue
+            .iter()
+            .filter_map(|value| value.as_ref().ok().copied())
+    }
+
+    pub fn drain(&mut self) -> Vec<EmulatorMemoryType> {
+        self.queue.drain(..).filter_map(Result::ok).collect()
+    }
+}
+
+impl Iterator for QueueInput {
+    type Item = Result<EmulatorMemoryType, EmulatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.queue.pop_front()
+    }
+}
+
+impl From<Vec<EmulatorMemoryType>> for QueueInput {
+    fn from(values: Vec<EmulatorMemoryType>) -> QueueInput {
+        QueueInput {
+            queue: values.into_iter().map(Ok).collect(),
+        }
+    }
+}
+
+impl Extend<EmulatorMemoryType> for QueueInput {
+    fn extend<T: IntoIterator<Item = EmulatorMemoryType>>(&mut self, values: T) {
+        self.queue.extend(values.into_iter().map(Ok));
+    }
+}
+
+impl Extend<Result<EmulatorMemoryType, EmulatorError>> for QueueInput {
+    fn extend<T: IntoIterator<Item = Result<EmulatorMemoryType, EmulatorError>>>(
+        &mut self,
+        values: T,
+    ) {
+        self.queue.extend(values);
+    }
+}
+
+impl Emulator<QueueInput> {
+    // An emulator with nothing queued, for programs driven interactively:
+    // step until NeedsInput, push_input, and step on.
+    pub fn with_queue(initial_memory: &[EmulatorMemoryType]) -> Emulator<QueueInput> {
+        Emulator::new(initial_memory, QueueInput::new())
+    }
+
+    pub fn with_inputs<T: IntoIterator<Item = EmulatorMemoryType>>(
+        initial_memory: &[EmulatorMemoryType],
+        inputs: T,
+    ) -> Emulator<QueueInput> {
+        let mut emulator = Emulator::new(initial_memory, QueueInput::new());
+        emulator.extend(inputs);
+        emulator
+    }
+
+    pub fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.input_iter.push(value);
+    }
+
+    pub fn push_inputs<T: IntoIterator<Item = EmulatorMemoryType>>(&mut self, values: T) {
+        self.input_iter.extend(values);
+    }
+
+    // Inputs pushed but not yet read by the program.
+    pub fn pending_inputs(&self) -> usize {
+        self.input_iter.len()
+    }
+
+    pub fn pending_input_values(&self) -> impl Iterator<Item = EmulatorMemoryType> + '_ {
+        self.input_iter.values()
+    }
+
+    // Empties the queue, returning what the program never read.
+    pub fn drain_pending_inputs(&mut self) -> Vec<EmulatorMemoryType> {
+        self.input_iter.drain()
+    }
+}
+
+impl Extend<EmulatorMemoryType> for Emulator<QueueInput> {
+    fn extend<T: IntoIterator<Item = EmulatorMemoryType>>(&mut self, values: T) {
+        self.input_iter.extend(values);
+    }
+}
+
+impl Extend<Result<EmulatorMemoryType, EmulatorError>> for Emulator<QueueInput> {
+    fn extend<T: IntoIterator<Item = Result<EmulatorMemoryType, EmulatorError>>>(
+        &mut self,
+        values: T,
+    ) {
+        self.input_iter.extend(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::EmulatorResult;
+
+    #[test]
+    fn test_extend_is_fifo_across_execution() -> Result<(), EmulatorError> {
+        // Echoes three inputs.
+        let program = [3, 13, 4, 13, 3, 13, 4, 13, 3, 13, 4, 13, 99, 0];
+        let mut emulator = Emulator::with_inputs(&program, vec![1]);
+        emulator.extend(vec![2]);
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(EmulatorResult::SuccessWithValue(1), emulator.step()?);
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(EmulatorResult::SuccessWithValue(2), emulator.step()?);
+
+        assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+        emulator.extend(vec![Ok(3)]);
+        emulator.push_input(4);
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(EmulatorResult::SuccessWithValue(3), emulator.step()?);
+        assert_eq!(EmulatorResult::Done, emulator.step()?);
+        Ok(())
+    }
+
+    // Draws a paddle at memory[100] and moves it by each joystick input,
+    // four times over.
+    fn paddle_program() -> Vec<EmulatorMemoryType> {
+        let mut program = vec![
+            4, 100, 104, 0, 104, 3, 3, 101, 1, 100, 101, 100, 1001, 102, -1, 102, 1005, 102, 0, 99,
+        ];
+        program.resize(103, 0);
+        program[100] = 5;
+        program[102] = 4;
+        program
+    }
+
+    #[test]
+    fn test_joystick_loop() -> Result<(), EmulatorError> {
+        // Like day 13: read tiles as they are drawn, and when the program
+        // asks for the joystick, steer the paddle towards the ball.
+        let ball = 2;
+        let mut emulator = Emulator::with_queue(&paddle_program());
+        let mut tile = Vec::new();
+        let mut paddle = None;
+        let mut drawn = Vec::new();
+        loop {
+            match emulator.step()? {
+                EmulatorResult::SuccessWithValue(value) => {
+                    tile.push(value);
+                    if let [x, _, 3] = tile[..] {
+                        paddle = Some(x);
+                        drawn.push(x);
+                    }
+                    if tile.len() == 3 {
+                        tile.clear();
+                    }
+                }
+                EmulatorResult::NeedsInput => {
+                    let paddle = paddle.expect("the paddle is drawn before input");
+                    emulator.push_input((ball - paddle).signum());
+                }
+                EmulatorResult::Done => break,
+                _ => {}
+            }
+        }
+        assert_eq!(vec![5, 4, 3, 2], drawn);
+        assert_eq!(ball, emulator.memory()[100]);
+
+        // The same moves pushed up front.
+        let mut emulator = Emulator::with_queue(&paddle_program());
+        emulator.push_inputs(vec![-1, -1, -1, 0]);
+        emulator.run_collecting_outputs()?;
+        assert_eq!(ball, emulator.memory()[100]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_inputs() -> Result<(), EmulatorError> {
+        // Echoes inputs forever.
+        let program = [3, 9, 4, 9, 1105, 1, 0, 0, 0, 0];
+        let mut emulator = Emulator::with_inputs(&program, vec![1, 2]);
+        assert_eq!(2, emulator.pending_inputs());
+        assert_eq!(
+            vec![1, 2],
+            emulator.pending_input_values().collect::<Vec<_>>()
+        );
+
+        assert_eq!(EmulatorResult::Success, emulator.step()?);
+        assert_eq!(1, emulator.pending_inputs());
+        emulator.push_input(3);
+        assert_eq!(
+            vec![2, 3],
+            emulator.pending_input_values().collect::<Vec<_>>()
+        );
+        for _ in 0..6 {
+            emulator.step()?;
+        }
+        assert_eq!(0, emulator.pending_inputs());
+        assert_eq!(EmulatorResult::SuccessWithValue(3), emulator.step()?);
+        emulator.step()?;
+        assert_eq!(EmulatorResult::NeedsInput, emulator.step()?);
+        assert_eq!(3, emulator.inputs_consumed());
+
+        // Reset keeps the queue; only reset_with_input replaces it.
+        emulator.extend(vec![4, 5]);
+        emulator.reset();
+        assert_eq!(2, emulator.pending_inputs());
+        emulator.step()?;
+        assert_eq!(vec![5], emulator.drain_pending_inputs());
+        assert_eq!(0, emulator.pending_inputs());
+        emulator.reset_with_input(QueueInput::from(vec![6, 7, 8]));
+        assert_eq!(3, emulator.pending_inputs());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queued_errors_are_forwarded() {
+        let mut emulator = Emulator::new(&[3, 0, 99], QueueInput::from(vec![]));
+        let error = EmulatorError::InstructionPointerOutOfBounds {
+            position: 7,
+            context: None,
+        };
+        emulator.extend(vec![Err(error.clone())]);
+        assert_eq!(Err(error), emulator.step());
+    }
+}
@@ -0,0 +1,110 @@
+use super::replay::Replay;
+use super::EmulatorMemoryType;
+use std::fmt::Write;
+
+fn cell_list(cells: &[EmulatorMemoryType]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Renders a #[test] that reruns the replay through the public API and
+// asserts the outputs and final memory hash, for pasting into tests/.
+pub fn emit_test(replay: &Replay, test_name: &str) -> String {
+    let mut source = String::new();
+    // Writing into a String can't fail.
+    let _ = writeln!(source, "#[test]");
+    let _ = writeln!(source, "fn {}() {{", test_name);
+    let _ = writeln!(
+        source,
+        "    use advent_of_code_2019::intcode::replay::memory_hash;"
+    );
+    let _ = writeln!(
+        source,
+        "    use advent_of_code_2019::intcode::{{Emulator, EmulatorMemoryType, EmulatorResult}};"
+    );
+    let _ = writeln!(source);
+    let _ = writeln!(
+        source,
+        "    let program: &[EmulatorMemoryType] = &[{}];",
+        cell_list(&replay.program)
+    );
+    let _ = writeln!(
+        source,
+        "    let inputs: &[EmulatorMemoryType] = &[{}];",
+        cell_list(&replay.inputs)
+    );
+    let _ = writeln!(
+        source,
+        "    let mut emulator = Emulator::new(program, inputs.iter().copied().map(Ok));"
+    );
+    let _ = writeln!(
+        source,
+        "    let mut outputs: Vec<EmulatorMemoryType> = Vec::new();"
+    );
+    let _ = writeln!(source, "    loop {{");
+    let _ = writeln!(source, "        match emulator.step().unwrap() {{");
+    let _ = writeln!(source, "            EmulatorResult::Done => break,");
+    let _ = writeln!(
+        source,
+        "            EmulatorResult::SuccessWithValue(value) => outputs.push(value),"
+    );
+    let _ = writeln!(
+        source,
+        "            EmulatorResult::NeedsInput => panic!(\"ran out of recorded inputs\"),"
+    );
+    let _ = writeln!(source, "            _ => {{}}");
+    let _ = writeln!(source, "        }}");
+    let _ = writeln!(source, "    }}");
+    let _ = writeln!(source);
+    let _ = writeln!(
+        source,
+        "    let expected_outputs: &[EmulatorMemoryType] = &[{}];",
+        cell_list(&replay.outputs)
+    );
+    let _ = writeln!(
+        source,
+        "    assert_eq!(expected_outputs, outputs.as_slice());"
+    );
+    let _ = writeln!(
+        source,
+        "    assert_eq!(0x{:016x}, memory_hash(emulator.memory()));",
+        replay.memory_hash
+    );
+    let _ = writeln!(source, "}}");
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_test_for_day_2_example() {
+        let replay = Replay::record(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]).unwrap();
+        let source = emit_test(&replay, "day_2_example");
+
+        assert!(source.starts_with("#[test]\nfn day_2_example() {\n"));
+        assert!(source.contains(
+            "    let program: &[EmulatorMemoryType] = &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];\n"
+        ));
+        assert!(source.contains("    let inputs: &[EmulatorMemoryType] = &[];\n"));
+        assert!(source.contains("    let expected_outputs: &[EmulatorMemoryType] = &[];\n"));
+        assert!(source.contains(&format!(
+            "    assert_eq!(0x{:016x}, memory_hash(emulator.memory()));\n",
+            replay.memory_hash
+        )));
+        assert!(source.ends_with("}\n"));
+    }
+
+    // tests/recorded.rs holds a test generated by this function, so the
+    // checked in copy must stay byte for byte what emit_test produces.
+    #[test]
+    fn test_checked_in_sample_is_current() {
+        let replay = Replay::record(&[3, 0, 4, 0, 99], &[1337]).unwrap();
+        let source = emit_test(&replay, "input_output_example");
+        assert!(include_str!("../../tests/recorded.rs").contains(&source));
+    }
+}
@@ -0,0 +1,110 @@
+use super::{OpCodeInfo, ParameterInfo, ParameterKind, OPCODE_TABLE};
+use std::io::{self, Write};
+
+// Mirrors the parameter mode handling make_op_code! generates: writes
+// accept position and relative mode, but not immediate.
+fn allowed_modes(kind: ParameterKind) -> &'static str {
+    match kind {
+        ParameterKind::Read => "read(pos|imm|rel)",
+        ParameterKind::Write => "write(pos|rel)",
+    }
+}
+
+fn describe_parameter(parameter: &ParameterInfo) -> String {
+    format!("{}:{}", parameter.name, allowed_modes(parameter.kind))
+}
+
+fn describe_effects(info: &OpCodeInfo) -> String {
+    let effects: Vec<&str> = [
+        (info.reads_input, "input"),
+        (info.writes_output, "output"),
+        (info.jumps, "jump"),
+        (info.halts, "halt"),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, effect)| *effect)
+    .collect();
+    if effects.is_empty() {
+        "-".to_string()
+    } else {
+        effects.join(",")
+    }
+}
+
+pub fn print_instruction_set<W: Write>(mut writer: W) -> io::Result<()> {
+    let header = [
+        "code".to_string(),
+        "mnemonic".to_string(),
+        "parameters".to_string(),
+        "effects".to_string(),
+    ];
+    let rows: Vec<[String; 4]> = OPCODE_TABLE
+        .iter()
+        .map(|info| {
+            [
+                info.code.to_string(),
+                info.mnemonic.to_string(),
+                info.parameters
+                    .iter()
+                    .map(describe_parameter)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                describe_effects(info),
+            ]
+        })
+        .collect();
+
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = std::cmp::max(*width, cell.len());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = format!(
+            "{:>code$}  {:mnemonic$}  {:parameters$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            code = widths[0],
+            mnemonic = widths[1],
+            parameters = widths[2],
+        );
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Adding or changing an opcode changes this output; regenerate the
+    // fixture with `aoc opcodes > tests/fixtures/opcodes.txt`.
+    #[test]
+    fn test_instruction_set_matches_golden() {
+        let mut output = Vec::new();
+        print_instruction_set(&mut output).unwrap();
+        assert_eq!(
+            include_str!("../../tests/fixtures/opcodes.txt"),
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table_is_in_declaration_order() {
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 99],
+            OPCODE_TABLE
+                .iter()
+                .map(|info| info.code)
+                .collect::<Vec<_>>()
+        );
+        let jump_if_true = &OPCODE_TABLE[4];
+        assert_eq!("JumpIfTrue", jump_if_true.mnemonic);
+        assert!(jump_if_true.jumps && !jump_if_true.halts);
+    }
+}
@@ -0,0 +1,461 @@
+use super::{cell_to_i64, Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, QueueInput};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const REPLAY_EXTENSION: &str = "icr";
+
+// A replay file is plain text, one field per line:
+//
+//   program: 3,0,4,0,99
+//   inputs: 1337
+//   outputs: 1337
+//   memory_hash: 3855d533eb9ffacc
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub program: Vec<EmulatorMemoryType>,
+    pub inputs: Vec<EmulatorMemoryType>,
+    pub outputs: Vec<EmulatorMemoryType>,
+    pub memory_hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    Output {
+        index: usize,
+        expected: Option<EmulatorMemoryType>,
+        found: Option<EmulatorMemoryType>,
+    },
+    MemoryHash {
+        expected: u64,
+        found: u64,
+    },
+    Error(EmulatorError),
+}
+
+impl std::fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayMismatch::Output {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Output {} differs: expected {}, but found {}",
+                index,
+                describe_output(*expected),
+                describe_output(*found)
+            ),
+            ReplayMismatch::MemoryHash { expected, found } => write!(
+                f,
+                "Final memory hash differs: expected {:016x}, but found {:016x}",
+                expected, found
+            ),
+            ReplayMismatch::Error(error) => write!(f, "Replay failed to run: {}", error),
+        }
+    }
+}
+
+fn describe_output(output: Option<EmulatorMemoryType>) -> String {
+    output.map_or("no output".to_string(), |value| value.to_string())
+}
+
+// FNV-1a over the little endian bytes of every cell. The hash ends up in
+// files on disk, so it can't depend on std's unspecified default hasher.
+pub fn memory_hash(memory: &[EmulatorMemoryType]) -> u64 {
+    memory
+        .iter()
+        // Cells that fit hash as i64s, so hashes match between widths.
+        .flat_map(|&cell| match cell_to_i64(cell) {
+            Some(narrow) => narrow.to_le_bytes().to_vec(),
+            None => cell.to_le_bytes().to_vec(),
+        })
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// An emulator that reads recorded_inputs, such as what
+// Emulator::consumed_inputs returned for an earlier session, so running it
+// plays that session again.
+pub fn replay(
+    program: &[EmulatorMemoryType],
+    recorded_inputs: &[EmulatorMemoryType],
+) -> Emulator<QueueInput> {
+    Emulator::with_inputs(program, recorded_inputs.iter().copied())
+}
+
+fn run(
+    program: &[EmulatorMemoryType],
+    inputs: &[EmulatorMemoryType],
+) -> Result<(Vec<EmulatorMemoryType>, u64), EmulatorError> {
+    let mut emulator = Emulator::new(program, inputs.iter().copied().map(Ok));
+    let mut outputs = Vec::new();
+    loop {
+        match emulator.step()? {
+            EmulatorResult::Done => break,
+            EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => {}
+            EmulatorResult::SuccessWithValue(value) => outputs.push(value),
+            EmulatorResult::NeedsInput => return Err(emulator.missing_input()),
+        }
+    }
+    Ok((outputs, memory_hash(emulator.memory())))
+}
+
+impl Replay {
+    pub fn record(
+        program: &[EmulatorMemoryType],
+        inputs: &[EmulatorMemoryType],
+    ) -> Result<Replay, EmulatorError> {
+        let (outputs, memory_hash) = run(program, inputs)?;
+        Ok(Replay {
+            program: program.into(),
+            inputs: inputs.into(),
+            outputs,
+            memory_hash,
+        })
+    }
+
+    pub fn verify(&self) -> Result<(), ReplayMismatch> {
+        let (outputs, memory_hash) =
+            run(&self.program, &self.inputs).map_err(ReplayMismatch::Error)?;
+        self.compare(&outputs, memory_hash)
+    }
+
+    // Like verify, but stops after the given number of steps. Outputs so far
+    // must start the recorded ones, and the memory hash is only checked if
+    // the program halts within the prefix.
+    pub fn verify_prefix(&self, steps: u64) -> Result<(), ReplayMismatch> {
+        let mut emulator = Emulator::new(&self.program, self.inputs.iter().copied().map(Ok));
+        let mut outputs = Vec::new();
+        while emulator.steps < steps {
+            match emulator.step().map_err(ReplayMismatch::Error)? {
+                EmulatorResult::Done => {
+                    return self.compare(&outputs, memory_hash(emulator.memory()))
+                }
+                EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => {}
+                EmulatorResult::SuccessWithValue(value) => outputs.push(value),
+                EmulatorResult::NeedsInput => {
+                    return Err(ReplayMismatch::Error(emulator.missing_input()))
+                }
+            }
+        }
+        self.compare_outputs(&outputs, outputs.len())
+    }
+
+    // The first count outputs, where either side running short is a
+    // mismatch too.
+    fn compare_outputs(
+        &self,
+        outputs: &[EmulatorMemoryType],
+        count: usize,
+    ) -> Result<(), ReplayMismatch> {
+        for index in 0..count {
+            let expected = self.outputs.get(index).copied();
+            let found = outputs.get(index).copied();
+            if expected != found {
+                return Err(ReplayMismatch::Output {
+                    index,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn compare(
+        &self,
+        outputs: &[EmulatorMemoryType],
+        memory_hash: u64,
+    ) -> Result<(), ReplayMismatch> {
+        self.compare_outputs(outputs, std::cmp::max(outputs.len(), self.outputs.len()))?;
+        if memory_hash != self.memory_hash {
+            return Err(ReplayMismatch::MemoryHash {
+                expected: self.memory_hash,
+                found: memory_hash,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    // Loads every .icr file in a directory, sorted by path so runs are stable.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<(PathBuf, Replay)>> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXTENSION));
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| Replay::load(&path).map(|replay| (path, replay)))
+            .collect()
+    }
+}
+
+fn join_cells(cells: &[EmulatorMemoryType]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_cells(line: &str) -> io::Result<Vec<EmulatorMemoryType>> {
+    if line.is_empty() {
+        return Ok(Vec::new());
+    }
+    line.split(',')
+        .map(|cell| {
+            cell.trim()
+                .parse()
+                .map_err(|_| invalid_data(format!("'{}' is not a memory cell", cell)))
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "program: {}", join_cells(&self.program))?;
+        writeln!(f, "inputs: {}", join_cells(&self.inputs))?;
+        writeln!(f, "outputs: {}", join_cells(&self.outputs))?;
+        writeln!(f, "memory_hash: {:016x}", self.memory_hash)
+    }
+}
+
+impl std::str::FromStr for Replay {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Replay> {
+        let mut program = None;
+        let mut inputs = None;
+        let mut outputs = None;
+        let mut memory_hash = None;
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let mut split = line.splitn(2, ':');
+            let key = split.next().unwrap_or("").trim();
+            let value = split
+                .next()
+                .ok_or_else(|| invalid_data(format!("'{}' is not a replay field", line)))?
+                .trim();
+            match key {
+                "program" => program = Some(parse_cells(value)?),
+                "inputs" => inputs = Some(parse_cells(value)?),
+                "outputs" => outputs = Some(parse_cells(value)?),
+                "memory_hash" => {
+                    memory_hash =
+                        Some(u64::from_str_radix(value, 16).map_err(|_| {
+                            invalid_data(format!("'{}' is not a memory hash", value))
+                        })?)
+                }
+                _ => return Err(invalid_data(format!("unknown replay field '{}'", key))),
+            }
+        }
+
+        let missing = |field: &str| invalid_data(format!("replay is missing '{}'", field));
+        Ok(Replay {
+            program: program.ok_or_else(|| missing("program"))?,
+            inputs: inputs.ok_or_else(|| missing("inputs"))?,
+            outputs: outputs.ok_or_else(|| missing("outputs"))?,
+            memory_hash: memory_hash.ok_or_else(|| missing("memory_hash"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG_EXAMPLE: [EmulatorMemoryType; 47] = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+
+    // Reads numbers until a 0, outputting the running total after each.
+    const RUNNING_TOTAL: [EmulatorMemoryType; 15] = [
+        3, 100, 1006, 100, 14, 1, 100, 101, 101, 4, 101, 1105, 1, 0, 99,
+    ];
+
+    // Feeds back each total until one passes 20, then stops the program.
+    fn play(emulator: &mut Emulator<QueueInput>) -> Vec<EmulatorMemoryType> {
+        let mut outputs = Vec::new();
+        emulator.push_input(1);
+        loop {
+            match emulator.step().unwrap() {
+                EmulatorResult::SuccessWithValue(total) => outputs.push(total),
+                EmulatorResult::NeedsInput => {
+                    let last = outputs.last().copied().unwrap_or(0);
+                    emulator.push_input(if last > 20 { 0 } else { last });
+                }
+                EmulatorResult::Done => return outputs,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_recorded_inputs() {
+        let mut emulator = Emulator::with_inputs(&RUNNING_TOTAL, vec![]);
+        emulator.record_inputs(true);
+        let outputs = play(&mut emulator);
+        assert_eq!(vec![1, 2, 4, 8, 16, 32], outputs);
+        assert_eq!(&[1, 1, 2, 4, 8, 16, 0], emulator.consumed_inputs());
+
+        let mut replayed = replay(&RUNNING_TOTAL, emulator.consumed_inputs());
+        assert_eq!(Ok(outputs), replayed.run_collecting_outputs());
+
+        // reset starts the recording over unless told to keep it.
+        emulator.reset();
+        assert!(emulator.consumed_inputs().is_empty());
+        emulator.keep_recorded_inputs_on_reset(true);
+        play(&mut emulator);
+        emulator.reset();
+        play(&mut emulator);
+        assert_eq!(14, emulator.consumed_inputs().len());
+
+        emulator.record_inputs(false);
+        assert!(emulator.consumed_inputs().is_empty());
+        let mut emulator = Emulator::with_inputs(&RUNNING_TOTAL, vec![]);
+        play(&mut emulator);
+        assert!(emulator.consumed_inputs().is_empty());
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "advent_of_code_2019_replay_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_record_save_load_verify() -> io::Result<()> {
+        let dir = scratch_dir("round_trip");
+        for (input, expected_output) in [(7, 999), (8, 1000), (9, 1001)].iter() {
+            let replay = Replay::record(&LONG_EXAMPLE, &[*input]).unwrap();
+            assert_eq!(vec![*expected_output], replay.outputs);
+
+            let path = dir.join(format!("long_example_{}.icr", input));
+            replay.save(&path)?;
+            let loaded = Replay::load(&path)?;
+            assert_eq!(replay, loaded);
+            assert_eq!(Ok(()), loaded.verify());
+        }
+
+        let replays = Replay::load_dir(&dir)?;
+        assert_eq!(3, replays.len());
+        assert!(replays.iter().all(|(_, replay)| replay.verify().is_ok()));
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn test_corrupted_output_is_reported() -> io::Result<()> {
+        let dir = scratch_dir("corrupted");
+        let path = dir.join("long_example.icr");
+        Replay::record(&LONG_EXAMPLE, &[8]).unwrap().save(&path)?;
+
+        let mut replay = Replay::load(&path)?;
+        replay.outputs[0] = 999;
+        assert_eq!(
+            Err(ReplayMismatch::Output {
+                index: 0,
+                expected: Some(999),
+                found: Some(1000),
+            }),
+            replay.verify()
+        );
+
+        replay.outputs.push(5);
+        replay.outputs[0] = 1000;
+        assert_eq!(
+            Err(ReplayMismatch::Output {
+                index: 1,
+                expected: Some(5),
+                found: None,
+            }),
+            replay.verify()
+        );
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn test_memory_hash_mismatch_is_reported() {
+        let mut replay = Replay::record(&LONG_EXAMPLE, &[9]).unwrap();
+        let found = replay.memory_hash;
+        replay.memory_hash ^= 1;
+        assert_eq!(
+            Err(ReplayMismatch::MemoryHash {
+                expected: found ^ 1,
+                found,
+            }),
+            replay.verify()
+        );
+    }
+
+    #[test]
+    fn test_verify_prefix() {
+        // Outputs 1, 2 and 3 at steps 1, 2 and 3.
+        let mut replay = Replay::record(&[104, 1, 104, 2, 104, 3, 99], &[]).unwrap();
+        for steps in 0..10 {
+            assert_eq!(Ok(()), replay.verify_prefix(steps));
+        }
+
+        replay.outputs[1] = 5;
+        assert_eq!(Ok(()), replay.verify_prefix(1));
+        let mismatch = Err(ReplayMismatch::Output {
+            index: 1,
+            expected: Some(5),
+            found: Some(2),
+        });
+        assert_eq!(mismatch, replay.verify_prefix(2));
+        assert_eq!(mismatch, replay.verify_prefix(100));
+
+        replay.outputs[1] = 2;
+        replay.memory_hash ^= 1;
+        assert_eq!(Ok(()), replay.verify_prefix(3));
+        assert!(matches!(
+            replay.verify_prefix(4),
+            Err(ReplayMismatch::MemoryHash { .. })
+        ));
+    }
+
+    #[test]
+    fn test_file_format() {
+        let replay = Replay::record(&[3, 0, 4, 0, 99], &[1337]).unwrap();
+        assert_eq!(
+            "program: 3,0,4,0,99\ninputs: 1337\noutputs: 1337\nmemory_hash: 3855d533eb9ffacc\n",
+            replay.to_string()
+        );
+    }
+
+    #[test]
+    fn test_malformed_replay_is_rejected() {
+        assert!("program: 99\ninputs: \noutputs: \n"
+            .parse::<Replay>()
+            .is_err());
+        assert!("program: 99,x\ninputs: \noutputs: \nmemory_hash: 0\n"
+            .parse::<Replay>()
+            .is_err());
+    }
+}
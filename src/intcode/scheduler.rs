@@ -0,0 +1,655 @@
+use super::trace::TraceExporter;
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, QueueInput};
+use std::path::{Path, PathBuf};
+
+pub type MachineId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MachineStep {
+    Ran,
+    ConsumedInput,
+    Output(EmulatorMemoryType),
+    // Nothing happened; the machine is waiting for input.
+    NeedsInput,
+    Halted,
+}
+
+// Anything the Scheduler can run one step at a time.
+pub trait Machine {
+    fn step(&mut self) -> Result<MachineStep, EmulatorError>;
+    fn push_input(&mut self, value: EmulatorMemoryType);
+
+    // Inputs pushed but not yet read. A machine waiting for input with none
+    // pending can't go any further.
+    fn pending_inputs(&self) -> usize {
+        0
+    }
+}
+
+// An emulator fed from a queue, retrying Input once more is pushed.
+pub struct IntcodeMachine {
+    emulator: Emulator<QueueInput>,
+}
+
+impl IntcodeMachine {
+    pub fn new(program: &[EmulatorMemoryType]) -> IntcodeMachine {
+        IntcodeMachine {
+            emulator: Emulator::new(program, QueueInput::new()),
+        }
+    }
+
+    pub fn emulator(&self) -> &Emulator<QueueInput> {
+        &self.emulator
+    }
+}
+
+impl Machine for IntcodeMachine {
+    fn step(&mut self) -> Result<MachineStep, EmulatorError> {
+        let inputs_consumed = self.emulator.inputs_consumed();
+        match self.emulator.step() {
+            Ok(EmulatorResult::Done) => Ok(MachineStep::Halted),
+            Ok(EmulatorResult::Success | EmulatorResult::Watchpoint { .. })
+                if self.emulator.inputs_consumed() > inputs_consumed =>
+            {
+                Ok(MachineStep::ConsumedInput)
+            }
+            Ok(EmulatorResult::Success | EmulatorResult::Watchpoint { .. }) => Ok(MachineStep::Ran),
+            Ok(EmulatorResult::SuccessWithValue(value)) => Ok(MachineStep::Output(value)),
+            Ok(EmulatorResult::NeedsInput) => Ok(MachineStep::NeedsInput),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn push_input(&mut self, value: EmulatorMemoryType) {
+        self.emulator.push_input(value);
+    }
+
+    fn pending_inputs(&self) -> usize {
+        self.emulator.pending_inputs()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchedulerConfig {
+    // How many steps a machine may run before yielding to the next one.
+    pub quantum_steps: u64,
+    // Steps across all machines before run() gives up.
+    pub max_total_steps: Option<u64>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> SchedulerConfig {
+        SchedulerConfig {
+            quantum_steps: 1_000,
+            max_total_steps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulerError {
+    Machine {
+        name: String,
+        error: EmulatorError,
+    },
+    // Names the machine that used the most steps, which is usually the one
+    // stuck in a loop.
+    BudgetExhausted {
+        busiest: String,
+        busiest_steps: u64,
+        total_steps: u64,
+    },
+    TraceWrite {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SchedulerError::Machine { name, error } => write!(f, "Machine '{}': {}", name, error),
+            SchedulerError::BudgetExhausted {
+                busiest,
+                busiest_steps,
+                total_steps,
+            } => write!(
+                f,
+                "Step budget of {} exhausted; machine '{}' used {} of them",
+                total_steps, busiest, busiest_steps
+            ),
+            SchedulerError::TraceWrite { path, message } => {
+                write!(f, "Couldn't write trace to {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    // Every machine halted.
+    Halted,
+    // No machine can make progress until it is given more input.
+    Idle,
+}
+
+impl std::fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunOutcome::Halted => write!(f, "halted"),
+            RunOutcome::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+// A value routed from one machine's output to another's input. Routing is
+// one value at a time, so payload holds exactly one value for now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedMessage {
+    pub from: MachineId,
+    pub to: MachineId,
+    pub payload: Vec<EmulatorMemoryType>,
+    // The scheduler's total step count when the message was routed.
+    pub step: u64,
+}
+
+pub type MessageTap = Box<dyn FnMut(&RoutedMessage)>;
+
+struct Scheduled {
+    name: String,
+    machine: Box<dyn Machine>,
+    halted: bool,
+    // Whether the last step asked for input.
+    waiting: bool,
+    steps: u64,
+    // Where outputs go. Unconnected outputs are kept in outputs.
+    destination: Option<MachineId>,
+    outputs: Vec<EmulatorMemoryType>,
+}
+
+// Runs machines round-robin, each for up to a quantum of steps or until it
+// blocks on input or halts.
+pub struct Scheduler {
+    machines: Vec<Scheduled>,
+    config: SchedulerConfig,
+    total_steps: u64,
+    trace: Option<TraceExporter>,
+    message_tap: Option<MessageTap>,
+    messages_routed: u64,
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig) -> Scheduler {
+        Scheduler {
+            machines: Vec::new(),
+            config,
+            total_steps: 0,
+            trace: None,
+            message_tap: None,
+            messages_routed: 0,
+        }
+    }
+
+    pub fn add<M: Machine + 'static>(&mut self, name: &str, machine: M) -> MachineId {
+        self.machines.push(Scheduled {
+            name: name.to_string(),
+            machine: Box::new(machine),
+            halted: false,
+            waiting: false,
+            steps: 0,
+            destination: None,
+            outputs: Vec::new(),
+        });
+        let id = self.machines.len() - 1;
+        if let Some(trace) = &mut self.trace {
+            trace.name_thread(id, name);
+        }
+        id
+    }
+
+    // Records a span per quantum and an instant per input consumed, output
+    // produced and output routed to another machine.
+    pub fn set_trace_exporter(&mut self, mut trace: TraceExporter) {
+        for (id, scheduled) in self.machines.iter().enumerate() {
+            trace.name_thread(id, &scheduled.name);
+        }
+        self.trace = Some(trace);
+    }
+
+    pub fn take_trace_exporter(&mut self) -> Option<TraceExporter> {
+        self.trace.take()
+    }
+
+    // Called with every routed message once it is queued on its destination.
+    // The tap only observes, so scheduling is the same with or without it.
+    pub fn set_message_tap<F: FnMut(&RoutedMessage) + 'static>(&mut self, tap: F) {
+        self.message_tap = Some(Box::new(tap));
+    }
+
+    // Sends every later output of from to the input of to.
+    pub fn connect(&mut self, from: MachineId, to: MachineId) {
+        self.machines[from].destination = Some(to);
+    }
+
+    pub fn push_input(&mut self, id: MachineId, value: EmulatorMemoryType) {
+        self.machines[id].machine.push_input(value);
+    }
+
+    pub fn name(&self, id: MachineId) -> &str {
+        &self.machines[id].name
+    }
+
+    pub fn outputs(&self, id: MachineId) -> &[EmulatorMemoryType] {
+        &self.machines[id].outputs
+    }
+
+    pub fn steps_used(&self, id: MachineId) -> u64 {
+        self.machines[id].steps
+    }
+
+    pub fn total_steps(&self) -> u64 {
+        self.total_steps
+    }
+
+    pub fn is_halted(&self, id: MachineId) -> bool {
+        self.machines[id].halted
+    }
+
+    fn budget_exhausted(&self) -> SchedulerError {
+        let busiest = self
+            .machines
+            .iter()
+            .max_by_key(|scheduled| scheduled.steps)
+            .expect("a budget can only run out with machines scheduled");
+        SchedulerError::BudgetExhausted {
+            busiest: busiest.name.clone(),
+            busiest_steps: busiest.steps,
+            total_steps: self.total_steps,
+        }
+    }
+
+    // Runs one quantum of the machine, returning whether it made progress.
+    fn run_quantum(&mut self, id: MachineId) -> Result<bool, SchedulerError> {
+        let start = self.total_steps;
+        let span = self
+            .trace
+            .as_mut()
+            .map(|trace| trace.begin_span(id, "quantum", start));
+        let result = self.run_quantum_steps(id);
+        if let (Some(trace), Some(span)) = (&mut self.trace, span) {
+            match result {
+                Ok(false) => trace.discard_span(span),
+                _ => trace.end_span(span, self.total_steps),
+            }
+        }
+        result
+    }
+
+    fn run_quantum_steps(&mut self, id: MachineId) -> Result<bool, SchedulerError> {
+        let mut progressed = false;
+        for _ in 0..self.config.quantum_steps {
+            if self
+                .config
+                .max_total_steps
+                .is_some_and(|max| self.total_steps >= max)
+            {
+                return Err(self.budget_exhausted());
+            }
+
+            let scheduled = &mut self.machines[id];
+            let step = scheduled
+                .machine
+                .step()
+                .map_err(|error| SchedulerError::Machine {
+                    name: scheduled.name.clone(),
+                    error,
+                })?;
+            scheduled.waiting = step == MachineStep::NeedsInput;
+            if scheduled.waiting {
+                break;
+            }
+            scheduled.steps += 1;
+            self.total_steps += 1;
+            progressed = true;
+            let timestamp = self.total_steps;
+            match step {
+                MachineStep::ConsumedInput => {
+                    if let Some(trace) = &mut self.trace {
+                        trace.instant(id, "input", timestamp, &[]);
+                    }
+                }
+                MachineStep::Output(value) => {
+                    if let Some(trace) = &mut self.trace {
+                        trace.instant(id, "output", timestamp, &[("value", value)]);
+                    }
+                    match scheduled.destination {
+                        Some(destination) => {
+                            self.machines[destination].machine.push_input(value);
+                            self.route(id, destination, value, timestamp);
+                        }
+                        None => scheduled.outputs.push(value),
+                    }
+                }
+                MachineStep::Halted => {
+                    scheduled.halted = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        Ok(progressed)
+    }
+
+    // Reports a value already pushed to destination to the trace and tap.
+    fn route(&mut self, from: MachineId, to: MachineId, value: EmulatorMemoryType, timestamp: u64) {
+        let flow_id = self.messages_routed;
+        self.messages_routed += 1;
+        if let Some(trace) = &mut self.trace {
+            trace.instant(
+                from,
+                "routed",
+                timestamp,
+                &[("to", to as EmulatorMemoryType), ("value", value)],
+            );
+            trace.flow("message", flow_id, (from, timestamp), (to, timestamp));
+        }
+        if let Some(tap) = &mut self.message_tap {
+            tap(&RoutedMessage {
+                from,
+                to,
+                payload: vec![value],
+                step: timestamp,
+            });
+        }
+    }
+
+    pub fn pending_inputs(&self, id: MachineId) -> usize {
+        self.machines[id].machine.pending_inputs()
+    }
+
+    // Runs until every machine has halted or none can make progress: each
+    // one still running is waiting for input and has none pending.
+    pub fn run(&mut self) -> Result<RunOutcome, SchedulerError> {
+        loop {
+            for id in 0..self.machines.len() {
+                if !self.machines[id].halted {
+                    self.run_quantum(id)?;
+                }
+            }
+            if self.machines.iter().all(|scheduled| scheduled.halted) {
+                return Ok(RunOutcome::Halted);
+            }
+            let idle = self.machines.iter().all(|scheduled| {
+                scheduled.halted || (scheduled.waiting && scheduled.machine.pending_inputs() == 0)
+            });
+            if idle {
+                return Ok(RunOutcome::Idle);
+            }
+        }
+    }
+
+    // Runs with a fresh TraceExporter attached and saves the trace to path,
+    // even when the run fails. A run error takes precedence over a failure
+    // to write the trace.
+    pub fn run_with_chrome_trace(&mut self, path: &Path) -> Result<RunOutcome, SchedulerError> {
+        self.set_trace_exporter(TraceExporter::new());
+        let result = self.run();
+        let trace = self.take_trace_exporter().unwrap_or_default();
+        let saved = trace
+            .save(path)
+            .map_err(|error| SchedulerError::TraceWrite {
+                path: path.to_path_buf(),
+                message: error.to_string(),
+            });
+        let outcome = result?;
+        saved?;
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs 1, 2, 3, ... one per step, halting after limit outputs if it
+    // has one.
+    struct Counter {
+        next: EmulatorMemoryType,
+        limit: Option<EmulatorMemoryType>,
+    }
+
+    impl Machine for Counter {
+        fn step(&mut self) -> Result<MachineStep, EmulatorError> {
+            if self.limit.is_some_and(|limit| self.next >= limit) {
+                return Ok(MachineStep::Halted);
+            }
+            self.next += 1;
+            Ok(MachineStep::Output(self.next))
+        }
+
+        fn push_input(&mut self, _: EmulatorMemoryType) {}
+    }
+
+    // Spins forever without output or input.
+    struct Spinner;
+
+    impl Machine for Spinner {
+        fn step(&mut self) -> Result<MachineStep, EmulatorError> {
+            Ok(MachineStep::Ran)
+        }
+
+        fn push_input(&mut self, _: EmulatorMemoryType) {}
+    }
+
+    #[test]
+    fn test_spinner_does_not_starve_others() {
+        let mut scheduler = Scheduler::new(SchedulerConfig {
+            quantum_steps: 10,
+            max_total_steps: Some(1_000),
+        });
+        let spinner = scheduler.add("spinner", Spinner);
+        let counter = scheduler.add(
+            "counter",
+            Counter {
+                next: 0,
+                limit: Some(50),
+            },
+        );
+
+        let error = scheduler.run().unwrap_err();
+        assert_eq!(
+            SchedulerError::BudgetExhausted {
+                busiest: "spinner".to_string(),
+                busiest_steps: 949,
+                total_steps: 1_000,
+            },
+            error
+        );
+        assert_eq!(
+            "Step budget of 1000 exhausted; machine 'spinner' used 949 of them",
+            error.to_string()
+        );
+        assert_eq!((1..=50).collect::<Vec<_>>(), scheduler.outputs(counter));
+        assert!(scheduler.is_halted(counter));
+        assert!(!scheduler.is_halted(spinner));
+    }
+
+    #[test]
+    fn test_machines_yield_after_their_quantum() {
+        let mut scheduler = Scheduler::new(SchedulerConfig {
+            quantum_steps: 3,
+            max_total_steps: Some(10),
+        });
+        let a = scheduler.add(
+            "a",
+            Counter {
+                next: 0,
+                limit: None,
+            },
+        );
+        let b = scheduler.add(
+            "b",
+            Counter {
+                next: 100,
+                limit: None,
+            },
+        );
+        assert!(scheduler.run().is_err());
+        assert_eq!(&[1, 2, 3, 4, 5, 6], scheduler.outputs(a));
+        assert_eq!(&[101, 102, 103, 104], scheduler.outputs(b));
+    }
+
+    // Adds 100 to each input it is given.
+    struct Adder {
+        inputs: std::collections::VecDeque<EmulatorMemoryType>,
+    }
+
+    impl Machine for Adder {
+        fn step(&mut self) -> Result<MachineStep, EmulatorError> {
+            Ok(self
+                .inputs
+                .pop_front()
+                .map_or(MachineStep::NeedsInput, |value| {
+                    MachineStep::Output(value + 100)
+                }))
+        }
+
+        fn push_input(&mut self, value: EmulatorMemoryType) {
+            self.inputs.push_back(value);
+        }
+    }
+
+    fn relay(tap: Option<MessageTap>) -> Scheduler {
+        let mut scheduler = Scheduler::new(SchedulerConfig {
+            quantum_steps: 2,
+            max_total_steps: None,
+        });
+        let counter = scheduler.add(
+            "counter",
+            Counter {
+                next: 0,
+                limit: Some(3),
+            },
+        );
+        let first = scheduler.add(
+            "first",
+            Adder {
+                inputs: Default::default(),
+            },
+        );
+        let second = scheduler.add(
+            "second",
+            Adder {
+                inputs: Default::default(),
+            },
+        );
+        scheduler.connect(counter, first);
+        scheduler.connect(first, second);
+        if let Some(tap) = tap {
+            scheduler.set_message_tap(tap);
+        }
+        assert_eq!(Ok(RunOutcome::Idle), scheduler.run());
+        scheduler
+    }
+
+    #[test]
+    fn test_message_tap_sees_every_routed_message() {
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let recorder = seen.clone();
+        let tapped = relay(Some(Box::new(move |message: &RoutedMessage| {
+            recorder.borrow_mut().push(message.clone())
+        })));
+        let message = |from, to, value, step| RoutedMessage {
+            from,
+            to,
+            payload: vec![value],
+            step,
+        };
+        assert_eq!(
+            vec![
+                message(0, 1, 1, 1),
+                message(0, 1, 2, 2),
+                message(1, 2, 101, 3),
+                message(1, 2, 102, 4),
+                message(0, 1, 3, 7),
+                message(1, 2, 103, 9),
+            ],
+            *seen.borrow()
+        );
+
+        let untapped = relay(None);
+        assert_eq!(untapped.total_steps(), tapped.total_steps());
+        for id in 0..3 {
+            assert_eq!(untapped.outputs(id), tapped.outputs(id));
+            assert_eq!(untapped.steps_used(id), tapped.steps_used(id));
+            assert_eq!(untapped.is_halted(id), tapped.is_halted(id));
+        }
+        assert_eq!(&[201, 202, 203], tapped.outputs(2));
+    }
+
+    #[test]
+    fn test_intcode_pipeline() {
+        // Doubles its input, then halts.
+        let doubler = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        let mut scheduler = Scheduler::new(SchedulerConfig::default());
+        let first = scheduler.add("first", IntcodeMachine::new(&doubler));
+        let second = scheduler.add("second", IntcodeMachine::new(&doubler));
+        scheduler.connect(second, first);
+        assert_eq!(Ok(RunOutcome::Idle), scheduler.run());
+        assert_eq!(0, scheduler.pending_inputs(second));
+
+        scheduler.push_input(second, 5);
+        assert_eq!(1, scheduler.pending_inputs(second));
+        assert_eq!(Ok(RunOutcome::Halted), scheduler.run());
+        assert_eq!(&[20], scheduler.outputs(first));
+        assert_eq!("halted", RunOutcome::Halted.to_string());
+        assert_eq!("idle", RunOutcome::Idle.to_string());
+    }
+
+    #[test]
+    fn test_run_with_chrome_trace() {
+        let doubler = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        let mut scheduler = Scheduler::new(SchedulerConfig::default());
+        let first = scheduler.add("first", IntcodeMachine::new(&doubler));
+        let second = scheduler.add("second", IntcodeMachine::new(&doubler));
+        scheduler.connect(second, first);
+        scheduler.push_input(second, 5);
+
+        let path = std::env::temp_dir().join(format!("aoc_trace_{}.json", std::process::id()));
+        assert_eq!(
+            Ok(RunOutcome::Halted),
+            scheduler.run_with_chrome_trace(&path)
+        );
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let events: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        for event in events.iter() {
+            for field in ["ph", "ts", "pid", "tid", "name"].iter() {
+                assert!(
+                    event.get(field).is_some(),
+                    "{} missing from {}",
+                    field,
+                    event
+                );
+            }
+        }
+        let count = |name: &str| events.iter().filter(|event| event["name"] == name).count();
+        assert_eq!(2, count("thread_name"));
+        assert_eq!(2, count("quantum"));
+        assert_eq!(2, count("input"));
+        assert_eq!(2, count("output"));
+        assert_eq!(1, count("routed"));
+        assert_eq!(2, count("message"));
+        assert_eq!(11, events.len());
+
+        let timestamps: Vec<u64> = events
+            .iter()
+            .map(|event| event["ts"].as_u64().unwrap())
+            .collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(Some(&7), timestamps.last());
+    }
+}
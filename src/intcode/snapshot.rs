@@ -0,0 +1,198 @@
+use super::replay::memory_hash;
+use super::{Emulator, EmulatorError, EmulatorMemoryType};
+
+// A cheap key for deduplicating machine states in searches. Memory is
+// hashed rather than cloned, so two different states can share a key with
+// probability around 2^-64. Confirm with Emulator::state_eq, or key on a
+// full EmulatorSnapshot, when a collision would matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateKey {
+    pub memory_hash: u64,
+    pub instruction_pointer: usize,
+    pub relative_base: EmulatorMemoryType,
+    pub halted: bool,
+}
+
+// The exact machine state, without the input source. With the serde
+// feature it can be saved and loaded with any serde format.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmulatorSnapshot {
+    pub memory: Vec<EmulatorMemoryType>,
+    pub instruction_pointer: usize,
+    pub relative_base: EmulatorMemoryType,
+    pub halted: bool,
+}
+
+impl EmulatorSnapshot {
+    // The same key the emulator had when the snapshot was taken.
+    pub fn state_key(&self) -> StateKey {
+        StateKey {
+            memory_hash: memory_hash(&self.memory),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            halted: self.halted,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>> Emulator<I> {
+    pub fn state_key(&self) -> StateKey {
+        StateKey {
+            memory_hash: memory_hash(&self.memory),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            halted: self.halted,
+        }
+    }
+
+    pub fn snapshot(&self) -> EmulatorSnapshot {
+        EmulatorSnapshot {
+            memory: self.memory.to_vec(),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            halted: self.halted,
+        }
+    }
+
+    // A machine in the snapshot's state, reading from input_iter. Inputs
+    // queued but not yet read when the snapshot was taken aren't part of
+    // it, so input_iter should start with them. Settings and history start
+    // fresh, and reset() goes back to the snapshot rather than the program.
+    pub fn restore(snapshot: &EmulatorSnapshot, input_iter: I) -> Emulator<I> {
+        let mut emulator = Emulator::new(&snapshot.memory, input_iter);
+        emulator.instruction_pointer = snapshot.instruction_pointer;
+        emulator.relative_base = snapshot.relative_base;
+        emulator.halted = snapshot.halted;
+        emulator
+    }
+
+    // Exact comparison of the state a StateKey summarises.
+    pub fn state_eq<J: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>>>(
+        &self,
+        other: &Emulator<J>,
+    ) -> bool {
+        self.instruction_pointer == other.instruction_pointer
+            && self.relative_base == other.relative_base
+            && self.halted == other.halted
+            && self.memory == other.memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{emulator_with_empty_input, QueueInput};
+    use std::collections::HashSet;
+
+    // Both inputs clear cell 20 and halt at 17, one through the jump at 2
+    // and one through the jump at 5.
+    const CONVERGING: [EmulatorMemoryType; 21] = [
+        3, 20, 1005, 20, 9, 1105, 1, 13, 0, 1101, 0, 0, 20, 1101, 0, 0, 20, 99, 0, 0, 0,
+    ];
+
+    #[test]
+    fn test_different_paths_to_the_same_state() -> Result<(), EmulatorError> {
+        let mut taken = Emulator::with_inputs(&CONVERGING, vec![4]);
+        let mut not_taken = Emulator::with_inputs(&CONVERGING, vec![0]);
+        taken.run_to_completion()?;
+        not_taken.run_to_completion()?;
+        assert_eq!(taken.state_key(), not_taken.state_key());
+        assert!(taken.state_eq(&not_taken));
+        assert_eq!(taken.snapshot(), not_taken.snapshot());
+        assert!(taken.snapshot().halted);
+        assert_eq!(taken.state_key(), taken.snapshot().state_key());
+
+        let mut one_cell_off = CONVERGING;
+        one_cell_off[18] = 1;
+        let mut different = Emulator::with_inputs(&one_cell_off, vec![0]);
+        different.run_to_completion()?;
+        assert_ne!(taken.state_key(), different.state_key());
+        assert!(!taken.state_eq(&different));
+        assert_ne!(taken.snapshot(), different.snapshot());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots_as_hash_keys() -> Result<(), EmulatorError> {
+        // Counts in cell 7 forever.
+        let mut emulator = emulator_with_empty_input(&[1001, 7, 1, 7, 1105, 1, 0, 0]);
+        let mut snapshots = HashSet::new();
+        let mut keys = HashSet::new();
+        let mut history = Vec::new();
+        for _ in 0..1_000 {
+            emulator.step()?;
+            assert!(snapshots.insert(emulator.snapshot()));
+            assert!(keys.insert(emulator.state_key()));
+            history.push(emulator.snapshot());
+        }
+        for snapshot in history {
+            assert!(!snapshots.insert(snapshot));
+        }
+        assert_eq!(1_000, snapshots.len());
+        assert_eq!(1_000, keys.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_base_is_part_of_the_state() -> Result<(), EmulatorError> {
+        // Moves the relative base by its input, then clears the input cell.
+        let program = [3, 9, 9, 9, 1101, 0, 0, 9, 99, 0];
+        let mut one = Emulator::with_inputs(&program, vec![1]);
+        let mut two = Emulator::with_inputs(&program, vec![2]);
+        one.run_to_completion()?;
+        two.run_to_completion()?;
+        assert_eq!(one.memory(), two.memory());
+        assert_ne!(one.state_key(), two.state_key());
+        assert!(!one.state_eq(&two));
+        assert_eq!(2, two.snapshot().relative_base);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_resumes_the_run() -> Result<(), EmulatorError> {
+        // Adds up inputs until it reads a 0, printing the running total.
+        let program = [
+            3, 100, 1006, 100, 15, 1, 100, 101, 101, 4, 101, 1105, 1, 0, 0, 99,
+        ];
+        let inputs = vec![5, 7, 11, 0];
+        let uninterrupted =
+            Emulator::with_inputs(&program, inputs.clone()).run_collecting_outputs()?;
+        assert_eq!(vec![5, 12, 23], uninterrupted);
+
+        let mut first_half = Emulator::with_inputs(&program, inputs);
+        let mut outputs = Vec::new();
+        while outputs.len() < 2 {
+            outputs.extend(first_half.step()?.output());
+        }
+        let snapshot = first_half.snapshot();
+        let pending = first_half.drain_pending_inputs();
+        let mut resumed = Emulator::restore(&snapshot, QueueInput::from(pending));
+        assert!(resumed.state_eq(&first_half));
+        outputs.extend(resumed.run_collecting_outputs()?);
+        assert_eq!(uninterrupted, outputs);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialized_snapshot_resumes_the_run() -> Result<(), EmulatorError> {
+        // Counts cell 20 up to 5, printing each count.
+        let program = [1001, 20, 1, 20, 4, 20, 1007, 20, 5, 21, 1005, 21, 0, 99];
+        let uninterrupted = emulator_with_empty_input(&program).run_collecting_outputs()?;
+
+        let mut emulator = emulator_with_empty_input(&program);
+        let mut outputs = Vec::new();
+        for _ in 0..7 {
+            outputs.extend(emulator.step()?.output());
+        }
+        let json = serde_json::to_string(&emulator.snapshot()).unwrap();
+        let snapshot: EmulatorSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(emulator.snapshot(), snapshot);
+        let mut resumed = Emulator::restore(&snapshot, std::iter::empty());
+        outputs.extend(resumed.run_collecting_outputs()?);
+        assert_eq!(vec![1, 2, 3, 4, 5], uninterrupted);
+        assert_eq!(uninterrupted, outputs);
+        Ok(())
+    }
+}
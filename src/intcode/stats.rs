@@ -0,0 +1,120 @@
+use super::{EmulatorMemoryType, OPCODE_TABLE};
+use std::collections::BTreeMap;
+
+// The widest bar in the opcode histogram.
+const HISTOGRAM_WIDTH: u64 = 40;
+
+// Collected by an Emulator once enable_stats() has been called.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExecutionStats {
+    // Instructions executed since stats were enabled.
+    pub total_steps: u64,
+    // Executions of each opcode, keyed by its code.
+    pub opcodes: BTreeMap<EmulatorMemoryType, u64>,
+    // (taken, not_taken) for each conditional jump address that executed.
+    pub branches: BTreeMap<usize, (u64, u64)>,
+}
+
+impl ExecutionStats {
+    pub fn new() -> ExecutionStats {
+        ExecutionStats::default()
+    }
+
+    pub(crate) fn record_instruction(&mut self, opcode: EmulatorMemoryType) {
+        self.total_steps += 1;
+        *self.opcodes.entry(opcode).or_insert(0) += 1;
+    }
+
+    pub fn opcode_count(&self, opcode: EmulatorMemoryType) -> u64 {
+        self.opcodes.get(&opcode).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn record_branch(&mut self, address: usize, taken: bool) {
+        let counts = self.branches.entry(address).or_insert((0, 0));
+        if taken {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+        }
+    }
+
+    pub fn branch(&self, address: usize) -> Option<(u64, u64)> {
+        self.branches.get(&address).copied()
+    }
+}
+
+pub(crate) fn mnemonic(opcode: EmulatorMemoryType) -> String {
+    OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == opcode)
+        .map_or(opcode.to_string(), |info| info.mnemonic.to_string())
+}
+
+// A histogram of opcodes, most executed first, then one line per
+// conditional jump in address order.
+impl std::fmt::Display for ExecutionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut opcodes: Vec<(EmulatorMemoryType, u64)> = self
+            .opcodes
+            .iter()
+            .map(|(&opcode, &count)| (opcode, count))
+            .collect();
+        opcodes.sort_by_key(|&(opcode, count)| (std::cmp::Reverse(count), opcode));
+        let most = opcodes.first().map_or(1, |&(_, count)| count);
+        for (opcode, count) in opcodes {
+            // Rounded up, so every executed opcode gets at least one mark.
+            let bar = (count * HISTOGRAM_WIDTH).div_ceil(most) as usize;
+            writeln!(
+                f,
+                "{:<18} {:>8} {}",
+                mnemonic(opcode),
+                count,
+                "#".repeat(bar)
+            )?;
+        }
+        if !self.opcodes.is_empty() {
+            writeln!(f, "{:<18} {:>8}", "total", self.total_steps)?;
+        }
+        for (address, (taken, not_taken)) in self.branches.iter() {
+            writeln!(
+                f,
+                "{:>5}: taken {}, not taken {}",
+                address, taken, not_taken
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let mut stats = ExecutionStats::new();
+        stats.record_branch(33, true);
+        stats.record_branch(6, false);
+        stats.record_branch(6, true);
+        stats.record_branch(6, false);
+        assert_eq!(
+            "    6: taken 1, not taken 2\n   33: taken 1, not taken 0\n",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_histogram() {
+        let mut stats = ExecutionStats::new();
+        for &opcode in [1, 5, 1, 1, 99, 5].iter() {
+            stats.record_instruction(opcode);
+        }
+        assert_eq!(
+            "Add                       3 ########################################\n\
+             JumpIfTrue                2 ###########################\n\
+             End                       1 ##############\n\
+             total                     6\n",
+            stats.to_string()
+        );
+    }
+}
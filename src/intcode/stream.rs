@@ -0,0 +1,115 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+// The consumer's end of Emulator::stream_outputs. Iteration ends once the
+// machine halts or fails; join the handle to find out which.
+pub struct OutputReceiver {
+    receiver: Receiver<EmulatorMemoryType>,
+}
+
+impl Iterator for OutputReceiver {
+    type Item = EmulatorMemoryType;
+
+    fn next(&mut self) -> Option<EmulatorMemoryType> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<I> Emulator<I>
+where
+    I: Iterator<Item = Result<EmulatorMemoryType, EmulatorError>> + Send + 'static,
+{
+    // Runs the machine on a worker thread, sending outputs through a channel
+    // holding at most capacity of them, so the machine blocks while the
+    // consumer lags. The handle gives the emulator back once it halts. If the
+    // receiver is dropped first, the machine stops at the output it couldn't
+    // send and is handed back unhalted.
+    pub fn stream_outputs(
+        mut self,
+        capacity: usize,
+    ) -> (
+        OutputReceiver,
+        JoinHandle<Result<Emulator<I>, EmulatorError>>,
+    ) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let handle = thread::spawn(move || {
+            loop {
+                match self.step()? {
+                    EmulatorResult::Done => break,
+                    EmulatorResult::Success | EmulatorResult::Watchpoint { .. } => {}
+                    EmulatorResult::SuccessWithValue(value) => {
+                        if sender.send(value).is_err() {
+                            break;
+                        }
+                    }
+                    // The thread owns the emulator, so no more input is coming.
+                    EmulatorResult::NeedsInput => return Err(self.missing_input()),
+                }
+            }
+            Ok(self)
+        });
+        (OutputReceiver { receiver }, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Echoes inputs forever, counting how many it has pulled.
+    fn echo_forever() -> (
+        Emulator<impl Iterator<Item = Result<EmulatorMemoryType, EmulatorError>> + Send>,
+        Arc<AtomicUsize>,
+    ) {
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let counter = pulled.clone();
+        let input = std::iter::repeat_with(move || {
+            Ok(counter.fetch_add(1, Ordering::SeqCst) as EmulatorMemoryType)
+        });
+        (Emulator::new(&[3, 7, 4, 7, 1105, 1, 0, 0], input), pulled)
+    }
+
+    #[test]
+    fn test_stream_outputs_to_completion() {
+        let emulator = Emulator::with_inputs(&[3, 11, 4, 11, 1002, 11, 2, 11, 4, 11, 99, 0], [21]);
+        let (outputs, handle) = emulator.stream_outputs(1);
+        assert_eq!(vec![21, 42], outputs.collect::<Vec<_>>());
+        let emulator = handle.join().unwrap().unwrap();
+        assert!(emulator.is_halted());
+        assert_eq!(42, emulator.memory()[11]);
+    }
+
+    #[test]
+    fn test_slow_consumer_holds_back_the_machine() {
+        let (emulator, pulled) = echo_forever();
+        let (mut outputs, handle) = emulator.stream_outputs(2);
+        assert_eq!(Some(0), outputs.next());
+        thread::sleep(Duration::from_millis(50));
+        // Two outputs queued and a third waiting to be sent, plus the one
+        // already taken.
+        assert!(pulled.load(Ordering::SeqCst) <= 4);
+        assert_eq!(vec![1, 2, 3], outputs.by_ref().take(3).collect::<Vec<_>>());
+
+        drop(outputs);
+        let emulator = handle.join().unwrap().unwrap();
+        assert!(!emulator.is_halted());
+    }
+
+    #[test]
+    fn test_errors_end_the_stream() {
+        let emulator = Emulator::with_inputs(&[104, 7, 3, 0, 99], []);
+        let (outputs, handle) = emulator.stream_outputs(4);
+        assert_eq!(vec![7], outputs.collect::<Vec<_>>());
+        assert_eq!(
+            Some(EmulatorError::InputNonExistent {
+                position: 2,
+                context: None,
+            }),
+            handle.join().unwrap().err()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::EmulatorMemoryType;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+
+// All events share one process; each machine is a thread.
+const PROCESS_ID: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Phase {
+    ThreadName(String),
+    // Duration in steps.
+    Complete(u64),
+    Instant,
+    // Flow events link two points on different threads by a shared id.
+    FlowStart(u64),
+    FlowEnd(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TraceEvent {
+    name: String,
+    phase: Phase,
+    timestamp: u64,
+    thread_id: usize,
+    args: Vec<(&'static str, String)>,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl TraceEvent {
+    fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"name\":\"{}\",\"pid\":{},\"tid\":{},\"ts\":{}",
+            escape(&self.name),
+            PROCESS_ID,
+            self.thread_id,
+            self.timestamp
+        );
+        let mut args = self.args.clone();
+        match &self.phase {
+            Phase::ThreadName(name) => {
+                json.push_str(",\"ph\":\"M\"");
+                args.push(("name", format!("\"{}\"", escape(name))));
+            }
+            Phase::Complete(duration) => {
+                let _ = write!(json, ",\"ph\":\"X\",\"dur\":{}", duration);
+            }
+            Phase::Instant => json.push_str(",\"ph\":\"i\",\"s\":\"t\""),
+            Phase::FlowStart(id) => {
+                let _ = write!(json, ",\"ph\":\"s\",\"id\":{},\"cat\":\"flow\"", id);
+            }
+            // Binds to the enclosing slice, so the arrow ends at the event.
+            Phase::FlowEnd(id) => {
+                let _ = write!(
+                    json,
+                    ",\"ph\":\"f\",\"bp\":\"e\",\"id\":{},\"cat\":\"flow\"",
+                    id
+                );
+            }
+        }
+        if !args.is_empty() {
+            let args: Vec<String> = args
+                .iter()
+                .map(|(key, value)| format!("\"{}\":{}", key, value))
+                .collect();
+            let _ = write!(json, ",\"args\":{{{}}}", args.join(","));
+        }
+        json.push('}');
+        json
+    }
+}
+
+// Collects Chrome trace-event JSON, loadable in chrome://tracing or
+// Perfetto. Timestamps are global step counts, shown as microseconds.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TraceExporter {
+    events: Vec<TraceEvent>,
+}
+
+impl TraceExporter {
+    pub fn new() -> TraceExporter {
+        TraceExporter::default()
+    }
+
+    pub fn name_thread(&mut self, thread_id: usize, name: &str) {
+        self.events.push(TraceEvent {
+            name: "thread_name".to_string(),
+            phase: Phase::ThreadName(name.to_string()),
+            timestamp: 0,
+            thread_id,
+            args: Vec::new(),
+        });
+    }
+
+    // Starts a duration event and returns a handle for end_span, so events
+    // stay in timestamp order.
+    pub fn begin_span(&mut self, thread_id: usize, name: &str, timestamp: u64) -> usize {
+        self.events.push(TraceEvent {
+            name: name.to_string(),
+            phase: Phase::Complete(0),
+            timestamp,
+            thread_id,
+            args: Vec::new(),
+        });
+        self.events.len() - 1
+    }
+
+    pub fn end_span(&mut self, span: usize, timestamp: u64) {
+        let event = &mut self.events[span];
+        event.phase = Phase::Complete(timestamp.saturating_sub(event.timestamp));
+    }
+
+    // Drops a span that turned out to be empty. Only valid before any later
+    // event has been added.
+    pub fn discard_span(&mut self, span: usize) {
+        self.events.remove(span);
+    }
+
+    pub fn instant(
+        &mut self,
+        thread_id: usize,
+        name: &str,
+        timestamp: u64,
+        args: &[(&'static str, EmulatorMemoryType)],
+    ) {
+        self.events.push(TraceEvent {
+            name: name.to_string(),
+            phase: Phase::Instant,
+            timestamp,
+            thread_id,
+            args: args
+                .iter()
+                .map(|&(key, value)| (key, value.to_string()))
+                .collect(),
+        });
+    }
+
+    // An arrow from one thread to another, such as a message between
+    // machines. Both ends share flow_id.
+    pub fn flow(
+        &mut self,
+        name: &str,
+        flow_id: u64,
+        (from_thread, from_timestamp): (usize, u64),
+        (to_thread, to_timestamp): (usize, u64),
+    ) {
+        let ends = vec![
+            (from_thread, from_timestamp, Phase::FlowStart(flow_id)),
+            (to_thread, to_timestamp, Phase::FlowEnd(flow_id)),
+        ];
+        for (thread_id, timestamp, phase) in ends {
+            self.events.push(TraceEvent {
+                name: name.to_string(),
+                phase,
+                timestamp,
+                thread_id,
+                args: Vec::new(),
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // A JSON array with one event per line.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "[")?;
+        for (index, event) in self.events.iter().enumerate() {
+            let separator = if index + 1 < self.events.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(writer, "{}{}", event.to_json(), separator)?;
+        }
+        writeln!(writer, "]")
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.write_to(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_to() {
+        let mut trace = TraceExporter::new();
+        trace.name_thread(0, "amp \"a\"");
+        let span = trace.begin_span(0, "quantum", 0);
+        trace.instant(0, "output", 3, &[("value", -5)]);
+        trace.flow("message", 9, (0, 3), (1, 3));
+        trace.end_span(span, 4);
+
+        let mut json = Vec::new();
+        trace.write_to(&mut json).unwrap();
+        assert_eq!(
+            "[\n\
+             {\"name\":\"thread_name\",\"pid\":1,\"tid\":0,\"ts\":0,\"ph\":\"M\",\"args\":{\"name\":\"amp \\\"a\\\"\"}},\n\
+             {\"name\":\"quantum\",\"pid\":1,\"tid\":0,\"ts\":0,\"ph\":\"X\",\"dur\":4},\n\
+             {\"name\":\"output\",\"pid\":1,\"tid\":0,\"ts\":3,\"ph\":\"i\",\"s\":\"t\",\"args\":{\"value\":-5}},\n\
+             {\"name\":\"message\",\"pid\":1,\"tid\":0,\"ts\":3,\"ph\":\"s\",\"id\":9,\"cat\":\"flow\"},\n\
+             {\"name\":\"message\",\"pid\":1,\"tid\":1,\"ts\":3,\"ph\":\"f\",\"bp\":\"e\",\"id\":9,\"cat\":\"flow\"}\n\
+             ]\n",
+            String::from_utf8(json).unwrap()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use super::{Emulator, EmulatorError, EmulatorMemoryType, Memory, MemoryCell};
+use std::sync::{Arc, Mutex};
+
+pub(super) type UnknownOpcodeHook<C> =
+    Arc<Mutex<dyn FnMut(&mut UnknownOpcodeCtx<C>) -> Result<(), EmulatorError> + Send>>;
+
+// What a hook registered with on_unknown_opcode gets to work with: the
+// instruction the emulator couldn't decode, where it is, and the machine's
+// memory. Unless the hook says otherwise, execution carries on at the cell
+// after the instruction.
+pub struct UnknownOpcodeCtx<'a, C = EmulatorMemoryType> {
+    instruction: C,
+    instruction_pointer: usize,
+    memory: &'a mut Memory<C>,
+    next_instruction_pointer: usize,
+}
+
+impl<'a, C: MemoryCell> UnknownOpcodeCtx<'a, C> {
+    // The whole cell, parameter modes included.
+    pub fn instruction(&self) -> C {
+        self.instruction
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn memory(&mut self) -> &mut Memory<C> {
+        self.memory
+    }
+
+    pub fn set_next_instruction_pointer(&mut self, next_instruction_pointer: usize) {
+        self.next_instruction_pointer = next_instruction_pointer;
+    }
+}
+
+impl<C: MemoryCell, I: Iterator<Item = Result<C, EmulatorError>>> Emulator<I, C> {
+    // Runs hook in place of failing with InvalidInstruction, for programs
+    // that use opcodes of their own. A hooked instruction counts as a step
+    // and goes into the history, but the observer isn't told about it, as
+    // there's no OpCode to report. An error from the hook stops the machine
+    // like any other. Replaces any earlier hook, and is shared with forks.
+    pub fn on_unknown_opcode(
+        &mut self,
+        hook: impl FnMut(&mut UnknownOpcodeCtx<C>) -> Result<(), EmulatorError> + Send + 'static,
+    ) {
+        self.unknown_opcode_hook = Some(Arc::new(Mutex::new(hook)));
+    }
+
+    pub fn clear_unknown_opcode_hook(&mut self) {
+        self.unknown_opcode_hook = None;
+    }
+
+    pub(super) fn has_unknown_opcode_hook(&self) -> bool {
+        self.unknown_opcode_hook.is_some()
+    }
+
+    // Returns where to carry on from.
+    pub(super) fn run_unknown_opcode(
+        &mut self,
+        instruction_pointer: usize,
+        instruction: C,
+    ) -> Result<usize, EmulatorError> {
+        let hook = match &self.unknown_opcode_hook {
+            Some(hook) => hook,
+            None => unreachable!("run_unknown_opcode without a hook"),
+        };
+        let mut ctx = UnknownOpcodeCtx {
+            instruction,
+            instruction_pointer,
+            memory: &mut self.memory,
+            next_instruction_pointer: instruction_pointer + 1,
+        };
+        let mut hook = hook.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (*hook)(&mut ctx)?;
+        Ok(ctx.next_instruction_pointer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::empty;
+
+    // 42 p doubles the cell p points at.
+    fn double(ctx: &mut UnknownOpcodeCtx) -> Result<(), EmulatorError> {
+        let instruction_pointer = ctx.instruction_pointer();
+        if ctx.instruction() != 42 {
+            return Err(EmulatorError::InvalidInstruction {
+                value_found: ctx.instruction().widen(),
+                position: instruction_pointer,
+                context: None,
+            });
+        }
+        let memory = ctx.memory();
+        let address = memory[instruction_pointer + 1] as usize;
+        memory[address] *= 2;
+        ctx.set_next_instruction_pointer(instruction_pointer + 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_opcode_hook() -> Result<(), EmulatorError> {
+        let program = [42, 11, 42, 11, 1001, 11, 1, 11, 4, 11, 99, 3];
+        let mut emulator = Emulator::new(&program, empty());
+        assert_eq!(
+            EmulatorError::InvalidInstruction {
+                value_found: 42,
+                position: 0,
+                context: None,
+            },
+            emulator.run_collecting_outputs().unwrap_err()
+        );
+
+        // Doubled twice and 1 added.
+        let mut emulator = Emulator::new(&program, empty());
+        emulator.on_unknown_opcode(double);
+        assert_eq!(vec![13], emulator.run_collecting_outputs()?);
+        assert!(emulator.is_halted());
+
+        // Errors from the hook come out of step as they are.
+        let mut emulator = Emulator::new(&[43, 0, 99], empty());
+        emulator.on_unknown_opcode(double);
+        assert_eq!(
+            EmulatorError::InvalidInstruction {
+                value_found: 43,
+                position: 0,
+                context: None,
+            },
+            emulator.step().unwrap_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use super::{EmulatorMemoryType, OpCodeInfo, ParameterKind, OPCODE_TABLE};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    UnknownOpcode {
+        instruction: EmulatorMemoryType,
+    },
+    // A mode digit other than 0, 1 or 2.
+    InvalidParameterMode {
+        instruction: EmulatorMemoryType,
+    },
+    // parameter is the index of the parameter, from 0.
+    ImmediateWrite {
+        parameter: usize,
+    },
+    // A position mode parameter addressing a cell that can't exist:
+    // negative, or past the end when memory can't grow.
+    OutsideMemory {
+        parameter: usize,
+        address: EmulatorMemoryType,
+    },
+    // The instruction's parameters run past the end of memory.
+    RunsOffEnd {
+        expected: usize,
+        found: usize,
+    },
+}
+
+// Something that will probably fail if the instruction at address runs.
+// Probably, because a program can rewrite its own instructions before
+// reaching them, so these are warnings rather than errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub address: usize,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.address)?;
+        match &self.kind {
+            IssueKind::UnknownOpcode { instruction } => {
+                write!(f, "unknown opcode in {}", instruction)
+            }
+            IssueKind::InvalidParameterMode { instruction } => {
+                write!(f, "invalid parameter mode in {}", instruction)
+            }
+            IssueKind::ImmediateWrite { parameter } => write!(
+                f,
+                "parameter {} is written to, but is in immediate mode",
+                parameter + 1
+            ),
+            IssueKind::OutsideMemory { parameter, address } => write!(
+                f,
+                "parameter {} addresses {}, outside memory",
+                parameter + 1,
+                address
+            ),
+            IssueKind::RunsOffEnd { expected, found } => write!(
+                f,
+                "expects {} parameters, but memory ends after {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+// Whether the instruction at address always jumps or halts, so the cells
+// after it are only code if something jumps to them.
+fn stops_straight_line(memory: &[EmulatorMemoryType], address: usize, info: &OpCodeInfo) -> bool {
+    if info.halts {
+        return true;
+    }
+    if !info.jumps || (memory[address] / 100) % 10 != 1 {
+        return false;
+    }
+    // Jump if true (5) with a non-zero immediate, or jump if false (6)
+    // with a zero one.
+    let condition = memory[address + 1];
+    (info.code == 5) == (condition != 0)
+}
+
+// The issues with the instruction at address, if it is one.
+fn check(
+    memory: &[EmulatorMemoryType],
+    address: usize,
+    info: &OpCodeInfo,
+    grows: bool,
+) -> Vec<IssueKind> {
+    let instruction = memory[address];
+    let found = memory.len() - address - 1;
+    if info.parameters.len() > found {
+        return vec![IssueKind::RunsOffEnd {
+            expected: info.parameters.len(),
+            found,
+        }];
+    }
+    let mut issues = Vec::new();
+    let mut mode_digits = instruction / 100;
+    for (index, parameter) in info.parameters.iter().enumerate() {
+        let word = memory[address + 1 + index];
+        match (mode_digits % 10, parameter.kind) {
+            (0, _) if word < 0 || (!grows && word as u64 >= memory.len() as u64) => {
+                issues.push(IssueKind::OutsideMemory {
+                    parameter: index,
+                    address: word,
+                })
+            }
+            (1, ParameterKind::Write) => {
+                issues.push(IssueKind::ImmediateWrite { parameter: index })
+            }
+            (0, _) | (1, _) | (2, _) => {}
+            _ => return vec![IssueKind::InvalidParameterMode { instruction }],
+        }
+        mode_digits /= 10;
+    }
+    issues
+}
+
+// The address a position mode Writable parameter of the instruction at
+// address writes to.
+fn written_address(
+    memory: &[EmulatorMemoryType],
+    address: usize,
+    info: &OpCodeInfo,
+) -> Option<usize> {
+    let mut mode_digits = memory[address] / 100;
+    for (index, parameter) in info.parameters.iter().enumerate() {
+        if parameter.kind == ParameterKind::Write && mode_digits % 10 == 0 {
+            return usize::try_from(*memory.get(address + 1 + index)?).ok();
+        }
+        mode_digits /= 10;
+    }
+    None
+}
+
+fn find_info(instruction: EmulatorMemoryType) -> Option<&'static OpCodeInfo> {
+    OPCODE_TABLE
+        .iter()
+        .find(|info| info.code == instruction % 100)
+}
+
+// The length of the instruction at address, if it has no issues.
+fn clean_length(memory: &[EmulatorMemoryType], address: usize, grows: bool) -> Option<usize> {
+    let info = find_info(memory[address])?;
+    if check(memory, address, info, grows).is_empty() {
+        Some(info.parameters.len() + 1)
+    } else {
+        None
+    }
+}
+
+// Whether address starts two instructions in a row with no issues, or one
+// that ends memory. Data decodes as a single clean instruction often
+// enough that one isn't taken as a sign of code.
+fn resumes_code(memory: &[EmulatorMemoryType], address: usize, grows: bool) -> bool {
+    clean_length(memory, address, grows).is_some_and(|length| {
+        address + length >= memory.len() || clean_length(memory, address + length, grows).is_some()
+    })
+}
+
+// Returns the issues found, and the cells instructions write to. Cells in
+// written are left alone, as what's in them when they run isn't known.
+fn sweep(
+    memory: &[EmulatorMemoryType],
+    grows: bool,
+    written: &HashSet<usize>,
+) -> (Vec<ValidationIssue>, HashSet<usize>) {
+    let mut issues = Vec::new();
+    let mut writes = HashSet::new();
+    let mut address = 0;
+    // After a halt, an unconditional jump or a cell that will be rewritten,
+    // the cells that follow may be data. They are skipped until
+    // resumes_code finds what looks like code again.
+    let mut maybe_data = false;
+    while address < memory.len() {
+        if maybe_data {
+            if !resumes_code(memory, address, grows) {
+                address += 1;
+                continue;
+            }
+            maybe_data = false;
+        }
+        let rewritten =
+            |length: usize| (address..address + length).any(|cell| written.contains(&cell));
+        let info = match find_info(memory[address]) {
+            Some(info) => info,
+            // Overwritten before it runs, so it may become any instruction
+            // of any length.
+            None if rewritten(1) => {
+                maybe_data = true;
+                address += 1;
+                continue;
+            }
+            None => {
+                issues.push(ValidationIssue {
+                    address,
+                    kind: IssueKind::UnknownOpcode {
+                        instruction: memory[address],
+                    },
+                });
+                address += 1;
+                continue;
+            }
+        };
+        let mut found = check(memory, address, info, grows);
+        // An instruction that fails doesn't write anything.
+        if found.is_empty() {
+            writes.extend(written_address(memory, address, info));
+        } else if rewritten(info.parameters.len() + 1) {
+            found.clear();
+        }
+        let runs_off_end = found
+            .iter()
+            .any(|kind| matches!(kind, IssueKind::RunsOffEnd { .. }));
+        issues.extend(
+            found
+                .into_iter()
+                .map(|kind| ValidationIssue { address, kind }),
+        );
+        if runs_off_end {
+            break;
+        }
+        maybe_data = stops_straight_line(memory, address, info);
+        address += info.parameters.len() + 1;
+    }
+    (issues, writes)
+}
+
+// The sweep runs twice: first to find which cells the program writes to,
+// then to report issues outside them.
+fn validate_with(memory: &[EmulatorMemoryType], grows: bool) -> Vec<ValidationIssue> {
+    let (_, written) = sweep(memory, grows, &HashSet::new());
+    sweep(memory, grows, &written).0
+}
+
+// A linear sweep from address 0 for instructions that would fail if run,
+// assuming memory grows to reach any address past the end as the emulator
+// does by default. Jumps aren't followed. Instructions the program
+// overwrites through a position mode parameter aren't checked, but ones it
+// rewrites any other way can still be reported wrongly.
+pub fn validate(program: &[EmulatorMemoryType]) -> Vec<ValidationIssue> {
+    validate_with(program, true)
+}
+
+// Like validate, for running with memory limited to the program, so that
+// position mode parameters past the end are issues too.
+pub fn validate_without_growth(program: &[EmulatorMemoryType]) -> Vec<ValidationIssue> {
+    validate_with(program, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(address: usize, kind: IssueKind) -> ValidationIssue {
+        ValidationIssue { address, kind }
+    }
+
+    #[test]
+    fn test_examples_have_no_issues() {
+        let examples: &[&[EmulatorMemoryType]] = &[
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[1002, 4, 3, 4, 33],
+            &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            &[3, 3, 1107, -1, 8, 3, 4, 3, 99],
+            &[
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            &[
+                3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+            ],
+            &[104, 1125899906842624, 99],
+        ];
+        for example in examples {
+            assert_eq!(Vec::<ValidationIssue>::new(), validate(example));
+            assert_eq!(
+                Vec::<ValidationIssue>::new(),
+                validate_without_growth(example)
+            );
+        }
+    }
+
+    #[test]
+    fn test_memory_growth() {
+        // The day 9 quine keeps its counter past the end of the program.
+        let quine = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(Vec::<ValidationIssue>::new(), validate(&quine));
+        let issues = validate_without_growth(&quine);
+        assert_eq!(
+            vec![
+                issue(
+                    4,
+                    IssueKind::OutsideMemory {
+                        parameter: 0,
+                        address: 100
+                    }
+                ),
+                issue(
+                    4,
+                    IssueKind::OutsideMemory {
+                        parameter: 2,
+                        address: 100
+                    }
+                ),
+            ],
+            issues[..2].to_vec()
+        );
+        assert_eq!(5, issues.len());
+    }
+
+    #[test]
+    fn test_corrupted_examples() {
+        assert_eq!(
+            vec![issue(0, IssueKind::UnknownOpcode { instruction: 1042 })],
+            validate(&[1042, 4, 3, 4, 33])
+        );
+        // The Add's destination switched to immediate mode.
+        assert_eq!(
+            vec![issue(0, IssueKind::ImmediateWrite { parameter: 2 })],
+            validate(&[10001, 0, 0, 0, 99])
+        );
+        assert_eq!(
+            vec![issue(
+                0,
+                IssueKind::InvalidParameterMode { instruction: 301 }
+            )],
+            validate(&[301, 0, 0, 0, 99])
+        );
+        assert_eq!(
+            vec![issue(
+                2,
+                IssueKind::OutsideMemory {
+                    parameter: 0,
+                    address: -1
+                }
+            )],
+            validate(&[3, 9, 8, -1, 10, 9, 4, 9, 99, -1, 8])
+        );
+        // Truncated in the middle of an Equals.
+        let issues = validate(&[3, 9, 8, 9, 10]);
+        assert_eq!(
+            vec![issue(
+                2,
+                IssueKind::RunsOffEnd {
+                    expected: 3,
+                    found: 2
+                }
+            )],
+            issues
+        );
+        assert_eq!(
+            "2: expects 3 parameters, but memory ends after 2",
+            issues[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_data_after_a_stop() {
+        // Cells after an unconditional jump aren't code until one decodes
+        // cleanly, but after a conditional one they are.
+        assert_eq!(
+            Vec::<ValidationIssue>::new(),
+            validate(&[1105, 1, 7, 42, 1101, 1, 50, 99])
+        );
+        assert_eq!(
+            vec![issue(3, IssueKind::UnknownOpcode { instruction: 42 })],
+            validate(&[1005, 7, 4, 42, 99, 0, 0, 0])
+        );
+        // Back in code, issues are reported again.
+        assert_eq!(
+            vec![issue(6, IssueKind::UnknownOpcode { instruction: 42 })],
+            validate(&[99, 50, 104, 1, 104, 2, 42, 99])
+        );
+        // One clean instruction could be data.
+        assert_eq!(
+            Vec::<ValidationIssue>::new(),
+            validate(&[99, 50, 104, 1, 42, 99])
+        );
+        // The Multiply turns the second 33 into a 99, but the first is still
+        // reported.
+        assert_eq!(
+            vec![issue(4, IssueKind::UnknownOpcode { instruction: 33 })],
+            validate(&[1002, 5, 3, 5, 33, 33])
+        );
+    }
+}
@@ -1,21 +1,46 @@
 #![deny(clippy::all)]
 
+pub mod cache;
 pub mod day1;
 
+pub mod day12;
+pub mod day13;
+pub mod day15;
+pub mod day16;
+pub mod day18;
 pub mod day2;
+pub mod day22;
+pub mod day24;
 pub mod day5;
 pub mod day7;
+pub mod grid;
+pub mod inputs;
 pub mod intcode;
+pub mod modmath;
+pub mod parse;
+pub mod program;
+pub mod progress;
+pub mod search;
+pub mod solution;
+pub mod verify;
 
-use std::fs::File;
-use std::io::BufRead;
+pub use opcode_macro::include_intcode;
 
-pub fn get_intcode_memory_from_file(path: &str) -> Vec<i64> {
-    let input_file = File::open(path).unwrap();
-    let reader = std::io::BufReader::new(input_file);
-    reader
-        .split(b',')
-        .map(|s| std::str::from_utf8(&s.unwrap()).unwrap().trim().parse())
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap()
+// Loads through cache::programs(), so each file is parsed once per process.
+pub fn get_intcode_memory_from_file(path: &str) -> Vec<intcode::EmulatorMemoryType> {
+    cache::programs().get_or_load(path).unwrap().to_vec()
+}
+
+// The program in path, with what intcode::validate makes of it. The issues
+// are only warnings, so the program is returned either way; what to do
+// about them is up to the caller.
+pub fn get_validated_intcode_memory_from_file(
+    path: &str,
+) -> (
+    Vec<intcode::EmulatorMemoryType>,
+    Vec<intcode::ValidationIssue>,
+) {
+    let program = cache::programs().get_or_load(path).unwrap().to_vec();
+    let issues = intcode::validate(&program);
+    (program, issues)
 }
@@ -1,13 +1,461 @@
 extern crate advent_of_code_2019;
 use advent_of_code_2019::*;
+use intcode::interactive::Session;
+use progress::ProgressSink;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "Usage: aoc verify --answers <answers.toml> --input-dir <dir>
+                  [--replay-dir <dir>]
+       aoc record-test <program> [--inputs <a,b,...>] --name <test_name> [--validate]
+       aoc opcodes
+       aoc examples [dayN]
+       aoc day2 <program> [--progress] [--validate]
+       aoc day7 <program> [--progress] [--validate]
+       aoc day15 <program> [--frames-dir <dir>] [--validate]
+       aoc interactive (<program> | --resume <session>) [--save <session>] [--validate]
+
+--replay-dir also checks every .icr replay in the directory still runs as
+recorded. --validate prints a warning for each problem intcode::validate
+finds in the program before running it. --progress shows how far the
+searches have got.";
 
 fn main() {
-    // day1::run_part_1("input/1.txt");
-    // day1::run_part_2("input/1.txt");
-    // println!("{}", day2::run_part_1("input/2.txt"));
-    // println!("{}", day2::run_part_2("input/2.txt").unwrap());
-    // println!("{}", day5::run_part_1("input/5.txt"));
-    // println!("{}", day5::run_part_2("input/5.txt"));
-    // println!("{}", day7::run_part_1("input/7.txt"));
-    println!("{}", day7::run_part_2("input/7.txt"));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("verify") => process::exit(run_verify(&args[1..])),
+        Some("record-test") => process::exit(run_record_test(&args[1..])),
+        Some("day2") => process::exit(run_day2(&args[1..])),
+        Some("day7") => process::exit(run_day7(&args[1..])),
+        Some("day15") => process::exit(run_day15(&args[1..])),
+        Some("examples") => process::exit(run_examples(&args[1..])),
+        Some("interactive") => process::exit(run_interactive(&args[1..])),
+        Some("opcodes") => {
+            if let Err(error) = intcode::reference::print_instruction_set(std::io::stdout()) {
+                eprintln!("Could not print the instruction set: {}", error);
+                process::exit(1);
+            }
+        }
+        Some(command) => {
+            eprintln!("Unknown command '{}'\n{}", command, USAGE);
+            process::exit(2);
+        }
+        None => {
+            // day1::run_part_1("input/1.txt");
+            // day1::run_part_2("input/1.txt");
+            // println!("{}", day2::run_part_1("input/2.txt"));
+            // println!("{}", day2::run_part_2("input/2.txt").unwrap());
+            // println!("{}", day5::run_part_1("input/5.txt"));
+            // println!("{}", day5::run_part_2("input/5.txt"));
+            // println!("{}", day7::run_part_1("input/7.txt"));
+            match day7::run_part_2("input/7.txt") {
+                Ok(thrust) => println!("{}", thrust),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
+// Reports a file that can't be read, so callers only need to return 2.
+fn load_program(path: &str, args: &[String]) -> Option<Vec<intcode::EmulatorMemoryType>> {
+    let program = match program::load_program(path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", path, error);
+            return None;
+        }
+    };
+    if args.iter().any(|arg| arg == "--validate") {
+        for issue in intcode::validate(&program) {
+            eprintln!("WARNING: {} at {}", path, issue);
+        }
+    }
+    Some(program)
+}
+
+// None without --progress. A progress bar with the indicatif feature,
+// otherwise lines on stderr.
+fn progress_sink(args: &[String], label: &str) -> Option<Box<dyn ProgressSink>> {
+    if !args.iter().any(|arg| arg == "--progress") {
+        return None;
+    }
+    #[cfg(feature = "indicatif")]
+    let sink = progress::IndicatifProgress::new(label);
+    #[cfg(not(feature = "indicatif"))]
+    let sink = progress::StderrProgress::new(label);
+    Some(Box::new(sink))
+}
+
+// Runs search with the sink for label, which is gone by the time the result
+// is printed, so the progress line has been ended.
+fn with_progress<T>(
+    args: &[String],
+    label: &str,
+    search: impl FnOnce(Option<&mut dyn ProgressSink>) -> T,
+) -> T {
+    let mut progress = progress_sink(args, label);
+    search(
+        progress
+            .as_mut()
+            .map(|sink| sink.as_mut() as &mut dyn ProgressSink),
+    )
+}
+
+fn program_arg(args: &[String]) -> Option<&str> {
+    args.first()
+        .map(String::as_str)
+        .filter(|program_path| !program_path.starts_with("--"))
+}
+
+fn run_day2(args: &[String]) -> i32 {
+    let program_path = match program_arg(args) {
+        Some(program_path) => program_path,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let program = match load_program(program_path, args) {
+        Some(program) => program,
+        None => return 2,
+    };
+    match day2::run_with_noun_verb(&program, 12, 2) {
+        Ok(output) => println!("Part 1: {}", output),
+        Err(error) => {
+            eprintln!("{} failed: {}", program_path, error);
+            return 1;
+        }
+    }
+    let answer = with_progress(args, "nouns and verbs", |progress| {
+        day2::find_noun_verb(&program, day2::TARGET_OUTPUT, progress)
+    });
+    print_search_answer(2, program_path, answer)
+}
+
+// Prints what a search for part found, or why it failed, and returns the
+// exit code.
+fn print_search_answer(
+    part: u32,
+    program_path: &str,
+    answer: Result<Option<intcode::EmulatorMemoryType>, intcode::EmulatorError>,
+) -> i32 {
+    match answer {
+        Ok(Some(answer)) => println!("Part {}: {}", part, answer),
+        Ok(None) => println!("Part {}: none", part),
+        Err(error) => {
+            eprintln!("{} failed: {}", program_path, error);
+            return 1;
+        }
+    }
+    0
+}
+
+fn run_day7(args: &[String]) -> i32 {
+    let program_path = match program_arg(args) {
+        Some(program_path) => program_path,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let program = match load_program(program_path, args) {
+        Some(program) => program,
+        None => return 2,
+    };
+    if let Err(error) = intcode::validate_entry(&program) {
+        eprintln!("{} failed: {}", program_path, error);
+        return 1;
+    }
+    let thrust = with_progress(args, "phase settings", |progress| {
+        day7::find_highest_thrust(&program, progress)
+    });
+    if print_search_answer(1, program_path, thrust) != 0 {
+        return 1;
+    }
+    let thrust = with_progress(args, "feedback phase settings", |progress| {
+        day7::find_highest_feedback_thrust(&program, progress)
+    });
+    print_search_answer(2, program_path, thrust)
+}
+
+fn run_verify(args: &[String]) -> i32 {
+    let (answers_path, input_dir) = match (
+        flag_value(args, "--answers"),
+        flag_value(args, "--input-dir"),
+    ) {
+        (Some(answers_path), Some(input_dir)) => (answers_path, input_dir),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let answers = match std::fs::read_to_string(answers_path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| verify::parse_answers(&text))
+    {
+        Ok(answers) => answers,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", answers_path, error);
+            return 2;
+        }
+    };
+
+    let replays = match flag_value(args, "--replay-dir") {
+        Some(replay_dir) => match intcode::replay::Replay::load_dir(replay_dir) {
+            Ok(replays) => replays,
+            Err(error) => {
+                eprintln!("Could not read replays from {}: {}", replay_dir, error);
+                return 2;
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let results = verify::verify(&answers, Path::new(input_dir));
+    for warning in results.iter().filter_map(|result| result.warning.as_ref()) {
+        eprintln!("WARNING: {}", warning);
+    }
+    print!("{}", verify::render_table(&results));
+    let mut failed = results.iter().any(verify::VerifyResult::is_failure);
+    for (path, replay) in replays {
+        match replay.verify() {
+            Ok(()) => println!("replay {}  pass", path.display()),
+            Err(mismatch) => {
+                println!("replay {}  FAIL  {}", path.display(), mismatch);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+fn run_record_test(args: &[String]) -> i32 {
+    let (program_path, name) = match (args.first(), flag_value(args, "--name")) {
+        (Some(program_path), Some(name)) if !program_path.starts_with("--") => (program_path, name),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let program = match load_program(program_path, args) {
+        Some(program) => program,
+        None => return 2,
+    };
+    let inputs = match flag_value(args, "--inputs")
+        .filter(|inputs| !inputs.is_empty())
+        .map_or(Ok(Vec::new()), |inputs| {
+            inputs
+                .split(',')
+                .map(|input| input.trim().parse())
+                .collect::<Result<Vec<_>, _>>()
+        }) {
+        Ok(inputs) => inputs,
+        Err(error) => {
+            eprintln!("Invalid --inputs: {}", error);
+            return 2;
+        }
+    };
+
+    match intcode::replay::Replay::record(&program, &inputs) {
+        Ok(replay) => {
+            print!("{}", intcode::record::emit_test(&replay, name));
+            0
+        }
+        Err(error) => {
+            eprintln!("{} failed to run: {}", program_path, error);
+            1
+        }
+    }
+}
+
+fn run_day15(args: &[String]) -> i32 {
+    let program_path = match args.first() {
+        Some(program_path) if !program_path.starts_with("--") => program_path,
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let program = match load_program(program_path, args) {
+        Some(program) => program,
+        None => return 2,
+    };
+    let frames_dir = flag_value(args, "--frames-dir").map(Path::new);
+    if let Some(dir) = frames_dir {
+        if let Err(error) = std::fs::create_dir_all(dir) {
+            eprintln!("Could not create {}: {}", dir.display(), error);
+            return 2;
+        }
+    }
+
+    let mut write_error = None;
+    let exploration = day15::explore_with_frames(&program, |frame, map| {
+        if let (Some(dir), None) = (frames_dir, &write_error) {
+            write_error = day15::write_frame(dir, frame, map).err();
+        }
+    });
+    if let Some(error) = write_error {
+        eprintln!("Could not write frames: {}", error);
+        return 1;
+    }
+    match exploration {
+        Ok(exploration) => {
+            let show = |value: Option<u32>| value.map_or("none".to_string(), |v| v.to_string());
+            println!("Part 1: {}", show(exploration.oxygen_distance));
+            println!("Part 2: {}", show(exploration.fill_minutes));
+            0
+        }
+        Err(error) => {
+            eprintln!("{} failed: {}", program_path, error);
+            1
+        }
+    }
+}
+
+fn run_examples(args: &[String]) -> i32 {
+    let solutions = match args.first() {
+        None => solution::registry(),
+        Some(day) => match day
+            .strip_prefix("day")
+            .unwrap_or(day)
+            .parse()
+            .ok()
+            .and_then(solution::find)
+        {
+            Some(solution) => vec![solution],
+            None => {
+                eprintln!("No solution for '{}'\n{}", day, USAGE);
+                return 2;
+            }
+        },
+    };
+
+    let mut failed = 0;
+    for solution in solutions {
+        for (index, example) in solution.examples().iter().enumerate() {
+            // A bug in a day module could still panic, which counts as a
+            // failure.
+            let answer = panic::catch_unwind(AssertUnwindSafe(|| {
+                solution::run_example(solution.as_ref(), example)
+            }));
+            let status = match answer {
+                Ok(Ok(answer)) if answer == example.expected => "ok".to_string(),
+                Ok(Ok(answer)) => format!("FAILED: expected {}, got {}", example.expected, answer),
+                Ok(Err(error)) => format!("FAILED: {}", error),
+                Err(_) => "FAILED: panicked".to_string(),
+            };
+            if status != "ok" {
+                failed += 1;
+            }
+            println!(
+                "day {} part {} example {}: {}",
+                solution.day(),
+                example.part.number(),
+                index + 1,
+                status
+            );
+        }
+    }
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+// ASCII programs print text; anything else is shown as a number on its own
+// line.
+fn print_outputs(outputs: &[intcode::EmulatorMemoryType]) {
+    let mut stdout = io::stdout();
+    for &output in outputs {
+        let _ = match u8::try_from(output) {
+            Ok(byte) if byte.is_ascii() => write!(stdout, "{}", byte as char),
+            _ => writeln!(stdout, "{}", output),
+        };
+    }
+    let _ = stdout.flush();
+}
+
+// Lines from stdin are fed to the program as ASCII. ":save" writes the
+// session and carries on, and the session is also written on exit.
+fn run_interactive(args: &[String]) -> i32 {
+    let resume_path = flag_value(args, "--resume");
+    let save_path = flag_value(args, "--save").or(resume_path);
+    let mut session = match (resume_path, args.first()) {
+        (Some(path), _) => match Session::resume(path) {
+            Ok(session) => session,
+            Err(error) => {
+                eprintln!("Could not resume {}: {}", path, error);
+                return 1;
+            }
+        },
+        (None, Some(program_path)) if !program_path.starts_with("--") => {
+            match load_program(program_path, args) {
+                Some(program) => Session::new(&program),
+                None => return 2,
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let save = |session: &Session| match save_path {
+        Some(path) => match session.save(path) {
+            Ok(()) => {
+                eprintln!("Saved session to {}", path);
+                true
+            }
+            Err(error) => {
+                eprintln!("Could not save to {}: {}", path, error);
+                false
+            }
+        },
+        None => {
+            eprintln!("No --save path given, so the session was not saved");
+            false
+        }
+    };
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        match session.run_until_input() {
+            Ok(outputs) => print_outputs(&outputs),
+            Err(error) => {
+                eprintln!("The program failed: {}", error);
+                return 1;
+            }
+        }
+        if session.emulator().is_halted() {
+            return 0;
+        }
+        match lines.next() {
+            Some(Ok(line)) if line.trim() == ":save" => {
+                save(&session);
+            }
+            Some(Ok(line)) => session.push_line(&line),
+            Some(Err(_)) | None => return if save(&session) { 0 } else { 1 },
+        }
+    }
 }
@@ -0,0 +1,278 @@
+// Modular arithmetic on u64 values. Products go through u128, so any modulus
+// up to u64::MAX works without overflow. gcd and lcm take u128, as lcms of
+// cycle lengths outgrow u64 quickly.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModMathError {
+    // value shares a factor with modulus, so it has no inverse.
+    NotInvertible { value: u64, modulus: u64 },
+}
+
+impl std::fmt::Display for ModMathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ModMathError::NotInvertible { value, modulus } => {
+                write!(f, "{} has no inverse modulo {}", value, modulus)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModMathError {}
+
+pub fn mulmod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+pub fn addmod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 + b as u128) % m as u128) as u64
+}
+
+pub fn powmod(base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    let mut base = base % m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mulmod(result, base, m);
+        }
+        base = mulmod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+pub fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = std::mem::replace(&mut b, remainder);
+    }
+    a
+}
+
+// 0 if either is 0.
+pub fn lcm(a: u128, b: u128) -> u128 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        a / gcd(a, b) * b
+    }
+}
+
+// Extended Euclid, which unlike Fermat's little theorem doesn't need a
+// prime modulus.
+pub fn inverse(value: u64, m: u64) -> Result<u64, ModMathError> {
+    let (mut old_r, mut r) = (value as i128 % m as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let quotient = old_r / r;
+        let next_r = old_r - quotient * r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_s = old_s - quotient * s;
+        old_s = std::mem::replace(&mut s, next_s);
+    }
+    if old_r != 1 || m == 1 {
+        return Err(ModMathError::NotInvertible { value, modulus: m });
+    }
+    Ok(old_s.rem_euclid(m as i128) as u64)
+}
+
+// x -> a * x + b (mod m).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Affine {
+    pub a: u64,
+    pub b: u64,
+    pub m: u64,
+}
+
+impl Affine {
+    // Signed coefficients are reduced into 0..m.
+    pub fn new(a: i128, b: i128, m: u64) -> Affine {
+        assert!(m > 0, "the modulus must be positive");
+        Affine {
+            a: a.rem_euclid(m as i128) as u64,
+            b: b.rem_euclid(m as i128) as u64,
+            m,
+        }
+    }
+
+    pub fn identity(m: u64) -> Affine {
+        Affine::new(1, 0, m)
+    }
+
+    pub fn apply(&self, x: u64) -> u64 {
+        addmod(mulmod(self.a, x % self.m, self.m), self.b, self.m)
+    }
+
+    // self after first: x -> self(first(x)).
+    pub fn compose(&self, first: &Affine) -> Affine {
+        assert_eq!(self.m, first.m, "composed maps must share a modulus");
+        Affine {
+            a: mulmod(self.a, first.a, self.m),
+            b: self.apply(first.b),
+            m: self.m,
+        }
+    }
+
+    // self applied k times, by repeated squaring.
+    pub fn pow(&self, mut k: u64) -> Affine {
+        let mut result = Affine::identity(self.m);
+        let mut square = *self;
+        while k > 0 {
+            if k & 1 == 1 {
+                result = square.compose(&result);
+            }
+            square = square.compose(&square);
+            k >>= 1;
+        }
+        result
+    }
+
+    // x -> (x - b) / a, which exists only when a is invertible mod m.
+    pub fn invert(&self) -> Result<Affine, ModMathError> {
+        let a_inverse = inverse(self.a, self.m)?;
+        Ok(Affine {
+            a: a_inverse,
+            b: mulmod(a_inverse, self.m - self.b % self.m, self.m),
+            m: self.m,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIMES: [u64; 5] = [
+        2,
+        10_007,
+        1_000_000_007,
+        119_315_717_514_047,
+        2_305_843_009_213_693_951,
+    ];
+    const COMPOSITES: [u64; 5] = [4, 10, 1 << 32, 1_000_000_000_000_000_000, u64::MAX];
+
+    // xorshift64, so the cases are the same on every run.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn affine(&mut self, m: u64) -> Affine {
+            Affine {
+                a: self.next() % m,
+                b: self.next() % m,
+                m,
+            }
+        }
+    }
+
+    #[test]
+    fn test_mulmod_does_not_overflow() {
+        let m = u64::MAX;
+        assert_eq!(1, mulmod(m - 1, m - 1, m));
+        assert_eq!(m - 2, addmod(m - 1, m - 1, m));
+        assert_eq!(1, powmod(m - 1, 2, m));
+        assert_eq!(0, powmod(5, 0, 1));
+    }
+
+    #[test]
+    fn test_gcd_and_lcm() {
+        assert_eq!(6, gcd(48, 18));
+        assert_eq!(7, gcd(0, 7));
+        assert_eq!(7, gcd(7, 0));
+        assert_eq!(0, gcd(0, 0));
+        assert_eq!(144, lcm(48, 18));
+        assert_eq!(0, lcm(0, 5));
+        assert_eq!(
+            u64::MAX as u128 * (u64::MAX - 1) as u128,
+            lcm(u64::MAX as u128, (u64::MAX - 1) as u128)
+        );
+        let mut random = Random(0x0123_4567_89ab_cdef);
+        for _ in 0..100 {
+            let (a, b) = (
+                random.next() as u128 % 100_000,
+                random.next() as u128 % 100_000,
+            );
+            assert_eq!(a * b, gcd(a, b) * lcm(a, b));
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        assert_eq!(Ok(4), inverse(3, 11));
+        assert_eq!(Ok(1), inverse(1, 2));
+        assert_eq!(
+            Err(ModMathError::NotInvertible {
+                value: 4,
+                modulus: 10
+            }),
+            inverse(4, 10)
+        );
+        assert!(inverse(0, 7).is_err());
+        assert!(inverse(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_compose_is_associative() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for &m in PRIMES.iter().chain(COMPOSITES.iter()) {
+            for _ in 0..100 {
+                let (f, g, h) = (random.affine(m), random.affine(m), random.affine(m));
+                assert_eq!(f.compose(&g).compose(&h), f.compose(&g.compose(&h)));
+                let x = random.next();
+                assert_eq!(f.apply(g.apply(x)), f.compose(&g).apply(x));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pow_matches_repeated_compose() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for &m in PRIMES.iter().chain(COMPOSITES.iter()) {
+            let f = random.affine(m);
+            let mut repeated = Affine::identity(m);
+            for k in 0..40 {
+                assert_eq!(repeated, f.pow(k), "{:?} to the {}", f, k);
+                repeated = f.compose(&repeated);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invert_undoes_apply() {
+        let mut random = Random(0xdead_beef_cafe_f00d);
+        for &m in PRIMES.iter().chain(COMPOSITES.iter()) {
+            for _ in 0..100 {
+                let f = random.affine(m);
+                match f.invert() {
+                    Ok(undo) => {
+                        assert_eq!(1, gcd(f.a as u128, m as u128));
+                        let x = random.next() % m;
+                        assert_eq!(x, undo.apply(f.apply(x)));
+                        assert_eq!(x, f.apply(undo.apply(x)));
+                        assert_eq!(Affine::identity(m), undo.compose(&f));
+                    }
+                    Err(error) => {
+                        assert_ne!(1, gcd(f.a as u128, m as u128));
+                        assert_eq!(
+                            ModMathError::NotInvertible {
+                                value: f.a,
+                                modulus: m
+                            },
+                            error
+                        );
+                    }
+                }
+            }
+        }
+        // Even moduli with an even multiplier never invert.
+        for &m in COMPOSITES.iter().filter(|&&m| m % 2 == 0) {
+            assert!(Affine::new(2, 1, m).invert().is_err());
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum LineParseError {
+    Io(io::Error),
+    // line is 1-based; text is the line as read, message the parser's error.
+    Invalid {
+        line: usize,
+        text: String,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for LineParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LineParseError::Io(error) => write!(f, "{}", error),
+            LineParseError::Invalid {
+                line,
+                text,
+                message,
+            } => write!(f, "Line {} ('{}'): {}", line, text, message),
+        }
+    }
+}
+
+impl std::error::Error for LineParseError {}
+
+impl From<io::Error> for LineParseError {
+    fn from(error: io::Error) -> LineParseError {
+        LineParseError::Io(error)
+    }
+}
+
+// Parses every line of a puzzle input. A single trailing empty line is
+// always ignored; other blank lines are errors unless skip_blank is set.
+// Lines are trimmed before parsing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineParser {
+    skip_blank: bool,
+}
+
+impl LineParser {
+    pub fn new() -> LineParser {
+        LineParser::default()
+    }
+
+    pub fn skip_blank(mut self, skip_blank: bool) -> LineParser {
+        self.skip_blank = skip_blank;
+        self
+    }
+
+    pub fn parse<T: FromStr, R: BufRead>(&self, reader: R) -> Result<Vec<T>, LineParseError>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.parse_with(reader, str::parse::<T>)
+    }
+
+    pub fn parse_with<T, E: std::fmt::Display, R: BufRead, F: FnMut(&str) -> Result<T, E>>(
+        &self,
+        reader: R,
+        mut parse: F,
+    ) -> Result<Vec<T>, LineParseError> {
+        let mut values = Vec::new();
+        let mut lines = reader.lines().enumerate().peekable();
+        while let Some((index, text)) = lines.next() {
+            let text = text?;
+            let is_last = lines.peek().is_none();
+            if text.trim().is_empty() {
+                if is_last || self.skip_blank {
+                    continue;
+                }
+                return Err(LineParseError::Invalid {
+                    line: index + 1,
+                    text,
+                    message: "blank line".to_string(),
+                });
+            }
+            match parse(text.trim()) {
+                Ok(value) => values.push(value),
+                Err(error) => {
+                    return Err(LineParseError::Invalid {
+                        line: index + 1,
+                        message: error.to_string(),
+                        text,
+                    })
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+pub fn parse_lines<T: FromStr, R: BufRead>(reader: R) -> Result<Vec<T>, LineParseError>
+where
+    T::Err: std::fmt::Display,
+{
+    LineParser::new().parse(reader)
+}
+
+pub fn parse_lines_with<T, E: std::fmt::Display, R: BufRead, F: FnMut(&str) -> Result<T, E>>(
+    reader: R,
+    parse: F,
+) -> Result<Vec<T>, LineParseError> {
+    LineParser::new().parse_with(reader, parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(result: Result<Vec<u64>, LineParseError>) -> (usize, String, String) {
+        match result {
+            Err(LineParseError::Invalid {
+                line,
+                text,
+                message,
+            }) => (line, text, message),
+            other => panic!("expected an invalid line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(
+            vec![12, 14, 1969],
+            parse_lines::<u64, _>(&b"12\n 14\n1969\n"[..]).unwrap()
+        );
+        assert_eq!(
+            (
+                2,
+                "1x4".to_string(),
+                "invalid digit found in string".to_string()
+            ),
+            invalid(parse_lines(&b"12\n1x4\n1969"[..]))
+        );
+        assert_eq!(
+            "Line 2 ('1x4'): invalid digit found in string",
+            parse_lines::<u64, _>(&b"12\n1x4\n"[..])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        // Only one trailing empty line is forgiven.
+        assert_eq!(vec![1, 2], parse_lines::<u64, _>(&b"1\n2\n\n"[..]).unwrap());
+        assert_eq!(
+            (3, "".to_string(), "blank line".to_string()),
+            invalid(parse_lines(&b"1\n2\n\n\n"[..]))
+        );
+        assert_eq!(
+            (2, "  ".to_string(), "blank line".to_string()),
+            invalid(parse_lines(&b"1\n  \n2\n"[..]))
+        );
+        assert_eq!(
+            vec![1, 2],
+            LineParser::new()
+                .skip_blank(true)
+                .parse::<u64, _>(&b"1\n\n  \n2\n\n\n"[..])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_lines_with() {
+        let pairs = parse_lines_with(&b"a)b\nb)c\n"[..], |line| {
+            let mut parts = line.splitn(2, ')');
+            match (parts.next(), parts.next()) {
+                (Some(a), Some(b)) => Ok((a.to_string(), b.to_string())),
+                _ => Err("missing ')'"),
+            }
+        });
+        assert_eq!(2, pairs.unwrap().len());
+
+        let error = parse_lines_with(&b"a)b\nbc\n"[..], |line| {
+            line.find(')').ok_or("missing ')'")
+        })
+        .unwrap_err();
+        assert_eq!("Line 2 ('bc'): missing ')'", error.to_string());
+    }
+}
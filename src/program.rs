@@ -0,0 +1,253 @@
+use super::intcode::{cell_to_i64, EmulatorMemoryType};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+// .icb layout: the magic bytes, a version byte, the cell count as a little
+// endian u64, then every cell as a little endian i64. Cells stay i64 on disk
+// whatever the width of EmulatorMemoryType, so files work in every build.
+pub const ICB_MAGIC: &[u8; 4] = b"ICB\0";
+pub const ICB_VERSION: u8 = 1;
+pub const ICB_EXTENSION: &str = "icb";
+
+#[derive(Debug)]
+pub enum ProgramLoadError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+    TruncatedHeader,
+    Truncated {
+        expected_cells: u64,
+        found_cells: u64,
+    },
+    InvalidCell {
+        index: usize,
+        text: String,
+    },
+}
+
+impl std::fmt::Display for ProgramLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProgramLoadError::Io(error) => write!(f, "{}", error),
+            ProgramLoadError::BadMagic => write!(f, "Not an .icb program (bad magic bytes)"),
+            ProgramLoadError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported .icb version {}", version)
+            }
+            ProgramLoadError::TruncatedHeader => write!(f, "Truncated .icb header"),
+            ProgramLoadError::Truncated {
+                expected_cells,
+                found_cells,
+            } => write!(
+                f,
+                "Truncated .icb program: expected {} cells, but found {}",
+                expected_cells, found_cells
+            ),
+            ProgramLoadError::InvalidCell { index, text } => {
+                write!(f, "Cell {} ('{}') is not an integer", index, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramLoadError {}
+
+impl From<io::Error> for ProgramLoadError {
+    fn from(error: io::Error) -> ProgramLoadError {
+        ProgramLoadError::Io(error)
+    }
+}
+
+pub fn write_icb<W: Write>(memory: &[EmulatorMemoryType], mut writer: W) -> io::Result<()> {
+    writer.write_all(ICB_MAGIC)?;
+    writer.write_all(&[ICB_VERSION])?;
+    writer.write_all(&(memory.len() as u64).to_le_bytes())?;
+    let mut bytes = Vec::with_capacity(memory.len() * 8);
+    for (index, &cell) in memory.iter().enumerate() {
+        let cell = cell_to_i64(cell).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cell {} ({}) does not fit in an .icb cell", index, cell),
+            )
+        })?;
+        bytes.extend_from_slice(&cell.to_le_bytes());
+    }
+    writer.write_all(&bytes)
+}
+
+pub fn read_icb<R: Read>(mut reader: R) -> Result<Vec<EmulatorMemoryType>, ProgramLoadError> {
+    let mut header = [0; 13];
+    let header_length = read_up_to(&mut reader, &mut header)?;
+    if header_length < ICB_MAGIC.len() || &header[..ICB_MAGIC.len()] != ICB_MAGIC {
+        return Err(ProgramLoadError::BadMagic);
+    }
+    if header_length > ICB_MAGIC.len() && header[4] != ICB_VERSION {
+        return Err(ProgramLoadError::UnsupportedVersion(header[4]));
+    }
+    if header_length < header.len() {
+        return Err(ProgramLoadError::TruncatedHeader);
+    }
+
+    let expected_cells = u64::from_le_bytes(header[5..].try_into().unwrap());
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let found_cells = bytes.len() as u64 / 8;
+    if found_cells < expected_cells || bytes.len() % 8 != 0 {
+        return Err(ProgramLoadError::Truncated {
+            expected_cells,
+            found_cells,
+        });
+    }
+
+    Ok(bytes
+        .chunks_exact(8)
+        .take(expected_cells as usize)
+        .map(|chunk| EmulatorMemoryType::from(i64::from_le_bytes(chunk.try_into().unwrap())))
+        .collect())
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+pub fn read_text<R: BufRead>(reader: R) -> Result<Vec<EmulatorMemoryType>, ProgramLoadError> {
+    reader
+        .split(b',')
+        .enumerate()
+        .map(|(index, cell)| {
+            let cell = cell?;
+            let text = String::from_utf8_lossy(&cell);
+            text.trim()
+                .parse()
+                .map_err(|_| ProgramLoadError::InvalidCell {
+                    index,
+                    text: text.trim().to_string(),
+                })
+        })
+        .collect()
+}
+
+// Picks the binary reader for .icb files or anything starting with the .icb
+// magic, and the comma separated text reader otherwise.
+pub fn load_program<P: AsRef<Path>>(path: P) -> Result<Vec<EmulatorMemoryType>, ProgramLoadError> {
+    let path = path.as_ref();
+    let mut reader = io::BufReader::new(File::open(path)?);
+    let has_icb_extension = path.extension().is_some_and(|ext| ext == ICB_EXTENSION);
+    if has_icb_extension || reader.fill_buf()?.starts_with(ICB_MAGIC) {
+        read_icb(reader)
+    } else {
+        read_text(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(memory: &[EmulatorMemoryType]) -> Vec<EmulatorMemoryType> {
+        let mut bytes = Vec::new();
+        write_icb(memory, &mut bytes).unwrap();
+        assert_eq!(13 + memory.len() * 8, bytes.len());
+        read_icb(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_examples() {
+        let examples: [&[EmulatorMemoryType]; 4] = [
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[1101, 100, -1, 4, 0],
+            &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            &[],
+        ];
+        for example in examples.iter() {
+            assert_eq!(example.to_vec(), round_trip(example));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_large_program() {
+        let memory: Vec<EmulatorMemoryType> = (0..1_000_000)
+            .map(|cell: i64| {
+                EmulatorMemoryType::from(cell.wrapping_mul(6_364_136_223_846_793_005) >> 3)
+            })
+            .collect();
+        assert_eq!(memory, round_trip(&memory));
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        assert!(matches!(
+            read_icb(&b"1,2,3,99"[..]),
+            Err(ProgramLoadError::BadMagic)
+        ));
+        assert!(matches!(
+            read_icb(&b""[..]),
+            Err(ProgramLoadError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unsupported_version() {
+        assert!(matches!(
+            read_icb(&b"ICB\x002\0\0\0\0\0\0\0\0"[..]),
+            Err(ProgramLoadError::UnsupportedVersion(b'2'))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_truncated_payload() {
+        let mut bytes = Vec::new();
+        write_icb(&[1, 0, 0, 0, 99], &mut bytes).unwrap();
+
+        let error = read_icb(&bytes[..bytes.len() - 8]).unwrap_err();
+        assert_eq!(
+            "Truncated .icb program: expected 5 cells, but found 4",
+            error.to_string()
+        );
+        assert!(matches!(
+            read_icb(&bytes[..bytes.len() - 3]),
+            Err(ProgramLoadError::Truncated {
+                expected_cells: 5,
+                found_cells: 4
+            })
+        ));
+        assert!(matches!(
+            read_icb(&bytes[..9]),
+            Err(ProgramLoadError::TruncatedHeader)
+        ));
+    }
+
+    #[test]
+    fn test_load_program_detects_format() {
+        let dir = std::env::temp_dir().join(format!("aoc_icb_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = [1002, 4, 3, 4, 33];
+
+        let text_path = dir.join("program.txt");
+        std::fs::write(&text_path, "1002,4,3,4,33\n").unwrap();
+        let binary_path = dir.join("program.icb");
+        write_icb(&program, File::create(&binary_path).unwrap()).unwrap();
+        let sniffed_path = dir.join("program.bin");
+        write_icb(&program, File::create(&sniffed_path).unwrap()).unwrap();
+
+        assert_eq!(program.to_vec(), load_program(&text_path).unwrap());
+        assert_eq!(program.to_vec(), load_program(&binary_path).unwrap());
+        assert_eq!(program.to_vec(), load_program(&sniffed_path).unwrap());
+
+        std::fs::write(&text_path, "1002,4,x,4,33").unwrap();
+        assert_eq!(
+            "Cell 2 ('x') is not an integer",
+            load_program(&text_path).unwrap_err().to_string()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+pub trait ProgressSink {
+    fn report(&mut self, done: u64, total: Option<u64>);
+}
+
+impl<F: FnMut(u64, Option<u64>)> ProgressSink for F {
+    fn report(&mut self, done: u64, total: Option<u64>) {
+        self(done, total)
+    }
+}
+
+pub(crate) fn report(progress: &mut Option<&mut dyn ProgressSink>, done: u64, total: Option<u64>) {
+    if let Some(progress) = progress {
+        progress.report(done, total);
+    }
+}
+
+// Prints at most a few updates per second to stderr, plus the final one.
+pub struct StderrProgress {
+    label: String,
+    min_interval: Duration,
+    last_printed: Option<Instant>,
+    // A line has been printed without its newline, as the search hasn't
+    // finished.
+    line_open: bool,
+}
+
+impl StderrProgress {
+    pub fn new(label: &str) -> StderrProgress {
+        StderrProgress {
+            label: label.to_string(),
+            min_interval: Duration::from_millis(250),
+            last_printed: None,
+            line_open: false,
+        }
+    }
+
+    fn should_print(&mut self, now: Instant, finished: bool) -> bool {
+        let due = self
+            .last_printed
+            .is_none_or(|last| now.duration_since(last) >= self.min_interval);
+        if due || finished {
+            self.last_printed = Some(now);
+        }
+        due || finished
+    }
+}
+
+impl ProgressSink for StderrProgress {
+    fn report(&mut self, done: u64, total: Option<u64>) {
+        let finished = total == Some(done);
+        if !self.should_print(Instant::now(), finished) {
+            return;
+        }
+
+        let line = match total {
+            Some(total) => format!("{}: {}/{}", self.label, done, total),
+            None => format!("{}: {}", self.label, done),
+        };
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{}", line);
+        if finished {
+            let _ = writeln!(stderr);
+        }
+        self.line_open = !finished;
+    }
+}
+
+// Searches that stop early never report the total, so the line is ended
+// here instead.
+impl Drop for StderrProgress {
+    fn drop(&mut self) {
+        if self.line_open {
+            let _ = writeln!(std::io::stderr());
+        }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+pub struct IndicatifProgress {
+    bar: indicatif::ProgressBar,
+}
+
+#[cfg(feature = "indicatif")]
+impl IndicatifProgress {
+    pub fn new(label: &str) -> IndicatifProgress {
+        let bar = indicatif::ProgressBar::no_length();
+        bar.set_message(label.to_string());
+        IndicatifProgress { bar }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl ProgressSink for IndicatifProgress {
+    fn report(&mut self, done: u64, total: Option<u64>) {
+        if let Some(total) = total {
+            self.bar.set_length(total);
+        }
+        self.bar.set_position(done);
+        if total == Some(done) {
+            self.bar.finish();
+        }
+    }
+}
+
+// Leaves the bar where an early stop left it, rather than clearing it.
+#[cfg(feature = "indicatif")]
+impl Drop for IndicatifProgress {
+    fn drop(&mut self) {
+        if !self.bar.is_finished() {
+            self.bar.abandon();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stderr_progress_is_rate_limited() {
+        let mut progress = StderrProgress::new("test");
+        let start = Instant::now();
+        assert!(progress.should_print(start, false));
+        assert!(!progress.should_print(start + Duration::from_millis(100), false));
+        assert!(progress.should_print(start + Duration::from_millis(150), true));
+        assert!(!progress.should_print(start + Duration::from_millis(300), false));
+        assert!(progress.should_print(start + Duration::from_millis(400), false));
+    }
+
+    #[test]
+    fn test_closures_are_sinks() {
+        let mut reports = Vec::new();
+        {
+            let mut sink = |done, total| reports.push((done, total));
+            let mut progress: Option<&mut dyn ProgressSink> = Some(&mut sink);
+            report(&mut progress, 1, Some(2));
+            report(&mut progress, 2, Some(2));
+        }
+        assert_eq!(vec![(1, Some(2)), (2, Some(2))], reports);
+    }
+}
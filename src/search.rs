@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// A sequence x0, x1 = step(x0), ... that first repeats a state after start
+// steps: x(start) is the first state seen again, period steps later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleInfo {
+    pub start: u64,
+    pub period: u64,
+}
+
+// Brent's algorithm, which keeps two states around however long the
+// sequence runs before repeating. step must be deterministic, as the
+// sequence is walked more than once.
+pub fn find_cycle<S: Clone + PartialEq>(initial: S, mut step: impl FnMut(&S) -> S) -> CycleInfo {
+    // The period first: the tortoise waits at each power of two while the
+    // hare runs ahead, until the hare comes round to it.
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    // Then the start: two walkers period apart meet at the first repeat.
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    CycleInfo { start, period }
+}
+
+// Like find_cycle, but remembering every state, so the sequence is walked
+// once. Also returns the states in order up to the repeat, which makes
+// states[start] the one seen twice.
+pub fn find_cycle_with_states<S: Clone + Eq + Hash>(
+    initial: S,
+    mut step: impl FnMut(&S) -> S,
+) -> (CycleInfo, Vec<S>) {
+    let mut seen = HashMap::new();
+    let mut states = Vec::new();
+    let mut state = initial;
+    loop {
+        let index = states.len() as u64;
+        if let Some(&start) = seen.get(&state) {
+            return (
+                CycleInfo {
+                    start,
+                    period: index - start,
+                },
+                states,
+            );
+        }
+        seen.insert(state.clone(), index);
+        let next = step(&state);
+        states.push(std::mem::replace(&mut state, next));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0, 1, ..., start - 1 lead into a loop over start..start + period.
+    fn rho(start: u64, period: u64) -> impl Fn(&u64) -> u64 {
+        move |&x| {
+            if x + 1 < start + period {
+                x + 1
+            } else {
+                start
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_cycles() {
+        for start in 0..20 {
+            for period in 1..20 {
+                let expected = CycleInfo { start, period };
+                assert_eq!(expected, find_cycle(0, rho(start, period)));
+
+                let (info, states) = find_cycle_with_states(0, rho(start, period));
+                assert_eq!(expected, info);
+                assert_eq!((0..start + period).collect::<Vec<_>>(), states);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixed_point() {
+        assert_eq!(
+            CycleInfo {
+                start: 0,
+                period: 1
+            },
+            find_cycle('a', |&c| c)
+        );
+        let (info, states) = find_cycle_with_states(5, |&x: &i32| (x - 1).max(0));
+        assert_eq!(
+            CycleInfo {
+                start: 5,
+                period: 1
+            },
+            info
+        );
+        assert_eq!(vec![5, 4, 3, 2, 1, 0], states);
+    }
+
+    #[test]
+    fn test_random_maps_agree() {
+        // xorshift64, so the maps are the same on every run.
+        let mut random: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            random
+        };
+        for size in 1..60 {
+            let map: Vec<usize> = (0..size).map(|_| next() as usize % size).collect();
+            let initial = next() as usize % size;
+
+            // The first index at which each state was seen, walking naively.
+            let mut first_seen = vec![None; size];
+            let mut state = initial;
+            let mut index = 0;
+            let expected = loop {
+                if let Some(start) = first_seen[state] {
+                    break CycleInfo {
+                        start,
+                        period: index - start,
+                    };
+                }
+                first_seen[state] = Some(index);
+                state = map[state];
+                index += 1;
+            };
+
+            assert_eq!(expected, find_cycle(initial, |&x| map[x]), "{:?}", map);
+            assert_eq!(expected, find_cycle_with_states(initial, |&x| map[x]).0);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::cache;
+use super::intcode::disassemble::disassemble;
+use super::intcode::{EmulatorError, EmulatorMemoryType};
+use super::{day1, day12, day16, day18, day2, day22, day24, day5, day7};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    One,
+    Two,
+}
+
+impl Part {
+    pub fn number(self) -> u32 {
+        match self {
+            Part::One => 1,
+            Part::Two => 2,
+        }
+    }
+}
+
+// A worked example from the puzzle text: the input as it would appear in
+// the input file, and the answer the part should give for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Example {
+    pub input: &'static str,
+    pub part: Part,
+    pub expected: &'static str,
+}
+
+impl Example {
+    pub fn new(part: Part, input: &'static str, expected: &'static str) -> Example {
+        Example {
+            input,
+            part,
+            expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AocError {
+    // The puzzle input is empty, or too short to be a whole input.
+    EmptyInput {
+        day: u32,
+        path: String,
+    },
+    // The puzzle input couldn't be read, or isn't in the day's format.
+    // Only the message is kept, as for Terminal.
+    BadInput {
+        day: u32,
+        path: String,
+        message: String,
+    },
+    // The input ran, but nothing in it answers the part.
+    NoAnswer {
+        day: u32,
+        part: u32,
+    },
+    Intcode(EmulatorError),
+    // Day 5's diagnostic program failed after printing these codes, which
+    // say which of its tests went wrong.
+    Diagnostics {
+        error: EmulatorError,
+        codes: Vec<EmulatorMemoryType>,
+    },
+    // Drawing to the terminal failed. io::Error is neither Clone nor
+    // PartialEq, so only its message is kept.
+    Terminal(String),
+}
+
+impl std::fmt::Display for AocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AocError::EmptyInput { day, path } => {
+                write!(f, "Day {} input {} is empty or truncated", day, path)
+            }
+            AocError::BadInput { day, path, message } => {
+                write!(
+                    f,
+                    "Day {} input {} could not be loaded: {}",
+                    day, path, message
+                )
+            }
+            AocError::NoAnswer { day, part } => {
+                write!(f, "Day {} part {} found no answer in its input", day, part)
+            }
+            AocError::Intcode(error) => write!(f, "{}", error),
+            AocError::Diagnostics { error, codes } => {
+                write!(f, "{} after diagnostic codes {:?}", error, codes)
+            }
+            AocError::Terminal(error) => write!(f, "Could not draw to the terminal: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AocError {}
+
+impl From<EmulatorError> for AocError {
+    fn from(error: EmulatorError) -> AocError {
+        AocError::Intcode(error)
+    }
+}
+
+pub trait Solution {
+    fn day(&self) -> u32;
+    fn part_1(&self, path: &str) -> Result<String, AocError>;
+    fn part_2(&self, path: &str) -> Result<String, AocError>;
+
+    // None for a part the day doesn't have.
+    fn run_part(&self, part: u32, path: &str) -> Result<Option<String>, AocError> {
+        match part {
+            1 => self.part_1(path).map(Some),
+            2 => self.part_2(path).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // Rejects inputs too short to be the real thing before anything runs.
+    // Inputs that can't be read are left for the parts to report.
+    fn check_input(&self, path: &str) -> Result<(), AocError> {
+        check_not_empty(self.day(), path)
+    }
+
+    fn try_run_part(&self, part: u32, path: &str) -> Result<Option<String>, AocError> {
+        self.check_input(path)?;
+        self.run_part(part, path)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        Vec::new()
+    }
+}
+
+// The parts only read from a path, so the example is written to a file of
+// its own. Every run gets a fresh name, as cached loads are keyed on the
+// path and its modification time.
+pub fn run_example(solution: &dyn Solution, example: &Example) -> Result<String, AocError> {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "aoc_example_{}_{}_{}.txt",
+        std::process::id(),
+        solution.day(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let path_text = path.to_string_lossy().into_owned();
+    std::fs::write(&path, example.input)
+        .map_err(|error| bad_input(solution.day(), &path_text, error))?;
+
+    struct Remove<'a>(&'a std::path::Path);
+    impl Drop for Remove<'_> {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(self.0);
+        }
+    }
+    let _remove = Remove(&path);
+
+    let answer = solution
+        .run_part(example.part.number(), &path_text)?
+        .expect("every part has a number run_part accepts");
+    Ok(answer)
+}
+
+fn empty_input(day: u32, path: &str) -> AocError {
+    AocError::EmptyInput {
+        day,
+        path: path.to_string(),
+    }
+}
+
+pub fn bad_input(day: u32, path: &str, error: impl std::fmt::Display) -> AocError {
+    AocError::BadInput {
+        day,
+        path: path.to_string(),
+        message: error.to_string(),
+    }
+}
+
+// The text of the input in path, run through parse.
+pub fn read_input<T, E: std::fmt::Display>(
+    day: u32,
+    path: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<T, AocError> {
+    let text = std::fs::read_to_string(path).map_err(|error| bad_input(day, path, error))?;
+    parse(&text).map_err(|error| bad_input(day, path, error))
+}
+
+// The Intcode program in path, through cache::programs() so each part
+// doesn't parse it again.
+pub fn read_intcode_input(day: u32, path: &str) -> Result<Vec<EmulatorMemoryType>, AocError> {
+    match cache::programs().get_or_load(path) {
+        Ok(program) => Ok(program.to_vec()),
+        Err(error) => Err(bad_input(day, path, error)),
+    }
+}
+
+pub fn check_not_empty(day: u32, path: &str) -> Result<(), AocError> {
+    match std::fs::read(path) {
+        Ok(bytes) if bytes.iter().all(u8::is_ascii_whitespace) => Err(empty_input(day, path)),
+        _ => Ok(()),
+    }
+}
+
+// An Intcode program of at least min_cells, which if reads_input is set
+// must contain an Input instruction somewhere a linear disassembly finds it.
+pub fn check_intcode_input(
+    day: u32,
+    path: &str,
+    min_cells: usize,
+    reads_input: bool,
+) -> Result<(), AocError> {
+    check_not_empty(day, path)?;
+    let program = cache::programs()
+        .get_or_load(path)
+        .map_err(|error| bad_input(day, path, error))?;
+    let has_input = || {
+        disassemble(&program, None)
+            .iter()
+            .any(|instruction| instruction.info.is_some_and(|info| info.reads_input))
+    };
+    if program.len() < min_cells || (reads_input && !has_input()) {
+        return Err(empty_input(day, path));
+    }
+    Ok(())
+}
+
+pub fn registry() -> Vec<Box<dyn Solution>> {
+    vec![
+        Box::new(day1::Day1),
+        Box::new(day2::Day2),
+        Box::new(day5::Day5),
+        Box::new(day7::Day7),
+        Box::new(day12::Day12),
+        Box::new(day16::Day16),
+        Box::new(day18::Day18),
+        Box::new(day22::Day22),
+        Box::new(day24::Day24),
+    ]
+}
+
+pub fn find(day: u32) -> Option<Box<dyn Solution>> {
+    registry()
+        .into_iter()
+        .find(|solution| solution.day() == day)
+}
@@ -0,0 +1,311 @@
+use super::inputs::{Manifest, ManifestProblem};
+use super::solution;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedAnswer {
+    pub day: u32,
+    pub part: u32,
+    pub answer: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Pass,
+    Fail { actual: String },
+    Error(String),
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyResult {
+    pub expected: ExpectedAnswer,
+    pub status: Status,
+    pub elapsed: Duration,
+    // Set when the input ran doesn't match the manifest.
+    pub warning: Option<String>,
+}
+
+impl VerifyResult {
+    pub fn is_failure(&self) -> bool {
+        match self.status {
+            Status::Fail { .. } | Status::Error(_) => true,
+            Status::Pass | Status::Skipped(_) => false,
+        }
+    }
+}
+
+// Answers files use a small subset of TOML: one `day.part = answer` pair per
+// line, where the key may be quoted ("1.2") and the answer is a string or an
+// integer. Blank lines and # comments are ignored.
+pub fn parse_answers(text: &str) -> Result<Vec<ExpectedAnswer>, String> {
+    let mut answers = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut split = line.splitn(2, '=');
+        let key = unquote(split.next().unwrap_or("").trim());
+        let value = split
+            .next()
+            .ok_or_else(|| format!("line {}: expected `day.part = answer`", line_number))?
+            .trim();
+
+        let mut key_parts = key.splitn(2, '.');
+        let day = key_parts.next().and_then(|day| day.trim().parse().ok());
+        let part = key_parts.next().and_then(|part| part.trim().parse().ok());
+        let (day, part) = match (day, part) {
+            (Some(day), Some(part)) => (day, part),
+            _ => {
+                return Err(format!(
+                    "line {}: '{}' is not a day.part key",
+                    line_number, key
+                ))
+            }
+        };
+
+        let answer = if value.starts_with('"') {
+            if value.len() < 2 || !value.ends_with('"') {
+                return Err(format!("line {}: unterminated string", line_number));
+            }
+            unquote(value).to_string()
+        } else if value.parse::<i128>().is_ok() {
+            value.to_string()
+        } else {
+            return Err(format!(
+                "line {}: '{}' is not a string or integer",
+                line_number, value
+            ));
+        };
+
+        answers.push(ExpectedAnswer { day, part, answer });
+    }
+    Ok(answers)
+}
+
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+pub(crate) fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+// Inputs are looked up in <input_dir>/manifest.toml, falling back to
+// <input_dir>/<day>.txt for days it doesn't list. An input whose hash doesn't
+// match the manifest still runs, with a warning on the result.
+pub fn verify(answers: &[ExpectedAnswer], input_dir: &Path) -> Vec<VerifyResult> {
+    let manifest = Manifest::load_from_dir(input_dir);
+    answers
+        .iter()
+        .map(|expected| {
+            let start = Instant::now();
+            let (status, warning) = match &manifest {
+                Ok(manifest) => run_one(expected, input_dir, manifest.as_ref()),
+                Err(error) => (Status::Error(error.clone()), None),
+            };
+            VerifyResult {
+                expected: expected.clone(),
+                status,
+                elapsed: start.elapsed(),
+                warning,
+            }
+        })
+        .collect()
+}
+
+fn run_one(
+    expected: &ExpectedAnswer,
+    input_dir: &Path,
+    manifest: Option<&Manifest>,
+) -> (Status, Option<String>) {
+    let solution = match solution::find(expected.day) {
+        Some(solution) => solution,
+        None => {
+            return (
+                Status::Error(format!("no solution for day {}", expected.day)),
+                None,
+            )
+        }
+    };
+
+    let (path, warning) =
+        match manifest.and_then(|manifest| manifest.check(input_dir, expected.day)) {
+            None => (input_dir.join(format!("{}.txt", expected.day)), None),
+            Some(Ok(path)) => (path, None),
+            Some(Err(problem)) => {
+                let message = problem.to_string();
+                match problem {
+                    ManifestProblem::Mismatch { path, .. } => (path, Some(message)),
+                    ManifestProblem::Missing { path, .. } => {
+                        return (
+                            Status::Skipped(format!("{} not found", path.display())),
+                            None,
+                        )
+                    }
+                    ManifestProblem::Unreadable { .. } => return (Status::Error(message), None),
+                }
+            }
+        };
+    if !path.is_file() {
+        return (
+            Status::Skipped(format!("{} not found", path.display())),
+            None,
+        );
+    }
+    let path = path.to_string_lossy();
+
+    // Bad inputs come back as errors, but a bug in a day module could still
+    // panic, so that is reported as an error too.
+    let run = AssertUnwindSafe(|| solution.try_run_part(expected.part, &path));
+    let status = match panic::catch_unwind(run) {
+        Ok(Ok(Some(actual))) if actual == expected.answer => Status::Pass,
+        Ok(Ok(Some(actual))) => Status::Fail { actual },
+        Ok(Ok(None)) => Status::Error(format!(
+            "day {} has no part {}",
+            expected.day, expected.part
+        )),
+        Ok(Err(error)) => Status::Error(error.to_string()),
+        Err(_) => Status::Error("solution panicked".to_string()),
+    };
+    (status, warning)
+}
+
+pub fn render_table(results: &[VerifyResult]) -> String {
+    let rows: Vec<[String; 5]> = results
+        .iter()
+        .map(|result| {
+            let (status, actual) = match &result.status {
+                Status::Pass => ("pass".to_string(), result.expected.answer.clone()),
+                Status::Fail { actual } => ("FAIL".to_string(), actual.clone()),
+                Status::Error(message) => ("ERROR".to_string(), message.clone()),
+                Status::Skipped(reason) => ("skipped".to_string(), reason.clone()),
+            };
+            [
+                format!("{}.{}", result.expected.day, result.expected.part),
+                status,
+                format!("{:.1?}", result.elapsed),
+                result.expected.answer.clone(),
+                actual,
+            ]
+        })
+        .collect();
+
+    let header = [
+        "day.part".to_string(),
+        "status".to_string(),
+        "time".to_string(),
+        "expected".to_string(),
+        "actual".to_string(),
+    ];
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = std::cmp::max(*width, cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_answers() {
+        let text =
+            "# my answers\n\"1.1\" = \"3434390\"\n1.2 = 5148724 # inline\n\n\"7.2\" = \"a # b\"\n";
+        assert_eq!(
+            Ok(vec![
+                ExpectedAnswer {
+                    day: 1,
+                    part: 1,
+                    answer: "3434390".to_string()
+                },
+                ExpectedAnswer {
+                    day: 1,
+                    part: 2,
+                    answer: "5148724".to_string()
+                },
+                ExpectedAnswer {
+                    day: 7,
+                    part: 2,
+                    answer: "a # b".to_string()
+                },
+            ]),
+            parse_answers(text)
+        );
+    }
+
+    #[test]
+    fn test_parse_answers_errors_name_the_line() {
+        assert_eq!(
+            Err("line 2: 'one.1' is not a day.part key".to_string()),
+            parse_answers("1.1 = 1\none.1 = 2")
+        );
+        assert_eq!(
+            Err("line 1: 'abc' is not a string or integer".to_string()),
+            parse_answers("1.1 = abc")
+        );
+        assert_eq!(
+            Err("line 1: unterminated string".to_string()),
+            parse_answers("1.1 = \"abc")
+        );
+    }
+
+    #[test]
+    fn test_render_table() {
+        let result = |day, status| VerifyResult {
+            expected: ExpectedAnswer {
+                day,
+                part: 1,
+                answer: "42".to_string(),
+            },
+            status,
+            elapsed: Duration::from_millis(3),
+            warning: None,
+        };
+        assert_eq!(
+            "day.part  status   time   expected  actual\n\
+             1.1       pass     3.0ms  42        42\n\
+             2.1       FAIL     3.0ms  42        41\n\
+             3.1       skipped  3.0ms  42        3.txt not found\n",
+            render_table(&[
+                result(1, Status::Pass),
+                result(
+                    2,
+                    Status::Fail {
+                        actual: "41".to_string()
+                    }
+                ),
+                result(3, Status::Skipped("3.txt not found".to_string())),
+            ])
+        );
+    }
+}
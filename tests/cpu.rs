@@ -0,0 +1,23 @@
+use advent_of_code_2019::day5;
+use advent_of_code_2019::get_intcode_memory_from_file;
+use advent_of_code_2019::intcode::cpu::EvalCpu;
+use advent_of_code_2019::intcode::{Emulator, QueueInput};
+
+// Day 5 on the Emulator and on EvalCpu must agree, for both parts.
+#[test]
+fn test_day_5_agrees_across_cpus() {
+    let program = get_intcode_memory_from_file("input/5.txt");
+    for &system_id in [1, 5].iter() {
+        let mut emulator = Emulator::new(&program, QueueInput::new());
+        let mut eval = EvalCpu::new(&program);
+        let expected = day5::run_diagnostics_on(&mut emulator, system_id).unwrap();
+        assert_eq!(
+            expected,
+            day5::run_diagnostics_on(&mut eval, system_id).unwrap()
+        );
+    }
+    assert_eq!(
+        day5::run_part_2("input/5.txt").unwrap(),
+        day5::run_diagnostics_on(&mut EvalCpu::new(&program), 5).unwrap()
+    );
+}
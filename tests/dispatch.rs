@@ -0,0 +1,111 @@
+use advent_of_code_2019::get_intcode_memory_from_file;
+use advent_of_code_2019::intcode::{
+    Dispatch, Emulator, EmulatorError, EmulatorMemoryType, EmulatorResult, QueueInput,
+};
+
+const MAX_STEPS: usize = 10_000;
+
+// Every step's result, then the final state, so any divergence between the
+// two dispatchers shows up.
+fn trace(
+    program: &[EmulatorMemoryType],
+    inputs: &[EmulatorMemoryType],
+    dispatch: Dispatch,
+) -> (Vec<Result<EmulatorResult, EmulatorError>>, String) {
+    let mut emulator =
+        Emulator::with_dispatch(program, QueueInput::from(inputs.to_vec()), dispatch);
+    let mut results = Vec::new();
+    for _ in 0..MAX_STEPS {
+        let result = emulator.step();
+        let finished = !matches!(result, Ok(EmulatorResult::Success))
+            && !matches!(result, Ok(EmulatorResult::SuccessWithValue(_)));
+        results.push(result);
+        if finished {
+            break;
+        }
+    }
+    (results, format!("{:?}", emulator.snapshot()))
+}
+
+fn assert_same(program: &[EmulatorMemoryType], inputs: &[EmulatorMemoryType]) {
+    assert_eq!(
+        trace(program, inputs, Dispatch::Match),
+        trace(program, inputs, Dispatch::Table),
+        "program {:?} with inputs {:?}",
+        program,
+        inputs
+    );
+}
+
+#[test]
+fn test_dispatch_table_matches_on_examples() {
+    let examples: Vec<Vec<EmulatorMemoryType>> = vec![
+        vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+        vec![1, 1, 1, 4, 99, 5, 6, 0, 99],
+        vec![3, 0, 4, 0, 99],
+        vec![1002, 4, 3, 4, 33],
+        vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+        vec![3, 3, 1107, -1, 8, 3, 4, 3, 99],
+        vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+        vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+        vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ],
+        vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ],
+        get_intcode_memory_from_file("input/2.txt"),
+        get_intcode_memory_from_file("input/5.txt"),
+        get_intcode_memory_from_file("input/7.txt"),
+    ];
+    for program in &examples {
+        for inputs in &[vec![], vec![1], vec![5], vec![8, 0], vec![-3, 7, 9]] {
+            assert_same(program, inputs);
+        }
+    }
+}
+
+// xorshift64, so the generated programs are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> EmulatorMemoryType {
+        (self.next() % bound) as EmulatorMemoryType
+    }
+}
+
+// Mostly well-formed instructions, with the occasional bad opcode, bad mode
+// or out-of-range address so the error paths are compared too.
+fn generate_program(rng: &mut Rng) -> Vec<EmulatorMemoryType> {
+    const OPCODES: [EmulatorMemoryType; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 99, 42];
+    let len = 8 + rng.below(32);
+    (0..len)
+        .map(|_| match rng.below(3) {
+            0 => {
+                let opcode = OPCODES[rng.below(OPCODES.len() as u64) as usize];
+                let modes = rng.below(3) * 100 + rng.below(3) * 1000 + rng.below(2) * 10000;
+                modes + opcode
+            }
+            _ => rng.below(len as u64 + 4) - 2,
+        })
+        .collect()
+}
+
+#[test]
+fn test_dispatch_table_matches_on_generated_programs() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..1000 {
+        let program = generate_program(&mut rng);
+        let inputs: Vec<_> = (0..rng.below(4)).map(|_| rng.below(20) - 10).collect();
+        assert_same(&program, &inputs);
+    }
+}
@@ -0,0 +1,122 @@
+use advent_of_code_2019::solution::{self, AocError, Example};
+
+#[test]
+fn test_every_published_example() {
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for solution in solution::registry() {
+        for example in solution.examples() {
+            count += 1;
+            let answer = solution::run_example(solution.as_ref(), &example).unwrap();
+            if answer != example.expected {
+                failures.push(format!(
+                    "day {} part {}: expected {}, got {} for\n{}",
+                    solution.day(),
+                    example.part.number(),
+                    example.expected,
+                    answer,
+                    example.input
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    assert!(count > 0);
+}
+
+#[test]
+fn test_examples_cover_both_parts() {
+    for day in &[1, 5, 7, 16, 18] {
+        let examples: Vec<Example> = solution::find(*day).unwrap().examples();
+        for part in &[solution::Part::One, solution::Part::Two] {
+            assert!(
+                examples.iter().any(|example| example.part == *part),
+                "day {} has no examples for {:?}",
+                day,
+                part
+            );
+        }
+    }
+}
+
+#[test]
+fn test_empty_and_truncated_inputs() {
+    let dir = std::env::temp_dir().join(format!("aoc_empty_inputs_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for solution in solution::registry() {
+        let day = solution.day();
+        let empty = dir.join(format!("{}_empty.txt", day));
+        std::fs::write(&empty, "\n").unwrap();
+        let empty = empty.to_string_lossy().into_owned();
+        let one_cell = dir.join(format!("{}_one_cell.txt", day));
+        std::fs::write(&one_cell, "99\n").unwrap();
+        let one_cell = one_cell.to_string_lossy().into_owned();
+
+        // A lone cell is a truncated Intcode program, but a whole day 1
+        // input. The other days' inputs aren't made of cells.
+        let truncated_inputs = match day {
+            2 | 5 | 7 => vec![empty, one_cell],
+            _ => vec![empty],
+        };
+        for path in truncated_inputs {
+            for part in 1..=2 {
+                assert_eq!(
+                    Err(AocError::EmptyInput {
+                        day,
+                        path: path.clone()
+                    }),
+                    solution.try_run_part(part, &path),
+                    "day {} part {}",
+                    day,
+                    part
+                );
+            }
+        }
+    }
+
+    let day_1 = solution::find(1).unwrap();
+    let one_mass = dir.join("1_one_cell.txt").to_string_lossy().into_owned();
+    assert_eq!(Ok(Some("31".to_string())), day_1.try_run_part(1, &one_mass));
+    assert_eq!(Ok(Some("39".to_string())), day_1.try_run_part(2, &one_mass));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unloadable_inputs() {
+    let dir = std::env::temp_dir().join(format!("aoc_bad_inputs_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let malformed = dir.join("malformed.txt");
+    std::fs::write(&malformed, "3,x,99\n").unwrap();
+    let malformed = malformed.to_string_lossy().into_owned();
+    let missing = dir.join("missing.txt").to_string_lossy().into_owned();
+
+    for solution in solution::registry() {
+        let day = solution.day();
+        for path in [&malformed, &missing] {
+            for part in 1..=2 {
+                let result = solution.try_run_part(part, path);
+                assert!(
+                    matches!(
+                        &result,
+                        Err(AocError::BadInput { day: error_day, path: error_path, .. })
+                            if *error_day == day && error_path == path
+                    ),
+                    "day {} part {}: {:?}",
+                    day,
+                    part,
+                    result
+                );
+            }
+        }
+    }
+
+    assert_eq!(
+        Err(AocError::BadInput {
+            day: 5,
+            path: malformed.clone(),
+            message: "Cell 1 ('x') is not an integer".to_string(),
+        }),
+        solution::find(5).unwrap().try_run_part(1, &malformed)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,221 @@
+// Every step of every published Intcode example, checked against the traces
+// recorded in tests/golden_traces/. Any change to what an instruction does,
+// the order its parameters are resolved in, or when outputs appear shows up
+// here as the first step that differs.
+//
+// After an intentional change, rewrite the fixtures with
+//
+//   cargo test --test golden_traces -- --ignored regenerate_golden
+//
+// and review the diff.
+
+use advent_of_code_2019::intcode::{Emulator, EmulatorMemoryType, EmulatorResult, QueueInput};
+use advent_of_code_2019::program;
+use advent_of_code_2019::solution::{self, Example, Part};
+use std::path::PathBuf;
+
+// Larger than any example needs, so a change that makes one loop forever
+// fails instead of hanging.
+const STEP_LIMIT: usize = 100_000;
+
+struct Case {
+    name: String,
+    program: Vec<EmulatorMemoryType>,
+    // One queue of starting inputs per machine.
+    inputs: Vec<Vec<EmulatorMemoryType>>,
+    // Whether the last machine's outputs go back to the first.
+    feedback: bool,
+    expected: String,
+}
+
+// The phase settings the puzzle gives as the best for each day 7 example,
+// in the order day 7 lists them.
+const DAY_7_PHASES: [[EmulatorMemoryType; 5]; 5] = [
+    [4, 3, 2, 1, 0],
+    [0, 1, 2, 3, 4],
+    [1, 0, 4, 3, 2],
+    [9, 8, 7, 6, 5],
+    [9, 7, 8, 5, 6],
+];
+
+fn case(day: u32, index: usize, example: &Example) -> Case {
+    let program = program::read_text(example.input.as_bytes()).unwrap();
+    let (inputs, feedback) = match day {
+        5 => {
+            let system_id = match example.part {
+                Part::One => 1,
+                Part::Two => 5,
+            };
+            (vec![vec![system_id]], false)
+        }
+        7 => {
+            let mut inputs: Vec<Vec<EmulatorMemoryType>> = DAY_7_PHASES[index]
+                .iter()
+                .map(|&phase| vec![phase])
+                .collect();
+            inputs[0].push(0);
+            (inputs, example.part == Part::Two)
+        }
+        _ => panic!("no golden trace inputs for day {}", day),
+    };
+    Case {
+        name: format!("day{}_example_{}", day, index + 1),
+        program,
+        inputs,
+        feedback,
+        expected: example.expected.to_string(),
+    }
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = Vec::new();
+    for day in &[5, 7] {
+        let examples = solution::find(*day).unwrap().examples();
+        for (index, example) in examples.iter().enumerate() {
+            cases.push(case(*day, index, example));
+        }
+    }
+    cases
+}
+
+fn fixture_path(case: &Case) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden_traces")
+        .join(format!("{}.json", case.name))
+}
+
+fn cell_list(cells: &[EmulatorMemoryType]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// One JSON object per executed instruction, across all machines in the
+// order they ran.
+fn trace(case: &Case) -> Vec<String> {
+    let mut machines: Vec<Emulator<QueueInput>> = case
+        .inputs
+        .iter()
+        .map(|inputs| Emulator::with_inputs(&case.program, inputs.iter().copied()))
+        .collect();
+    let mut records = Vec::new();
+    let mut last_output = None;
+
+    // Each machine runs until it blocks on input or halts, then hands over
+    // to the next, like day 7's amplifiers.
+    while machines.iter().any(|machine| !machine.is_halted()) {
+        let mut progressed = false;
+        for id in 0..machines.len() {
+            while !machines[id].is_halted() {
+                let result = machines[id]
+                    .step()
+                    .unwrap_or_else(|error| panic!("{} machine {}: {}", case.name, id, error));
+                let output = match result {
+                    EmulatorResult::NeedsInput => break,
+                    EmulatorResult::SuccessWithValue(value) => Some(value),
+                    EmulatorResult::Success
+                    | EmulatorResult::Done
+                    | EmulatorResult::Watchpoint { .. } => None,
+                };
+                progressed = true;
+
+                let machine = &machines[id];
+                let record = machine.recent_history().last().unwrap();
+                records.push(format!(
+                    "{{\"machine\":{},\"step\":{},\"ip\":{},\"instruction\":{},\"parameters\":[{}],\"relative_base\":{},\"output\":{}}}",
+                    id,
+                    record.step,
+                    record.instruction_pointer,
+                    record.instruction,
+                    cell_list(&record.parameters),
+                    machine.relative_base(),
+                    output.map_or("null".to_string(), |value| value.to_string())
+                ));
+                assert!(records.len() <= STEP_LIMIT, "{} ran too long", case.name);
+
+                if let Some(value) = output {
+                    last_output = Some(value);
+                    let next = id + 1;
+                    if next < machines.len() {
+                        machines[next].push_input(value);
+                    } else if case.feedback {
+                        machines[0].push_input(value);
+                    }
+                }
+            }
+        }
+        assert!(progressed, "{} is waiting for input", case.name);
+    }
+
+    assert_eq!(
+        Some(case.expected.clone()),
+        last_output.map(|value| value.to_string()),
+        "{} gave the wrong answer",
+        case.name
+    );
+    records
+}
+
+// A JSON array with one record per line, so fixtures diff line by line.
+fn to_json(records: &[String]) -> String {
+    format!("[\n{}\n]\n", records.join(",\n"))
+}
+
+fn from_json(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|line| *line != "[" && *line != "]")
+        .map(|line| line.trim_end_matches(',').to_string())
+        .collect()
+}
+
+#[test]
+fn test_traces_match_golden() {
+    let mut failures = Vec::new();
+    for case in cases() {
+        let path = fixture_path(&case);
+        let golden = match std::fs::read_to_string(&path) {
+            Ok(text) => from_json(&text),
+            Err(error) => {
+                failures.push(format!(
+                    "{}: can't read {}: {}",
+                    case.name,
+                    path.display(),
+                    error
+                ));
+                continue;
+            }
+        };
+        let fresh = trace(&case);
+
+        let differing =
+            (0..golden.len().max(fresh.len())).find(|&index| golden.get(index) != fresh.get(index));
+        if let Some(index) = differing {
+            let describe = |record: Option<&String>| {
+                record.map_or("(trace ended)".to_string(), |record| record.clone())
+            };
+            failures.push(format!(
+                "{}: record {} differs\n  golden: {}\n  fresh:  {}",
+                case.name,
+                index,
+                describe(golden.get(index)),
+                describe(fresh.get(index))
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\nIf the change is intended, run `cargo test --test golden_traces -- --ignored regenerate_golden`",
+        failures.join("\n")
+    );
+}
+
+#[test]
+#[ignore]
+fn regenerate_golden() {
+    for case in cases() {
+        std::fs::write(fixture_path(&case), to_json(&trace(&case))).unwrap();
+    }
+}
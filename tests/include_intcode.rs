@@ -0,0 +1,23 @@
+use advent_of_code_2019::get_intcode_memory_from_file;
+use advent_of_code_2019::include_intcode;
+use advent_of_code_2019::intcode::EmulatorMemoryType;
+
+const DAY_5: &[EmulatorMemoryType] = include_intcode!("../input/5.txt");
+
+#[test]
+fn test_embedded_program_matches_runtime_load() {
+    assert_eq!(get_intcode_memory_from_file("input/5.txt"), DAY_5);
+    assert_eq!(
+        get_intcode_memory_from_file("tests/fixtures/intcode/tolerant.txt"),
+        include_intcode!("fixtures/intcode/tolerant.txt")
+    );
+    assert_eq!(
+        &[1, -2, 3, 99],
+        include_intcode!("fixtures/intcode/tolerant.txt")
+    );
+}
+
+#[test]
+fn test_malformed_programs_fail_to_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/include_intcode_*.rs");
+}
@@ -0,0 +1,25 @@
+// Generated by `aoc record-test`. A test in intcode::record checks that this
+// still matches what the generator produces.
+
+#[test]
+fn input_output_example() {
+    use advent_of_code_2019::intcode::replay::memory_hash;
+    use advent_of_code_2019::intcode::{Emulator, EmulatorMemoryType, EmulatorResult};
+
+    let program: &[EmulatorMemoryType] = &[3, 0, 4, 0, 99];
+    let inputs: &[EmulatorMemoryType] = &[1337];
+    let mut emulator = Emulator::new(program, inputs.iter().copied().map(Ok));
+    let mut outputs: Vec<EmulatorMemoryType> = Vec::new();
+    loop {
+        match emulator.step().unwrap() {
+            EmulatorResult::Done => break,
+            EmulatorResult::SuccessWithValue(value) => outputs.push(value),
+            EmulatorResult::NeedsInput => panic!("ran out of recorded inputs"),
+            _ => {}
+        }
+    }
+
+    let expected_outputs: &[EmulatorMemoryType] = &[1337];
+    assert_eq!(expected_outputs, outputs.as_slice());
+    assert_eq!(0x3855d533eb9ffacc, memory_hash(emulator.memory()));
+}
@@ -0,0 +1,17 @@
+use advent_of_code_2019::intcode::{EmulatorBuilder, EmulatorError};
+
+// A write to 1 << 40 adds one cell rather than allocating up to it.
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn test_far_write_with_sparse_memory() -> Result<(), EmulatorError> {
+    let far = 1 << 40;
+    let program = [1101, 7, 0, far, 1001, far, 1, far, 4, far, 99];
+    let mut emulator = EmulatorBuilder::new()
+        .program(&program)
+        .sparse_memory(true)
+        .build()?;
+    assert_eq!(vec![8], emulator.run_collecting_outputs()?);
+    assert_eq!(program.len() + 1, emulator.touched_cells());
+    assert_eq!(vec![(1 << 40, 8)], emulator.sparse_cells());
+    Ok(())
+}
@@ -0,0 +1,7 @@
+use advent_of_code_2019::include_intcode;
+
+const PROGRAM: &[i64] = include_intcode!("missing.txt");
+
+fn main() {
+    println!("{:?}", PROGRAM);
+}
@@ -0,0 +1,35 @@
+use advent_of_code_2019::intcode::{IssueKind, ValidationIssue};
+use advent_of_code_2019::{get_intcode_memory_from_file, get_validated_intcode_memory_from_file};
+
+#[test]
+fn test_puzzle_inputs_have_no_issues() {
+    for path in &["input/2.txt", "input/5.txt", "input/7.txt"] {
+        let (program, issues) = get_validated_intcode_memory_from_file(path);
+        assert_eq!(Vec::<ValidationIssue>::new(), issues, "{}", path);
+        assert_eq!(get_intcode_memory_from_file(path), program);
+    }
+}
+
+#[test]
+fn test_corrupted_input() {
+    let path = std::env::temp_dir().join(format!("aoc_validate_{}.txt", std::process::id()));
+    let mut program = get_intcode_memory_from_file("input/2.txt");
+    // The Multiply at 16 becomes opcode 42.
+    assert_eq!(2, program[16]);
+    program[16] = 42;
+    let text: Vec<String> = program.iter().map(|cell| cell.to_string()).collect();
+    std::fs::write(&path, text.join(",")).unwrap();
+    let path = path.to_str().unwrap();
+
+    let (loaded, issues) = get_validated_intcode_memory_from_file(path);
+    assert_eq!(program, loaded);
+    assert_eq!(
+        ValidationIssue {
+            address: 16,
+            kind: IssueKind::UnknownOpcode { instruction: 42 },
+        },
+        issues[0]
+    );
+    assert_eq!(program, get_intcode_memory_from_file(path));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,158 @@
+use advent_of_code_2019::intcode::replay::Replay;
+use advent_of_code_2019::verify::{self, Status};
+use std::path::Path;
+use std::process::Command;
+
+const ANSWERS: &str = "tests/fixtures/verify/answers.toml";
+const INPUT_DIR: &str = "tests/fixtures/verify/inputs";
+const MANIFEST_DIR: &str = "tests/fixtures/inputs";
+
+#[test]
+fn test_verify_reports_each_answer() {
+    let answers = verify::parse_answers(&std::fs::read_to_string(ANSWERS).unwrap()).unwrap();
+    let results = verify::verify(&answers, Path::new(INPUT_DIR));
+    let statuses: Vec<(u32, u32, Status)> = results
+        .into_iter()
+        .map(|result| (result.expected.day, result.expected.part, result.status))
+        .collect();
+
+    assert_eq!(
+        vec![
+            (1, 1, Status::Pass),
+            (
+                1,
+                2,
+                Status::Fail {
+                    actual: "51316".to_string()
+                }
+            ),
+            (
+                2,
+                1,
+                Status::Skipped(format!(
+                    "{} not found",
+                    Path::new(INPUT_DIR).join("2.txt").display()
+                ))
+            ),
+            (5, 1, Status::Pass),
+            (5, 2, Status::Pass),
+        ],
+        statuses
+    );
+}
+
+#[test]
+fn test_verify_command_exits_nonzero_on_mismatch() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aoc"))
+        .args(["verify", "--answers", ANSWERS, "--input-dir", INPUT_DIR])
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("day.part  status"));
+    assert!(stdout.contains("1.2       FAIL"));
+    assert!(stdout.contains("2.1       skipped"));
+}
+
+#[test]
+fn test_verify_command_passes_when_everything_matches() {
+    let dir = std::env::temp_dir().join(format!("aoc_verify_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let answers = dir.join("answers.toml");
+    std::fs::write(&answers, "1.1 = 34241\n1.2 = 51316\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_aoc"))
+        .args(["verify", "--answers"])
+        .arg(&answers)
+        .args(["--input-dir", INPUT_DIR])
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(Some(0), status.code());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_verify_follows_the_manifest_and_warns_on_mismatch() {
+    let dir = std::env::temp_dir().join(format!("aoc_verify_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let answers = dir.join("answers.toml");
+    std::fs::write(&answers, "1.1 = 34241\n5.1 = 0\n").unwrap();
+
+    // Day 1's input is listed under another name; day 5's is missing.
+    let results = verify::verify(
+        &verify::parse_answers(&std::fs::read_to_string(&answers).unwrap()).unwrap(),
+        Path::new(MANIFEST_DIR),
+    );
+    assert_eq!(Status::Pass, results[0].status);
+    assert_eq!(None, results[0].warning);
+    assert_eq!(
+        Status::Skipped(format!(
+            "{} not found",
+            Path::new(MANIFEST_DIR).join("5.txt").display()
+        )),
+        results[1].status
+    );
+
+    // Day 2's file is a copy of day 1's input, so it runs with a warning.
+    std::fs::write(&answers, "2.1 = 0\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aoc"))
+        .args(["verify", "--answers"])
+        .arg(&answers)
+        .args(["--input-dir", MANIFEST_DIR])
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "WARNING: day 2: {} has sha256",
+            Path::new(MANIFEST_DIR).join("2.txt").display()
+        )),
+        "{}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_verify_command_checks_replays() {
+    let dir = std::env::temp_dir().join(format!("aoc_verify_replays_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let answers = dir.join("answers.toml");
+    std::fs::write(&answers, "1.1 = 34241\n").unwrap();
+    let replay = Replay::record(&[3, 0, 4, 0, 99], &[1337]).unwrap();
+    replay.save(dir.join("echo.icr")).unwrap();
+
+    let run = || {
+        Command::new(env!("CARGO_BIN_EXE_aoc"))
+            .args(["verify", "--answers"])
+            .arg(&answers)
+            .args(["--input-dir", INPUT_DIR, "--replay-dir"])
+            .arg(&dir)
+            .output()
+            .unwrap()
+    };
+    let output = run();
+    assert_eq!(Some(0), output.status.code());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("echo.icr  pass"), "{}", stdout);
+
+    let mut corrupted = replay;
+    corrupted.outputs[0] = 1;
+    corrupted.save(dir.join("corrupted.icr")).unwrap();
+    let output = run();
+    assert_eq!(Some(1), output.status.code());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("corrupted.icr  FAIL  Output 0 differs: expected 1, but found 1337"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("echo.icr  pass"), "{}", stdout);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}